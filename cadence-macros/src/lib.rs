@@ -93,6 +93,52 @@
 //! statsd_set!("some.set", 123, "tag" => "val", "another" => "thing");
 //! ```
 //!
+//! Macros can also be used with a specific client instead of the global default by
+//! passing it as the first argument. This is useful for libraries or modules that have
+//! their own client and don't want to depend on a process-wide default being set.
+//!
+//! ```rust
+//! use cadence::{StatsdClient, NopMetricSink};
+//! use cadence_macros::statsd_count;
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//!
+//! statsd_count!(client = client, "some.counter", 123);
+//! statsd_count!(client = client, "some.counter", 123, "tag" => "val");
+//! ```
+//!
+//! ### Arguments
+//!
+//! Every macro accepts the same arguments, in the following order. Items in
+//! brackets are optional and `*` means the item can be repeated any number of times.
+//!
+//! ```text
+//! statsd_count!([client = <client>,] <key>, <value> [, <tag key> => <tag value>]* [, rate = <sample rate>])
+//! ```
+//!
+//! * `client = <client>`: a `StatsdClient` (or a reference to one) to use instead of
+//!   the global default client.
+//! * `<key>`: the name of the metric, appended to the prefix of the client.
+//! * `<value>`: the value of the metric. Any type the corresponding method of the client
+//!   accepts can be used, e.g. a `u64` or `Duration` for `statsd_time!`.
+//! * `<tag key> => <tag value>`: a key-value tag added to the metric.
+//! * `rate = <sample rate>`: randomly sample the metric at this rate, between 0 and 1,
+//!   using `MetricBuilder::with_sample_rate`. This must be the last argument.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use cadence::{StatsdClient, NopMetricSink};
+//! use cadence_macros::statsd_histogram;
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//!
+//! // "my.prefix.some.histogram:5000000|h|@0.25|#route:/api", sent about a quarter of the time
+//! statsd_histogram!(client = client, "some.histogram", Duration::from_millis(5), "route" => "/api", rate = 0.25);
+//! ```
+//!
+//! By default, macros that use the global default client panic if it hasn't been set.
+//! This can be changed using the `set_unset_behavior` function.
+//!
 //! ## Limitations
 //!
 //! Some limitations with the current implemenation of Cadence macros are described below
@@ -102,8 +148,14 @@
 //!
 
 pub use crate::state::{
-    get_global_default, is_global_default_set, set_global_default, GlobalDefaultNotSet, SingletonHolder,
+    get_global_default, is_global_default_set, replace_global_default, set_global_default, try_global_default,
+    try_set_global_default, GlobalDefaultAlreadySet, GlobalDefaultNotSet, SingletonHolder,
 };
 
+pub use crate::state::{set_unset_behavior, unset_behavior, UnsetBehavior};
+
+#[doc(hidden)]
+pub use crate::state::_global_default_for_macro;
+
 mod macros;
 mod state;
@@ -11,11 +11,19 @@
 // NOTE: Comments here are mostly just copy/pasted. Make sure to update all of
 //  them if you make changes!
 
-/// Emit a counter using the default global client, optionally with tags
+/// Emit a counter using the default global client, optionally with tags and a sample rate
 ///
 /// The counter will use the prefix from the default global client combined
 /// with the provided key.
 ///
+/// A specific client can be used instead of the default global client by passing
+/// it as the first argument, prefixed with `client =`. In this case, the prefix and
+/// error handler of the given client are used and no global client needs to be set.
+///
+/// A sample rate between 0 and 1 can be given as the last argument, prefixed with
+/// `rate =`. The metric is then randomly sampled at that rate, see
+/// `cadence::MetricBuilder::with_sample_rate` for more information.
+///
 /// Any errors encountered sending metrics will be handled by the error handler
 /// registered with the default global client. This error handler is a no-op
 /// unless explicitly set. Callers should set the error handler for the default
@@ -24,7 +32,9 @@
 /// # Panics
 ///
 /// This macro will panic if the default global client has not been set when
-/// it is invoked (via `cadence_macros::set_global_default`).
+/// it is invoked (via `cadence_macros::set_global_default`) and a specific
+/// client was not given, unless this has been changed with
+/// `cadence_macros::set_unset_behavior`.
 ///
 /// # Examples
 ///
@@ -44,6 +54,13 @@
 /// statsd_count!("some.counter", 123, "tag" => "val");
 /// // "my.prefix.some.counter:123|c|#tag:val,another:thing"
 /// statsd_count!("some.counter", 123, "tag" => "val", "another" => "thing");
+/// // "my.prefix.some.counter:123|c|@0.5|#tag:val", sent about half the time
+/// statsd_count!("some.counter", 123, "tag" => "val", rate = 0.5);
+///
+/// let other = StatsdClient::from_sink("other.prefix", NopMetricSink);
+///
+/// // "other.prefix.some.counter:123|c|#tag:val"
+/// statsd_count!(client = other, "some.counter", 123, "tag" => "val");
 /// ```
 ///
 /// # Limitations
@@ -52,20 +69,36 @@
 /// supported, e.g. `builder.with_tag_value("val")`.
 #[macro_export]
 macro_rules! statsd_count {
+    (client = $client:expr, $key:expr, $val:expr) => {
+        $crate::statsd_count!(client = $client, $key, $val,)
+    };
+
+    (client = $client:expr, $key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(client = $client, count_with_tags, $key, $val; $($rest)*)
+    };
+
     ($key:expr, $val:expr) => {
         $crate::statsd_count!($key, $val,)
     };
 
-    ($key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
-        $crate::_generate_impl!(count_with_tags, $key, $val, $($tag_key => $tag_val),*)
+    ($key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(count_with_tags, $key, $val; $($rest)*)
     }
 }
 
-/// Emit a timer using the default global client, optionally with tags
+/// Emit a timer using the default global client, optionally with tags and a sample rate
 ///
 /// The timer will use the prefix from the default global client combined
 /// with the provided key.
 ///
+/// A specific client can be used instead of the default global client by passing
+/// it as the first argument, prefixed with `client =`. In this case, the prefix and
+/// error handler of the given client are used and no global client needs to be set.
+///
+/// A sample rate between 0 and 1 can be given as the last argument, prefixed with
+/// `rate =`. The metric is then randomly sampled at that rate, see
+/// `cadence::MetricBuilder::with_sample_rate` for more information.
+///
 /// Any errors encountered sending metrics will be handled by the error handler
 /// registered with the default global client. This error handler is a no-op
 /// unless explicitly set. Callers should set the error handler for the default
@@ -74,7 +107,9 @@ macro_rules! statsd_count {
 /// # Panics
 ///
 /// This macro will panic if the default global client has not been set when
-/// it is invoked (via `cadence_macros::set_global_default`).
+/// it is invoked (via `cadence_macros::set_global_default`) and a specific
+/// client was not given, unless this has been changed with
+/// `cadence_macros::set_unset_behavior`.
 ///
 /// # Examples
 ///
@@ -94,6 +129,13 @@ macro_rules! statsd_count {
 /// statsd_time!("some.timer", 123, "tag" => "val");
 /// // "my.prefix.some.timer:123|ms|#tag:val,another:thing"
 /// statsd_time!("some.timer", 123, "tag" => "val", "another" => "thing");
+/// // "my.prefix.some.timer:123|ms|@0.5|#tag:val", sent about half the time
+/// statsd_time!("some.timer", 123, "tag" => "val", rate = 0.5);
+///
+/// let other = StatsdClient::from_sink("other.prefix", NopMetricSink);
+///
+/// // "other.prefix.some.timer:123|ms|#tag:val"
+/// statsd_time!(client = other, "some.timer", 123, "tag" => "val");
 /// ```
 ///
 /// # Limitations
@@ -102,20 +144,36 @@ macro_rules! statsd_count {
 /// supported, e.g. `builder.with_tag_value("val")`.
 #[macro_export]
 macro_rules! statsd_time {
+    (client = $client:expr, $key:expr, $val:expr) => {
+        $crate::statsd_time!(client = $client, $key, $val,)
+    };
+
+    (client = $client:expr, $key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(client = $client, time_with_tags, $key, $val; $($rest)*)
+    };
+
     ($key:expr, $val:expr) => {
         $crate::statsd_time!($key, $val,)
     };
 
-    ($key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
-        $crate::_generate_impl!(time_with_tags, $key, $val, $($tag_key => $tag_val),*)
+    ($key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(time_with_tags, $key, $val; $($rest)*)
     }
 }
 
-/// Emit a gauge using the default global client, optionally with tags
+/// Emit a gauge using the default global client, optionally with tags and a sample rate
 ///
 /// The gauge will use the prefix from the default global client combined
 /// with the provided key.
 ///
+/// A specific client can be used instead of the default global client by passing
+/// it as the first argument, prefixed with `client =`. In this case, the prefix and
+/// error handler of the given client are used and no global client needs to be set.
+///
+/// A sample rate between 0 and 1 can be given as the last argument, prefixed with
+/// `rate =`. The metric is then randomly sampled at that rate, see
+/// `cadence::MetricBuilder::with_sample_rate` for more information.
+///
 /// Any errors encountered sending metrics will be handled by the error handler
 /// registered with the default global client. This error handler is a no-op
 /// unless explicitly set. Callers should set the error handler for the default
@@ -124,7 +182,9 @@ macro_rules! statsd_time {
 /// # Panics
 ///
 /// This macro will panic if the default global client has not been set when
-/// it is invoked (via `cadence_macros::set_global_default`).
+/// it is invoked (via `cadence_macros::set_global_default`) and a specific
+/// client was not given, unless this has been changed with
+/// `cadence_macros::set_unset_behavior`.
 ///
 /// # Examples
 ///
@@ -144,6 +204,13 @@ macro_rules! statsd_time {
 /// statsd_gauge!("some.gauge", 123, "tag" => "val");
 /// // "my.prefix.some.gauge:123|g|#tag:val,another:thing"
 /// statsd_gauge!("some.gauge", 123, "tag" => "val", "another" => "thing");
+/// // "my.prefix.some.gauge:123|g|@0.5|#tag:val", sent about half the time
+/// statsd_gauge!("some.gauge", 123, "tag" => "val", rate = 0.5);
+///
+/// let other = StatsdClient::from_sink("other.prefix", NopMetricSink);
+///
+/// // "other.prefix.some.gauge:123|g|#tag:val"
+/// statsd_gauge!(client = other, "some.gauge", 123, "tag" => "val");
 /// ```
 ///
 /// # Limitations
@@ -152,20 +219,36 @@ macro_rules! statsd_time {
 /// supported, e.g. `builder.with_tag_value("val")`.
 #[macro_export]
 macro_rules! statsd_gauge {
+    (client = $client:expr, $key:expr, $val:expr) => {
+        $crate::statsd_gauge!(client = $client, $key, $val,)
+    };
+
+    (client = $client:expr, $key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(client = $client, gauge_with_tags, $key, $val; $($rest)*)
+    };
+
     ($key:expr, $val:expr) => {
         $crate::statsd_gauge!($key, $val,)
     };
 
-    ($key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
-        $crate::_generate_impl!(gauge_with_tags, $key, $val, $($tag_key => $tag_val),*)
+    ($key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(gauge_with_tags, $key, $val; $($rest)*)
     }
 }
 
-/// Emit a meter using the default global client, optionally with tags
+/// Emit a meter using the default global client, optionally with tags and a sample rate
 ///
 /// The meter will use the prefix from the default global client combined
 /// with the provided key.
 ///
+/// A specific client can be used instead of the default global client by passing
+/// it as the first argument, prefixed with `client =`. In this case, the prefix and
+/// error handler of the given client are used and no global client needs to be set.
+///
+/// A sample rate between 0 and 1 can be given as the last argument, prefixed with
+/// `rate =`. The metric is then randomly sampled at that rate, see
+/// `cadence::MetricBuilder::with_sample_rate` for more information.
+///
 /// Any errors encountered sending metrics will be handled by the error handler
 /// registered with the default global client. This error handler is a no-op
 /// unless explicitly set. Callers should set the error handler for the default
@@ -174,7 +257,9 @@ macro_rules! statsd_gauge {
 /// # Panics
 ///
 /// This macro will panic if the default global client has not been set when
-/// it is invoked (via `cadence_macros::set_global_default`).
+/// it is invoked (via `cadence_macros::set_global_default`) and a specific
+/// client was not given, unless this has been changed with
+/// `cadence_macros::set_unset_behavior`.
 ///
 /// # Examples
 ///
@@ -194,6 +279,13 @@ macro_rules! statsd_gauge {
 /// statsd_meter!("some.meter", 123, "tag" => "val");
 /// // "my.prefix.some.meter:123|m|#tag:val,another:thing"
 /// statsd_meter!("some.meter", 123, "tag" => "val", "another" => "thing");
+/// // "my.prefix.some.meter:123|m|@0.5|#tag:val", sent about half the time
+/// statsd_meter!("some.meter", 123, "tag" => "val", rate = 0.5);
+///
+/// let other = StatsdClient::from_sink("other.prefix", NopMetricSink);
+///
+/// // "other.prefix.some.meter:123|m|#tag:val"
+/// statsd_meter!(client = other, "some.meter", 123, "tag" => "val");
 /// ```
 ///
 /// # Limitations
@@ -202,20 +294,36 @@ macro_rules! statsd_gauge {
 /// supported, e.g. `builder.with_tag_value("val")`.
 #[macro_export]
 macro_rules! statsd_meter {
+    (client = $client:expr, $key:expr, $val:expr) => {
+        $crate::statsd_meter!(client = $client, $key, $val,)
+    };
+
+    (client = $client:expr, $key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(client = $client, meter_with_tags, $key, $val; $($rest)*)
+    };
+
     ($key:expr, $val:expr) => {
         $crate::statsd_meter!($key, $val,)
     };
 
-    ($key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
-        $crate::_generate_impl!(meter_with_tags, $key, $val, $($tag_key => $tag_val),*)
+    ($key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(meter_with_tags, $key, $val; $($rest)*)
     }
 }
 
-/// Emit a histogram using the default global client, optionally with tags
+/// Emit a histogram using the default global client, optionally with tags and a sample rate
 ///
 /// The histogram will use the prefix from the default global client combined
 /// with the provided key.
 ///
+/// A specific client can be used instead of the default global client by passing
+/// it as the first argument, prefixed with `client =`. In this case, the prefix and
+/// error handler of the given client are used and no global client needs to be set.
+///
+/// A sample rate between 0 and 1 can be given as the last argument, prefixed with
+/// `rate =`. The metric is then randomly sampled at that rate, see
+/// `cadence::MetricBuilder::with_sample_rate` for more information.
+///
 /// Any errors encountered sending metrics will be handled by the error handler
 /// registered with the default global client. This error handler is a no-op
 /// unless explicitly set. Callers should set the error handler for the default
@@ -224,7 +332,9 @@ macro_rules! statsd_meter {
 /// # Panics
 ///
 /// This macro will panic if the default global client has not been set when
-/// it is invoked (via `cadence_macros::set_global_default`).
+/// it is invoked (via `cadence_macros::set_global_default`) and a specific
+/// client was not given, unless this has been changed with
+/// `cadence_macros::set_unset_behavior`.
 ///
 /// # Examples
 ///
@@ -244,6 +354,13 @@ macro_rules! statsd_meter {
 /// statsd_histogram!("some.histogram", 123, "tag" => "val");
 /// // "my.prefix.some.histogram:123|h|#tag:val,another:thing"
 /// statsd_histogram!("some.histogram", 123, "tag" => "val", "another" => "thing");
+/// // "my.prefix.some.histogram:123|h|@0.5|#tag:val", sent about half the time
+/// statsd_histogram!("some.histogram", 123, "tag" => "val", rate = 0.5);
+///
+/// let other = StatsdClient::from_sink("other.prefix", NopMetricSink);
+///
+/// // "other.prefix.some.histogram:123|h|#tag:val"
+/// statsd_histogram!(client = other, "some.histogram", 123, "tag" => "val");
 /// ```
 ///
 /// # Limitations
@@ -252,20 +369,36 @@ macro_rules! statsd_meter {
 /// supported, e.g. `builder.with_tag_value("val")`.
 #[macro_export]
 macro_rules! statsd_histogram {
+    (client = $client:expr, $key:expr, $val:expr) => {
+        $crate::statsd_histogram!(client = $client, $key, $val,)
+    };
+
+    (client = $client:expr, $key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(client = $client, histogram_with_tags, $key, $val; $($rest)*)
+    };
+
     ($key:expr, $val:expr) => {
         $crate::statsd_histogram!($key, $val,)
     };
 
-    ($key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
-        $crate::_generate_impl!(histogram_with_tags, $key, $val, $($tag_key => $tag_val),*)
+    ($key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(histogram_with_tags, $key, $val; $($rest)*)
     }
 }
 
-/// Emit a distribution using the default global client, optionally with tags
+/// Emit a distribution using the default global client, optionally with tags and a sample rate
 ///
 /// The distribution will use the prefix from the default global client combined
 /// with the provided key.
 ///
+/// A specific client can be used instead of the default global client by passing
+/// it as the first argument, prefixed with `client =`. In this case, the prefix and
+/// error handler of the given client are used and no global client needs to be set.
+///
+/// A sample rate between 0 and 1 can be given as the last argument, prefixed with
+/// `rate =`. The metric is then randomly sampled at that rate, see
+/// `cadence::MetricBuilder::with_sample_rate` for more information.
+///
 /// Any errors encountered sending metrics will be handled by the error handler
 /// registered with the default global client. This error handler is a no-op
 /// unless explicitly set. Callers should set the error handler for the default
@@ -274,7 +407,9 @@ macro_rules! statsd_histogram {
 /// # Panics
 ///
 /// This macro will panic if the default global client has not been set when
-/// it is invoked (via `cadence_macros::set_global_default`).
+/// it is invoked (via `cadence_macros::set_global_default`) and a specific
+/// client was not given, unless this has been changed with
+/// `cadence_macros::set_unset_behavior`.
 ///
 /// # Examples
 ///
@@ -294,6 +429,13 @@ macro_rules! statsd_histogram {
 /// statsd_distribution!("some.distribution", 123, "tag" => "val");
 /// // "my.prefix.some.distribution:123|d|#tag:val,another:thing"
 /// statsd_distribution!("some.distribution", 123, "tag" => "val", "another" => "thing");
+/// // "my.prefix.some.distribution:123|d|@0.5|#tag:val", sent about half the time
+/// statsd_distribution!("some.distribution", 123, "tag" => "val", rate = 0.5);
+///
+/// let other = StatsdClient::from_sink("other.prefix", NopMetricSink);
+///
+/// // "other.prefix.some.distribution:123|d|#tag:val"
+/// statsd_distribution!(client = other, "some.distribution", 123, "tag" => "val");
 /// ```
 ///
 /// # Limitations
@@ -302,20 +444,36 @@ macro_rules! statsd_histogram {
 /// supported, e.g. `builder.with_tag_value("val")`.
 #[macro_export]
 macro_rules! statsd_distribution {
+    (client = $client:expr, $key:expr, $val:expr) => {
+        $crate::statsd_distribution!(client = $client, $key, $val,)
+    };
+
+    (client = $client:expr, $key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(client = $client, distribution_with_tags, $key, $val; $($rest)*)
+    };
+
     ($key:expr, $val:expr) => {
         $crate::statsd_distribution!($key, $val,)
     };
 
-    ($key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
-        $crate::_generate_impl!(distribution_with_tags, $key, $val, $($tag_key => $tag_val),*)
+    ($key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(distribution_with_tags, $key, $val; $($rest)*)
     }
 }
 
-/// Emit a set using the default global client, optionally with tags
+/// Emit a set using the default global client, optionally with tags and a sample rate
 ///
 /// The set will use the prefix from the default global client combined
 /// with the provided key.
 ///
+/// A specific client can be used instead of the default global client by passing
+/// it as the first argument, prefixed with `client =`. In this case, the prefix and
+/// error handler of the given client are used and no global client needs to be set.
+///
+/// A sample rate between 0 and 1 can be given as the last argument, prefixed with
+/// `rate =`. The metric is then randomly sampled at that rate, see
+/// `cadence::MetricBuilder::with_sample_rate` for more information.
+///
 /// Any errors encountered sending metrics will be handled by the error handler
 /// registered with the default global client. This error handler is a no-op
 /// unless explicitly set. Callers should set the error handler for the default
@@ -324,7 +482,9 @@ macro_rules! statsd_distribution {
 /// # Panics
 ///
 /// This macro will panic if the default global client has not been set when
-/// it is invoked (via `cadence_macros::set_global_default`).
+/// it is invoked (via `cadence_macros::set_global_default`) and a specific
+/// client was not given, unless this has been changed with
+/// `cadence_macros::set_unset_behavior`.
 ///
 /// # Examples
 ///
@@ -344,6 +504,13 @@ macro_rules! statsd_distribution {
 /// statsd_set!("some.set", 123, "tag" => "val");
 /// // "my.prefix.some.set:123|s|#tag:val,another:thing"
 /// statsd_set!("some.set", 123, "tag" => "val", "another" => "thing");
+/// // "my.prefix.some.set:123|s|@0.5|#tag:val", sent about half the time
+/// statsd_set!("some.set", 123, "tag" => "val", rate = 0.5);
+///
+/// let other = StatsdClient::from_sink("other.prefix", NopMetricSink);
+///
+/// // "other.prefix.some.set:123|s|#tag:val"
+/// statsd_set!(client = other, "some.set", 123, "tag" => "val");
 /// ```
 ///
 /// # Limitations
@@ -352,23 +519,54 @@ macro_rules! statsd_distribution {
 /// supported, e.g. `builder.with_tag_value("val")`.
 #[macro_export]
 macro_rules! statsd_set {
+    (client = $client:expr, $key:expr, $val:expr) => {
+        $crate::statsd_set!(client = $client, $key, $val,)
+    };
+
+    (client = $client:expr, $key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(client = $client, set_with_tags, $key, $val; $($rest)*)
+    };
+
     ($key:expr, $val:expr) => {
         $crate::statsd_set!($key, $val,)
     };
 
-    ($key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
-        $crate::_generate_impl!(set_with_tags, $key, $val, $($tag_key => $tag_val),*)
+    ($key:expr, $val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(set_with_tags, $key, $val; $($rest)*)
     }
 }
 
 #[macro_export]
 #[doc(hidden)]
 macro_rules! _generate_impl {
-    ($method:ident, $key:expr, $val:expr, $($tag_key:expr => $tag_val:expr),*) => {
+    // Tags and an optional sample rate are applied to the builder one at a time
+    // since a sample rate after a variable number of tags can't be matched by a
+    // single rule without being ambiguous.
+    (@send $builder:expr;) => {
+        $builder.send()
+    };
+
+    (@send $builder:expr; rate = $rate:expr $(,)?) => {
+        $builder.with_sample_rate($rate).send()
+    };
+
+    (@send $builder:expr; $tag_key:expr => $tag_val:expr) => {
+        $builder.with_tag($tag_key, $tag_val).send()
+    };
+
+    (@send $builder:expr; $tag_key:expr => $tag_val:expr, $($rest:tt)*) => {
+        $crate::_generate_impl!(@send $builder.with_tag($tag_key, $tag_val); $($rest)*)
+    };
+
+    (client = $client:expr, $method:ident, $key:expr, $val:expr; $($rest:tt)*) => {
+        use cadence::prelude::*;
+        $crate::_generate_impl!(@send $client.$method($key, $val); $($rest)*)
+    };
+
+    ($method:ident, $key:expr, $val:expr; $($rest:tt)*) => {
         use cadence::prelude::*;
-        let client = $crate::get_global_default().unwrap();
-        let builder = client.$method($key, $val);
-        $(let builder = builder.with_tag($tag_key, $tag_val);)*
-        builder.send()
+        if let Some(client) = $crate::_global_default_for_macro() {
+            $crate::_generate_impl!(@send client.$method($key, $val); $($rest)*)
+        }
     }
 }
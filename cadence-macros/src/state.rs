@@ -8,22 +8,24 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use arc_swap::ArcSwapOption;
 use cadence::StatsdClient;
-use std::cell::UnsafeCell;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::Arc;
 
-const UNSET: usize = 0;
-const LOADING: usize = 1;
-const COMPLETE: usize = 2;
-
 /// Global default StatsdClient to be used by macros
 static HOLDER: SingletonHolder<StatsdClient> = SingletonHolder::new();
 
+/// What macros do when the global default StatsdClient isn't set
+static UNSET_BEHAVIOR: AtomicU8 = AtomicU8::new(UnsetBehavior::Panic as u8);
+
+/// Has a warning been printed about the global default StatsdClient not being set
+static UNSET_WARNED: AtomicBool = AtomicBool::new(false);
+
 /// Holder to allow global reads of a value from multiple threads while
-/// allowing the value to be written (set) a single time.
+/// allowing the value to be set once or replaced.
 ///
 /// This type is public to allow it to be used in integration tests for
 /// this crate but it is not part of the public API and may change at any
@@ -31,16 +33,14 @@ static HOLDER: SingletonHolder<StatsdClient> = SingletonHolder::new();
 #[doc(hidden)]
 #[derive(Debug, Default)]
 pub struct SingletonHolder<T> {
-    value: UnsafeCell<Option<Arc<T>>>,
-    state: AtomicUsize,
+    value: ArcSwapOption<T>,
 }
 
 impl<T> SingletonHolder<T> {
     /// Create a new empty holder
     pub const fn new() -> Self {
         SingletonHolder {
-            value: UnsafeCell::new(None),
-            state: AtomicUsize::new(UNSET),
+            value: ArcSwapOption::const_empty(),
         }
     }
 }
@@ -48,46 +48,41 @@ impl<T> SingletonHolder<T> {
 impl<T> SingletonHolder<T> {
     /// Get a pointer to the contained value if set, None otherwise
     pub fn get(&self) -> Option<Arc<T>> {
-        if !self.is_set() {
-            return None;
-        }
-
-        // SAFETY: We've ensured that the state is "complete" and the
-        // set method has completed and set a value for the UnsafeCell.
-        unsafe { &*self.value.get() }.clone()
+        self.value.load_full()
     }
 
     pub fn is_set(&self) -> bool {
-        COMPLETE == self.state.load(Ordering::Acquire)
+        self.value.load().is_some()
     }
 
     /// Set the value if it has not already been set, otherwise this is a no-op
     pub fn set(&self, val: T) {
-        if self
-            .state
-            .compare_exchange(UNSET, LOADING, Ordering::AcqRel, Ordering::Relaxed)
-            .is_err()
-        {
-            return;
+        let _ = self.try_set(val);
+    }
+
+    /// Set the value if it has not already been set, otherwise return the
+    /// given value as an error
+    pub fn try_set(&self, val: T) -> Result<(), T> {
+        let new = Arc::new(val);
+        let prev = self.value.compare_and_swap(&None::<Arc<T>>, Some(new.clone()));
+        if prev.is_none() {
+            return Ok(());
         }
 
-        // SAFETY: There are no readers at this point since we've guaranteed the
-        // state could not have been "complete". There are no other writers since
-        // we've ensured that the state was previously "unset" and we've been able
-        // to compare-and-swap it to "loading".
-        let ptr = self.value.get();
-        unsafe {
-            *ptr = Some(Arc::new(val));
+        // The swap didn't happen so our copy of the new value is the only one
+        // and the value can be taken back out of it.
+        match Arc::try_unwrap(new) {
+            Ok(val) => Err(val),
+            Err(_) => unreachable!("value not set but still referenced"),
         }
+    }
 
-        self.state.store(COMPLETE, Ordering::Release);
+    /// Set the value, returning the previous value if there was one
+    pub fn replace(&self, val: T) -> Option<Arc<T>> {
+        self.value.swap(Some(Arc::new(val)))
     }
 }
 
-unsafe impl<T: Send> Send for SingletonHolder<T> {}
-
-unsafe impl<T: Sync> Sync for SingletonHolder<T> {}
-
 /// Error indicating that a global default `StatsdClient` was not set
 /// when a call to `get_global_default` was made.
 #[derive(Debug)]
@@ -101,9 +96,23 @@ impl Display for GlobalDefaultNotSet {
 
 impl Error for GlobalDefaultNotSet {}
 
+/// Error indicating that a global default `StatsdClient` was already set
+/// when a call to `try_set_global_default` was made.
+#[derive(Debug)]
+pub struct GlobalDefaultAlreadySet;
+
+impl Display for GlobalDefaultAlreadySet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt("global default StatsdClient instance already set", f)
+    }
+}
+
+impl Error for GlobalDefaultAlreadySet {}
+
 /// Set the global default `StatsdClient` instance
 ///
 /// If the global default client has already been set, this method does nothing.
+/// Use `replace_global_default` to replace an existing global default.
 ///
 /// # Example
 ///
@@ -117,6 +126,63 @@ pub fn set_global_default(client: StatsdClient) {
     HOLDER.set(client);
 }
 
+/// Set the global default `StatsdClient` instance if it hasn't been set
+///
+/// Unlike `set_global_default`, this lets callers know whether the client
+/// they gave was set as the global default. This is useful for libraries or
+/// plugins that set a global default defensively and need to know whether
+/// another one was already set (for example, by the application using them).
+///
+/// # Errors
+///
+/// This method will return an error if the global default has already been
+/// set. The existing global default is left unchanged.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{StatsdClient, NopMetricSink};
+///
+/// let first = StatsdClient::from_sink("first.prefix", NopMetricSink);
+/// assert!(cadence_macros::try_set_global_default(first).is_ok());
+///
+/// let second = StatsdClient::from_sink("second.prefix", NopMetricSink);
+/// assert!(cadence_macros::try_set_global_default(second).is_err());
+/// ```
+pub fn try_set_global_default(client: StatsdClient) -> Result<(), GlobalDefaultAlreadySet> {
+    HOLDER.try_set(client).map_err(|_| GlobalDefaultAlreadySet)
+}
+
+/// Set the global default `StatsdClient` instance, returning the previous
+/// one if it was set
+///
+/// Unlike `set_global_default`, this replaces any existing global default.
+/// This is useful for swapping clients at runtime (for example, after
+/// reloading configuration) or for restoring the previous client in tests.
+///
+/// Callers that obtained the previous client via `get_global_default` keep
+/// using it until they call `get_global_default` again. Any metrics buffered
+/// by the previous client are not flushed by this method, call `.flush()` on
+/// the returned client to do so.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{StatsdClient, NopMetricSink};
+///
+/// let first = StatsdClient::from_sink("first.prefix", NopMetricSink);
+/// assert!(cadence_macros::replace_global_default(first).is_none());
+///
+/// let second = StatsdClient::from_sink("second.prefix", NopMetricSink);
+/// let previous = cadence_macros::replace_global_default(second);
+/// assert!(previous.is_some());
+/// ```
+pub fn replace_global_default(client: StatsdClient) -> Option<StatsdClient> {
+    HOLDER
+        .replace(client)
+        .map(|previous| Arc::try_unwrap(previous).unwrap_or_else(|shared| (*shared).clone()))
+}
+
 /// Get a reference to the global default `StatsdClient` instance
 ///
 /// # Errors
@@ -142,6 +208,130 @@ pub fn get_global_default() -> Result<Arc<StatsdClient>, GlobalDefaultNotSet> {
     HOLDER.get().ok_or(GlobalDefaultNotSet)
 }
 
+/// Get a reference to the global default `StatsdClient` instance if it
+/// has been set, `None` otherwise
+///
+/// Unlike calling `is_global_default_set` followed by `get_global_default`,
+/// this checks for and gets the global default in a single step. This is
+/// useful for code that should only emit metrics when they've been configured.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{StatsdClient, NopMetricSink};
+///
+/// assert!(cadence_macros::try_global_default().is_none());
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// cadence_macros::set_global_default(client);
+///
+/// if let Some(client) = cadence_macros::try_global_default() {
+///     client.count("some.counter", 1).unwrap();
+/// }
+///
+/// assert!(cadence_macros::try_global_default().is_some());
+/// ```
+pub fn try_global_default() -> Option<Arc<StatsdClient>> {
+    HOLDER.get()
+}
+
+/// What macros do when they are used without a specific client before the
+/// global default `StatsdClient` has been set
+///
+/// See `set_unset_behavior` for more information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsetBehavior {
+    /// Panic. This is the default, matching how macros behaved before the
+    /// behavior could be configured, and catches metrics that are emitted
+    /// before they've been configured.
+    Panic = 0,
+    /// Print a warning to standard error the first time it happens and
+    /// otherwise do nothing.
+    Warn = 1,
+    /// Do nothing.
+    Silent = 2,
+}
+
+impl Default for UnsetBehavior {
+    fn default() -> Self {
+        UnsetBehavior::Panic
+    }
+}
+
+impl UnsetBehavior {
+    fn from_u8(val: u8) -> Self {
+        match val {
+            1 => UnsetBehavior::Warn,
+            2 => UnsetBehavior::Silent,
+            _ => UnsetBehavior::Panic,
+        }
+    }
+}
+
+/// Set what macros do when they are used without a specific client before
+/// the global default `StatsdClient` has been set
+///
+/// By default, macros panic when the global default hasn't been set. This
+/// catches code that emits metrics before they've been configured, but may be
+/// undesirable for applications where metrics are optional. Metrics emitted
+/// by macros while the global default isn't set are discarded unless the
+/// behavior is `UnsetBehavior::Panic`.
+///
+/// The default is `UnsetBehavior::Panic` rather than `UnsetBehavior::Silent`
+/// for backwards compatibility: macros have always panicked in this case and
+/// silently discarding metrics instead would hide the mistake from existing
+/// users. Applications that prefer to discard them need to opt in.
+///
+/// # Example
+///
+/// ```
+/// use cadence_macros::{statsd_count, UnsetBehavior};
+///
+/// // Catch metrics emitted before configuring them during development
+/// // but don't take down the application for them in production.
+/// if cfg!(debug_assertions) {
+///     cadence_macros::set_unset_behavior(UnsetBehavior::Warn);
+/// } else {
+///     cadence_macros::set_unset_behavior(UnsetBehavior::Silent);
+/// }
+///
+/// // Not sent anywhere since the global default isn't set
+/// statsd_count!("some.counter", 123);
+/// ```
+pub fn set_unset_behavior(behavior: UnsetBehavior) {
+    UNSET_BEHAVIOR.store(behavior as u8, Ordering::Release);
+}
+
+/// Get what macros do when they are used without a specific client before
+/// the global default `StatsdClient` has been set
+pub fn unset_behavior() -> UnsetBehavior {
+    UnsetBehavior::from_u8(UNSET_BEHAVIOR.load(Ordering::Acquire))
+}
+
+/// Get the global default `StatsdClient` for use by macros, applying the
+/// configured `UnsetBehavior` if it has not been set
+///
+/// This function is public to allow it to be used by the macros in this
+/// crate but it is not part of the public API and may change at any time.
+#[doc(hidden)]
+pub fn _global_default_for_macro() -> Option<Arc<StatsdClient>> {
+    let client = HOLDER.get();
+    if client.is_none() {
+        match unset_behavior() {
+            UnsetBehavior::Panic => panic!("{}", GlobalDefaultNotSet),
+            UnsetBehavior::Warn => {
+                if !UNSET_WARNED.swap(true, Ordering::AcqRel) {
+                    eprintln!("cadence-macros: {}, metrics will be discarded", GlobalDefaultNotSet);
+                }
+            }
+            UnsetBehavior::Silent => {}
+        }
+    }
+
+    client
+}
+
 /// Return true if the global default `StatsdClient` is set, false otherwise
 ///
 /// # Example
@@ -159,3 +349,75 @@ pub fn get_global_default() -> Result<Arc<StatsdClient>, GlobalDefaultNotSet> {
 pub fn is_global_default_set() -> bool {
     HOLDER.is_set()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{_global_default_for_macro, set_unset_behavior, unset_behavior, SingletonHolder, UnsetBehavior};
+    use std::panic;
+
+    // The global default is never set by these tests so macros would always
+    // apply the unset behavior. All behaviors are tested in a single test since
+    // the behavior is global.
+    #[test]
+    fn test_global_default_for_macro_unset_behavior() {
+        assert_eq!(UnsetBehavior::Panic, unset_behavior());
+        assert!(panic::catch_unwind(_global_default_for_macro).is_err());
+
+        set_unset_behavior(UnsetBehavior::Warn);
+        assert_eq!(UnsetBehavior::Warn, unset_behavior());
+        assert!(_global_default_for_macro().is_none());
+        assert!(_global_default_for_macro().is_none());
+
+        set_unset_behavior(UnsetBehavior::Silent);
+        assert_eq!(UnsetBehavior::Silent, unset_behavior());
+        assert!(_global_default_for_macro().is_none());
+
+        set_unset_behavior(UnsetBehavior::Panic);
+        assert!(panic::catch_unwind(_global_default_for_macro).is_err());
+    }
+
+    #[test]
+    fn test_singleton_holder_set() {
+        let holder = SingletonHolder::new();
+        assert!(!holder.is_set());
+        assert!(holder.get().is_none());
+
+        holder.set(1);
+        holder.set(2);
+
+        assert!(holder.is_set());
+        assert_eq!(Some(1), holder.get().as_deref().copied());
+    }
+
+    #[test]
+    fn test_singleton_holder_try_set() {
+        let holder = SingletonHolder::new();
+
+        assert_eq!(Ok(()), holder.try_set(1));
+        assert_eq!(Err(2), holder.try_set(2));
+        assert_eq!(Some(1), holder.get().as_deref().copied());
+    }
+
+    #[test]
+    fn test_singleton_holder_replace() {
+        let holder = SingletonHolder::new();
+        assert!(holder.replace(1).is_none());
+
+        let previous = holder.replace(2);
+
+        assert_eq!(Some(1), previous.as_deref().copied());
+        assert_eq!(Some(2), holder.get().as_deref().copied());
+    }
+
+    #[test]
+    fn test_singleton_holder_replace_outstanding_reference() {
+        let holder = SingletonHolder::new();
+        holder.set(1);
+
+        let current = holder.get().unwrap();
+        holder.replace(2);
+
+        assert_eq!(1, *current);
+        assert_eq!(Some(2), holder.get().as_deref().copied());
+    }
+}
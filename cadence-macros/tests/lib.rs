@@ -44,21 +44,27 @@ fn test_macros() {
     fn test_counter_macros() {
         statsd_count!("some.counter", 123);
         statsd_count!("some.counter", 123, "host" => "web01.example.com", "slice" => "a");
+        statsd_count!("some.counter", 124, "host" => "web02.example.com", rate = 1.0);
+        statsd_count!("some.counter", 125u64, rate = 1.0);
 
         let metrics = read_all_metrics();
         assert!(metrics.contains("my.prefix.some.counter:123|c"));
         assert!(metrics.contains("my.prefix.some.counter:123|c|#host:web01.example.com,slice:a"));
+        assert!(metrics.contains("my.prefix.some.counter:124|c|#host:web02.example.com"));
+        assert!(metrics.contains("my.prefix.some.counter:125|c"));
     }
 
     fn test_timer_macros() {
         statsd_time!("some.timer", 334);
         statsd_time!("some.timer", 334, "type" => "api", "status" => "200");
         statsd_time!("some.timer", Duration::from_millis(334), "type" => "web");
+        statsd_time!("some.timer", Duration::from_millis(335), "type" => "web", "status" => "500", rate = 1.0);
 
         let metrics = read_all_metrics();
         assert!(metrics.contains("my.prefix.some.timer:334|ms"));
         assert!(metrics.contains("my.prefix.some.timer:334|ms|#type:api,status:200"));
         assert!(metrics.contains("my.prefix.some.timer:334|ms|#type:web"));
+        assert!(metrics.contains("my.prefix.some.timer:335|ms|#type:web,status:500"));
     }
 
     fn test_gauge_macros() {
@@ -86,12 +92,16 @@ fn test_macros() {
         statsd_histogram!("some.histogram", 223, "method" => "auth", "result" => "error");
         statsd_histogram!("some.histogram", Duration::from_nanos(223), "method" => "list");
         statsd_histogram!("some.histogram", 22.3, "method" => "list");
+        statsd_histogram!("some.histogram", Duration::from_millis(5), "route" => "/api", rate = 1.0,);
+        statsd_histogram!("some.histogram", 224, "route" => "/api", rate = 0.0);
 
         let metrics = read_all_metrics();
         assert!(metrics.contains("my.prefix.some.histogram:223|h"));
         assert!(metrics.contains("my.prefix.some.histogram:223|h|#method:auth,result:error"));
         assert!(metrics.contains("my.prefix.some.histogram:223|h|#method:list"));
         assert!(metrics.contains("my.prefix.some.histogram:22.3|h|#method:list"));
+        assert!(metrics.contains("my.prefix.some.histogram:5000000|h|#route:/api"));
+        assert!(!metrics.iter().any(|m| m.starts_with("my.prefix.some.histogram:224")));
     }
 
     fn test_distribution_macros() {
@@ -122,3 +132,58 @@ fn test_macros() {
     test_distribution_macros();
     test_set_macros();
 }
+
+#[test]
+fn test_macros_with_client() {
+    // Uses a separate client instead of the global default so metrics
+    // from this test don't end up in the channel for the test above.
+    let (rx, sink) = SpyMetricSink::new();
+    let client = StatsdClient::from_sink("other.prefix", sink);
+    let client_ref = &client;
+
+    statsd_count!(client = client, "some.counter", 123);
+    statsd_time!(client = client, "some.timer", 334, "type" => "api");
+    statsd_gauge!(client = client_ref, "some.gauge", 42, "org" => "123", "service" => "gateway");
+    statsd_meter!(client = client, "some.meter", 1);
+    statsd_histogram!(client = client, "some.histogram", 22.3, "method" => "list");
+    statsd_distribution!(client = client, "some.distribution", 22);
+    statsd_set!(client = client_ref, "some.set", 348, "service" => "user");
+
+    let mut metrics = HashSet::new();
+    while let Ok(v) = rx.try_recv() {
+        metrics.insert(String::from_utf8(v).unwrap());
+    }
+
+    assert_eq!(7, metrics.len());
+    assert!(metrics.contains("other.prefix.some.counter:123|c"));
+    assert!(metrics.contains("other.prefix.some.timer:334|ms|#type:api"));
+    assert!(metrics.contains("other.prefix.some.gauge:42|g|#org:123,service:gateway"));
+    assert!(metrics.contains("other.prefix.some.meter:1|m"));
+    assert!(metrics.contains("other.prefix.some.histogram:22.3|h|#method:list"));
+    assert!(metrics.contains("other.prefix.some.distribution:22|d"));
+    assert!(metrics.contains("other.prefix.some.set:348|s|#service:user"));
+}
+
+#[test]
+fn test_macros_with_client_and_sample_rate() {
+    let (rx, sink) = SpyMetricSink::new();
+    let client = StatsdClient::from_sink("other.prefix", sink);
+
+    // Metrics are randomly sampled so send enough of them that at least one of
+    // each is all but guaranteed to be sent.
+    for _ in 0..64 {
+        statsd_count!(client = client, "some.counter", 1, "host" => "web01", rate = 0.5);
+        statsd_time!(client = client, "some.timer", Duration::from_millis(5), "type" => "api", rate = 0.5);
+        statsd_histogram!(client = &client, "some.histogram", 22.3, "method" => "list", "result" => "ok", rate = 0.5);
+    }
+
+    let mut metrics = HashSet::new();
+    while let Ok(v) = rx.try_recv() {
+        metrics.insert(String::from_utf8(v).unwrap());
+    }
+
+    assert_eq!(3, metrics.len());
+    assert!(metrics.contains("other.prefix.some.counter:1|c|@0.5|#host:web01"));
+    assert!(metrics.contains("other.prefix.some.timer:5|ms|@0.5|#type:api"));
+    assert!(metrics.contains("other.prefix.some.histogram:22.3|h|@0.5|#method:list,result:ok"));
+}
@@ -1,13 +1,17 @@
 use cadence::prelude::*;
 use cadence::{
-    BufferedUdpMetricSink, Counter, Distribution, Gauge, Histogram, Meter, NopMetricSink, QueuingMetricSink, Set,
-    StatsdClient, Timer, UdpMetricSink, DEFAULT_PORT,
+    BufferedUdpMetricSink, Counter, Distribution, Gauge, Histogram, Meter, NopMetricSink, QueuingMetricSink,
+    QueuingMetricSinkBuilder, Set, StatsdClient, Timer, UdpMetricSink, DEFAULT_PORT,
 };
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 
 const TARGET_HOST: (&str, u16) = ("127.0.0.1", DEFAULT_PORT);
 const QUEUE_SIZE: usize = 512 * 1024;
+const CONTENDING_THREADS: usize = 4;
 
 fn new_nop_client() -> StatsdClient {
     StatsdClient::from_sink("client.bench", NopMetricSink)
@@ -46,6 +50,16 @@ fn new_queuing_buffered_udp_client(bound: Option<usize>) -> StatsdClient {
     StatsdClient::from_sink("client.bench", queuing)
 }
 
+fn new_queuing_batched_buffered_udp_client(batch_size: usize) -> StatsdClient {
+    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    let buffered = BufferedUdpMetricSink::from(TARGET_HOST, socket).unwrap();
+    let queuing = QueuingMetricSinkBuilder::new()
+        .with_batch_size(batch_size)
+        .build(buffered);
+
+    StatsdClient::from_sink("client.bench", queuing)
+}
+
 fn benchmark_statsdclient_nop(c: &mut Criterion) {
     c.bench_function("statsdclient_nop", |b| {
         let client = new_nop_client();
@@ -64,6 +78,32 @@ fn benchmark_statsdclient_nop(c: &mut Criterion) {
     });
 }
 
+fn benchmark_statsdclient_nop_contended(c: &mut Criterion) {
+    // Emit metrics while other threads emit metrics with clones of the same
+    // client to measure the cost of sharing the client (and its sink) between
+    // threads.
+    c.bench_function("statsdclient_nop_contended", |b| {
+        let client = new_nop_client();
+        let done = Arc::new(AtomicBool::new(false));
+        let threads: Vec<_> = (0..CONTENDING_THREADS)
+            .map(|_| {
+                let client = client.clone();
+                let done = done.clone();
+                thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        let _ = client.count("some.counter", 4);
+                    }
+                })
+            })
+            .collect();
+
+        b.iter(|| client.count("some.counter", 4));
+
+        done.store(true, Ordering::Relaxed);
+        threads.into_iter().for_each(|t| t.join().unwrap());
+    });
+}
+
 fn benchmark_statsdclient_udp(c: &mut Criterion) {
     c.bench_function("statsdclient_udp", |b| {
         let client = new_udp_client();
@@ -134,6 +174,11 @@ fn benchmark_statsdclient_queuing(c: &mut Criterion) {
         })
     });
 
+    c.bench_function("statsdclient_queuing_batched_buffered_udp", |b| {
+        let client = new_queuing_batched_buffered_udp_client(64);
+        b.iter(|| client.count("some.counter", 4));
+    });
+
     c.bench_function("statsdclient_queuing_nop_back_pressure", |b| {
         let client = new_queuing_nop_client(Some(QUEUE_SIZE));
         b.iter(|| client.count("some.counter", 4));
@@ -167,6 +212,28 @@ fn benchmark_statsdclient_queuing(c: &mut Criterion) {
     });
 }
 
+fn benchmark_statsdclient_batch(c: &mut Criterion) {
+    let counts: Vec<(String, i64)> = (0..100).map(|i| (format!("some.counter.{}", i), i)).collect();
+
+    c.bench_function("statsdclient_buffered_udp_count_loop", |b| {
+        let client = new_buffered_udp_client();
+        b.iter(|| {
+            for (key, value) in counts.iter() {
+                client.count(key, *value).unwrap();
+            }
+        });
+    });
+
+    c.bench_function("statsdclient_buffered_udp_count_batch", |b| {
+        let client = new_buffered_udp_client();
+        b.iter(|| {
+            client
+                .count_batch(counts.iter().map(|(key, value)| (key.as_str(), *value)))
+                .unwrap();
+        });
+    });
+}
+
 fn benchmark_new_metric_obj(c: &mut Criterion) {
     c.bench_function("counter_new", |b| b.iter(|| Counter::new("prefix", "some.counter", 5)));
     c.bench_function("timer_new", |b| b.iter(|| Timer::new("prefix", "some.timer", 5)));
@@ -193,9 +260,11 @@ fn benchmark_new_metric_obj(c: &mut Criterion) {
 criterion_group!(
     benches,
     benchmark_statsdclient_nop,
+    benchmark_statsdclient_nop_contended,
     benchmark_statsdclient_udp,
     benchmark_statsdclient_buffered_udp,
     benchmark_statsdclient_queuing,
+    benchmark_statsdclient_batch,
     benchmark_new_metric_obj
 );
 
@@ -10,9 +10,21 @@
 // except according to those terms.
 
 use crate::client::{MetricBackend, StatsdClient};
-use crate::types::{Metric, MetricError, MetricResult};
+use crate::sampling;
+use crate::types::{
+    Cardinality, Counter, ErrorKind, Metric, MetricError, MetricKind, MetricResult, ScalingMode, TagSet,
+};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt::{self, Write};
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::panic::RefUnwindSafe;
+
+// Largest timestamp accepted for metrics, the last second that can be
+// represented with ten digits (in the year 2286). Anything larger is almost
+// certainly a timestamp in milliseconds or nanoseconds by mistake.
+const MAX_TIMESTAMP_SECS: u64 = 9_999_999_999;
 
 /// Type of metric that knows how to display itself
 #[derive(Debug, Clone, Copy)]
@@ -40,6 +52,136 @@ impl fmt::Display for MetricType {
     }
 }
 
+/// Codes used to identify the type of each metric when it's emitted.
+///
+/// Standard Statsd servers use `c` for counters, `ms` for timers, `g` for
+/// gauges, etc. Some servers use different codes for some types of metrics.
+/// A `TypeCodeMap` can be given to `StatsdClientBuilder::with_type_codes()`
+/// to override the code used for any type of metric. Codes that aren't
+/// overridden use the standard values.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{StatsdClient, NopMetricSink, Metric, TypeCodeMap};
+///
+/// let codes = TypeCodeMap::new().with_timer("t").unwrap();
+/// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+///     .with_type_codes(codes)
+///     .build();
+///
+/// let res = client.time("some.timer", 34);
+/// assert_eq!("my.prefix.some.timer:34|t", res.unwrap().as_metric_str());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeCodeMap {
+    counter: String,
+    timer: String,
+    gauge: String,
+    meter: String,
+    histogram: String,
+    set: String,
+    distribution: String,
+}
+
+impl TypeCodeMap {
+    /// Create a new map using the standard code for each type of metric.
+    pub fn new() -> Self {
+        TypeCodeMap {
+            counter: MetricType::Counter.to_string(),
+            timer: MetricType::Timer.to_string(),
+            gauge: MetricType::Gauge.to_string(),
+            meter: MetricType::Meter.to_string(),
+            histogram: MetricType::Histogram.to_string(),
+            set: MetricType::Set.to_string(),
+            distribution: MetricType::Distribution.to_string(),
+        }
+    }
+
+    /// Set the code used for counters, `c` by default.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the code is empty or contains a character used
+    /// to separate parts of a metric: `|`, `:`, `#`, or a newline.
+    pub fn with_counter(mut self, code: &str) -> MetricResult<Self> {
+        self.counter = Self::validate(code)?;
+        Ok(self)
+    }
+
+    /// Set the code used for timers, `ms` by default. See `.with_counter()`
+    /// for valid codes.
+    pub fn with_timer(mut self, code: &str) -> MetricResult<Self> {
+        self.timer = Self::validate(code)?;
+        Ok(self)
+    }
+
+    /// Set the code used for gauges, `g` by default. See `.with_counter()`
+    /// for valid codes.
+    pub fn with_gauge(mut self, code: &str) -> MetricResult<Self> {
+        self.gauge = Self::validate(code)?;
+        Ok(self)
+    }
+
+    /// Set the code used for meters, `m` by default. See `.with_counter()`
+    /// for valid codes.
+    pub fn with_meter(mut self, code: &str) -> MetricResult<Self> {
+        self.meter = Self::validate(code)?;
+        Ok(self)
+    }
+
+    /// Set the code used for histograms, `h` by default. See `.with_counter()`
+    /// for valid codes.
+    pub fn with_histogram(mut self, code: &str) -> MetricResult<Self> {
+        self.histogram = Self::validate(code)?;
+        Ok(self)
+    }
+
+    /// Set the code used for sets, `s` by default. See `.with_counter()`
+    /// for valid codes.
+    pub fn with_set(mut self, code: &str) -> MetricResult<Self> {
+        self.set = Self::validate(code)?;
+        Ok(self)
+    }
+
+    /// Set the code used for distributions, `d` by default. See `.with_counter()`
+    /// for valid codes.
+    pub fn with_distribution(mut self, code: &str) -> MetricResult<Self> {
+        self.distribution = Self::validate(code)?;
+        Ok(self)
+    }
+
+    fn validate(code: &str) -> MetricResult<String> {
+        if code.is_empty() || code.contains(['|', ':', '#', '\n']) {
+            return Err(MetricError::from((
+                ErrorKind::InvalidInput,
+                "type code must not be empty or contain '|', ':', '#', or a newline",
+            )));
+        }
+
+        Ok(code.to_string())
+    }
+
+    fn code(&self, type_: MetricType) -> &str {
+        match type_ {
+            MetricType::Counter => &self.counter,
+            MetricType::Timer => &self.timer,
+            MetricType::Gauge => &self.gauge,
+            MetricType::Meter => &self.meter,
+            MetricType::Histogram => &self.histogram,
+            MetricType::Set => &self.set,
+            MetricType::Distribution => &self.distribution,
+        }
+    }
+}
+
+impl Default for TypeCodeMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Holder for primitive metric values that knows how to display itself
 ///
 /// This struct is internal to how various types that are valid for each type
@@ -55,6 +197,7 @@ pub enum MetricValue {
     PackedUnsigned(Vec<u64>),
     Float(f64),
     PackedFloat(Vec<f64>),
+    Str(String),
 }
 
 impl MetricValue {
@@ -66,6 +209,57 @@ impl MetricValue {
             _ => 1,
         }
     }
+
+    // True if every number in this value is greater than zero. String values
+    // aren't numbers and so are always considered positive.
+    pub(crate) fn is_positive(&self) -> bool {
+        match self {
+            Self::Signed(x) => *x > 0,
+            Self::PackedSigned(x) => x.iter().all(|v| *v > 0),
+            Self::Unsigned(x) => *x > 0,
+            Self::PackedUnsigned(x) => x.iter().all(|v| *v > 0),
+            Self::Float(x) => *x > 0.0,
+            Self::PackedFloat(x) => x.iter().all(|v| *v > 0.0),
+            Self::Str(_) => true,
+        }
+    }
+
+    // Value multiplied by the inverse of a sample rate, rounding integers to
+    // the nearest integer. String values aren't numbers and aren't changed.
+    fn scaled(&self, rate: f64) -> MetricValue {
+        let scale = |x: f64| x / rate;
+        match self {
+            Self::Signed(x) => Self::Signed(scale(*x as f64).round() as i64),
+            Self::PackedSigned(x) => Self::PackedSigned(x.iter().map(|v| scale(*v as f64).round() as i64).collect()),
+            Self::Unsigned(x) => Self::Unsigned(scale(*x as f64).round() as u64),
+            Self::PackedUnsigned(x) => {
+                Self::PackedUnsigned(x.iter().map(|v| scale(*v as f64).round() as u64).collect())
+            }
+            Self::Float(x) => Self::Float(scale(*x)),
+            Self::PackedFloat(x) => Self::PackedFloat(x.iter().map(|v| scale(*v)).collect()),
+            Self::Str(x) => Self::Str(x.clone()),
+        }
+    }
+
+    // Expected number of bytes needed to display this value. String values
+    // are an exact size, numbers are assumed to need about ten bytes each.
+    fn size_hint(&self) -> usize {
+        match self {
+            Self::Str(x) => x.len(),
+            _ => 10 * self.count(),
+        }
+    }
+}
+
+// Write a float with a fixed number of decimal places, removing any trailing
+// zeros (and the decimal point if nothing is left after it).
+fn write_float_precision(out: &mut String, val: f64, precision: usize) {
+    let start = out.len();
+    let _ = write!(out, "{:.*}", precision, val);
+    if out[start..].contains('.') {
+        let trimmed = out.trim_end_matches('0').trim_end_matches('.').len();
+        out.truncate(trimmed);
+    }
 }
 
 fn write_value<T>(f: &mut fmt::Formatter<'_>, vals: &[T]) -> fmt::Result
@@ -91,8 +285,38 @@ impl fmt::Display for MetricValue {
             MetricValue::PackedUnsigned(v) => write_value(f, v),
             MetricValue::Float(v) => v.fmt(f),
             MetricValue::PackedFloat(v) => write_value(f, v),
+            MetricValue::Str(v) => v.fmt(f),
+        }
+    }
+}
+
+type TransformFn = dyn Fn(&str) -> Cow<'_, str> + Sync + Send + RefUnwindSafe;
+
+/// Function applied to the full name (prefix and key) of every metric
+/// emitted by a client when it's formatted.
+pub(crate) struct NameTransform {
+    transform: Box<TransformFn>,
+}
+
+impl NameTransform {
+    pub(crate) fn new<F>(transform: F) -> Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + Sync + Send + RefUnwindSafe + 'static,
+    {
+        NameTransform {
+            transform: Box::new(transform),
         }
     }
+
+    fn apply<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        (self.transform)(name)
+    }
+}
+
+impl fmt::Debug for NameTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NameTransform {{ ... }}")
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -102,11 +326,23 @@ pub(crate) struct MetricFormatter<'a> {
     val: MetricValue,
     type_: MetricType,
     tags: Vec<(Option<&'a str>, &'a str)>,
+    sorted_tags: bool,
+    always_tag_separator: bool,
+    trim: bool,
+    name_transform: Option<&'a NameTransform>,
+    type_codes: Option<&'a TypeCodeMap>,
+    float_precision: Option<usize>,
+    float_decimal_point: bool,
     // Datadog extensions:
     // https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=metrics#the-dogstatsd-protocol
     timestamp: Option<u64>,
     sampling_rate: Option<f64>,
+    // Whether the client should randomly decide to send this metric based
+    // on the sampling rate or just forward the rate to the server.
+    sampled: bool,
+    scaling: ScalingMode,
     container_id: Option<&'a str>,
+    cardinality: Option<Cardinality>,
     base_size: usize,
     kv_size: usize,
 }
@@ -144,63 +380,278 @@ impl<'a> MetricFormatter<'a> {
 
     #[rustfmt::skip]
     fn from_val(prefix: &'a str, key: &'a str, val: MetricValue, type_: MetricType) -> Self {
-        let value_count = val.count();
+        let value_size = val.size_hint();
         MetricFormatter {
             prefix,
             key,
             type_,
             val,
             tags: Vec::new(),
+            sorted_tags: false,
+            always_tag_separator: false,
+            trim: false,
+            name_transform: None,
+            type_codes: None,
+            float_precision: None,
+            float_decimal_point: false,
             // keep track of the number of bytes we expect to use for both the key-value
             // part of the tags for this metric as well as the base metric (name, value,
             // and type). incrementing these counters when tags are added saves us from
             // having to loop through the tags to count the expected number of bytes to
             // allocate.
             kv_size: 0,
-            base_size: prefix.len() + key.len() + 1 /* : */ + 10 * value_count /* value(s) */ + 1 /* | */ + 2, /* type */
+            base_size: prefix.len() + key.len() + 1 /* : */ + value_size /* value(s) */ + 1 /* | */ + 2, /* type */
             timestamp: None,
             sampling_rate: None,
+            sampled: false,
+            scaling: ScalingMode::ServerSide,
             container_id: None,
+            cardinality: None,
+        }
+    }
+
+    pub(crate) fn kind(&self) -> MetricKind {
+        match self.type_ {
+            MetricType::Counter => MetricKind::Counter,
+            MetricType::Timer => MetricKind::Timer,
+            MetricType::Gauge => MetricKind::Gauge,
+            MetricType::Meter => MetricKind::Meter,
+            MetricType::Histogram => MetricKind::Histogram,
+            MetricType::Set => MetricKind::Set,
+            MetricType::Distribution => MetricKind::Distribution,
         }
     }
 
-    fn with_tag(&mut self, key: &'a str, value: &'a str) {
+    pub(crate) fn with_tag(&mut self, key: &'a str, value: &'a str) {
         self.tags.push((Some(key), value));
         self.kv_size += key.len() + 1 /* : */ + value.len();
     }
 
-    fn with_tag_value(&mut self, value: &'a str) {
+    pub(crate) fn with_tag_value(&mut self, value: &'a str) {
         self.tags.push((None, value));
         self.kv_size += value.len();
     }
 
+    pub(crate) fn tags(&self) -> &[(Option<&'a str>, &'a str)] {
+        &self.tags
+    }
+
+    pub(crate) fn clear_tags(&mut self) {
+        self.tags.clear();
+        self.kv_size = 0;
+    }
+
+    pub(crate) fn with_sorted_tags(&mut self) {
+        self.sorted_tags = true;
+    }
+
+    pub(crate) fn with_always_tag_separator(&mut self) {
+        self.always_tag_separator = true;
+    }
+
+    pub(crate) fn with_trim(&mut self) {
+        self.trim = true;
+    }
+
+    pub(crate) fn with_name_transform(&mut self, transform: &'a NameTransform) {
+        self.name_transform = Some(transform);
+    }
+
+    pub(crate) fn with_type_codes(&mut self, codes: &'a TypeCodeMap) {
+        self.type_codes = Some(codes);
+    }
+
+    pub(crate) fn with_float_precision(&mut self, precision: usize) {
+        self.float_precision = Some(precision);
+    }
+
+    pub(crate) fn with_float_decimal_point(&mut self) {
+        self.float_decimal_point = true;
+    }
+
     fn with_timestamp(&mut self, timestamp: u64) {
         self.timestamp = Some(timestamp);
     }
 
-    fn with_container_id(&mut self, container_id: &'a str) {
+    pub(crate) fn with_container_id(&mut self, container_id: &'a str) {
         self.container_id = Some(container_id);
     }
 
+    pub(crate) fn with_cardinality(&mut self, cardinality: Cardinality) {
+        self.cardinality = Some(cardinality);
+    }
+
     fn with_sampling_rate(&mut self, rate: f64) {
         self.sampling_rate = Some(rate);
+        self.sampled = false;
+    }
+
+    pub(crate) fn with_sample_rate(&mut self, rate: f64) {
+        // Metrics sampled at a rate of 1 are always sent so there's no need
+        // to tell the server about the rate. Any other rate is kept, even if
+        // it's invalid, so that it can be rejected by `.validate()`.
+        if rate == 1.0 {
+            self.sampling_rate = None;
+            self.sampled = false;
+        } else {
+            self.sampling_rate = Some(rate);
+            self.sampled = true;
+        }
+    }
+
+    fn with_scaling(&mut self, scaling: ScalingMode) {
+        self.scaling = scaling;
+    }
+
+    // Sample rate to scale the value of this metric by before sending it,
+    // `None` if the value is sent unchanged. A rate of zero can't be scaled
+    // by and is sent unchanged.
+    fn client_side_rate(&self) -> Option<f64> {
+        match (self.scaling, self.sampling_rate) {
+            (ScalingMode::ClientSide, Some(rate)) if rate > 0.0 => Some(rate),
+            _ => None,
+        }
+    }
+
+    /// Rate that the client should use to randomly decide if this metric
+    /// is sent, `None` if it should always be sent.
+    pub(crate) fn sample_rate(&self) -> Option<f64> {
+        if self.sampled {
+            self.sampling_rate
+        } else {
+            None
+        }
+    }
+
+    // Key of the metric, without leading or trailing whitespace if trimming
+    // is enabled.
+    fn key(&self) -> &'a str {
+        if self.trim {
+            self.key.trim()
+        } else {
+            self.key
+        }
+    }
+
+    fn write_name(&self, out: &mut String) {
+        match self.name_transform {
+            Some(transform) => {
+                let name = format!("{}{}", self.prefix, self.key());
+                out.push_str(&transform.apply(&name));
+            }
+            None => {
+                out.push_str(self.prefix);
+                out.push_str(self.key());
+            }
+        }
     }
 
     fn write_base_metric(&self, out: &mut String) {
-        let _ = write!(out, "{}{}:{}|{}", self.prefix, self.key, self.val, self.type_);
+        self.write_name(out);
+        out.push(':');
+
+        let scaled;
+        let val = match self.client_side_rate() {
+            Some(rate) => {
+                scaled = self.val.scaled(rate);
+                &scaled
+            }
+            None => &self.val,
+        };
+
+        self.write_value(out, val);
+        out.push('|');
+
+        match self.type_codes {
+            Some(codes) => out.push_str(codes.code(self.type_)),
+            None => {
+                let _ = write!(out, "{}", self.type_);
+            }
+        }
+    }
+
+    /// Value rendered the same way it would be in the formatted metric,
+    /// including any float precision or decimal point settings.
+    pub(crate) fn format_value(&self, val: &MetricValue) -> String {
+        let mut out = String::new();
+        self.write_value(&mut out, val);
+        out
+    }
+
+    fn write_value(&self, out: &mut String, val: &MetricValue) {
+        match val {
+            MetricValue::Float(v) => self.write_float(out, *v),
+            MetricValue::PackedFloat(vals) => {
+                for (i, v) in vals.iter().enumerate() {
+                    if i > 0 {
+                        out.push(':');
+                    }
+                    self.write_float(out, *v);
+                }
+            }
+            val => {
+                let _ = write!(out, "{}", val);
+            }
+        }
+    }
+
+    fn write_float(&self, out: &mut String, val: f64) {
+        let start = out.len();
+        match self.float_precision {
+            Some(precision) => write_float_precision(out, val, precision),
+            None => {
+                let _ = write!(out, "{}", val);
+            }
+        }
+
+        // Whole numbers are written without a decimal point by default, which
+        // some servers treat as an integer rather than a float.
+        if self.float_decimal_point && val.is_finite() && !out[start..].contains('.') {
+            out.push_str(".0");
+        }
     }
 
     fn write_sampling_rate(&self, out: &mut String) {
+        if self.scaling == ScalingMode::ClientSide {
+            return;
+        }
+
         if let Some(rate) = self.sampling_rate {
             // See https://github.com/DataDog/datadog-go/blob/v5.5.0/statsd/format.go#L28
             let _ = write!(out, "|@{}", rate);
         }
     }
 
+    // Tags of the metric, without leading or trailing whitespace in keys or
+    // values if trimming is enabled.
+    fn trimmed_tags(&self) -> Cow<'_, [(Option<&'a str>, &'a str)]> {
+        if self.trim {
+            Cow::Owned(self.tags.iter().map(|&(k, v)| (k.map(str::trim), v.trim())).collect())
+        } else {
+            Cow::Borrowed(&self.tags)
+        }
+    }
+
     fn write_tags(&self, out: &mut String) {
-        if !self.tags.is_empty() {
+        let tags = self.trimmed_tags();
+
+        if self.sorted_tags {
+            let mut tags = tags.into_owned();
+            // Sort key-value tags by key and then value, followed by value-only
+            // tags sorted by value.
+            tags.sort_unstable_by(|a, b| a.0.is_none().cmp(&b.0.is_none()).then_with(|| a.cmp(b)));
+            self.write_tag_list(out, &tags);
+        } else {
+            self.write_tag_list(out, &tags);
+        }
+    }
+
+    fn write_tag_list(&self, out: &mut String, tags: &[(Option<&str>, &str)]) {
+        // Some parsers expect the tag section to always be present, even if
+        // it's empty, while others reject an empty tag section.
+        if !tags.is_empty() || self.always_tag_separator {
             out.push_str(Self::TAG_PREFIX);
-            for (i, &(key, value)) in self.tags.iter().enumerate() {
+            for (i, &(key, value)) in tags.iter().enumerate() {
                 if i > 0 {
                     out.push(',');
                 }
@@ -227,9 +678,21 @@ impl<'a> MetricFormatter<'a> {
         }
     }
 
+    fn write_cardinality(&self, out: &mut String) {
+        if let Some(cardinality) = self.cardinality {
+            // Written after the container ID and before the timestamp, per
+            // the dogstatsd protocol linked above.
+            let _ = write!(out, "|card:{}", cardinality);
+        }
+    }
+
     fn tag_size_hint(&self) -> usize {
         if self.tags.is_empty() {
-            return 0;
+            return if self.always_tag_separator {
+                Self::TAG_PREFIX.len()
+            } else {
+                0
+            };
         }
 
         // prefix, keys and values, commas
@@ -263,12 +726,77 @@ impl<'a> MetricFormatter<'a> {
         }
     }
 
+    fn cardinality_size_hint(&self) -> usize {
+        if let Some(_cardinality) = self.cardinality {
+            /* |card: */
+            6 + /* orchestrator */ 12
+        } else {
+            0
+        }
+    }
+
     fn size_hint(&self) -> usize {
         self.base_size
             + self.sampling_rate_size_hint()
             + self.tag_size_hint()
             + self.timestamp_size_hint()
             + self.container_id_size_hint()
+            + self.cardinality_size_hint()
+    }
+
+    /// Hash of the full name and tags of this metric, ignoring the order
+    /// of the tags. Metrics with the same name and tags are part of the same
+    /// series (for the purposes of counting how many unique series there are)
+    /// regardless of their values.
+    pub(crate) fn series_hash(&self) -> u64 {
+        let tags = self.trimmed_tags().iter().fold(0u64, |acc, tag| {
+            let mut hasher = DefaultHasher::new();
+            tag.hash(&mut hasher);
+            acc.wrapping_add(hasher.finish())
+        });
+
+        let mut hasher = DefaultHasher::new();
+        match self.name_transform {
+            Some(transform) => {
+                let name = format!("{}{}", self.prefix, self.key());
+                hasher.write(transform.apply(&name).as_bytes());
+            }
+            None => {
+                hasher.write(self.prefix.as_bytes());
+                hasher.write(self.key().as_bytes());
+            }
+        }
+        hasher.write_u64(tags);
+        hasher.finish()
+    }
+
+    /// Full name and tags of this metric in the same format they would be
+    /// emitted, without the value or any other fields.
+    pub(crate) fn series_name(&self) -> String {
+        let mut out = String::with_capacity(self.prefix.len() + self.key.len() + self.tag_size_hint());
+        self.write_name(&mut out);
+        self.write_tags(&mut out);
+        out
+    }
+
+    /// Return an error if this metric can't be formatted into something a
+    /// Statsd server will accept, such as when both the prefix and key are
+    /// empty and the metric would otherwise start with `:`.
+    pub(crate) fn validate(&self) -> MetricResult<()> {
+        if self.prefix.is_empty() && self.key().is_empty() {
+            return Err(MetricError::from((
+                ErrorKind::InvalidInput,
+                "metric name must not be empty",
+            )));
+        }
+
+        // Rates set on individual metrics are validated when they're set but
+        // a default rate from the client is only validated here.
+        if let Some(rate) = self.sample_rate() {
+            sampling::validate_rate(rate)?;
+        }
+
+        Ok(())
     }
 
     pub(crate) fn format(&self) -> String {
@@ -278,6 +806,7 @@ impl<'a> MetricFormatter<'a> {
         self.write_sampling_rate(&mut metric_string);
         self.write_tags(&mut metric_string);
         self.write_container_id(&mut metric_string);
+        self.write_cardinality(&mut metric_string);
         self.write_timestamp(&mut metric_string);
         metric_string
     }
@@ -444,153 +973,497 @@ where
         self
     }
 
-    /// Add tags to this metric.
-    pub(crate) fn with_tags<V>(mut self, tags: V) -> Self
-    where
-        V: IntoIterator<Item = (Option<&'m str>, &'m str)>,
-    {
-        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
-            for tag in tags.into_iter() {
-                match tag {
-                    (Some(key), value) => formatter.with_tag(key, value),
-                    (None, value) => formatter.with_tag_value(value),
-                }
-            }
-        }
-
-        self
-    }
-
-    /// Add a container_id to this metric.
-    pub fn with_container_id(mut self, container_id: &'m str) -> Self {
-        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
-            formatter.with_container_id(container_id);
-        }
-        self
-    }
-
-    pub(crate) fn with_container_id_opt(mut self, container_id: Option<&'m str>) -> Self {
-        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
-            if let Some(container_id) = container_id {
-                formatter.with_container_id(container_id);
-            }
-        }
-        self
-    }
-
-    /// Add a UNIX timestamp in seconds to this metric.
+    /// Add multiple key-value tags to this metric.
+    ///
+    /// This is equivalent to calling `.with_tag()` for each key and value and
+    /// is useful when the tags come from a collection built elsewhere.
+    ///
     /// # Example
     ///
     /// ```
     /// use cadence::prelude::*;
     /// use cadence::{StatsdClient, NopMetricSink, Metric};
-    /// use std::time::{SystemTime, UNIX_EPOCH};
     ///
     /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
-    /// let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    /// let tags = vec![("user", "authenticated"), ("region", "us-east-1")];
     /// let res = client.count_with_tags("some.key", 1)
-    ///   .with_timestamp(timestamp)
-    ///   .try_send();
+    ///    .with_tags(tags)
+    ///    .try_send();
     ///
     /// assert_eq!(
-    ///   "some.prefix.some.key:1|c|T".to_string() + &timestamp.to_string(),
-    ///  res.unwrap().as_metric_str()
+    ///    "some.prefix.some.key:1|c|#user:authenticated,region:us-east-1",
+    ///    res.unwrap().as_metric_str()
     /// );
     /// ```
-    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+    pub fn with_tags<I>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = (&'m str, &'m str)>,
+    {
         if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
-            formatter.with_timestamp(timestamp);
+            for (key, value) in tags {
+                formatter.with_tag(key, value);
+            }
         }
-
         self
     }
 
-    /// Add a sampling rate to this metric.
+    /// Add multiple value tags to this metric.
     ///
-    /// The sampling rate is a float between 0 and 1 that determines the rate at which
-    /// the metric is sampled. For example, a sampling rate of 0.5 would mean that the
-    /// metric is sent 50% of the time. The sampling has to be done by the caller, cadence
-    /// will simply forward it to the backend.
+    /// This is equivalent to calling `.with_tag_value()` for each value.
     ///
     /// # Example
+    ///
     /// ```
     /// use cadence::prelude::*;
     /// use cadence::{StatsdClient, NopMetricSink, Metric};
     ///
     /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
-    /// let res = client.distribution_with_tags("some.key", 1)
-    ///  .with_sampling_rate(0.5)
-    ///  .try_send();
+    /// let res = client.count_with_tags("some.key", 4)
+    ///    .with_tag_values(["beta-testing", "canary"])
+    ///    .try_send();
     ///
     /// assert_eq!(
-    ///  "some.prefix.some.key:1|d|@0.5",
-    ///  res.unwrap().as_metric_str()
+    ///    "some.prefix.some.key:4|c|#beta-testing,canary",
+    ///    res.unwrap().as_metric_str()
     /// );
-    pub fn with_sampling_rate(mut self, rate: f64) -> Self {
+    /// ```
+    pub fn with_tag_values<I>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = &'m str>,
+    {
         if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
-            formatter.with_sampling_rate(rate);
+            for value in values {
+                formatter.with_tag_value(value);
+            }
         }
-
         self
     }
 
-    /// Send a metric using the client that created this builder.
+    /// Add all the tags of a `TagSet` to this metric.
     ///
-    /// Note that the builder is consumed by this method and thus `.try_send()`
-    /// can only be called a single time per builder.
+    /// The tags are added in the order they were added to the set, after any
+    /// tags already added to this metric. The tags aren't copied, the set must
+    /// live at least as long as this builder.
     ///
     /// # Example
     ///
     /// ```
     /// use cadence::prelude::*;
-    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    /// use cadence::{StatsdClient, NopMetricSink, Metric, TagSet};
     ///
     /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
-    /// let res = client.gauge_with_tags("some.key", 7)
-    ///    .with_tag("test-segment", "12345")
+    /// let tags = TagSet::new().with("region", "us-east-1");
+    /// let res = client.count_with_tags("some.key", 1)
+    ///    .with_tag("user", "authenticated")
+    ///    .with_tag_set(&tags)
     ///    .try_send();
     ///
     /// assert_eq!(
-    ///    "some.prefix.some.key:7|g|#test-segment:12345",
+    ///    "some.prefix.some.key:1|c|#user:authenticated,region:us-east-1",
     ///    res.unwrap().as_metric_str()
     /// );
     /// ```
-    pub fn try_send(self) -> MetricResult<T> {
-        match self.repr {
-            BuilderRepr::Error(err, _) => Err(err),
-            BuilderRepr::Success(ref formatter, client) => {
-                let metric = T::from(formatter.format());
-                client.send_metric(&metric)?;
-                Ok(metric)
+    pub fn with_tag_set(mut self, tags: &'m TagSet) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            for (key, value) in tags.iter() {
+                match key {
+                    Some(key) => formatter.with_tag(key, value),
+                    None => formatter.with_tag_value(value),
+                }
             }
         }
+        self
     }
 
-    /// Send a metric using the client that created this builder, discarding
-    /// successful results and invoking a custom handler for error results.
-    ///
-    /// By default, if no handler is given, a "no-op" handler is used that
-    /// simply discards all errors. If this isn't desired, a custom handler
-    /// should be supplied when creating a new `StatsdClient` instance.
+    /// Return an iterator over the tags currently set for this metric as
+    /// pairs of optional key and value, in the order they were added.
     ///
-    /// Note that the builder is consumed by this method and thus `.send()`
-    /// can only be called a single time per builder.
+    /// This includes any default tags of the client followed by tags added
+    /// to the builder. Tags with only a value have a key of `None`. Along with
+    /// `.clear_tags()`, this allows wrappers to enforce policies about tags,
+    /// such as removing tags with too many possible values, before a metric is
+    /// sent. No tags are returned if the metric is invalid and would fail to be
+    /// sent.
     ///
     /// # Example
     ///
     /// ```
     /// use cadence::prelude::*;
-    /// use cadence::{StatsdClient, MetricError, NopMetricSink};
-    ///
-    /// fn my_handler(err: MetricError) {
-    ///     println!("Metric error: {}", err);
-    /// }
-    ///
-    /// let client = StatsdClient::builder("some.prefix", NopMetricSink)
-    ///     .with_error_handler(my_handler)
-    ///     .build();
+    /// use cadence::{StatsdClient, NopMetricSink};
     ///
-    /// client.gauge_with_tags("some.key", 7)
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let builder = client.count_with_tags("some.key", 1)
+    ///    .with_tag("region", "us-west-1")
+    ///    .with_tag_value("beta");
+    ///
+    /// assert_eq!(
+    ///    vec![(Some("region"), "us-west-1"), (None, "beta")],
+    ///    builder.tags().collect::<Vec<_>>()
+    /// );
+    /// ```
+    pub fn tags(&self) -> impl Iterator<Item = (Option<&'m str>, &'m str)> + '_ {
+        let tags = match self.repr {
+            BuilderRepr::Success(ref formatter, _) => formatter.tags(),
+            BuilderRepr::Error(_, _) => &[],
+        };
+
+        tags.iter().copied()
+    }
+
+    /// Remove all tags currently set for this metric, including any default
+    /// tags of the client.
+    ///
+    /// Tags can be added to the metric again afterwards. For example, a wrapper
+    /// can copy the tags from `.tags()`, clear them, and add back only the tags
+    /// it allows.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let builder = client.count_with_tags("some.key", 1)
+    ///    .with_tag("user_id", "1234")
+    ///    .with_tag("region", "us-west-1");
+    ///
+    /// let allowed: Vec<_> = builder.tags().filter(|&(k, _)| k != Some("user_id")).collect();
+    /// let mut builder = builder.clear_tags();
+    /// for (key, value) in allowed {
+    ///     builder = match key {
+    ///         Some(key) => builder.with_tag(key, value),
+    ///         None => builder.with_tag_value(value),
+    ///     };
+    /// }
+    ///
+    /// assert_eq!(
+    ///    "some.prefix.some.key:1|c|#region:us-west-1",
+    ///    builder.try_send().unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn clear_tags(mut self) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.clear_tags();
+        }
+        self
+    }
+
+    /// Add a container ID to this metric, overriding any default set for the
+    /// client.
+    ///
+    /// This allows a single client to send metrics on behalf of multiple
+    /// containers, such as a sidecar or proxy forwarding metrics for several
+    /// workloads, with the correct origin for each metric. The container ID is
+    /// only included in metrics when set for either the client or the metric.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{NopMetricSink, StatsdClient, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let res = client.count_with_tags("some.key", 1)
+    ///   .with_container_id("abc123")
+    ///   .try_send();
+    ///
+    /// assert_eq!("some.prefix.some.key:1|c|c:abc123", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_container_id(mut self, container_id: &'m str) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_container_id(container_id);
+        }
+        self
+    }
+
+    /// Add a tag cardinality hint to this metric, overriding any default set
+    /// for the client.
+    ///
+    /// The cardinality determines which tags the Datadog agent adds to the
+    /// metric based on the container it was sent from. See [Cardinality] for
+    /// more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{Cardinality, NopMetricSink, StatsdClient, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let res = client.count_with_tags("some.key", 1)
+    ///   .with_cardinality(Cardinality::High)
+    ///   .try_send();
+    ///
+    /// assert_eq!("some.prefix.some.key:1|c|card:high", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_cardinality(mut self, cardinality: Cardinality) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_cardinality(cardinality);
+        }
+        self
+    }
+
+    /// Add a UNIX timestamp in seconds to this metric.
+    ///
+    /// Timestamps allow metrics to be backfilled or replayed for a point in time
+    /// other than when they are received by the server. Note that timestamps are
+    /// a [Datadog](https://docs.datadoghq.com/developers/dogstatsd/) extension to
+    /// Statsd and may not be supported by your server.
+    ///
+    /// Timestamps must be in seconds. Values too large to be a timestamp in seconds
+    /// (more than ten digits, for example a timestamp in milliseconds) will result
+    /// in an `ErrorKind::InvalidInput` error when the metric is sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    /// use std::time::{SystemTime, UNIX_EPOCH};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    /// let res = client.count_with_tags("some.key", 1)
+    ///   .with_timestamp(timestamp)
+    ///   .try_send();
+    ///
+    /// assert_eq!(
+    ///   "some.prefix.some.key:1|c|T".to_string() + &timestamp.to_string(),
+    ///  res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, client) = self.repr {
+            if timestamp > MAX_TIMESTAMP_SECS {
+                self.repr = BuilderRepr::Error(
+                    MetricError::from((ErrorKind::InvalidInput, "timestamp must be in seconds")),
+                    client,
+                );
+            } else {
+                formatter.with_timestamp(timestamp);
+            }
+        }
+
+        self
+    }
+
+    /// Add a sampling rate to this metric.
+    ///
+    /// The sampling rate is a float between 0 and 1 that determines the rate at which
+    /// the metric is sampled. For example, a sampling rate of 0.5 would mean that the
+    /// metric is sent 50% of the time. The sampling has to be done by the caller, cadence
+    /// will simply forward it to the backend. Use `.with_sample_rate()` to have cadence
+    /// do the sampling as well.
+    ///
+    /// # Example
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let res = client.distribution_with_tags("some.key", 1)
+    ///  .with_sampling_rate(0.5)
+    ///  .try_send();
+    ///
+    /// assert_eq!(
+    ///  "some.prefix.some.key:1|d|@0.5",
+    ///  res.unwrap().as_metric_str()
+    /// );
+    pub fn with_sampling_rate(mut self, rate: f64) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_sampling_rate(rate);
+        }
+
+        self
+    }
+
+    /// Randomly sample this metric at the given rate and add the rate to it.
+    ///
+    /// The sample rate is a float between 0 and 1 that determines how often the
+    /// metric is sent. For example, a sample rate of 0.25 means the metric is sent
+    /// 25% of the time. Unlike `.with_sampling_rate()`, the decision to send the
+    /// metric or not is made by Cadence when the metric is sent. When the metric
+    /// is not sent, `.try_send()` still returns the metric that would have been
+    /// sent but nothing is written to the sink.
+    ///
+    /// Metrics that are sent include the rate (e.g. `|@0.25`) so that the server
+    /// can scale them accordingly. A sample rate of 1 always sends the metric and
+    /// doesn't include the rate. A sample rate less than 0 or greater than 1 will
+    /// result in an `ErrorKind::InvalidInput` error when the metric is sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let res = client.histogram_with_tags("some.key", 12)
+    ///   .with_sample_rate(0.25)
+    ///   .try_send();
+    ///
+    /// assert_eq!(
+    ///   "some.prefix.some.key:12|h|@0.25",
+    ///   res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, client) = self.repr {
+            match sampling::validate_rate(rate) {
+                Ok(()) => formatter.with_sample_rate(rate),
+                Err(e) => self.repr = BuilderRepr::Error(e, client),
+            }
+        }
+
+        self
+    }
+
+    /// Format the metric exactly as it would be sent by `.try_send()` without
+    /// sending it.
+    ///
+    /// This includes the default tags of the client and any tags added to the
+    /// builder so far. Nothing is emitted and no other checks configured on
+    /// the client (such as a cardinality limit) are run, so this has no side
+    /// effects. `None` is returned if the metric is invalid and would fail to
+    /// be sent, in which case `.try_send()` returns the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let builder = client.count_with_tags("some.key", 1)
+    ///    .with_tag("region", "us-west-1");
+    ///
+    /// assert_eq!(
+    ///    "some.prefix.some.key:1|c|#region:us-west-1",
+    ///    builder.preview().unwrap()
+    /// );
+    /// ```
+    pub fn preview(&self) -> Option<String> {
+        match self.repr {
+            BuilderRepr::Error(_, _) => None,
+            BuilderRepr::Success(ref formatter, _) => formatter.validate().ok().map(|_| formatter.format()),
+        }
+    }
+
+    /// Return the number of bytes the metric will occupy when sent, without
+    /// sending it.
+    ///
+    /// This can be used to decide whether a metric fits in the remaining space
+    /// of a packet or other size-constrained payload. See `.preview()` for more
+    /// information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let builder = client.gauge_with_tags("some.key", 7);
+    ///
+    /// assert_eq!("some.prefix.some.key:7|g".len(), builder.formatted_len().unwrap());
+    /// ```
+    pub fn formatted_len(&self) -> Option<usize> {
+        self.preview().map(|metric| metric.len())
+    }
+
+    /// Return the number of values that will be sent as part of this metric.
+    ///
+    /// Packed values (multiple values for a single metric, such as
+    /// `some.key:4:5:6|h`) are sent as a single line but may be counted as
+    /// multiple observations by the server. This can be used to keep track
+    /// of the number of observations recorded without parsing the formatted
+    /// metric. Zero is returned if the metric is invalid and would fail to be
+    /// sent, matching the behavior of `.preview()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    ///
+    /// assert_eq!(1, client.histogram_with_tags("some.key", 4).value_count());
+    /// assert_eq!(3, client.histogram_with_tags("some.key", vec![4, 5, 6]).value_count());
+    /// ```
+    pub fn value_count(&self) -> usize {
+        match self.repr {
+            BuilderRepr::Error(_, _) => 0,
+            BuilderRepr::Success(ref formatter, _) => formatter.validate().map_or(0, |_| formatter.val.count()),
+        }
+    }
+
+    // Return the formatter and client of this metric so that it can be
+    // formatted when the group it's part of is sent, or the error encountered
+    // while building it.
+    pub(crate) fn into_group_parts(self) -> MetricResult<(MetricFormatter<'m>, &'c StatsdClient)> {
+        match self.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            BuilderRepr::Success(formatter, client) => Ok((formatter, client)),
+        }
+    }
+
+    /// Send a metric using the client that created this builder.
+    ///
+    /// Note that the builder is consumed by this method and thus `.try_send()`
+    /// can only be called a single time per builder.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let res = client.gauge_with_tags("some.key", 7)
+    ///    .with_tag("test-segment", "12345")
+    ///    .try_send();
+    ///
+    /// assert_eq!(
+    ///    "some.prefix.some.key:7|g|#test-segment:12345",
+    ///    res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn try_send(self) -> MetricResult<T> {
+        match self.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            // The formatted string is emitted directly by the client and only
+            // wrapped in the typed metric for the return value.
+            BuilderRepr::Success(ref formatter, client) => Ok(T::from(client.send_formatted(formatter)?)),
+        }
+    }
+
+    /// Send a metric using the client that created this builder, discarding
+    /// successful results and invoking a custom handler for error results.
+    ///
+    /// By default, if no handler is given, a "no-op" handler is used that
+    /// simply discards all errors. If this isn't desired, a custom handler
+    /// should be supplied when creating a new `StatsdClient` instance.
+    ///
+    /// Note that the builder is consumed by this method and thus `.send()`
+    /// can only be called a single time per builder.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, MetricError, NopMetricSink};
+    ///
+    /// fn my_handler(err: MetricError) {
+    ///     println!("Metric error: {}", err);
+    /// }
+    ///
+    /// let client = StatsdClient::builder("some.prefix", NopMetricSink)
+    ///     .with_error_handler(my_handler)
+    ///     .build();
+    ///
+    /// client.gauge_with_tags("some.key", 7)
     ///    .with_tag("region", "us-west-1")
     ///    .send();
     /// ```
@@ -606,47 +1479,132 @@ where
     }
 }
 
+impl<'m, 'c> MetricBuilder<'m, 'c, Counter> {
+    /// Set how the value of this counter is scaled when it has a sample rate.
+    ///
+    /// By default (`ScalingMode::ServerSide`), the value is sent unchanged
+    /// along with the sample rate (e.g. `|@0.1`) and the server scales it up
+    /// to estimate the true count. This is the standard behavior of Statsd
+    /// servers. With `ScalingMode::ClientSide`, the value is multiplied by the
+    /// inverse of the sample rate and sent without the rate, for servers that
+    /// ignore it. Integer values are rounded to the nearest integer once
+    /// scaled. This only changes how the metric is formatted, the metric is
+    /// still only sent at the given rate when using `.with_sample_rate()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric, ScalingMode};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let res = client.count_with_tags("some.key", 1)
+    ///   .with_sampling_rate(0.1)
+    ///   .with_sample_rate_scaling(ScalingMode::ClientSide)
+    ///   .try_send();
+    ///
+    /// assert_eq!(
+    ///   "some.prefix.some.key:10|c",
+    ///   res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn with_sample_rate_scaling(mut self, scaling: ScalingMode) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_scaling(scaling);
+        }
+
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{MetricBuilder, MetricFormatter, MetricValue};
+    use super::{MetricBuilder, MetricFormatter, MetricType, MetricValue, NameTransform, TypeCodeMap};
     use crate::client::StatsdClient;
-    use crate::sinks::NopMetricSink;
+    use crate::sinks::{NopMetricSink, SpyMetricSink};
     use crate::test::ErrorMetricSink;
-    use crate::types::Counter;
+    use crate::types::{Cardinality, Counter, ErrorKind, Histogram, Metric, MetricError, ScalingMode};
+    use std::borrow::Cow;
     use std::sync::atomic::{AtomicU64, Ordering};
     use std::sync::Arc;
 
     #[test]
-    fn test_metric_formatter_tag_size_hint_no_tags() {
-        let fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
-        assert_eq!(0, fmt.tag_size_hint());
+    fn test_metric_formatter_tag_size_hint_no_tags() {
+        let fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        assert_eq!(0, fmt.tag_size_hint());
+    }
+
+    #[test]
+    fn test_metric_formatter_tag_size_hint_value() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_tag_value("test");
+
+        assert_eq!(6, fmt.tag_size_hint());
+    }
+
+    #[test]
+    fn test_metric_formatter_tag_size_hint_key_value() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_tag("host", "web");
+        fmt.with_tag("user", "123");
+
+        assert_eq!(19, fmt.tag_size_hint());
+    }
+
+    #[test]
+    fn test_metric_formatter_container_id() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_container_id("1234");
+
+        let expected = "prefix.some.key:1|c|c:1234";
+        assert_eq!(expected, &fmt.format());
+        assert_eq!(35, fmt.size_hint());
+    }
+
+    #[test]
+    fn test_metric_formatter_cardinality_none() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_cardinality(Cardinality::None);
+
+        assert_eq!("prefix.some.key:1|c|card:none", &fmt.format());
     }
 
     #[test]
-    fn test_metric_formatter_tag_size_hint_value() {
+    fn test_metric_formatter_cardinality_low() {
         let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
-        fmt.with_tag_value("test");
+        fmt.with_cardinality(Cardinality::Low);
 
-        assert_eq!(6, fmt.tag_size_hint());
+        assert_eq!("prefix.some.key:1|c|card:low", &fmt.format());
     }
 
     #[test]
-    fn test_metric_formatter_tag_size_hint_key_value() {
+    fn test_metric_formatter_cardinality_orchestrator() {
         let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
-        fmt.with_tag("host", "web");
-        fmt.with_tag("user", "123");
+        fmt.with_cardinality(Cardinality::Orchestrator);
 
-        assert_eq!(19, fmt.tag_size_hint());
+        let expected = "prefix.some.key:1|c|card:orchestrator";
+        assert_eq!(expected, &fmt.format());
+        assert_eq!(47, fmt.size_hint());
     }
 
     #[test]
-    fn test_metric_formatter_container_id() {
+    fn test_metric_formatter_cardinality_high() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_cardinality(Cardinality::High);
+
+        assert_eq!("prefix.some.key:1|c|card:high", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_cardinality_ordering() {
         let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_tag("host", "web");
+        fmt.with_timestamp(1234567890);
+        fmt.with_cardinality(Cardinality::Low);
         fmt.with_container_id("1234");
 
-        let expected = "prefix.some.key:1|c|c:1234";
+        let expected = "prefix.some.key:1|c|#host:web|c:1234|card:low|T1234567890";
         assert_eq!(expected, &fmt.format());
-        assert_eq!(35, fmt.size_hint());
     }
 
     #[test]
@@ -678,6 +1636,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_metric_formatter_sorted_tags() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_tag_value("beta");
+        fmt.with_tag("host", "app03.example.com");
+        fmt.with_tag_value("alpha");
+        fmt.with_tag("bucket", "2");
+        fmt.with_tag("bucket", "1");
+        fmt.with_sorted_tags();
+
+        assert_eq!(
+            "prefix.some.key:4|c|#bucket:1,bucket:2,host:app03.example.com,alpha,beta",
+            &fmt.format()
+        );
+        assert_eq!(
+            "prefix.some.key|#bucket:1,bucket:2,host:app03.example.com,alpha,beta",
+            &fmt.series_name()
+        );
+    }
+
+    #[test]
+    fn test_metric_formatter_always_tag_separator_no_tags() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_always_tag_separator();
+
+        assert_eq!(2, fmt.tag_size_hint());
+        assert_eq!("prefix.some.key:4|c|#", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_always_tag_separator_with_tags() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_tag("host", "app03.example.com");
+        fmt.with_always_tag_separator();
+
+        assert_eq!("prefix.some.key:4|c|#host:app03.example.com", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_name_transform() {
+        let transform = NameTransform::new(|name: &str| Cow::Owned(name.replace('.', "_")));
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_tag("host", "app03.example.com");
+        fmt.with_sampling_rate(0.5);
+        fmt.with_name_transform(&transform);
+
+        assert_eq!("prefix_some_key:4|c|@0.5|#host:app03.example.com", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_name_transform_borrowed() {
+        let transform = NameTransform::new(|name: &str| Cow::Borrowed(name));
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_name_transform(&transform);
+
+        assert_eq!("prefix.some.key:4|c", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_type_codes() {
+        let codes = TypeCodeMap::new().with_timer("t").unwrap();
+        let mut fmt = MetricFormatter::timer("prefix.", "some.method", MetricValue::Unsigned(21));
+        fmt.with_tag("host", "app03.example.com");
+        fmt.with_type_codes(&codes);
+
+        assert_eq!("prefix.some.method:21|t|#host:app03.example.com", &fmt.format());
+
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_type_codes(&codes);
+
+        assert_eq!("prefix.some.key:4|c", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_value_is_positive() {
+        assert!(MetricValue::Unsigned(1).is_positive());
+        assert!(!MetricValue::Unsigned(0).is_positive());
+        assert!(!MetricValue::Signed(-1).is_positive());
+        assert!(!MetricValue::Float(0.0).is_positive());
+        assert!(MetricValue::PackedUnsigned(vec![1, 2]).is_positive());
+        assert!(!MetricValue::PackedSigned(vec![1, -2]).is_positive());
+        assert!(MetricValue::Str("abc".to_string()).is_positive());
+    }
+
+    #[test]
+    fn test_type_code_map_defaults() {
+        let codes = TypeCodeMap::new();
+
+        assert_eq!("c", codes.code(MetricType::Counter));
+        assert_eq!("ms", codes.code(MetricType::Timer));
+        assert_eq!("g", codes.code(MetricType::Gauge));
+        assert_eq!("m", codes.code(MetricType::Meter));
+        assert_eq!("h", codes.code(MetricType::Histogram));
+        assert_eq!("s", codes.code(MetricType::Set));
+        assert_eq!("d", codes.code(MetricType::Distribution));
+    }
+
+    #[test]
+    fn test_type_code_map_overrides() {
+        let codes = TypeCodeMap::new()
+            .with_counter("C")
+            .and_then(|c| c.with_gauge("G"))
+            .and_then(|c| c.with_meter("M"))
+            .and_then(|c| c.with_histogram("hist"))
+            .and_then(|c| c.with_set("S"))
+            .and_then(|c| c.with_distribution("D"))
+            .unwrap();
+
+        assert_eq!("C", codes.code(MetricType::Counter));
+        assert_eq!("ms", codes.code(MetricType::Timer));
+        assert_eq!("G", codes.code(MetricType::Gauge));
+        assert_eq!("M", codes.code(MetricType::Meter));
+        assert_eq!("hist", codes.code(MetricType::Histogram));
+        assert_eq!("S", codes.code(MetricType::Set));
+        assert_eq!("D", codes.code(MetricType::Distribution));
+    }
+
+    #[test]
+    fn test_type_code_map_invalid() {
+        for code in ["", "t|", "t:", "#t", "t\n"] {
+            let res = TypeCodeMap::new().with_timer(code);
+            assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind(), "code {:?}", code);
+        }
+    }
+
     #[test]
     fn test_metric_formatter_timer_no_tags() {
         let fmt = MetricFormatter::timer("prefix.", "some.method", MetricValue::Unsigned(21));
@@ -810,6 +1893,41 @@ mod tests {
         assert_eq!(68, fmt.size_hint());
     }
 
+    #[test]
+    fn test_metric_formatter_scaling_server_side() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(3));
+        fmt.with_sampling_rate(0.1);
+        fmt.with_scaling(ScalingMode::ServerSide);
+
+        assert_eq!("prefix.some.key:3|c|@0.1", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_scaling_client_side() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(3));
+        fmt.with_sampling_rate(0.25);
+        fmt.with_scaling(ScalingMode::ClientSide);
+
+        assert_eq!("prefix.some.key:12|c", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_scaling_client_side_rounding() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::PackedUnsigned(vec![1, 2]));
+        fmt.with_sampling_rate(0.3);
+        fmt.with_scaling(ScalingMode::ClientSide);
+
+        assert_eq!("prefix.some.key:3:7|c", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_scaling_client_side_no_rate() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(3));
+        fmt.with_scaling(ScalingMode::ClientSide);
+
+        assert_eq!("prefix.some.key:3|c", &fmt.format());
+    }
+
     #[test]
     fn test_metric_formatter_sampling_rate_small() {
         let mut fmt =
@@ -849,6 +1967,114 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_metric_formatter_trim() {
+        let mut fmt = MetricFormatter::counter("prefix.", " some.counter\n", MetricValue::Signed(4));
+        fmt.with_tag(" host ", " web01 ");
+        fmt.with_tag_value("\tbeta ");
+        assert_eq!("prefix. some.counter\n:4|c|# host : web01 ,\tbeta ", &fmt.format());
+
+        fmt.with_trim();
+        assert_eq!("prefix.some.counter:4|c|#host:web01,beta", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_trim_sorted_tags() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(4));
+        fmt.with_tag("b", "2");
+        fmt.with_tag(" a", "1");
+        fmt.with_sorted_tags();
+        fmt.with_trim();
+
+        assert_eq!("prefix.some.counter:4|c|#a:1,b:2", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_trim_validate_empty_name() {
+        let mut fmt = MetricFormatter::counter("", "  ", MetricValue::Signed(4));
+        assert!(fmt.validate().is_ok());
+
+        fmt.with_trim();
+        assert_eq!(ErrorKind::InvalidInput, fmt.validate().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_metric_formatter_float_precision() {
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(0.1 + 0.2));
+        assert_eq!("prefix.some.gauge:0.30000000000000004|g", &fmt.format());
+
+        fmt.with_float_precision(3);
+        assert_eq!("prefix.some.gauge:0.3|g", &fmt.format());
+
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(1.23456));
+        fmt.with_float_precision(2);
+        assert_eq!("prefix.some.gauge:1.23|g", &fmt.format());
+
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(100.0));
+        fmt.with_float_precision(2);
+        assert_eq!("prefix.some.gauge:100|g", &fmt.format());
+
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(2.5));
+        fmt.with_float_precision(0);
+        assert_eq!("prefix.some.gauge:2|g", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_float_decimal_point() {
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(1.0));
+        assert_eq!("prefix.some.gauge:1|g", &fmt.format());
+
+        fmt.with_float_decimal_point();
+        assert_eq!("prefix.some.gauge:1.0|g", &fmt.format());
+
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(0.0));
+        fmt.with_float_decimal_point();
+        assert_eq!("prefix.some.gauge:0.0|g", &fmt.format());
+
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(1.5));
+        fmt.with_float_decimal_point();
+        assert_eq!("prefix.some.gauge:1.5|g", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_float_decimal_point_non_float() {
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Unsigned(0));
+        fmt.with_float_decimal_point();
+        assert_eq!("prefix.some.gauge:0|g", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_float_decimal_point_with_precision() {
+        let mut fmt = MetricFormatter::distribution(
+            "prefix.",
+            "some.distribution",
+            MetricValue::PackedFloat(vec![1.0, 0.0001, 2.5]),
+        );
+        fmt.with_float_precision(2);
+        fmt.with_float_decimal_point();
+        assert_eq!("prefix.some.distribution:1.0:0.0:2.5|d", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_float_precision_multiple_values() {
+        let mut fmt = MetricFormatter::distribution(
+            "prefix.",
+            "some.distribution",
+            MetricValue::PackedFloat(vec![0.1 + 0.2, 1.0, 2.126]),
+        );
+        fmt.with_float_precision(2);
+
+        assert_eq!("prefix.some.distribution:0.3:1:2.13|d", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_float_precision_non_float() {
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Unsigned(100));
+        fmt.with_float_precision(2);
+
+        assert_eq!("prefix.some.gauge:100|g", &fmt.format());
+    }
+
     #[test]
     fn test_metric_formatter_set_no_tags() {
         let fmt = MetricFormatter::set("prefix.", "users.uniques", MetricValue::Signed(44));
@@ -872,6 +2098,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_metric_formatter_validate_empty_name() {
+        let fmt = MetricFormatter::counter("", "", MetricValue::Signed(1));
+        assert_eq!(ErrorKind::InvalidInput, fmt.validate().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_metric_formatter_validate_empty_prefix() {
+        let fmt = MetricFormatter::counter("", "some.counter", MetricValue::Signed(1));
+        assert!(fmt.validate().is_ok());
+        assert_eq!("some.counter:1|c", &fmt.format());
+    }
+
     #[test]
     fn test_metric_builder_send_success() {
         let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
@@ -925,4 +2164,177 @@ mod tests {
 
         assert!(res.is_err(), "expected Err result from try_send");
     }
+
+    #[test]
+    fn test_metric_builder_with_tags() {
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+        let tags = vec![("host", "web01"), ("region", "us-west-2")];
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder
+            .with_tag("env", "prod")
+            .with_tags(tags)
+            .with_tag_values(vec!["beta", "canary"])
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.counter:11|c|#env:prod,host:web01,region:us-west-2,beta,canary",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_metric_builder_with_tags_empty() {
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder.with_tags(Vec::new()).with_tag_values(Vec::new()).try_send();
+
+        assert_eq!("prefix.some.counter:11|c", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_metric_builder_with_timestamp() {
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder.with_timestamp(1727352000).try_send();
+
+        assert_eq!("prefix.some.counter:11|c|T1727352000", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_metric_builder_with_timestamp_millis() {
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder.with_timestamp(1727352000000).try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_metric_builder_try_send_empty_name() {
+        let fmt = MetricFormatter::counter("", "", MetricValue::Signed(1));
+        let client = StatsdClient::from_sink("", NopMetricSink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder.try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_metric_builder_send_empty_name() {
+        let errors = Arc::new(AtomicU64::new(0));
+        let errors_ref = errors.clone();
+
+        let fmt = MetricFormatter::counter("", "", MetricValue::Signed(1));
+        let client = StatsdClient::builder("", NopMetricSink)
+            .with_error_handler(move |_e| {
+                errors_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        builder.send();
+
+        assert_eq!(1, errors.load(Ordering::Acquire));
+    }
+    #[test]
+    fn test_metric_builder_preview() {
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let builder = builder.with_tag("host", "web01");
+
+        assert_eq!(
+            Some("prefix.some.counter:11|c|#host:web01".to_string()),
+            builder.preview()
+        );
+        assert_eq!(Some(36), builder.formatted_len());
+        assert!(rx.try_recv().is_err(), "expected nothing to be sent by preview");
+
+        builder.try_send().unwrap();
+        assert_eq!(
+            b"prefix.some.counter:11|c|#host:web01",
+            rx.try_recv().unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn test_metric_builder_preview_error() {
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+        let err = MetricError::from((ErrorKind::InvalidInput, "some error"));
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_error(err, &client);
+
+        assert_eq!(None, builder.preview());
+        assert_eq!(None, builder.formatted_len());
+        assert_eq!(0, builder.value_count());
+    }
+
+    #[test]
+    fn test_metric_builder_tags() {
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let builder = builder.with_tag("host", "web01").with_tag_value("beta");
+
+        assert_eq!(
+            vec![(Some("host"), "web01"), (None, "beta")],
+            builder.tags().collect::<Vec<_>>()
+        );
+
+        let builder = builder.clear_tags().with_tag("region", "us-west-1");
+
+        assert_eq!(vec![(Some("region"), "us-west-1")], builder.tags().collect::<Vec<_>>());
+        assert_eq!(Some(42), builder.formatted_len());
+        assert_eq!(
+            "prefix.some.counter:11|c|#region:us-west-1",
+            builder.try_send().unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_metric_builder_tags_error() {
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+        let err = MetricError::from((ErrorKind::InvalidInput, "some error"));
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_error(err, &client);
+
+        assert_eq!(0, builder.tags().count());
+    }
+
+    #[test]
+    fn test_metric_builder_value_count() {
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+
+        let single = MetricFormatter::histogram("prefix.", "some.histogram", MetricValue::Unsigned(4));
+        let packed =
+            MetricFormatter::histogram("prefix.", "some.histogram", MetricValue::PackedUnsigned(vec![4, 5, 6]));
+        let single: MetricBuilder<'_, '_, Histogram> = MetricBuilder::from_fmt(single, &client);
+        let packed: MetricBuilder<'_, '_, Histogram> = MetricBuilder::from_fmt(packed, &client);
+
+        assert_eq!(1, single.value_count());
+        assert_eq!(3, packed.value_count());
+        assert_eq!(Some("prefix.some.histogram:4:5:6|h".to_string()), packed.preview());
+    }
+
+    #[test]
+    fn test_metric_builder_preview_empty_name() {
+        let fmt = MetricFormatter::counter("", "", MetricValue::Signed(1));
+        let client = StatsdClient::from_sink("", NopMetricSink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+
+        assert_eq!(None, builder.preview());
+    }
 }
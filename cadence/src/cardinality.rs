@@ -0,0 +1,192 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::builder::MetricFormatter;
+use std::collections::HashSet;
+use std::fmt;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Tracks the distinct combinations of metric name and tags emitted by a
+/// client and invokes a callback the first time a limit is exceeded.
+///
+/// Only a hash of each combination is stored and no more than `limit` hashes
+/// are ever kept, so memory use is bounded no matter how many combinations
+/// are emitted. Once the limit has been exceeded, metrics are no longer
+/// tracked at all.
+pub(crate) struct CardinalityGuard {
+    limit: usize,
+    seen: Mutex<HashSet<u64>>,
+    exceeded: AtomicBool,
+    on_exceed: Box<dyn Fn(&str) + Sync + Send + RefUnwindSafe>,
+}
+
+impl CardinalityGuard {
+    pub(crate) fn new<F>(limit: usize, on_exceed: F) -> Self
+    where
+        F: Fn(&str) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        CardinalityGuard {
+            limit,
+            seen: Mutex::new(HashSet::new()),
+            exceeded: AtomicBool::new(false),
+            on_exceed: Box::new(on_exceed),
+        }
+    }
+
+    pub(crate) fn observe(&self, formatter: &MetricFormatter<'_>) {
+        if self.exceeded.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let hash = formatter.series_hash();
+        let is_new = {
+            let mut seen = self.seen.lock().unwrap();
+            if seen.contains(&hash) {
+                return;
+            }
+
+            if seen.len() < self.limit {
+                seen.insert(hash);
+                false
+            } else {
+                true
+            }
+        };
+
+        // Invoke the callback outside of the lock and make sure that only
+        // a single thread does so, even if several see a new combination
+        // at the same time.
+        if is_new && !self.exceeded.swap(true, Ordering::Relaxed) {
+            (self.on_exceed)(&formatter.series_name());
+        }
+    }
+}
+
+impl fmt::Debug for CardinalityGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CardinalityGuard {{ limit: {:?}, exceeded: {:?}, on_exceed: ... }}",
+            self.limit, self.exceeded
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CardinalityGuard;
+    use crate::builder::{MetricFormatter, MetricValue, NameTransform};
+    use std::borrow::Cow;
+    use std::sync::{Arc, Mutex};
+
+    fn new_guard(limit: usize) -> (Arc<Mutex<Vec<String>>>, CardinalityGuard) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls_ref = calls.clone();
+        let guard = CardinalityGuard::new(limit, move |key: &str| {
+            calls_ref.lock().unwrap().push(key.to_string());
+        });
+
+        (calls, guard)
+    }
+
+    #[test]
+    fn test_cardinality_guard_under_limit() {
+        let (calls, guard) = new_guard(2);
+        let mut fmt1 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(1));
+        fmt1.with_tag("host", "web01");
+        let mut fmt2 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(1));
+        fmt2.with_tag("host", "web02");
+
+        guard.observe(&fmt1);
+        guard.observe(&fmt2);
+        guard.observe(&fmt1);
+
+        assert!(calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cardinality_guard_exceeded_once() {
+        let (calls, guard) = new_guard(1);
+        let mut fmt1 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(1));
+        fmt1.with_tag("host", "web01");
+        let mut fmt2 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(1));
+        fmt2.with_tag("host", "web02");
+        let mut fmt3 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(1));
+        fmt3.with_tag("host", "web03");
+
+        guard.observe(&fmt1);
+        guard.observe(&fmt2);
+        guard.observe(&fmt3);
+
+        assert_eq!(
+            vec!["prefix.some.counter|#host:web02".to_string()],
+            *calls.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cardinality_guard_tag_order_ignored() {
+        let (calls, guard) = new_guard(1);
+        let mut fmt1 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(1));
+        fmt1.with_tag("host", "web01");
+        fmt1.with_tag_value("beta");
+        let mut fmt2 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(5));
+        fmt2.with_tag_value("beta");
+        fmt2.with_tag("host", "web01");
+
+        guard.observe(&fmt1);
+        guard.observe(&fmt2);
+
+        assert!(calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cardinality_guard_trimmed() {
+        let (calls, guard) = new_guard(1);
+        let mut fmt1 = MetricFormatter::counter("prefix.", " some.counter ", MetricValue::Signed(1));
+        fmt1.with_tag(" host ", "web01 ");
+        fmt1.with_trim();
+        let mut fmt2 = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(1));
+        fmt2.with_tag("host", "web01");
+        fmt2.with_trim();
+        let mut fmt3 = MetricFormatter::counter("prefix.", " some.counter", MetricValue::Signed(1));
+        fmt3.with_tag("host ", " web02");
+        fmt3.with_trim();
+
+        guard.observe(&fmt1);
+        guard.observe(&fmt2);
+        guard.observe(&fmt3);
+
+        assert_eq!(
+            vec!["prefix.some.counter|#host:web02".to_string()],
+            *calls.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cardinality_guard_name_transform() {
+        let (calls, guard) = new_guard(1);
+        let transform = NameTransform::new(|name: &str| Cow::Owned(name.to_lowercase()));
+        let mut fmt1 = MetricFormatter::counter("prefix.", "Foo", MetricValue::Signed(1));
+        fmt1.with_name_transform(&transform);
+        let mut fmt2 = MetricFormatter::counter("prefix.", "foo", MetricValue::Signed(1));
+        fmt2.with_name_transform(&transform);
+        let mut fmt3 = MetricFormatter::counter("prefix.", "Bar", MetricValue::Signed(1));
+        fmt3.with_name_transform(&transform);
+
+        guard.observe(&fmt1);
+        guard.observe(&fmt2);
+        guard.observe(&fmt3);
+
+        assert_eq!(vec!["prefix.bar".to_string()], *calls.lock().unwrap());
+    }
+}
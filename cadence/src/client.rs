@@ -8,15 +8,100 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::builder::{MetricBuilder, MetricFormatter, MetricValue};
+use crate::builder::{MetricBuilder, MetricFormatter, MetricValue, NameTransform, TypeCodeMap};
+use crate::cardinality::CardinalityGuard;
+use crate::clock::{Clock, SystemClock};
+use crate::group::MetricGroup;
+use crate::sampling;
 use crate::sealed::Sealed;
-use crate::sinks::MetricSink;
+use crate::self_metrics::SelfMetricsReporter;
+use crate::sinks::{
+    bind_nonblocking, get_addr, BufferedUdpMetricSinkBuilder, MetricSink, QueueStats, QueuingMetricSinkBuilder,
+    SharedSink, SinkStats, DEFAULT_BUFFER_SIZE,
+};
 use crate::types::{
-    Counter, Distribution, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, MetricResult, Set, Timer,
+    Cardinality, Counter, Distribution, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, MetricKind,
+    MetricResult, Set, TagSet, Timer,
 };
+use arc_swap::{ArcSwap, Guard};
+use std::borrow::Cow;
+use std::env;
 use std::fmt;
+use std::io;
+use std::net::ToSocketAddrs;
 use std::panic::RefUnwindSafe;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use crate::sinks::BufferedUnixMetricSinkBuilder;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(unix)]
+use std::path::Path;
+
+/// Tag keys and the environment variables used for their values by
+/// `StatsdClientBuilder::with_dd_unified_tags()`.
+const DD_UNIFIED_TAGS: &[(&str, &str)] = &[("env", "DD_ENV"), ("service", "DD_SERVICE"), ("version", "DD_VERSION")];
+
+/// Maximum number of values that `StatsdClient::histogram_weighted()` will
+/// expand its input into, to avoid accidentally emitting huge numbers of metrics.
+const MAX_WEIGHTED_HISTOGRAM_VALUES: u64 = 10_000;
+
+// NaN and infinite values can't be represented in the Statsd protocol, they'd
+// be formatted as `NaN` or `inf` and rejected (or misinterpreted) by servers.
+fn float_value(val: f64) -> MetricResult<MetricValue> {
+    if val.is_finite() {
+        Ok(MetricValue::Float(val))
+    } else {
+        Err(MetricError::from((ErrorKind::InvalidInput, "non-finite float")))
+    }
+}
+
+fn packed_float_value(vals: Vec<f64>) -> MetricResult<MetricValue> {
+    if vals.iter().all(|v| v.is_finite()) {
+        Ok(MetricValue::PackedFloat(vals))
+    } else {
+        Err(MetricError::from((ErrorKind::InvalidInput, "non-finite float")))
+    }
+}
+
+// Collect values from an iterator directly into a packed value, rejecting
+// iterators without any values since they'd result in an invalid metric.
+fn packed_float_iter<I>(values: I) -> MetricResult<MetricValue>
+where
+    I: IntoIterator<Item = f64>,
+{
+    let values: Vec<f64> = values.into_iter().collect();
+    if values.is_empty() {
+        Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "no values for packed metric",
+        )))
+    } else {
+        packed_float_value(values)
+    }
+}
+
+fn packed_millis_iter<I>(values: I) -> MetricResult<MetricValue>
+where
+    I: IntoIterator<Item = Duration>,
+{
+    let values = values
+        .into_iter()
+        .map(|d| u64::try_from(d.as_millis()))
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))?;
+
+    if values.is_empty() {
+        Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "no values for packed metric",
+        )))
+    } else {
+        Ok(MetricValue::PackedUnsigned(values))
+    }
+}
 
 /// Conversion trait for valid values for counters
 ///
@@ -57,7 +142,7 @@ impl ToCounterValue for u32 {
 /// Conversion trait for valid values for timers
 ///
 /// This trait must be implemented for any types that are used as timer
-/// values (currently `u64`, `Duration`, and `Vec`s of those types).
+/// values (currently `u64`, `Duration`, and `Vec`s or slices of those types).
 /// This trait is internal to how values are formatted as part of metrics
 /// but is exposed publicly for documentation purposes.
 ///
@@ -78,6 +163,12 @@ impl ToTimerValue for Vec<u64> {
     }
 }
 
+impl ToTimerValue for &[u64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::PackedUnsigned(self.to_vec()))
+    }
+}
+
 impl ToTimerValue for Duration {
     fn try_to_value(self) -> MetricResult<MetricValue> {
         let as_millis = self.as_millis();
@@ -90,6 +181,12 @@ impl ToTimerValue for Duration {
 }
 
 impl ToTimerValue for Vec<Duration> {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        ToTimerValue::try_to_value(self.as_slice())
+    }
+}
+
+impl ToTimerValue for &[Duration] {
     fn try_to_value(self) -> MetricResult<MetricValue> {
         if self.iter().any(|x| x.as_millis() > u64::MAX as u128) {
             Err(MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
@@ -104,9 +201,9 @@ impl ToTimerValue for Vec<Duration> {
 /// Conversion trait for valid values for gauges
 ///
 /// This trait must be implemented for any types that are used as gauge
-/// values (currently `u64` and `f64`). This trait is internal to how values
-/// are formatted as part of metrics but is exposed publicly for documentation
-/// purposes.
+/// values (currently `u64`, `i64`, `i32`, `f64`, and `bool`). This trait is
+/// internal to how values are formatted as part of metrics but is exposed
+/// publicly for documentation purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToGaugeValue {
@@ -119,9 +216,27 @@ impl ToGaugeValue for u64 {
     }
 }
 
+impl ToGaugeValue for i64 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Signed(self))
+    }
+}
+
+impl ToGaugeValue for i32 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Signed(self.into()))
+    }
+}
+
 impl ToGaugeValue for f64 {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::Float(self))
+        float_value(self)
+    }
+}
+
+impl ToGaugeValue for bool {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Unsigned(self.into()))
     }
 }
 
@@ -146,9 +261,9 @@ impl ToMeterValue for u64 {
 /// Conversion trait for valid values for histograms
 ///
 /// This trait must be implemented for any types that are used as histogram
-/// values (currently `u64`, `f64`, `Duration`, and `Vec`s of those types).
-/// This trait is internal to how values are formatted as part of metrics
-/// but is exposed publicly for documentation purposes.
+/// values (currently `u64`, `f64`, `Duration`, and `Vec`s or slices of those
+/// types). This trait is internal to how values are formatted as part of
+/// metrics but is exposed publicly for documentation purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToHistogramValue {
@@ -163,7 +278,7 @@ impl ToHistogramValue for u64 {
 
 impl ToHistogramValue for f64 {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::Float(self))
+        float_value(self)
     }
 }
 
@@ -186,11 +301,29 @@ impl ToHistogramValue for Vec<u64> {
 
 impl ToHistogramValue for Vec<f64> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::PackedFloat(self))
+        packed_float_value(self)
+    }
+}
+
+impl ToHistogramValue for &[u64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::PackedUnsigned(self.to_vec()))
+    }
+}
+
+impl ToHistogramValue for &[f64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_float_value(self.to_vec())
     }
 }
 
 impl ToHistogramValue for Vec<Duration> {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        ToHistogramValue::try_to_value(self.as_slice())
+    }
+}
+
+impl ToHistogramValue for &[Duration] {
     fn try_to_value(self) -> MetricResult<MetricValue> {
         if self.iter().any(|x| x.as_nanos() > u64::MAX as u128) {
             Err(MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
@@ -205,9 +338,9 @@ impl ToHistogramValue for Vec<Duration> {
 /// Conversion trait for valid values for distributions
 ///
 /// This trait must be implemented for any types that are used as distribution
-/// values (currently `u64`, `f64`, and `Vec`s of those types). This trait is
-/// internal to how values are formatted as part of metrics but is exposed
-/// publicly for documentation purposes.
+/// values (currently `u64`, `f64`, and `Vec`s or slices of those types). This
+/// trait is internal to how values are formatted as part of metrics but is
+/// exposed publicly for documentation purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToDistributionValue {
@@ -222,7 +355,7 @@ impl ToDistributionValue for u64 {
 
 impl ToDistributionValue for f64 {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::Float(self))
+        float_value(self)
     }
 }
 
@@ -234,16 +367,28 @@ impl ToDistributionValue for Vec<u64> {
 
 impl ToDistributionValue for Vec<f64> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::PackedFloat(self))
+        packed_float_value(self)
+    }
+}
+
+impl ToDistributionValue for &[u64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::PackedUnsigned(self.to_vec()))
+    }
+}
+
+impl ToDistributionValue for &[f64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_float_value(self.to_vec())
     }
 }
 
 /// Conversion trait for valid values for sets
 ///
-/// This trait must be implemented for any types that are used as counter
-/// values (currently only `i64`). This trait is internal to how values are
-/// formatted as part of metrics but is exposed publicly for documentation
-/// purposes.
+/// This trait must be implemented for any types that are used as set
+/// values (currently `i64`, `Vec<i64>`, `&str`, and `String`). This trait
+/// is internal to how values are formatted as part of metrics but is exposed
+/// publicly for documentation purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToSetValue {
@@ -256,6 +401,41 @@ impl ToSetValue for i64 {
     }
 }
 
+impl ToSetValue for Vec<i64> {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::PackedSigned(self))
+    }
+}
+
+impl ToSetValue for &str {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        validate_set_member(self)?;
+        Ok(MetricValue::Str(self.to_owned()))
+    }
+}
+
+impl ToSetValue for String {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        validate_set_member(&self)?;
+        Ok(MetricValue::Str(self))
+    }
+}
+
+// String set members can't contain characters that are used as delimiters
+// in the Statsd protocol, since they would be ambiguous for the server.
+fn validate_set_member(member: &str) -> MetricResult<()> {
+    if member.is_empty() {
+        Err(MetricError::from((ErrorKind::InvalidInput, "empty set member")))
+    } else if member.contains([':', '|', '\n'].as_ref()) {
+        Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "set member contains a reserved character",
+        )))
+    } else {
+        Ok(())
+    }
+}
+
 /// Trait for incrementing and decrementing counters.
 ///
 /// Counters are simple values incremented or decremented by a client. The
@@ -265,6 +445,14 @@ impl ToSetValue for i64 {
 ///
 /// The following types are valid for counters:
 /// * `i64`
+/// * `i32`
+/// * `u64`
+/// * `u32`
+///
+/// Unsigned values can only increment a counter. They're formatted as-is,
+/// so amounts larger than `i64::MAX` (such as large byte counts) can be sent
+/// without being truncated by a cast to `i64`. Counters are decremented by
+/// sending a negative amount using one of the signed types.
 ///
 /// See the [Statsd spec](https://github.com/b/statsd_spec) for more
 /// information.
@@ -280,9 +468,30 @@ where
         self.count_with_tags(key, count).try_send()
     }
 
+    /// Increment or decrement the counter by the given amount, randomly
+    /// sampled at the given rate
+    ///
+    /// The count is only sent a fraction of the time given by the rate (between
+    /// 0 and 1) and includes the rate so the server can scale it. The count is
+    /// sent as-is, it's up to the server to multiply it by the inverse of the
+    /// rate. For example, a count of `1` sampled at a rate of `0.1` is emitted as
+    /// `key:1|c|@0.1` roughly 10% of the time and counted as `10` by the server.
+    /// The metric is returned even when it isn't sent. This is a shortcut for
+    /// calling `.with_sample_rate()` on the builder returned by `count_with_tags`.
+    fn count_sampled(&self, key: &str, count: T, rate: f64) -> MetricResult<Counter> {
+        self.count_with_tags(key, count).with_sample_rate(rate).try_send()
+    }
+
     /// Increment or decrement the counter by the given amount and return
     /// a `MetricBuilder` that can be used to add tags to the metric.
-    fn count_with_tags<'a>(&'a self, key: &'a str, count: T) -> MetricBuilder<'_, '_, Counter>;
+    fn count_with_tags<'a>(&'a self, key: &'a str, count: T) -> MetricBuilder<'a, 'a, Counter>;
+
+    /// Increment or decrement the counter by the given amount with the tags
+    /// of a `TagSet`, and return a `MetricBuilder` that can be used to add
+    /// more tags to the metric.
+    fn count_with_tagset<'a>(&'a self, key: &'a str, count: T, tags: &'a TagSet) -> MetricBuilder<'a, 'a, Counter> {
+        self.count_with_tags(key, count).with_tag_set(tags)
+    }
 }
 
 /// Trait for convenience methods for counters
@@ -297,7 +506,7 @@ pub trait CountedExt: Counted<i64> {
 
     /// Increment the counter by 1 and return a `MetricBuilder` that can
     /// be used to add tags to the metric.
-    fn incr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'_, '_, Counter> {
+    fn incr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'a, 'a, Counter> {
         self.count_with_tags(key, 1)
     }
 
@@ -308,7 +517,7 @@ pub trait CountedExt: Counted<i64> {
 
     /// Decrement the counter by 1 and return a `MetricBuilder` that can
     /// be used to add tags to the metric.
-    fn decr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'_, '_, Counter> {
+    fn decr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'a, 'a, Counter> {
         self.count_with_tags(key, -1)
     }
 }
@@ -340,7 +549,70 @@ where
 
     /// Record a timing in milliseconds with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'_, '_, Timer>;
+    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'a, 'a, Timer>;
+
+    /// Record a timing value with the given key and the tags of a
+    /// `TagSet`, and return a `MetricBuilder` that can be used to add more
+    /// tags to the metric.
+    fn time_with_tagset<'a>(&'a self, key: &'a str, time: T, tags: &'a TagSet) -> MetricBuilder<'a, 'a, Timer> {
+        self.time_with_tags(key, time).with_tag_set(tags)
+    }
+}
+
+/// Trait for convenience methods for timers
+///
+/// This trait specifically implements methods for recording the time elapsed
+/// since an `Instant` and multiple durations at once using timers with
+/// `Duration` types.
+pub trait TimedExt: Timed<Duration> + for<'a> Timed<&'a [Duration]> {
+    /// Record the time elapsed since `start` in milliseconds with the given key
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Instant;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let start = Instant::now();
+    /// // Some expensive operation ...
+    /// client.time_since("some.operation", start).unwrap();
+    /// ```
+    fn time_since(&self, key: &str, start: Instant) -> MetricResult<Timer> {
+        self.time_since_with_tags(key, start).try_send()
+    }
+
+    /// Record the time elapsed since `start` in milliseconds with the given key
+    /// and return a `MetricBuilder` that can be used to add tags to the metric.
+    fn time_since_with_tags<'a>(&'a self, key: &'a str, start: Instant) -> MetricBuilder<'a, 'a, Timer> {
+        self.time_with_tags(key, start.elapsed())
+    }
+
+    /// Record multiple timings in milliseconds with the given key as a single
+    /// packed metric
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let durations = [Duration::from_millis(12), Duration::from_millis(34)];
+    /// client.time_durations("some.operation", &durations).unwrap();
+    /// ```
+    fn time_durations(&self, key: &str, durations: &[Duration]) -> MetricResult<Timer> {
+        self.time_durations_with_tags(key, durations).try_send()
+    }
+
+    /// Record multiple timings in milliseconds with the given key as a single
+    /// packed metric and return a `MetricBuilder` that can be used to add tags
+    /// to the metric.
+    fn time_durations_with_tags<'a>(&'a self, key: &'a str, durations: &[Duration]) -> MetricBuilder<'a, 'a, Timer> {
+        self.time_with_tags(key, durations)
+    }
 }
 
 /// Trait for recording gauge values.
@@ -351,7 +623,22 @@ where
 ///
 /// The following types are valid for gauges:
 /// * `u64`
+/// * `i64`
+/// * `i32`
 /// * `f64`
+/// * `bool`
+///
+/// Boolean values are emitted as `1` for `true` and `0` for `false`. This
+/// makes it simple to track things like feature flags or whether a circuit
+/// breaker is open. Simple enums can be recorded the same way by converting
+/// them to a `u64` first.
+///
+/// Signed values are emitted as-is, so negative values like temperatures or
+/// balances are emitted with a leading `-` (e.g. `some.gauge:-5|g`). Note that
+/// some servers (including the original Etsy Statsd) interpret a value with a
+/// leading sign as a change to the current value of the gauge instead of an
+/// absolute value. Check how your server handles negative gauges before
+/// relying on them.
 ///
 /// See the [Statsd spec](https://github.com/b/statsd_spec) for more
 /// information.
@@ -369,7 +656,14 @@ where
 
     /// Record a gauge value with the given key and return a `MetricBuilder`
     /// that can be used to add tags to the metric.
-    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Gauge>;
+    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Gauge>;
+
+    /// Record a gauge value with the given key and the tags of a
+    /// `TagSet`, and return a `MetricBuilder` that can be used to add more
+    /// tags to the metric.
+    fn gauge_with_tagset<'a>(&'a self, key: &'a str, value: T, tags: &'a TagSet) -> MetricBuilder<'a, 'a, Gauge> {
+        self.gauge_with_tags(key, value).with_tag_set(tags)
+    }
 }
 
 /// Trait for recording meter values.
@@ -399,7 +693,14 @@ where
 
     /// Record a meter value with the given key and return a `MetricBuilder`
     /// that can be used to add tags to the metric.
-    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Meter>;
+    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Meter>;
+
+    /// Record a meter value with the given key and the tags of a
+    /// `TagSet`, and return a `MetricBuilder` that can be used to add more
+    /// tags to the metric.
+    fn meter_with_tagset<'a>(&'a self, key: &'a str, value: T, tags: &'a TagSet) -> MetricBuilder<'a, 'a, Meter> {
+        self.meter_with_tags(key, value).with_tag_set(tags)
+    }
 }
 
 /// Trait for recording histogram values.
@@ -431,9 +732,67 @@ where
         self.histogram_with_tags(key, value).try_send()
     }
 
+    /// Record a single histogram value with the given key, randomly sampled
+    /// at the given rate
+    ///
+    /// The value is only sent a fraction of the time given by the rate (between
+    /// 0 and 1) and includes the rate so the server can scale it. The metric is
+    /// returned even when it isn't sent. This is a shortcut for calling
+    /// `.with_sample_rate()` on the builder returned by `histogram_with_tags`.
+    fn histogram_sampled(&self, key: &str, value: T, rate: f64) -> MetricResult<Histogram> {
+        self.histogram_with_tags(key, value).with_sample_rate(rate).try_send()
+    }
+
     /// Record a single histogram value with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Histogram>;
+    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Histogram>;
+
+    /// Record a histogram value with the given key and the tags of a
+    /// `TagSet`, and return a `MetricBuilder` that can be used to add more
+    /// tags to the metric.
+    fn histogram_with_tagset<'a>(
+        &'a self,
+        key: &'a str,
+        value: T,
+        tags: &'a TagSet,
+    ) -> MetricBuilder<'a, 'a, Histogram> {
+        self.histogram_with_tags(key, value).with_tag_set(tags)
+    }
+}
+
+/// Trait for convenience methods for histograms
+///
+/// This trait specifically implements methods for recording multiple durations
+/// at once using histograms with `Duration` types.
+pub trait HistogrammedExt: for<'a> Histogrammed<&'a [Duration]> {
+    /// Record multiple durations in nanoseconds with the given key as a single
+    /// packed metric
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let durations = [Duration::from_nanos(12), Duration::from_nanos(34)];
+    /// client.histogram_durations("some.operation", &durations).unwrap();
+    /// ```
+    fn histogram_durations(&self, key: &str, durations: &[Duration]) -> MetricResult<Histogram> {
+        self.histogram_durations_with_tags(key, durations).try_send()
+    }
+
+    /// Record multiple durations in nanoseconds with the given key as a single
+    /// packed metric and return a `MetricBuilder` that can be used to add tags
+    /// to the metric.
+    fn histogram_durations_with_tags<'a>(
+        &'a self,
+        key: &'a str,
+        durations: &[Duration],
+    ) -> MetricBuilder<'a, 'a, Histogram> {
+        self.histogram_with_tags(key, durations)
+    }
 }
 
 /// Trait for recording distribution values.
@@ -461,9 +820,34 @@ where
         self.distribution_with_tags(key, value).try_send()
     }
 
+    /// Record a single distribution value with the given key, randomly sampled
+    /// at the given rate
+    ///
+    /// The value is only sent a fraction of the time given by the rate (between
+    /// 0 and 1) and includes the rate so the server can scale it. The metric is
+    /// returned even when it isn't sent. This is a shortcut for calling
+    /// `.with_sample_rate()` on the builder returned by `distribution_with_tags`.
+    fn distribution_sampled(&self, key: &str, value: T, rate: f64) -> MetricResult<Distribution> {
+        self.distribution_with_tags(key, value)
+            .with_sample_rate(rate)
+            .try_send()
+    }
+
     /// Record a single distribution value with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Distribution>;
+    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Distribution>;
+
+    /// Record a distribution value with the given key and the tags of a
+    /// `TagSet`, and return a `MetricBuilder` that can be used to add more
+    /// tags to the metric.
+    fn distribution_with_tagset<'a>(
+        &'a self,
+        key: &'a str,
+        value: T,
+        tags: &'a TagSet,
+    ) -> MetricBuilder<'a, 'a, Distribution> {
+        self.distribution_with_tags(key, value).with_tag_set(tags)
+    }
 }
 
 /// Trait for recording set values.
@@ -473,6 +857,20 @@ where
 ///
 /// The following types are valid for sets:
 /// * `i64`
+/// * `Vec<i64>`
+/// * `&str`
+/// * `String`
+///
+/// Multiple set members can be emitted in a single metric by using a `Vec`
+/// of values (e.g. `prefix.key:1:2:3|s`). Note that not all servers support
+/// these packed values.
+///
+/// String set members (such as user IDs that aren't numeric) are a
+/// [Datadog](https://docs.datadoghq.com/developers/dogstatsd/) extension to
+/// Statsd and may not be supported by your server. String members must not
+/// be empty or contain the `:`, `|`, or newline characters since these are
+/// delimiters in the Statsd protocol. Using a member with any of these will
+/// result in an `ErrorKind::InvalidInput` error.
 ///
 /// See the [Statsd spec](https://github.com/b/statsd_spec) for more
 /// information.
@@ -487,7 +885,14 @@ where
 
     /// Record a single set value with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Set>;
+    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Set>;
+
+    /// Record a set value with the given key and the tags of a
+    /// `TagSet`, and return a `MetricBuilder` that can be used to add more
+    /// tags to the metric.
+    fn set_with_tagset<'a>(&'a self, key: &'a str, value: T, tags: &'a TagSet) -> MetricBuilder<'a, 'a, Set> {
+        self.set_with_tags(key, value).with_tag_set(tags)
+    }
 }
 
 /// Trait that encompasses all other traits for sending metrics.
@@ -513,6 +918,8 @@ where
 /// client.time("some.timer", vec![42]).unwrap();
 /// client.time("some.timer", vec![Duration::from_millis(42)]).unwrap();
 /// client.gauge("some.gauge", 8).unwrap();
+/// client.gauge("some.gauge", -8i64).unwrap();
+/// client.gauge("some.gauge", true).unwrap();
 /// client.meter("some.meter", 13).unwrap();
 /// client.histogram("some.histogram", 4).unwrap();
 /// client.histogram("some.histogram", Duration::from_nanos(4)).unwrap();
@@ -521,6 +928,8 @@ where
 /// client.distribution("some.distribution", 4).unwrap();
 /// client.distribution("some.distribution", vec![4]).unwrap();
 /// client.set("some.set", 5).unwrap();
+/// client.set("some.set", vec![5, 6]).unwrap();
+/// client.set("some.set", "user-1234").unwrap();
 /// ```
 pub trait MetricClient:
     Counted<i64>
@@ -532,8 +941,14 @@ pub trait MetricClient:
     + Timed<Duration>
     + Timed<Vec<u64>>
     + Timed<Vec<Duration>>
+    + for<'a> Timed<&'a [u64]>
+    + for<'a> Timed<&'a [Duration]>
+    + TimedExt
     + Gauged<u64>
+    + Gauged<i64>
+    + Gauged<i32>
     + Gauged<f64>
+    + Gauged<bool>
     + Metered<u64>
     + Histogrammed<u64>
     + Histogrammed<f64>
@@ -541,11 +956,20 @@ pub trait MetricClient:
     + Histogrammed<Vec<u64>>
     + Histogrammed<Vec<f64>>
     + Histogrammed<Vec<Duration>>
+    + for<'a> Histogrammed<&'a [u64]>
+    + for<'a> Histogrammed<&'a [f64]>
+    + for<'a> Histogrammed<&'a [Duration]>
+    + HistogrammedExt
     + Distributed<u64>
     + Distributed<f64>
     + Distributed<Vec<u64>>
     + Distributed<Vec<f64>>
+    + for<'a> Distributed<&'a [u64]>
+    + for<'a> Distributed<&'a [f64]>
     + Setted<i64>
+    + Setted<Vec<i64>>
+    + for<'a> Setted<&'a str>
+    + Setted<String>
 {
 }
 
@@ -632,6 +1056,18 @@ pub trait MetricBackend: Sealed {
     where
         M: Metric;
 
+    /// Send a full formed `Metric` implementation via the underlying `MetricSink`,
+    /// returning the number of bytes written by the sink.
+    ///
+    /// This behaves exactly like `.send_metric()` except that the number of bytes
+    /// reported by the `MetricSink` is returned instead of being discarded. This
+    /// can be used to keep track of the volume of metrics being sent. Note that
+    /// some sinks (such as those that buffer or queue metrics) may report zero
+    /// bytes when a metric isn't written immediately.
+    fn send_metric_bytes<M>(&self, metric: &M) -> MetricResult<usize>
+    where
+        M: Metric;
+
     /// Consume a possible error from attempting to send a metric.
     ///
     /// When callers have elected to quietly send metrics via the `MetricBuilder::send()`
@@ -642,6 +1078,59 @@ pub trait MetricBackend: Sealed {
     /// use this method. This is only useful if you are extending Cadence with a
     /// custom metric type or something similar.
     fn consume_error(&self, err: MetricError);
+
+    /// Return the `MetricSink` currently used to send metrics.
+    ///
+    /// This can be used to read statistics of the sink or, combined with
+    /// `MetricSink::as_any()`, to downcast it to its concrete type and use
+    /// methods specific to it. Note that sending metrics directly with the
+    /// sink bypasses the client entirely (prefix, default tags, etc.).
+    fn sink(&self) -> Arc<dyn MetricSink + Sync + Send + RefUnwindSafe>;
+}
+
+type ErrorHandler = Arc<dyn Fn(MetricError) + Sync + Send + RefUnwindSafe>;
+type EmitObserver = Arc<dyn Fn(&str) + Sync + Send + RefUnwindSafe>;
+type BoxedSink = Box<dyn MetricSink + Sync + Send + RefUnwindSafe>;
+type ClientSink = Arc<dyn MetricSink + Sync + Send + RefUnwindSafe>;
+
+// Sink used by a client, either ready to use as-is or only built once the
+// rest of the client is configured because it needs the error handler. Sinks
+// that are built wrapped in a queuing sink also return its queue statistics.
+enum PendingSink {
+    Ready(BoxedSink),
+    Deferred(Box<dyn FnOnce(ErrorHandler) -> (BoxedSink, Option<QueueStats>) + Send + Sync>),
+}
+
+impl PendingSink {
+    fn build(self, errors: ErrorHandler) -> (BoxedSink, Option<QueueStats>) {
+        match self {
+            PendingSink::Ready(sink) => (sink, None),
+            PendingSink::Deferred(build) => build(errors),
+        }
+    }
+}
+
+// Sink used by the client passed to `StatsdClient::batch()` to collect the
+// metrics emitted so that they can be sent together afterwards.
+#[derive(Debug, Default)]
+struct BatchSink {
+    metrics: Mutex<Vec<String>>,
+}
+
+impl BatchSink {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.metrics.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl MetricSink for BatchSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(metric.to_string());
+        Ok(metric.len())
+    }
 }
 
 /// Builder for creating and customizing `StatsdClient` instances.
@@ -672,10 +1161,25 @@ pub trait MetricBackend: Sealed {
 /// ```
 pub struct StatsdClientBuilder {
     prefix: String,
-    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
-    errors: Box<dyn Fn(MetricError) + Sync + Send + RefUnwindSafe>,
+    sink: PendingSink,
+    errors: ErrorHandler,
+    emit_observer: Option<EmitObserver>,
+    clock: Arc<dyn Clock>,
     tags: Vec<(Option<String>, String)>,
+    type_tags: Vec<(MetricKind, String, String)>,
     container_id: Option<String>,
+    cardinality: Option<Cardinality>,
+    cardinality_guard: Option<CardinalityGuard>,
+    sorted_tags: bool,
+    always_tag_separator: bool,
+    trim: bool,
+    name_transform: Option<NameTransform>,
+    type_codes: Option<TypeCodeMap>,
+    float_precision: Option<usize>,
+    float_decimal_point: bool,
+    default_sample_rate: Option<f64>,
+    strict_meters: bool,
+    self_metrics: Option<(String, Duration)>,
 }
 
 impl StatsdClientBuilder {
@@ -684,15 +1188,51 @@ impl StatsdClientBuilder {
     where
         T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
     {
+        Self::from_pending(prefix, PendingSink::Ready(Box::new(sink)))
+    }
+
+    // Wrap the sink in a queuing sink that reports errors from the wrapped
+    // sink to the same error handler as the client, when the client is built.
+    fn queuing<T>(prefix: &str, sink: T, queuing: QueuingMetricSinkBuilder) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::from_pending(
+            prefix,
+            PendingSink::Deferred(Box::new(move |errors: ErrorHandler| {
+                let queuing = queuing.with_default_error_handler(move |e| errors(MetricError::from(e)));
+                let sink = queuing.build(sink);
+                let stats = sink.queue_stats();
+                (Box::new(sink), Some(stats))
+            })),
+        )
+    }
+
+    fn from_pending(prefix: &str, sink: PendingSink) -> Self {
         StatsdClientBuilder {
             // required
             prefix: Self::formatted_prefix(prefix),
-            sink: Box::new(sink),
+            sink,
 
             // optional with defaults
-            errors: Box::new(nop_error_handler),
+            errors: Arc::new(nop_error_handler),
+            emit_observer: None,
+            clock: Arc::new(SystemClock),
             tags: Vec::new(),
+            type_tags: Vec::new(),
             container_id: None,
+            cardinality: None,
+            cardinality_guard: None,
+            sorted_tags: false,
+            always_tag_separator: false,
+            trim: false,
+            name_transform: None,
+            type_codes: None,
+            float_precision: None,
+            float_decimal_point: false,
+            default_sample_rate: None,
+            strict_meters: false,
+            self_metrics: None,
         }
     }
 
@@ -705,11 +1245,54 @@ impl StatsdClientBuilder {
     /// The error handler should consume the error without panicking. The error
     /// may be logged, printed to stderr, discarded, etc. - this is up to the
     /// implementation.
+    ///
+    /// When the builder was created with `StatsdClient::queuing_builder()`, the
+    /// error handler is also invoked (from the thread of the `QueuingMetricSink`)
+    /// for errors returned by the wrapped sink, unless the `QueuingMetricSinkBuilder`
+    /// already had its own error handler set.
     pub fn with_error_handler<F>(mut self, errors: F) -> Self
     where
         F: Fn(MetricError) + Sync + Send + RefUnwindSafe + 'static,
     {
-        self.errors = Box::new(errors);
+        self.errors = Arc::new(errors);
+        self
+    }
+
+    /// Set a function to be called with each metric after it has been sent
+    /// successfully by the built [StatsdClient].
+    ///
+    /// This is a lightweight way to observe every metric sent by the client,
+    /// e.g. for auditing or debugging, without wrapping the sink. The function
+    /// is called on the thread sending the metric, after the sink accepted it,
+    /// so it should be cheap and must not panic. Metrics that fail to be sent
+    /// are passed to the error handler instead. By default, there is no
+    /// observer and there's no additional cost for sending metrics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let sent = Arc::new(AtomicUsize::new(0));
+    /// let sent_ref = sent.clone();
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_emit_observer(move |metric: &str| {
+    ///         sent_ref.fetch_add(metric.len(), Ordering::Relaxed);
+    ///     })
+    ///     .build();
+    ///
+    /// client.count("some.counter", 1).unwrap();
+    /// assert_eq!(26, sent.load(Ordering::Relaxed));
+    /// ```
+    pub fn with_emit_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&str) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.emit_observer = Some(Arc::new(observer));
         self
     }
 
@@ -734,6 +1317,113 @@ impl StatsdClientBuilder {
         self
     }
 
+    /// Add a default tag to every metric published by the built [StatsdClient]
+    /// for each pair of tag key and environment variable, using the value of
+    /// the environment variable as the value of the tag.
+    ///
+    /// Environment variables are read when this method is called. Tags are
+    /// only added for environment variables that are set to valid unicode,
+    /// others are skipped. This is useful for adding tags based on the context
+    /// provided by a deployment environment, such as `DD_ENV`, `DD_SERVICE`, or
+    /// the name of a pod.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{NopMetricSink, StatsdClient};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_tags_from_env(&[("env", "DD_ENV"), ("service", "DD_SERVICE"), ("version", "DD_VERSION")])
+    ///     .build();
+    /// ```
+    pub fn with_tags_from_env<K, V>(self, tags: &[(K, V)]) -> Self
+    where
+        K: ToString,
+        V: AsRef<str>,
+    {
+        self.with_tags_from_lookup(tags, |var| env::var(var).ok())
+    }
+
+    // Add a default tag for each pair of tag key and variable name that the
+    // lookup function returns a value for. This allows tests to provide values
+    // without modifying the environment of the (multithreaded) test process.
+    pub(crate) fn with_tags_from_lookup<K, V, F>(mut self, tags: &[(K, V)], lookup: F) -> Self
+    where
+        K: ToString,
+        V: AsRef<str>,
+        F: Fn(&str) -> Option<String>,
+    {
+        for (key, var) in tags {
+            if let Some(value) = lookup(var.as_ref()) {
+                self.tags.push((Some(key.to_string()), value));
+            }
+        }
+
+        self
+    }
+
+    /// Add the Datadog unified service tags to every metric published by the
+    /// built [StatsdClient] based on the standard environment variables.
+    ///
+    /// The following environment variables are read when this method is called
+    /// and, when set, added as default tags. Environment variables that aren't
+    /// set are skipped. See the
+    /// [Datadog docs](https://docs.datadoghq.com/getting_started/tagging/unified_service_tagging/)
+    /// for more information.
+    ///
+    /// * `DD_ENV` as the `env` tag
+    /// * `DD_SERVICE` as the `service` tag
+    /// * `DD_VERSION` as the `version` tag
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{NopMetricSink, StatsdClient};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_dd_unified_tags()
+    ///     .build();
+    /// ```
+    pub fn with_dd_unified_tags(self) -> Self {
+        self.with_tags_from_lookup(DD_UNIFIED_TAGS, |var| env::var(var).ok())
+    }
+
+    /// Add a default tag to every metric of the given type published by the
+    /// built [StatsdClient].
+    ///
+    /// Tags for a type of metric are added after the default tags added to
+    /// every metric, in the order they're added to the builder. This is useful
+    /// for tags that only make sense for some types of metrics, such as the
+    /// unit of timers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{Metric, MetricKind, NopMetricSink, StatsdClient};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_tag("env", "prod")
+    ///     .with_type_tag(MetricKind::Timer, "unit", "ms")
+    ///     .build();
+    ///
+    /// let res = client.time("some.timer", 12).unwrap();
+    /// assert_eq!("my.prefix.some.timer:12|ms|#env:prod,unit:ms", res.as_metric_str());
+    ///
+    /// let res = client.count("some.counter", 1).unwrap();
+    /// assert_eq!("my.prefix.some.counter:1|c|#env:prod", res.as_metric_str());
+    /// ```
+    pub fn with_type_tag<K, V>(mut self, kind: MetricKind, key: K, value: V) -> Self
+    where
+        K: ToString,
+        V: ToString,
+    {
+        self.type_tags.push((kind, key.to_string(), value.to_string()));
+        self
+    }
+
     /// Add a default container ID to every metric published by the built
     /// [StatsdClient].
     pub fn with_container_id<K>(mut self, container_id: K) -> Self
@@ -744,7 +1434,413 @@ impl StatsdClientBuilder {
         self
     }
 
-    /// Construct a new `StatsdClient` instance based on current settings.
+    /// Add a default tag cardinality hint to every metric published by the
+    /// built [StatsdClient].
+    ///
+    /// This can be overridden for individual metrics using
+    /// `MetricBuilder::with_cardinality()`. See [Cardinality] for more information.
+    pub fn with_cardinality(mut self, cardinality: Cardinality) -> Self {
+        self.cardinality = Some(cardinality);
+        self
+    }
+
+    /// Sort the tags of every metric published by the built [StatsdClient].
+    ///
+    /// By default, tags are emitted in the order they were added: default tags
+    /// of the client first and then tags added to each metric. When sorting is
+    /// enabled, key-value tags are emitted first, sorted by key and then value,
+    /// followed by value-only tags sorted by value. This results in the same
+    /// output for the same set of tags regardless of where they were added,
+    /// which can be useful for servers that aggregate metrics by their tags.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_tag("region", "us-east-1")
+    ///     .with_sorted_tags()
+    ///     .build();
+    ///
+    /// let res = client.count_with_tags("some.counter", 1)
+    ///     .with_tag_value("beta")
+    ///     .with_tag("host", "web01")
+    ///     .try_send();
+    ///
+    /// assert_eq!(
+    ///     "prefix.some.counter:1|c|#host:web01,region:us-east-1,beta",
+    ///     res.unwrap().as_metric_str(),
+    /// );
+    /// ```
+    pub fn with_sorted_tags(mut self) -> Self {
+        self.sorted_tags = true;
+        self
+    }
+
+    /// Set whether every metric published by the built [StatsdClient] includes
+    /// the tag separator (`|#`) even when it doesn't have any tags.
+    ///
+    /// By default, the separator is only written when a metric has at least one
+    /// tag. Some parsers require it to always be present while strict Statsd
+    /// servers reject a metric with an empty tag section, so only enable this
+    /// when the server receiving metrics requires it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_always_tag_separator(true)
+    ///     .build();
+    ///
+    /// let res = client.count("some.counter", 1);
+    /// assert_eq!("prefix.some.counter:1|c|#", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_always_tag_separator(mut self, always: bool) -> Self {
+        self.always_tag_separator = always;
+        self
+    }
+
+    /// Set whether leading and trailing whitespace is removed from the key,
+    /// tag keys, and tag values of every metric published by the built
+    /// [StatsdClient].
+    ///
+    /// Keys and tags that come from configuration files or environment
+    /// variables sometimes include stray whitespace that results in invalid
+    /// metrics. By default, keys and tags are written exactly as given. The
+    /// prefix of the client isn't trimmed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_tag("host", " web01\n")
+    ///     .with_trim(true)
+    ///     .build();
+    ///
+    /// let res = client.count(" some.counter ", 1);
+    /// assert_eq!("prefix.some.counter:1|c|#host:web01", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Transform the full name (prefix and key) of every metric published by
+    /// the built [StatsdClient] when it's formatted.
+    ///
+    /// This can be used to adapt names to the conventions of a particular
+    /// server centrally instead of changing every key, such as replacing dots
+    /// with underscores or converting names to lowercase. Only the name is
+    /// passed to the transform, the value, type, and tags of the metric are
+    /// unchanged. By default, names are used as-is.
+    ///
+    /// The transform is called once for each metric emitted. When it's set,
+    /// the prefix and key of each metric are joined into a temporary `String`
+    /// before being passed to it and a transform that returns `Cow::Owned`
+    /// allocates another `String` per metric. Return `Cow::Borrowed` when the
+    /// name doesn't need to be changed to avoid this extra allocation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_name_transform(|name: &str| {
+    ///         if name.contains('.') {
+    ///             Cow::Owned(name.replace('.', "_"))
+    ///         } else {
+    ///             Cow::Borrowed(name)
+    ///         }
+    ///     })
+    ///     .build();
+    ///
+    /// let res = client.count("some.counter", 1);
+    /// assert_eq!("my_prefix_some_counter:1|c", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_name_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.name_transform = Some(NameTransform::new(transform));
+        self
+    }
+
+    /// Use the given codes to identify the type of every metric published by
+    /// the built [StatsdClient].
+    ///
+    /// This is only needed for servers that don't use the standard Statsd type
+    /// codes (`c` for counters, `ms` for timers, etc.) for some types of metrics.
+    /// See [TypeCodeMap] for more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric, TypeCodeMap};
+    ///
+    /// let codes = TypeCodeMap::new().with_timer("t").unwrap();
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_type_codes(codes)
+    ///     .build();
+    ///
+    /// let res = client.time("some.timer", 34);
+    /// assert_eq!("my.prefix.some.timer:34|t", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_type_codes(mut self, codes: TypeCodeMap) -> Self {
+        self.type_codes = Some(codes);
+        self
+    }
+
+    /// Format floating point values of every metric published by the built
+    /// [StatsdClient] with at most the given number of decimal places.
+    ///
+    /// By default, floats are formatted with as many decimal places as needed
+    /// to represent them exactly, which can result in long values such as
+    /// `0.30000000000000004`. When a precision is set, values are rounded to
+    /// the given number of decimal places and any trailing zeros are removed
+    /// (e.g. `1.5` instead of `1.500`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_float_precision(3)
+    ///     .build();
+    ///
+    /// let res = client.gauge("some.gauge", 0.1 + 0.2);
+    /// assert_eq!("my.prefix.some.gauge:0.3|g", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_float_precision(mut self, precision: usize) -> Self {
+        self.float_precision = Some(precision);
+        self
+    }
+
+    /// Always include a decimal point in floating point values of every metric
+    /// published by the built [StatsdClient].
+    ///
+    /// By default, floats that are whole numbers are formatted without a
+    /// decimal point (e.g. `1` for `1.0`), making them indistinguishable from
+    /// integers. Some servers, such as DogStatsD, use the format of a value as
+    /// a hint for its type. When enabled, `.0` is added to floats that would
+    /// otherwise be formatted as whole numbers (e.g. `1.0` and `0.0`). This
+    /// applies after rounding to the precision set by `with_float_precision()`,
+    /// if any. Integer values and floats that aren't finite are unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_float_decimal_point(true)
+    ///     .build();
+    ///
+    /// let res = client.gauge("some.gauge", 1.0);
+    /// assert_eq!("my.prefix.some.gauge:1.0|g", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_float_decimal_point(mut self, decimal_point: bool) -> Self {
+        self.float_decimal_point = decimal_point;
+        self
+    }
+
+    /// Randomly sample every metric published by the built [StatsdClient] at
+    /// the given rate unless a different rate is set for a particular metric.
+    ///
+    /// This works exactly like calling `.with_sample_rate()` on the builder of
+    /// every metric: metrics are randomly sent at the given rate and include
+    /// the rate (e.g. `|@0.25`) so that the server can scale them accordingly.
+    /// Calling `.with_sample_rate()` or `.with_sampling_rate()` on the builder
+    /// of a metric replaces the default rate for that metric. A rate less than
+    /// 0 or greater than 1 will result in an `ErrorKind::InvalidInput` error
+    /// for every metric that uses the default rate.
+    ///
+    /// This is useful for reducing the volume of metrics sent by an entire
+    /// application, for example, based on its configuration.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_default_sample_rate(0.25)
+    ///     .build();
+    ///
+    /// let res = client.count("some.counter", 1);
+    /// assert_eq!("my.prefix.some.counter:1|c|@0.25", res.unwrap().as_metric_str());
+    ///
+    /// let res = client.count_with_tags("important.counter", 1)
+    ///     .with_sample_rate(1.0)
+    ///     .try_send();
+    /// assert_eq!("my.prefix.important.counter:1|c", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_default_sample_rate(mut self, rate: f64) -> Self {
+        self.default_sample_rate = Some(rate);
+        self
+    }
+
+    /// Reject meter values that aren't positive when they're emitted by the
+    /// built [StatsdClient].
+    ///
+    /// Meters mark occurrences of an event and so only ever increase. A meter
+    /// value of zero (or a negative value) is almost always a logic error, such
+    /// as a meter being fed a delta that should have been recorded as a counter
+    /// or gauge. When enabled, these values result in an `ErrorKind::InvalidInput`
+    /// error instead of being sent. By default, any meter value is sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, ErrorKind};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_strict_meters()
+    ///     .build();
+    ///
+    /// assert!(client.meter("some.meter", 1).is_ok());
+    /// assert_eq!(ErrorKind::InvalidInput, client.meter("some.meter", 0).unwrap_err().kind());
+    /// ```
+    pub fn with_strict_meters(mut self) -> Self {
+        self.strict_meters = true;
+        self
+    }
+
+    /// Use the given `Clock` to determine the current time when measuring
+    /// elapsed time with the built [StatsdClient].
+    ///
+    /// The clock is used by `.time_since()` and, when the `async` feature is
+    /// enabled, `.time_future()` to measure how much time has passed. This
+    /// allows code that records these timers to be tested deterministically
+    /// with a clock that is controlled by the test. By default, the monotonic
+    /// system clock (`SystemClock`) is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use cadence::prelude::*;
+    /// use cadence::ext::Clock;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// // Clock that's always 250ms ahead of when it was created
+    /// #[derive(Debug)]
+    /// struct FixedClock(Instant);
+    ///
+    /// impl Clock for FixedClock {
+    ///     fn now(&self) -> Instant {
+    ///         self.0 + Duration::from_millis(250)
+    ///     }
+    /// }
+    ///
+    /// let start = Instant::now();
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_clock(FixedClock(start))
+    ///     .build();
+    ///
+    /// let res = client.time_since("some.operation", start);
+    /// assert_eq!("my.prefix.some.operation:250|ms", res.unwrap().as_metric_str());
+    /// ```
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Track the number of unique combinations of metric name and tags emitted
+    /// by the built [StatsdClient], invoking a callback when there are more than
+    /// `limit` of them.
+    ///
+    /// This is meant as an early warning for accidentally using a tag with an
+    /// unbounded number of values (like a user ID or a request ID) which can be
+    /// expensive or even break the server receiving metrics. The callback is
+    /// invoked at most once, with the name and tags of the first metric that
+    /// exceeded the limit. Metrics are still sent normally after the limit has
+    /// been exceeded.
+    ///
+    /// Only a hash of each combination is kept and memory use is bounded by
+    /// `limit`. Tracking stops entirely once the limit has been exceeded. The
+    /// order of tags doesn't matter when determining if combinations are unique.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_cardinality_limit(1000, |key: &str| {
+    ///         eprintln!("too many unique metrics! latest: {}", key);
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn with_cardinality_limit<F>(mut self, limit: usize, on_exceed: F) -> Self
+    where
+        F: Fn(&str) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.cardinality_guard = Some(CardinalityGuard::new(limit, on_exceed));
+        self
+    }
+
+    /// Periodically emit metrics about the sink used by the built [StatsdClient]
+    /// under the given prefix, every `interval`.
+    ///
+    /// This gives visibility into the metrics pipeline itself, for example to
+    /// alert when metrics are being dropped. A thread is started when the client
+    /// is built that emits the following metrics, using the same sink and default
+    /// tags as the client but with the given prefix instead of the prefix of the
+    /// client:
+    ///
+    /// * `sink.bytes_sent`, `sink.packets_sent`, `sink.bytes_dropped`, and
+    ///   `sink.packets_dropped`: counters with the change in each of the values
+    ///   from `MetricSink::stats()` since the previous interval.
+    /// * `queue.depth`: a gauge of the number of metrics waiting to be sent.
+    /// * `queue.panics`: a counter of the number of times the wrapped sink
+    ///   panicked since the previous interval.
+    ///
+    /// The `queue` metrics are only emitted by clients created with
+    /// `StatsdClient::queuing_builder()` or `StatsdClient::production_udp_builder()`.
+    /// The thread is stopped when the client and all of its clones are dropped.
+    ///
+    /// Note that since self-metrics are sent with the same sink as all other
+    /// metrics, they are themselves included in the sink statistics reported
+    /// for the next interval. Use a prefix that is distinct from the prefix of
+    /// the client so that they can be told apart from application metrics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_self_metrics("cadence", Duration::from_secs(10))
+    ///     .build();
+    /// ```
+    pub fn with_self_metrics(mut self, prefix: &str, interval: Duration) -> Self {
+        self.self_metrics = Some((Self::formatted_prefix(prefix), interval));
+        self
+    }
+
+    /// Construct a new `StatsdClient` instance based on current settings.
     pub fn build(self) -> StatsdClient {
         StatsdClient::from_builder(self)
     }
@@ -801,7 +1897,7 @@ impl StatsdClientBuilder {
 ///
 /// ## Wrapping With An `Arc`
 ///
-/// In order to share a client between multiple threads, you'll need to wrap it
+/// One way to share a client between multiple threads is to wrap it
 /// with an atomic reference counting pointer (`std::sync::Arc`). You should refer
 /// to the client by the trait of all its methods for recording metrics
 /// (`MetricClient`) as well as the `Send` and `Sync` traits since the idea is to
@@ -840,12 +1936,56 @@ impl StatsdClientBuilder {
 ///     }
 /// }
 /// ```
+///
+/// ## Cloning
+///
+/// Alternatively, the client itself can be cloned and each clone moved into
+/// the thread that uses it. Cloning is cheap since the sink, error handler,
+/// and default tags are shared between all clones instead of being copied.
+/// Metrics emitted by any of the clones are sent to the same sink.
+///
+/// ``` no_run
+/// use std::net::UdpSocket;
+/// use std::thread;
+/// use cadence::prelude::*;
+/// use cadence::{StatsdClient, BufferedUdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let host = ("localhost", DEFAULT_PORT);
+/// let sink = BufferedUdpMetricSink::from(host, socket).unwrap();
+/// let client = StatsdClient::from_sink("some.prefix", sink);
+///
+/// let client_ref = client.clone();
+/// let t = thread::spawn(move || {
+///     client_ref.count("request.handler", 1);
+/// });
+///
+/// t.join().unwrap();
+/// client.count("request.main", 1);
+/// ```
+#[derive(Clone)]
 pub struct StatsdClient {
     prefix: String,
-    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
-    errors: Box<dyn Fn(MetricError) + Sync + Send + RefUnwindSafe>,
-    tags: Vec<(Option<String>, String)>,
+    sink: Arc<ArcSwap<ClientSink>>,
+    errors: ErrorHandler,
+    emit_observer: Option<EmitObserver>,
+    clock: Arc<dyn Clock>,
+    tags: Arc<Vec<(Option<String>, String)>>,
+    type_tags: Arc<Vec<(MetricKind, String, String)>>,
     container_id: Option<String>,
+    cardinality: Option<Cardinality>,
+    cardinality_guard: Option<Arc<CardinalityGuard>>,
+    sorted_tags: bool,
+    always_tag_separator: bool,
+    trim: bool,
+    name_transform: Option<Arc<NameTransform>>,
+    type_codes: Option<Arc<TypeCodeMap>>,
+    float_precision: Option<usize>,
+    float_decimal_point: bool,
+    default_sample_rate: Option<f64>,
+    strict_meters: bool,
+    self_metrics: Option<Arc<SelfMetricsReporter>>,
+    raw: bool,
 }
 
 impl StatsdClient {
@@ -901,6 +2041,68 @@ impl StatsdClient {
         Self::builder(prefix, sink).build()
     }
 
+    /// Create a new client instance that will use the given prefix and send
+    /// metrics with a sink shared with other clients.
+    ///
+    /// This allows multiple clients with different prefixes to send metrics
+    /// using a single underlying sink (and socket, queue, etc.). It's
+    /// equivalent to calling `from_sink()` with a clone of the shared sink.
+    /// To customize the client (tags, error handler, etc.), pass a clone of
+    /// the shared sink to `builder()` instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, SharedSink};
+    ///
+    /// let shared = SharedSink::new(NopMetricSink);
+    /// let db = StatsdClient::from_shared("my.app.db", &shared);
+    /// let cache = StatsdClient::builder("my.app.cache", shared.clone())
+    ///     .with_tag("region", "us-east-1")
+    ///     .build();
+    ///
+    /// db.count("queries", 1).unwrap();
+    /// cache.count("hits", 1).unwrap();
+    /// ```
+    pub fn from_shared(prefix: &str, sink: &SharedSink) -> Self {
+        Self::from_sink(prefix, sink.clone())
+    }
+
+    /// Create a new client instance that passes keys through unchanged to the
+    /// given `MetricSink` implementation.
+    ///
+    /// The client has no prefix and none of the defaults that can be set with
+    /// a `StatsdClientBuilder` (tags, container ID, sample rate, etc.). Metrics
+    /// are formatted with only the key, value, and anything added to each
+    /// `MetricBuilder`, skipping the work of applying client defaults entirely.
+    /// This is the configuration with the least overhead, meant for forwarders
+    /// and proxies that already have fully qualified metric names.
+    ///
+    /// Like `from_sink()`, this client will discard errors encountered when
+    /// sending metrics via the `MetricBuilder::send()` method.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::raw(NopMetricSink);
+    ///
+    /// let res = client.count("upstream.service.requests", 1);
+    /// assert_eq!("upstream.service.requests:1|c", res.unwrap().as_metric_str());
+    /// ```
+    pub fn raw<T>(sink: T) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        StatsdClient {
+            raw: true,
+            ..Self::from_sink("", sink)
+        }
+    }
+
     /// Create a new builder with the provided prefix and metric sink.
     ///
     /// A prefix and a metric sink are required to create a new client
@@ -945,695 +2147,3349 @@ impl StatsdClient {
         StatsdClientBuilder::new(prefix, sink)
     }
 
-    /// Flush the underlying metric sink.
+    /// Create a new builder with the provided prefix and a metric sink that
+    /// will be wrapped in a `QueuingMetricSink` when the client is built.
     ///
-    /// This is helpful for when you'd like to buffer metrics
-    /// but still want strong control over when to emit them.
-    /// For example, you are using a BufferedUdpMetricSink and
-    /// have just emitted some time-sensitive metrics, but you
-    /// aren't sure if the buffer is full or not. Thus, you can
-    /// use `flush` to force the sink to flush your metrics now.
+    /// Errors from the wrapped sink happen in the thread of the `QueuingMetricSink`
+    /// after metrics have already been handed off by the client and so normally
+    /// can only be observed with an error handler set on the `QueuingMetricSinkBuilder`.
+    /// Clients created with this builder pass these errors (converted to a
+    /// `MetricError`) to the same error handler used for `MetricBuilder::send()`,
+    /// set via `StatsdClientBuilder::with_error_handler()`. If the
+    /// `QueuingMetricSinkBuilder` already has an error handler, it's used instead.
     ///
-    /// # Buffered UDP Socket Example
+    /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// use std::net::UdpSocket;
     /// use cadence::prelude::*;
-    /// use cadence::{StatsdClient, BufferedUdpMetricSink, DEFAULT_PORT};
-    ///
-    /// let prefix = "my.stats";
-    /// let host = ("127.0.0.1", DEFAULT_PORT);
+    /// use cadence::{StatsdClient, QueuingMetricSinkBuilder, BufferedUdpMetricSink, DEFAULT_PORT};
     ///
     /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
-    ///
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
     /// let sink = BufferedUdpMetricSink::from(host, socket).unwrap();
-    /// let client = StatsdClient::from_sink(prefix, sink);
+    /// let queuing = QueuingMetricSinkBuilder::new().with_capacity(64 * 1024);
     ///
-    /// client.count("time-sensitive.keyA", 1);
-    /// client.count("time-sensitive.keyB", 2);
-    /// client.count("time-sensitive.keyC", 3);
-    /// // Any number of time-sensitive metrics ...
-    /// client.flush();
+    /// let client = StatsdClient::queuing_builder("some.prefix", sink, queuing)
+    ///     .with_error_handler(|e| eprintln!("Error sending metrics: {}", e))
+    ///     .build();
+    ///
+    /// client.count("some.counter", 1);
     /// ```
-    pub fn flush(&self) -> MetricResult<()> {
-        Ok(self.sink.flush()?)
+    pub fn queuing_builder<T>(prefix: &str, sink: T, queuing: QueuingMetricSinkBuilder) -> StatsdClientBuilder
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        StatsdClientBuilder::queuing(prefix, sink, queuing)
     }
 
-    // Create a new StatsdClient by consuming the builder
-    fn from_builder(builder: StatsdClientBuilder) -> Self {
-        StatsdClient {
-            prefix: builder.prefix,
-            sink: builder.sink,
-            errors: builder.errors,
-            tags: builder.tags,
-            container_id: builder.container_id,
-        }
+    /// Create a new client that sends metrics to the given host using the
+    /// recommended configuration for production use.
+    ///
+    /// Metrics are sent by a `BufferedUdpMetricSink` using a non-blocking socket,
+    /// wrapped in a `QueuingMetricSink` with an unbounded queue so that sending
+    /// metrics doesn't block the calling thread. This is equivalent to creating
+    /// each of these yourself. See `StatsdClient::production_udp_builder()` to
+    /// customize the sinks or the client.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, DEFAULT_PORT};
+    ///
+    /// let client = StatsdClient::production_udp("my.prefix", ("metrics.example.com", DEFAULT_PORT)).unwrap();
+    /// client.count("my.counter.thing", 29);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
+    /// * It is unable to bind a local UDP socket.
+    pub fn production_udp<A>(prefix: &str, host: A) -> MetricResult<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let builder = Self::production_udp_builder(
+            prefix,
+            host,
+            BufferedUdpMetricSinkBuilder::new(),
+            QueuingMetricSinkBuilder::new(),
+        )?;
+
+        Ok(builder.build())
     }
 
-    fn tags(&self) -> impl IntoIterator<Item = (Option<&str>, &str)> {
-        self.tags.iter().map(|(k, v)| (k.as_deref(), v.as_str()))
+    /// Create a new builder for a client that sends metrics to the given host
+    /// using the recommended configuration for production use, with the given
+    /// configuration for each sink.
+    ///
+    /// A local UDP socket is bound to an unspecified address of the same family
+    /// (IPv4 or IPv6) as the host and set to non-blocking. This is used by a
+    /// `BufferedUdpMetricSink` created by the `buffered` builder which is then
+    /// wrapped in a `QueuingMetricSink` created by the `queuing` builder. As
+    /// with `StatsdClient::queuing_builder()`, errors from the buffered sink are
+    /// passed to the error handler of the client unless the queuing builder has
+    /// its own error handler.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::prelude::*;
+    /// use cadence::{BufferedUdpMetricSinkBuilder, QueuingMetricSinkBuilder, StatsdClient, DEFAULT_PORT};
+    ///
+    /// let client = StatsdClient::production_udp_builder(
+    ///     "my.prefix",
+    ///     ("metrics.example.com", DEFAULT_PORT),
+    ///     BufferedUdpMetricSinkBuilder::new().with_capacity(1432),
+    ///     QueuingMetricSinkBuilder::new().with_capacity(128 * 1024),
+    /// )
+    /// .unwrap()
+    /// .with_error_handler(|e| eprintln!("Error sending metrics: {}", e))
+    /// .build();
+    ///
+    /// client.count("my.counter.thing", 29);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
+    /// * It is unable to bind a local UDP socket.
+    pub fn production_udp_builder<A>(
+        prefix: &str,
+        host: A,
+        buffered: BufferedUdpMetricSinkBuilder,
+        queuing: QueuingMetricSinkBuilder,
+    ) -> MetricResult<StatsdClientBuilder>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = get_addr(host)?;
+        let socket = bind_nonblocking(&addr)?;
+        let sink = buffered.build(addr, socket)?;
+        Ok(Self::queuing_builder(prefix, sink, queuing))
     }
-}
 
-impl Sealed for StatsdClient {}
+    /// Create a new client that sends metrics to the Unix socket at the given
+    /// path using the recommended configuration for production use.
+    ///
+    /// Metrics are sent by a `BufferedUnixMetricSink` with a buffer of
+    /// `DEFAULT_BUFFER_SIZE` bytes using a non-blocking, unbound socket,
+    /// wrapped in a `QueuingMetricSink` with an unbounded queue so that sending
+    /// metrics doesn't block the calling thread. This is equivalent to creating
+    /// each of these yourself. See `StatsdClient::production_unix_builder()` to
+    /// customize the sinks or the client. This method is only available on Unix
+    /// platforms.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::prelude::*;
+    /// use cadence::StatsdClient;
+    ///
+    /// let client = StatsdClient::production_unix("my.prefix", "/var/run/datadog/dsd.socket").unwrap();
+    /// client.count("my.counter.thing", 29);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if it is unable to create a Unix socket.
+    #[cfg(unix)]
+    pub fn production_unix<P>(prefix: &str, path: P) -> MetricResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let builder = Self::production_unix_builder(
+            prefix,
+            path,
+            BufferedUnixMetricSinkBuilder::new(),
+            QueuingMetricSinkBuilder::new(),
+        )?;
+
+        Ok(builder.build())
+    }
 
-impl MetricBackend for StatsdClient {
-    fn send_metric<M>(&self, metric: &M) -> MetricResult<()>
+    /// Create a new builder for a client that sends metrics to the Unix socket
+    /// at the given path using the recommended configuration for production
+    /// use, with the given configuration for each sink.
+    ///
+    /// An unbound Unix datagram socket is created and set to non-blocking. This
+    /// is used by a `BufferedUnixMetricSink` created by the `buffered` builder
+    /// which is then wrapped in a `QueuingMetricSink` created by the `queuing`
+    /// builder. As with `StatsdClient::queuing_builder()`, errors from the
+    /// buffered sink are passed to the error handler of the client unless the
+    /// queuing builder has its own error handler. Note that, unlike UDP, sending
+    /// to a path that nothing is listening on results in an error. To use a
+    /// socket configured differently (bound, blocking with a timeout, etc.)
+    /// build the sink with `BufferedUnixMetricSinkBuilder::build()` and pass it
+    /// to `StatsdClient::queuing_builder()` instead. This method is only
+    /// available on Unix platforms.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::prelude::*;
+    /// use cadence::{BufferedUnixMetricSinkBuilder, QueuingMetricSinkBuilder, StatsdClient};
+    ///
+    /// let client = StatsdClient::production_unix_builder(
+    ///     "my.prefix",
+    ///     "/var/run/datadog/dsd.socket",
+    ///     BufferedUnixMetricSinkBuilder::new().with_capacity(8192),
+    ///     QueuingMetricSinkBuilder::new().with_capacity(128 * 1024),
+    /// )
+    /// .unwrap()
+    /// .with_error_handler(|e| eprintln!("Error sending metrics: {}", e))
+    /// .build();
+    ///
+    /// client.count("my.counter.thing", 29);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if it is unable to create a Unix socket.
+    #[cfg(unix)]
+    pub fn production_unix_builder<P>(
+        prefix: &str,
+        path: P,
+        buffered: BufferedUnixMetricSinkBuilder,
+        queuing: QueuingMetricSinkBuilder,
+    ) -> MetricResult<StatsdClientBuilder>
     where
-        M: Metric,
+        P: AsRef<Path>,
+    {
+        let socket = UnixDatagram::unbound()?;
+        socket.set_nonblocking(true)?;
+        let sink = buffered.build(path, socket);
+        Ok(Self::queuing_builder(prefix, sink, queuing))
+    }
+
+    /// Emit a counter for each of the given keys and values.
+    ///
+    /// Each counter is formatted exactly as if `.count()` were called for it,
+    /// including the default tags of the client. All counters are then passed
+    /// to the underlying `MetricSink` in a single call to `.emit_many()`. Sinks
+    /// that buffer metrics are able to handle this more efficiently than many
+    /// separate calls, for example by only acquiring a lock once. This is useful
+    /// for periodically reporting many values at once, such as from an in-memory
+    /// map of counters.
+    ///
+    /// Every counter is passed to the sink, even if sending some of them fails.
+    /// The first error encountered, if any, is returned. If any of the counters
+    /// can't be formatted (for example, an empty key with no prefix) an error
+    /// is returned and none of them are sent. Like other methods that
+    /// return errors, the error handler of the client isn't invoked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let counts = vec![("requests.success", 120), ("requests.failure", 3)];
+    ///
+    /// client.count_batch(counts).unwrap();
+    /// ```
+    pub fn count_batch<'a, I>(&self, metrics: I) -> MetricResult<()>
+    where
+        I: IntoIterator<Item = (&'a str, i64)>,
     {
-        let metric_string = metric.as_metric_str();
-        self.sink.emit(metric_string)?;
+        let mut formatted = Vec::new();
+        for (key, value) in metrics {
+            let formatter = self.with_defaults(MetricFormatter::counter(&self.prefix, key, MetricValue::Signed(value)));
+            let metric = self.format(&formatter)?;
+            // Counters that aren't picked by the default sample rate of the
+            // client are skipped, just like when they're sent individually.
+            if formatter.sample_rate().map_or(true, sampling::should_send) {
+                formatted.push(metric);
+            }
+        }
+
+        let metrics: Vec<&str> = formatted.iter().map(String::as_str).collect();
+        self.emit_many_str(&metrics)?;
         Ok(())
     }
 
-    fn consume_error(&self, err: MetricError) {
-        (self.errors)(err);
+    /// Emit a histogram for values that have already been counted locally,
+    /// given as pairs of a value and the number of times it was observed.
+    ///
+    /// Statsd has no way to send a value along with a count for histograms so
+    /// each value is repeated `count` times in packed histograms (multiple
+    /// values in a single metric, e.g. `some.key:4:4:9|h`). The values are
+    /// split across as many packed histograms as needed to keep each of them
+    /// within `DEFAULT_BUFFER_SIZE` bytes where possible. A single value that
+    /// doesn't fit on its own is still sent in a histogram by itself. Every
+    /// histogram is formatted exactly as if `.histogram()` were called for it,
+    /// including the default tags of the client, and all of them are passed to
+    /// the underlying `MetricSink` in a single call to `.emit_many()`.
+    ///
+    /// To guard against emitting an enormous number of metrics, the counts
+    /// may add up to at most 10,000 values. An `ErrorKind::InvalidInput` error
+    /// is returned and nothing is sent if they add up to more than that. Pairs
+    /// with a count of zero are skipped. Like other methods that return errors,
+    /// the error handler of the client isn't invoked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    ///
+    /// // Emitted as "my.prefix.response.size:128:128:128:512|h"
+    /// client.histogram_weighted("response.size", &[(128u64, 3), (512, 1)]).unwrap();
+    /// ```
+    pub fn histogram_weighted<T>(&self, key: &str, values: &[(T, u64)]) -> MetricResult<()>
+    where
+        T: ToHistogramValue + Copy,
+    {
+        let total = values
+            .iter()
+            .try_fold(0u64, |total, (_, count)| total.checked_add(*count))
+            .filter(|total| *total <= MAX_WEIGHTED_HISTOGRAM_VALUES);
+        if total.is_none() {
+            return Err(MetricError::from((
+                ErrorKind::InvalidInput,
+                "too many values for a weighted histogram",
+            )));
+        }
+
+        // Each value is only converted and displayed once, no matter how many
+        // times it's repeated. Values are rendered by a formatter with the same
+        // defaults as the sent metrics so float settings of the client apply.
+        let renderer = self.with_defaults(MetricFormatter::histogram(
+            &self.prefix,
+            key,
+            MetricValue::Str(String::new()),
+        ));
+        let mut displayed = Vec::with_capacity(values.len());
+        for (value, count) in values {
+            if *count > 0 {
+                displayed.push((renderer.format_value(&value.try_to_value()?), *count));
+            }
+        }
+
+        let overhead = self.histogram_overhead(key)?;
+        let mut chunks = Vec::new();
+        let mut current = String::new();
+
+        for (value, count) in displayed.iter() {
+            for _ in 0..*count {
+                if !current.is_empty() && overhead + current.len() + 1 + value.len() > DEFAULT_BUFFER_SIZE {
+                    chunks.push(std::mem::take(&mut current));
+                }
+
+                if !current.is_empty() {
+                    current.push(':');
+                }
+                current.push_str(value);
+            }
+        }
+
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+
+        let mut formatted = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let formatter = self.with_defaults(MetricFormatter::histogram(&self.prefix, key, MetricValue::Str(chunk)));
+            let metric = self.format(&formatter)?;
+            if formatter.sample_rate().map_or(true, sampling::should_send) {
+                formatted.push(metric);
+            }
+        }
+
+        let metrics: Vec<&str> = formatted.iter().map(String::as_str).collect();
+        self.emit_many_str(&metrics)?;
+        Ok(())
     }
-}
 
-impl fmt::Debug for StatsdClient {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "StatsdClient {{ prefix: {:?}, sink: ..., errors: ..., tags: {:?} }}",
-            self.prefix, self.tags,
-        )
+    /// Record a ratio (e.g. `0.25`) as a percentage gauge (e.g. `25`) with the
+    /// given key, rounded to the given number of decimal places.
+    ///
+    /// The ratio is multiplied by 100 and rounded to `decimals` places (with
+    /// trailing zeros removed, like `with_float_precision()`), which takes
+    /// precedence over any precision set by `with_float_precision()` on
+    /// the client builder. Ratios outside of `0.0` to `1.0` are not clamped.
+    /// An `ErrorKind::InvalidInput` error is returned if the ratio is NaN or
+    /// infinite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let res = client.gauge_pct("disk.used", 0.8137, 1);
+    ///
+    /// assert_eq!("my.prefix.disk.used:81.4|g", res.unwrap().as_metric_str());
+    /// ```
+    pub fn gauge_pct(&self, key: &str, ratio: f64, decimals: usize) -> MetricResult<Gauge> {
+        self.gauge_pct_with_tags(key, ratio, decimals).try_send()
     }
-}
 
-impl<T> Counted<T> for StatsdClient
-where
-    T: ToCounterValue,
-{
-    fn count_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Counter> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::counter(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
+    /// Record a ratio as a percentage gauge with the given key, rounded to the
+    /// given number of decimal places, and return a `MetricBuilder` that
+    /// can be used to add tags to the metric. See `.gauge_pct()` for more
+    /// information.
+    pub fn gauge_pct_with_tags<'a>(
+        &'a self,
+        key: &'a str,
+        ratio: f64,
+        decimals: usize,
+    ) -> MetricBuilder<'a, 'a, Gauge> {
+        match float_value(ratio * 100.0) {
+            Ok(v) => {
+                let mut formatter = self.with_defaults(MetricFormatter::gauge(&self.prefix, key, v));
+                formatter.with_float_precision(decimals);
+                MetricBuilder::from_fmt(formatter, self)
+            }
             Err(e) => MetricBuilder::from_error(e, self),
         }
     }
-}
 
-impl CountedExt for StatsdClient {}
+    /// Record multiple timings in milliseconds from an iterator with the given
+    /// key as a single packed metric (e.g. `some.key:12:34|ms`).
+    ///
+    /// Values are read from the iterator straight into the metric, without
+    /// needing to collect them into a separate `Vec` or slice first. This is
+    /// useful for summarizing a large collection, or values computed on the
+    /// fly, as a single metric. An `ErrorKind::InvalidInput` error is returned
+    /// if the iterator doesn't have any values or a duration is too large to
+    /// be represented in milliseconds as a `u64`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let latencies = [12u64, 34, 56];
+    /// let res = client.time_iter("some.timer", latencies.iter().map(|ms| Duration::from_millis(*ms)));
+    ///
+    /// assert_eq!("my.prefix.some.timer:12:34:56|ms", res.unwrap().as_metric_str());
+    /// ```
+    pub fn time_iter<I>(&self, key: &str, values: I) -> MetricResult<Timer>
+    where
+        I: IntoIterator<Item = Duration>,
+    {
+        self.time_iter_with_tags(key, values).try_send()
+    }
 
-impl<T> Timed<T> for StatsdClient
-where
-    T: ToTimerValue,
-{
-    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'_, '_, Timer> {
-        match time.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::timer(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
+    /// Record multiple timings in milliseconds from an iterator with the given
+    /// key as a single packed metric and return a `MetricBuilder` that can be
+    /// used to add tags to the metric. See `.time_iter()` for more information.
+    pub fn time_iter_with_tags<'a, I>(&'a self, key: &'a str, values: I) -> MetricBuilder<'a, 'a, Timer>
+    where
+        I: IntoIterator<Item = Duration>,
+    {
+        match packed_millis_iter(values) {
+            Ok(v) => MetricBuilder::from_fmt(self.with_defaults(MetricFormatter::timer(&self.prefix, key, v)), self),
             Err(e) => MetricBuilder::from_error(e, self),
         }
     }
-}
 
-impl<T> Gauged<T> for StatsdClient
-where
-    T: ToGaugeValue,
-{
-    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Gauge> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::gauge(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
-        }
+    /// Record multiple histogram values from an iterator with the given key
+    /// as a single packed metric (e.g. `some.key:1.5:2.5|h`).
+    ///
+    /// Values are read from the iterator straight into the metric, without
+    /// needing to collect them into a separate `Vec` or slice first. This is
+    /// useful for summarizing a large collection, or values computed on the
+    /// fly, as a single metric. An `ErrorKind::InvalidInput` error is returned
+    /// if the iterator doesn't have any values or any value is NaN or infinite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let sizes = [1024u32, 2048, 512];
+    /// let res = client.histogram_iter("some.histogram", sizes.iter().map(|s| f64::from(*s) / 1024.0));
+    ///
+    /// assert_eq!("my.prefix.some.histogram:1:2:0.5|h", res.unwrap().as_metric_str());
+    /// ```
+    pub fn histogram_iter<I>(&self, key: &str, values: I) -> MetricResult<Histogram>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        self.histogram_iter_with_tags(key, values).try_send()
     }
-}
 
-impl<T> Metered<T> for StatsdClient
-where
-    T: ToMeterValue,
-{
-    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Meter> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::meter(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
+    /// Record multiple histogram values from an iterator with the given key
+    /// as a single packed metric and return a `MetricBuilder` that can be used
+    /// to add tags to the metric. See `.histogram_iter()` for more information.
+    pub fn histogram_iter_with_tags<'a, I>(&'a self, key: &'a str, values: I) -> MetricBuilder<'a, 'a, Histogram>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        match packed_float_iter(values) {
+            Ok(v) => MetricBuilder::from_fmt(
+                self.with_defaults(MetricFormatter::histogram(&self.prefix, key, v)),
+                self,
+            ),
             Err(e) => MetricBuilder::from_error(e, self),
         }
     }
-}
 
-impl<T> Histogrammed<T> for StatsdClient
-where
-    T: ToHistogramValue,
-{
-    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Histogram> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::histogram(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
-        }
+    /// Record multiple distribution values from an iterator with the given
+    /// key as a single packed metric (e.g. `some.key:1.5:2.5|d`).
+    ///
+    /// Values are read from the iterator straight into the metric, without
+    /// needing to collect them into a separate `Vec` or slice first. This is
+    /// useful for summarizing a large collection, or values computed on the
+    /// fly, as a single metric. An `ErrorKind::InvalidInput` error is returned
+    /// if the iterator doesn't have any values or any value is NaN or infinite.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let scores = [0.5, 1.5, 2.5];
+    /// let res = client.distribution_iter("some.distribution", scores.iter().copied());
+    ///
+    /// assert_eq!("my.prefix.some.distribution:0.5:1.5:2.5|d", res.unwrap().as_metric_str());
+    /// ```
+    pub fn distribution_iter<I>(&self, key: &str, values: I) -> MetricResult<Distribution>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        self.distribution_iter_with_tags(key, values).try_send()
     }
-}
 
-impl<T> Distributed<T> for StatsdClient
-where
-    T: ToDistributionValue,
-{
-    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Distribution> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::distribution(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
+    /// Record multiple distribution values from an iterator with the given
+    /// key as a single packed metric and return a `MetricBuilder` that can be
+    /// used to add tags to the metric. See `.distribution_iter()` for more
+    /// information.
+    pub fn distribution_iter_with_tags<'a, I>(&'a self, key: &'a str, values: I) -> MetricBuilder<'a, 'a, Distribution>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        match packed_float_iter(values) {
+            Ok(v) => MetricBuilder::from_fmt(
+                self.with_defaults(MetricFormatter::distribution(&self.prefix, key, v)),
+                self,
+            ),
             Err(e) => MetricBuilder::from_error(e, self),
         }
     }
-}
 
-impl<T> Setted<T> for StatsdClient
-where
-    T: ToSetValue,
-{
-    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Set> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::set(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
+    /// Create a new `MetricGroup` for sending multiple metrics together, with a
+    /// single sampling decision for all of them.
+    ///
+    /// Metrics are added to the group by passing their `MetricBuilder` to
+    /// `.with_metric()`. When the group is sent, either every metric in it is sent, in
+    /// a single call to `.emit_many()` of the underlying `MetricSink`, or none
+    /// of them are. This is useful for metrics that should always be sent
+    /// together, such as a counter and timer for the same operation, even
+    /// when sampled. See `MetricGroup` for more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    ///
+    /// client.group()
+    ///     .with_sample_rate(0.1)
+    ///     .with_metric(client.count_with_tags("cache.lookups", 1))
+    ///     .with_metric(client.count_with_tags("cache.misses", 1))
+    ///     .try_send()
+    ///     .unwrap();
+    /// ```
+    pub fn group(&self) -> MetricGroup<'_, '_> {
+        MetricGroup::new(self, self.default_sample_rate)
+    }
+
+    /// Send every metric emitted by the given function together, in a single
+    /// call to the `.emit_many()` method of the underlying `MetricSink`.
+    ///
+    /// The function is called with a copy of this client that collects the
+    /// metrics emitted with it instead of sending them. Once the function
+    /// returns, all the collected metrics are passed to the sink at once. This
+    /// keeps related metrics together: sinks that buffer metrics write them to
+    /// the same datagram where possible, without metrics emitted by other
+    /// threads in between. Metrics are formatted exactly as they would be by
+    /// this client, including its default tags and sample rate. Metrics not
+    /// picked by sampling are not collected. Flushing the client passed to the
+    /// function has no effect.
+    ///
+    /// Errors returned by methods of the client passed to the function (such
+    /// as an invalid key) are returned to the function as usual. The result of
+    /// sending the collected metrics with the sink is returned by this method.
+    /// Like other methods that return errors, the error handler of the client
+    /// isn't invoked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    ///
+    /// client.batch(|b| {
+    ///     b.count("request.count", 1).unwrap();
+    ///     b.time("request.time", Duration::from_millis(35)).unwrap();
+    ///     b.histogram("request.size", 512).unwrap();
+    /// }).unwrap();
+    /// ```
+    pub fn batch<F>(&self, f: F) -> MetricResult<()>
+    where
+        F: FnOnce(&StatsdClient),
+    {
+        let collector = Arc::new(BatchSink::default());
+        let batch = StatsdClient {
+            sink: Arc::new(ArcSwap::from_pointee(collector.clone())),
+            // Metrics are observed when they're actually sent below
+            emit_observer: None,
+            self_metrics: None,
+            ..self.clone()
+        };
+
+        f(&batch);
+
+        let formatted = collector.take();
+        if formatted.is_empty() {
+            return Ok(());
         }
+
+        let metrics: Vec<&str> = formatted.iter().map(String::as_str).collect();
+        self.emit_many_str(&metrics)?;
+        Ok(())
     }
-}
 
-impl MetricClient for StatsdClient {}
+    // Size of everything in a histogram except for the values themselves: the
+    // prefix, key, type, tags, etc. Checks configured on the client (such as
+    // the cardinality guard) aren't run since nothing is being sent yet.
+    fn histogram_overhead(&self, key: &str) -> MetricResult<usize> {
+        let formatter = self.with_defaults(MetricFormatter::histogram(
+            &self.prefix,
+            key,
+            MetricValue::Str(String::new()),
+        ));
+        formatter.validate()?;
+        Ok(formatter.format().len())
+    }
 
-#[allow(clippy::needless_pass_by_value)]
-fn nop_error_handler(_err: MetricError) {
-    // nothing
-}
+    /// Flush the underlying metric sink.
+    ///
+    /// This is helpful for when you'd like to buffer metrics
+    /// but still want strong control over when to emit them.
+    /// For example, you are using a BufferedUdpMetricSink and
+    /// have just emitted some time-sensitive metrics, but you
+    /// aren't sure if the buffer is full or not. Thus, you can
+    /// use `flush` to force the sink to flush your metrics now.
+    ///
+    /// # Buffered UDP Socket Example
+    ///
+    /// ```
+    /// use std::net::UdpSocket;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, BufferedUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let prefix = "my.stats";
+    /// let host = ("127.0.0.1", DEFAULT_PORT);
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    ///
+    /// let sink = BufferedUdpMetricSink::from(host, socket).unwrap();
+    /// let client = StatsdClient::from_sink(prefix, sink);
+    ///
+    /// client.count("time-sensitive.keyA", 1);
+    /// client.count("time-sensitive.keyB", 2);
+    /// client.count("time-sensitive.keyC", 3);
+    /// // Any number of time-sensitive metrics ...
+    /// client.flush();
+    /// ```
+    pub fn flush(&self) -> MetricResult<()> {
+        Ok(self.current_sink().flush()?)
+    }
+
+    /// Flush the underlying metric sink, giving up if it takes longer than
+    /// (roughly) the given timeout.
+    ///
+    /// This is useful during graceful shutdown when flushing must not block
+    /// forever if the Statsd server has stalled. Not all sinks are able to
+    /// honor the timeout, see `MetricSink::flush_with_timeout()` for more
+    /// information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::net::UdpSocket;
+    /// use std::time::Duration;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, BufferedUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let sink = BufferedUdpMetricSink::from(("127.0.0.1", DEFAULT_PORT), socket).unwrap();
+    /// let client = StatsdClient::from_sink("my.stats", sink);
+    ///
+    /// client.count("some.counter", 1);
+    /// client.flush_with_timeout(Duration::from_secs(1));
+    /// ```
+    pub fn flush_with_timeout(&self, timeout: Duration) -> MetricResult<()> {
+        Ok(self.current_sink().flush_with_timeout(timeout)?)
+    }
+
+    /// Return a guard that flushes the underlying metric sink when it's dropped.
+    ///
+    /// Short-lived programs such as CLI tools and batch jobs using a buffered
+    /// sink often exit before the buffer is flushed, losing the last metrics
+    /// they emitted. Keeping the guard alive until the end of `main` ensures
+    /// that the sink is flushed when it returns, including when it returns
+    /// early or unwinds because of a panic. Since `Drop` can't return an error,
+    /// any error flushing the sink is passed to the error handler of the client.
+    ///
+    /// Note that the guard can only flush the sink when it's dropped normally.
+    /// Nothing is flushed when the process is aborted (e.g. `std::process::abort()`,
+    /// `panic = "abort"`, or a signal such as `SIGKILL`) or when it exits via
+    /// `std::process::exit()`, since destructors aren't run in those cases.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::net::UdpSocket;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, BufferedUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let sink = BufferedUdpMetricSink::from(("127.0.0.1", DEFAULT_PORT), socket).unwrap();
+    /// let client = StatsdClient::from_sink("my.stats", sink);
+    /// let _guard = client.flush_guard();
+    ///
+    /// client.count("some.counter", 1);
+    /// // The sink is flushed when `_guard` goes out of scope
+    /// ```
+    pub fn flush_guard(&self) -> FlushGuard<'_> {
+        FlushGuard { client: self }
+    }
+
+    /// Return `true` if the underlying metric sink is able to accept more
+    /// metrics, `false` if it's under backpressure.
+    ///
+    /// This can be used to skip computing metrics that are expensive to compute
+    /// when they're likely to be dropped anyway. It's advisory only: metrics may
+    /// still be dropped when the sink is ready and sending metrics when it isn't
+    /// ready isn't an error. See `MetricSink::is_ready()` for more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    ///
+    /// if client.sink_ready() {
+    ///     client.gauge("expensive.gauge", 42).unwrap();
+    /// }
+    /// ```
+    pub fn sink_ready(&self) -> bool {
+        self.current_sink().is_ready()
+    }
+
+    /// Replace the sink used to send metrics by this client and every copy
+    /// of it, returning the result of flushing the previous sink.
+    ///
+    /// This allows a running application to start sending metrics somewhere
+    /// else, such as after reloading its configuration, without having to
+    /// build a new client and hand it to everything holding the old one.
+    /// Metrics being sent while the sink is replaced are sent with either the
+    /// previous sink or the new one, never dropped. The previous sink is
+    /// flushed once it has been replaced. Any metrics sent with it after that
+    /// (by calls that started before it was replaced) are sent when the last
+    /// reference to it is dropped, for sinks that flush when dropped.
+    ///
+    /// Note that the statistics reported by `with_self_metrics()` describe the
+    /// new sink after it's replaced. The `queue` metrics continue to describe
+    /// the queue the client was originally built with, if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, SpyMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+    /// let client_ref = client.clone();
+    ///
+    /// let (rx, sink) = SpyMetricSink::new();
+    /// client.replace_sink(sink).unwrap();
+    /// client_ref.count("some.counter", 1).unwrap();
+    ///
+    /// assert_eq!(b"my.prefix.some.counter:1|c", rx.recv().unwrap().as_slice());
+    /// ```
+    pub fn replace_sink<T>(&self, sink: T) -> MetricResult<()>
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        let sink: ClientSink = Arc::new(sink);
+        let previous = self.sink.swap(Arc::new(sink));
+        Ok(previous.flush()?)
+    }
+
+    // Return the sink currently in use by the client. Loading the sink doesn't
+    // take a lock or (usually) update a shared reference count so it's cheap
+    // even when many threads emit metrics with the same client at once, and
+    // replacing the sink never has to wait for metrics being sent.
+    fn current_sink(&self) -> Guard<Arc<ClientSink>> {
+        self.sink.load()
+    }
+
+    // Format and emit a metric from a builder, returning the formatted string
+    // so that the builder can wrap it in the appropriate metric type.
+    pub(crate) fn send_formatted(&self, formatter: &MetricFormatter<'_>) -> MetricResult<String> {
+        let metric = self.format(formatter)?;
+        // Metrics that aren't picked by sampling are still returned to the
+        // caller, they just aren't sent anywhere.
+        if formatter.sample_rate().map_or(true, sampling::should_send) {
+            self.emit_str(&metric)?;
+        }
+
+        Ok(metric)
+    }
+
+    // Format a metric after running any checks that apply to every metric
+    // emitted by this client.
+    pub(crate) fn format(&self, formatter: &MetricFormatter<'_>) -> MetricResult<String> {
+        formatter.validate()?;
+
+        if let Some(ref guard) = self.cardinality_guard {
+            guard.observe(formatter);
+        }
+
+        Ok(formatter.format())
+    }
+
+    // Emit an already formatted metric to the sink, returning the number of
+    // bytes written. Used to avoid going through the `Metric` trait when the
+    // formatted string is already available.
+    pub(crate) fn emit_str(&self, metric: &str) -> MetricResult<usize> {
+        let written = self.current_sink().emit(metric)?;
+        if let Some(ref observer) = self.emit_observer {
+            observer(metric);
+        }
+
+        Ok(written)
+    }
+
+    // Send multiple metrics in a single call to the sink, passing each of
+    // them to the emit observer (if any) once they've been sent.
+    pub(crate) fn emit_many_str(&self, metrics: &[&str]) -> MetricResult<usize> {
+        let written = self.current_sink().emit_many(metrics)?;
+        if let Some(ref observer) = self.emit_observer {
+            metrics.iter().for_each(|m| observer(m));
+        }
+
+        Ok(written)
+    }
+
+    // Return the current statistics of the underlying metric sink.
+    pub(crate) fn sink_stats(&self) -> SinkStats {
+        self.current_sink().stats()
+    }
+
+    // Create a new StatsdClient by consuming the builder
+    fn from_builder(builder: StatsdClientBuilder) -> Self {
+        let (sink, queue) = builder.sink.build(builder.errors.clone());
+        let mut client = StatsdClient {
+            prefix: builder.prefix,
+            sink: Arc::new(ArcSwap::from_pointee(Arc::from(sink))),
+            errors: builder.errors,
+            emit_observer: builder.emit_observer,
+            clock: builder.clock,
+            tags: Arc::new(builder.tags),
+            type_tags: Arc::new(builder.type_tags),
+            container_id: builder.container_id,
+            cardinality: builder.cardinality,
+            cardinality_guard: builder.cardinality_guard.map(Arc::new),
+            sorted_tags: builder.sorted_tags,
+            always_tag_separator: builder.always_tag_separator,
+            trim: builder.trim,
+            name_transform: builder.name_transform.map(Arc::new),
+            type_codes: builder.type_codes.map(Arc::new),
+            float_precision: builder.float_precision,
+            float_decimal_point: builder.float_decimal_point,
+            default_sample_rate: builder.default_sample_rate,
+            strict_meters: builder.strict_meters,
+            self_metrics: None,
+            raw: false,
+        };
+
+        // The reporter uses its own copy of the client without a reporter so
+        // that it doesn't keep itself running after every other copy is dropped.
+        // Self-metrics are never sampled since they're only sent periodically.
+        if let Some((prefix, interval)) = builder.self_metrics {
+            let reporter = StatsdClient {
+                prefix,
+                default_sample_rate: None,
+                ..client.clone()
+            };
+            client.self_metrics = Some(Arc::new(SelfMetricsReporter::spawn(reporter, interval, queue)));
+        }
+
+        client
+    }
+
+    // Add the default tags, container ID, and cardinality of this client to a
+    // new metric before any that are specific to it, along with any options
+    // that change how it's formatted.
+    fn with_defaults<'a>(&'a self, mut formatter: MetricFormatter<'a>) -> MetricFormatter<'a> {
+        // Raw clients don't have any defaults to add
+        if self.raw {
+            return formatter;
+        }
+
+        for (key, value) in self.tags.iter() {
+            match key {
+                Some(key) => formatter.with_tag(key, value),
+                None => formatter.with_tag_value(value),
+            }
+        }
+
+        let kind = formatter.kind();
+        for (_, key, value) in self.type_tags.iter().filter(|(k, _, _)| *k == kind) {
+            formatter.with_tag(key, value);
+        }
+
+        if let Some(ref container_id) = self.container_id {
+            formatter.with_container_id(container_id);
+        }
+
+        if let Some(cardinality) = self.cardinality {
+            formatter.with_cardinality(cardinality);
+        }
+
+        if self.sorted_tags {
+            formatter.with_sorted_tags();
+        }
+
+        if self.always_tag_separator {
+            formatter.with_always_tag_separator();
+        }
+
+        if self.trim {
+            formatter.with_trim();
+        }
+
+        if let Some(ref transform) = self.name_transform {
+            formatter.with_name_transform(transform);
+        }
+
+        if let Some(ref codes) = self.type_codes {
+            formatter.with_type_codes(codes);
+        }
+
+        if let Some(precision) = self.float_precision {
+            formatter.with_float_precision(precision);
+        }
+
+        if self.float_decimal_point {
+            formatter.with_float_decimal_point();
+        }
+
+        if let Some(rate) = self.default_sample_rate {
+            formatter.with_sample_rate(rate);
+        }
+
+        formatter
+    }
+}
+
+#[cfg(feature = "async")]
+impl StatsdClient {
+    /// Await the given future and record the time it took to complete in
+    /// milliseconds with the given key, returning its output.
+    ///
+    /// The time is measured from when the returned future is first polled
+    /// until the given future completes, including any time spent waiting to
+    /// be polled again. The timer is sent like it would be by calling `.send()`
+    /// on a `MetricBuilder`: any error sending it is passed to the error handler
+    /// of the client. This method is only available when the optional `async`
+    /// feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::StatsdClient;
+    ///
+    /// async fn fetch_user(id: u64) -> String {
+    ///     format!("user-{}", id)
+    /// }
+    ///
+    /// async fn handle_request(client: &StatsdClient) -> String {
+    ///     client.time_future("fetch.user", fetch_user(42)).await
+    /// }
+    /// ```
+    pub async fn time_future<F>(&self, key: &str, future: F) -> F::Output
+    where
+        F: std::future::Future,
+    {
+        let (output, builder) = self.time_future_with_tags(key, future).await;
+        builder.send();
+        output
+    }
+
+    /// Await the given future and return its output along with a `MetricBuilder`
+    /// for a timer of the time it took to complete in milliseconds with the
+    /// given key.
+    ///
+    /// The timer isn't sent until `.send()` or `.try_send()` is called on the
+    /// builder, after adding any tags to it. This method is only available when
+    /// the optional `async` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::StatsdClient;
+    ///
+    /// async fn fetch_user(id: u64) -> Option<String> {
+    ///     Some(format!("user-{}", id))
+    /// }
+    ///
+    /// async fn handle_request(client: &StatsdClient) -> Option<String> {
+    ///     let (user, builder) = client.time_future_with_tags("fetch.user", fetch_user(42)).await;
+    ///     builder.with_tag("found", if user.is_some() { "true" } else { "false" }).send();
+    ///     user
+    /// }
+    /// ```
+    pub async fn time_future_with_tags<'a, F>(
+        &'a self,
+        key: &'a str,
+        future: F,
+    ) -> (F::Output, MetricBuilder<'a, 'a, Timer>)
+    where
+        F: std::future::Future,
+    {
+        let start = self.clock.now();
+        let output = future.await;
+        (
+            output,
+            self.time_with_tags(key, self.clock.now().saturating_duration_since(start)),
+        )
+    }
+}
+
+impl Sealed for StatsdClient {}
+
+impl MetricBackend for StatsdClient {
+    fn send_metric<M>(&self, metric: &M) -> MetricResult<()>
+    where
+        M: Metric,
+    {
+        self.emit_str(metric.as_metric_str())?;
+        Ok(())
+    }
+
+    fn send_metric_bytes<M>(&self, metric: &M) -> MetricResult<usize>
+    where
+        M: Metric,
+    {
+        self.emit_str(metric.as_metric_str())
+    }
+
+    fn consume_error(&self, err: MetricError) {
+        (self.errors)(err);
+    }
+
+    fn sink(&self) -> ClientSink {
+        ClientSink::clone(&self.current_sink())
+    }
+}
+
+impl fmt::Debug for StatsdClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "StatsdClient {{ prefix: {:?}, sink: ..., errors: ..., tags: {:?} }}",
+            self.prefix, self.tags,
+        )
+    }
+}
+
+impl<T> Counted<T> for StatsdClient
+where
+    T: ToCounterValue,
+{
+    fn count_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Counter> {
+        match value.try_to_value() {
+            Ok(v) => MetricBuilder::from_fmt(self.with_defaults(MetricFormatter::counter(&self.prefix, key, v)), self),
+            Err(e) => MetricBuilder::from_error(e, self),
+        }
+    }
+}
+
+impl CountedExt for StatsdClient {}
+
+impl TimedExt for StatsdClient {
+    fn time_since_with_tags<'a>(&'a self, key: &'a str, start: Instant) -> MetricBuilder<'a, 'a, Timer> {
+        self.time_with_tags(key, self.clock.now().saturating_duration_since(start))
+    }
+}
+
+impl HistogrammedExt for StatsdClient {}
+
+impl<T> Timed<T> for StatsdClient
+where
+    T: ToTimerValue,
+{
+    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'a, 'a, Timer> {
+        match time.try_to_value() {
+            Ok(v) => MetricBuilder::from_fmt(self.with_defaults(MetricFormatter::timer(&self.prefix, key, v)), self),
+            Err(e) => MetricBuilder::from_error(e, self),
+        }
+    }
+}
+
+impl<T> Gauged<T> for StatsdClient
+where
+    T: ToGaugeValue,
+{
+    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Gauge> {
+        match value.try_to_value() {
+            Ok(v) => MetricBuilder::from_fmt(self.with_defaults(MetricFormatter::gauge(&self.prefix, key, v)), self),
+            Err(e) => MetricBuilder::from_error(e, self),
+        }
+    }
+}
+
+impl<T> Metered<T> for StatsdClient
+where
+    T: ToMeterValue,
+{
+    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Meter> {
+        match value.try_to_value() {
+            Ok(v) if self.strict_meters && !v.is_positive() => MetricBuilder::from_error(
+                MetricError::from((ErrorKind::InvalidInput, "meter values must be positive")),
+                self,
+            ),
+            Ok(v) => MetricBuilder::from_fmt(self.with_defaults(MetricFormatter::meter(&self.prefix, key, v)), self),
+            Err(e) => MetricBuilder::from_error(e, self),
+        }
+    }
+}
+
+impl<T> Histogrammed<T> for StatsdClient
+where
+    T: ToHistogramValue,
+{
+    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Histogram> {
+        match value.try_to_value() {
+            Ok(v) => MetricBuilder::from_fmt(
+                self.with_defaults(MetricFormatter::histogram(&self.prefix, key, v)),
+                self,
+            ),
+            Err(e) => MetricBuilder::from_error(e, self),
+        }
+    }
+}
+
+impl<T> Distributed<T> for StatsdClient
+where
+    T: ToDistributionValue,
+{
+    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Distribution> {
+        match value.try_to_value() {
+            Ok(v) => MetricBuilder::from_fmt(
+                self.with_defaults(MetricFormatter::distribution(&self.prefix, key, v)),
+                self,
+            ),
+            Err(e) => MetricBuilder::from_error(e, self),
+        }
+    }
+}
+
+impl<T> Setted<T> for StatsdClient
+where
+    T: ToSetValue,
+{
+    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Set> {
+        match value.try_to_value() {
+            Ok(v) => MetricBuilder::from_fmt(self.with_defaults(MetricFormatter::set(&self.prefix, key, v)), self),
+            Err(e) => MetricBuilder::from_error(e, self),
+        }
+    }
+}
+
+impl MetricClient for StatsdClient {}
+
+/// Guard that flushes the sink of a `StatsdClient` when dropped.
+///
+/// Created by `StatsdClient::flush_guard()`, see that method for more
+/// information.
+#[must_use = "The sink is flushed as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct FlushGuard<'a> {
+    client: &'a StatsdClient,
+}
+
+impl<'a> Drop for FlushGuard<'a> {
+    fn drop(&mut self) {
+        if let Err(e) = self.client.flush() {
+            self.client.consume_error(e);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn nop_error_handler(_err: MetricError) {
+    // nothing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Counted, CountedExt, Distributed, Gauged, Histogrammed, HistogrammedExt, Metered, MetricBackend, MetricClient,
+        Setted, StatsdClient, Timed, TimedExt, DD_UNIFIED_TAGS,
+    };
+    use crate::clock::{Clock, MockClock};
+    #[cfg(unix)]
+    use crate::sinks::BufferedUnixMetricSinkBuilder;
+    use crate::sinks::{
+        BufferedSpyMetricSink, BufferedUdpMetricSinkBuilder, MetricSink, NopMetricSink, QueuingMetricSink,
+        QueuingMetricSinkBuilder, SharedSink, SpyMetricSink, DEFAULT_BUFFER_SIZE,
+    };
+    #[cfg(unix)]
+    use crate::test::TempDir;
+    use crate::types::{Cardinality, Counter, ErrorKind, Metric, MetricError, MetricKind, ScalingMode, TagSet};
+    use crate::{StatsdClientBuilder, TypeCodeMap};
+    use std::borrow::Cow;
+    use std::io;
+    use std::net::UdpSocket;
+    #[cfg(unix)]
+    use std::os::unix::net::UnixDatagram;
+    use std::panic::RefUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_statsd_client_empty_prefix() {
+        let client = StatsdClient::from_sink("", NopMetricSink);
+        let res = client.count("some.method", 1);
+
+        assert_eq!("some.method:1|c", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_with_container_id() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_container_id("1234")
+            .build();
+        let res = client.count("some.method", 1);
+
+        assert_eq!("prefix.some.method:1|c|c:1234", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_per_metric_container_id() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let first = client
+            .count_with_tags("some.method", 1)
+            .with_tag("source", "proxy")
+            .with_container_id("aaa")
+            .try_send();
+        let second = client
+            .count_with_tags("some.method", 1)
+            .with_tag("source", "proxy")
+            .with_container_id("bbb")
+            .try_send();
+        let neither = client.count("some.method", 1);
+
+        assert_eq!(
+            "prefix.some.method:1|c|#source:proxy|c:aaa",
+            first.unwrap().as_metric_str()
+        );
+        assert_eq!(
+            "prefix.some.method:1|c|#source:proxy|c:bbb",
+            second.unwrap().as_metric_str()
+        );
+        assert_eq!("prefix.some.method:1|c", neither.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_container_id_override() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_container_id("1234")
+            .build();
+        let res = client
+            .count_with_tags("some.method", 1)
+            .with_container_id("5678")
+            .try_send();
+
+        assert_eq!("prefix.some.method:1|c|c:5678", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_with_sorted_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("region", "us-west-2")
+            .with_tag_value("production")
+            .with_sorted_tags()
+            .build();
+        let res = client
+            .count_with_tags("some.counter", 1)
+            .with_tag("host", "web01")
+            .with_tag_value("canary")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.counter:1|c|#host:web01,region:us-west-2,canary,production",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_with_always_tag_separator() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_always_tag_separator(true)
+            .build();
+
+        let res = client.count("some.counter", 1);
+        assert_eq!("prefix.some.counter:1|c|#", res.unwrap().as_metric_str());
+
+        let res = client
+            .count_with_tags("some.counter", 1)
+            .with_tag("host", "web01")
+            .try_send();
+        assert_eq!("prefix.some.counter:1|c|#host:web01", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_with_trim() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag(" host ", " web01 ")
+            .with_trim(true)
+            .build();
+
+        let res = client
+            .count_with_tags(" some.counter ", 1)
+            .with_tag("region ", " us-east-1")
+            .with_tag_value(" beta")
+            .try_send();
+        assert_eq!(
+            "prefix.some.counter:1|c|#host:web01,region:us-east-1,beta",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_with_float_decimal_point() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_float_decimal_point(true)
+            .build();
+
+        assert_eq!(
+            "prefix.some.gauge:1.0|g",
+            client.gauge("some.gauge", 1.0).unwrap().as_metric_str()
+        );
+        assert_eq!(
+            "prefix.some.gauge:0.0|g",
+            client.gauge("some.gauge", 0.0).unwrap().as_metric_str()
+        );
+        assert_eq!(
+            "prefix.some.gauge:1|g",
+            client.gauge("some.gauge", 1u64).unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_without_trim() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag(" host ", " web01 ")
+            .build();
+
+        let res = client.count(" some.counter ", 1);
+        assert_eq!(
+            "prefix. some.counter :1|c|# host : web01 ",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_without_always_tag_separator() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_always_tag_separator(false)
+            .build();
+        let res = client.count("some.counter", 1);
+
+        assert_eq!("prefix.some.counter:1|c", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_with_name_transform() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_ref = calls.clone();
+
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("region", "us-west.2")
+            .with_name_transform(move |name: &str| {
+                calls_ref.fetch_add(1, Ordering::Relaxed);
+                Cow::Owned(name.replace('.', "_"))
+            })
+            .build();
+        let res = client.time("some.method", 21);
+
+        assert_eq!(
+            "prefix_some_method:21|ms|#region:us-west.2",
+            res.unwrap().as_metric_str()
+        );
+        assert_eq!(1, calls.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_statsd_client_with_tagset() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("region", "us-west-2")
+            .build();
+        let tags = TagSet::new().with("endpoint", "/users").with_value("canary");
+
+        let res = client.count_with_tagset("some.counter", 1, &tags).try_send();
+        assert_eq!(
+            "prefix.some.counter:1|c|#region:us-west-2,endpoint:/users,canary",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.time_with_tagset("some.timer", 34, &tags).try_send();
+        assert_eq!(
+            "prefix.some.timer:34|ms|#region:us-west-2,endpoint:/users,canary",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.gauge_with_tagset("some.gauge", 2.5, &tags).try_send();
+        assert_eq!(
+            "prefix.some.gauge:2.5|g|#region:us-west-2,endpoint:/users,canary",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.meter_with_tagset("some.meter", 5, &tags).try_send();
+        assert_eq!(
+            "prefix.some.meter:5|m|#region:us-west-2,endpoint:/users,canary",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.histogram_with_tagset("some.histo", 44, &tags).try_send();
+        assert_eq!(
+            "prefix.some.histo:44|h|#region:us-west-2,endpoint:/users,canary",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.distribution_with_tagset("some.distr", 27, &tags).try_send();
+        assert_eq!(
+            "prefix.some.distr:27|d|#region:us-west-2,endpoint:/users,canary",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.set_with_tagset("some.set", 3, &tags).try_send();
+        assert_eq!(
+            "prefix.some.set:3|s|#region:us-west-2,endpoint:/users,canary",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_with_tagset_and_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let tags = TagSet::new().with("endpoint", "/users");
+
+        let res = client
+            .count_with_tagset("some.counter", 1, &tags)
+            .with_tag("status", "200")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.counter:1|c|#endpoint:/users,status:200",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_with_type_codes() {
+        let codes = TypeCodeMap::new().with_timer("t").unwrap();
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_type_codes(codes)
+            .build();
+
+        let res = client.time("some.timer", 34);
+        assert_eq!("prefix.some.timer:34|t", res.unwrap().as_metric_str());
+
+        let res = client.time("some.timer", vec![34, 35]);
+        assert_eq!("prefix.some.timer:34:35|t", res.unwrap().as_metric_str());
+
+        let res = client.count("some.counter", 1);
+        assert_eq!("prefix.some.counter:1|c", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_merging_default_tags_with_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("hello", "world")
+            .with_tag_value("production")
+            .build();
+        let res = client
+            .count_with_tags("some.counter", 3)
+            .with_tag("foo", "bar")
+            .with_tag_value("fizz")
+            .with_tag("bucket", "123")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.counter:3|c|#hello:world,production,foo:bar,fizz,bucket:123",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_type_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("env", "prod")
+            .with_type_tag(MetricKind::Timer, "unit", "ms")
+            .with_type_tag(MetricKind::Histogram, "unit", "ns")
+            .build();
+
+        let res = client
+            .time_with_tags("some.timer", 12)
+            .with_tag("foo", "bar")
+            .try_send();
+        assert_eq!(
+            "prefix.some.timer:12|ms|#env:prod,unit:ms,foo:bar",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.histogram("some.histogram", 34);
+        assert_eq!(
+            "prefix.some.histogram:34|h|#env:prod,unit:ns",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.count("some.counter", 1);
+        assert_eq!("prefix.some.counter:1|c|#env:prod", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_type_tags_without_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_type_tag(MetricKind::Timer, "unit", "ms")
+            .build();
+
+        assert_eq!(
+            "prefix.some.timer:12|ms|#unit:ms",
+            client.time("some.timer", 12).unwrap().as_metric_str()
+        );
+        assert_eq!(
+            "prefix.some.gauge:5|g",
+            client.gauge("some.gauge", 5).unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_count_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .count_with_tags("some.counter", 3)
+            .with_tag("foo", "bar")
+            .try_send();
+
+        assert_eq!("prefix.some.counter:3|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_count_sampled_always() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let res = client.count_sampled("some.counter", 3, 1.0);
+
+        assert_eq!("prefix.some.counter:3|c", res.unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.some.counter:3|c",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_count_sampled_rejected() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let res = client.count_sampled("some.counter", 3, 0.0);
+
+        assert_eq!("prefix.some.counter:3|c|@0", res.unwrap().as_metric_str());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_statsd_client_count_sampled_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+
+        for _ in 0..100 {
+            let res = client.count_sampled("some.counter", 3, 0.5);
+            assert_eq!("prefix.some.counter:3|c|@0.5", res.unwrap().as_metric_str());
+        }
+
+        // It's vanishingly unlikely that none of these were sent at a rate of 0.5
+        assert_eq!(
+            "prefix.some.counter:3|c|@0.5",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_count_sample_rate_scaling() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let server_side = client
+            .count_with_tags("some.counter", 3)
+            .with_sample_rate(0.5)
+            .with_sample_rate_scaling(ScalingMode::ServerSide)
+            .try_send();
+        let client_side = client
+            .count_with_tags("some.counter", 3)
+            .with_sample_rate(0.5)
+            .with_sample_rate_scaling(ScalingMode::ClientSide)
+            .try_send();
+
+        assert_eq!("prefix.some.counter:3|c|@0.5", server_side.unwrap().as_metric_str());
+        assert_eq!("prefix.some.counter:6|c", client_side.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_count_sampled_invalid_rate() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.count_sampled("some.counter", 3, -0.5);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_count_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("hello", "world")
+            .build();
+        let res = client.count_with_tags("some.counter", 3).try_send();
+
+        assert_eq!("prefix.some.counter:3|c|#hello:world", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_incr_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.incr_with_tags("some.counter").with_tag("foo", "bar").try_send();
+
+        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_incr_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.incr_with_tags("some.counter").try_send();
+
+        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_decr_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.decr_with_tags("some.counter").with_tag("foo", "bar").try_send();
+
+        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_decr_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.decr_with_tags("some.counter").try_send();
+
+        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_gauge_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .gauge_with_tags("some.gauge", 4)
+            .with_tag("bucket", "A")
+            .with_tag_value("file-server")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.gauge:4|g|#bucket:A,file-server",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_gauge_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.gauge_with_tags("some.gauge", 4).try_send();
+
+        assert_eq!("prefix.some.gauge:4|g|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_gauge_with_timestamp() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .gauge_with_tags("some.gauge", 4)
+            .with_timestamp(1234567890)
+            .try_send();
+
+        assert_eq!("prefix.some.gauge:4|g|T1234567890", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_gauge_negative() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        assert_eq!("prefix.temp:-5|g", client.gauge("temp", -5i64).unwrap().as_metric_str());
+        assert_eq!("prefix.temp:-5|g", client.gauge("temp", -5i32).unwrap().as_metric_str());
+        assert_eq!("prefix.temp:5|g", client.gauge("temp", 5i64).unwrap().as_metric_str());
+        assert_eq!("prefix.temp:-5|g", client.gauge("temp", -5).unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_gauge_bool() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        assert_eq!(
+            "prefix.some.flag:1|g",
+            client.gauge("some.flag", true).unwrap().as_metric_str()
+        );
+        assert_eq!(
+            "prefix.some.flag:0|g",
+            client.gauge("some.flag", false).unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_time_duration() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.time("key", Duration::from_millis(157));
+
+        assert_eq!("prefix.key:157|ms", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_since() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let start = Instant::now() - Duration::from_millis(150);
+        let res = client.time_since("key", start).unwrap();
+        let elapsed: u64 = res
+            .as_metric_str()
+            .strip_prefix("prefix.key:")
+            .and_then(|s| s.strip_suffix("|ms"))
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        assert!(elapsed >= 150, "unexpected elapsed time {}", elapsed);
+    }
+
+    #[test]
+    fn test_statsd_client_time_since_with_clock() {
+        let clock = MockClock::new();
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_clock(clock.clone())
+            .build();
+
+        let start = clock.now();
+        clock.advance(Duration::from_millis(150));
+        let res = client.time_since("key", start);
+        assert_eq!("prefix.key:150|ms", res.unwrap().as_metric_str());
+
+        // Instants after the current time of the clock are treated as no time passing
+        let res = client.time_since("key", start + Duration::from_secs(1));
+        assert_eq!("prefix.key:0|ms", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_since_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .time_since_with_tags("key", Instant::now())
+            .with_tag("foo", "bar")
+            .try_send();
+
+        let metric = res.unwrap();
+        assert!(metric.as_metric_str().starts_with("prefix.key:"));
+        assert!(metric.as_metric_str().ends_with("|ms|#foo:bar"));
+    }
+
+    #[test]
+    fn test_statsd_client_time_multiple_durations() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = vec![
+            Duration::from_millis(157),
+            Duration::from_millis(158),
+            Duration::from_millis(159),
+        ];
+        let res = client.time("key", durations);
+
+        assert_eq!("prefix.key:157:158:159|ms", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_durations() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = [
+            Duration::from_millis(157),
+            Duration::from_millis(158),
+            Duration::from_millis(159),
+        ];
+
+        let res = client.time_durations("key", &durations);
+        assert_eq!("prefix.key:157:158:159|ms", res.unwrap().as_metric_str());
+
+        let res = client
+            .time_durations_with_tags("key", &durations[..1])
+            .with_tag("foo", "bar")
+            .try_send();
+        assert_eq!("prefix.key:157|ms|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_durations_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = [Duration::from_millis(157), Duration::from_secs(u64::MAX)];
+
+        let res = client.time_durations("key", &durations);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_time_duration_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.time("key", Duration::from_secs(u64::MAX));
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind())
+    }
+
+    #[test]
+    fn test_statsd_client_time_multiple_durations_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = vec![
+            Duration::from_millis(157),
+            Duration::from_secs(u64::MAX),
+            Duration::from_millis(159),
+        ];
+        let res = client.time("key", durations);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind())
+    }
+
+    #[test]
+    fn test_statsd_client_time_duration_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .time_with_tags("key", Duration::from_millis(157))
+            .with_tag("foo", "bar")
+            .with_tag_value("quux")
+            .try_send();
+
+        assert_eq!("prefix.key:157|ms|#foo:bar,quux", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_duration_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.time("key", Duration::from_millis(157));
+
+        assert_eq!("prefix.key:157|ms|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_multiple_durations_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = vec![
+            Duration::from_millis(157),
+            Duration::from_millis(158),
+            Duration::from_millis(159),
+        ];
+        let res = client
+            .time_with_tags("key", durations)
+            .with_tag("foo", "bar")
+            .with_tag_value("quux")
+            .try_send();
+
+        assert_eq!("prefix.key:157:158:159|ms|#foo:bar,quux", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_duration_with_tags_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .time_with_tags("key", Duration::from_secs(u64::MAX))
+            .with_tag("foo", "bar")
+            .with_tag_value("quux")
+            .try_send();
+
+        assert!(res.is_err());
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_time_multiple_durations_with_tags_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = vec![
+            Duration::from_millis(157),
+            Duration::from_secs(u64::MAX),
+            Duration::from_millis(159),
+        ];
+        let res = client
+            .time_with_tags("key", durations)
+            .with_tag("foo", "bar")
+            .with_tag_value("quux")
+            .try_send();
+
+        assert!(res.is_err());
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_meter_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .meter_with_tags("some.meter", 64)
+            .with_tag("segment", "142")
+            .with_tag_value("beta")
+            .try_send();
+
+        assert_eq!("prefix.some.meter:64|m|#segment:142,beta", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_meter_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.meter_with_tags("some.meter", 64).try_send();
+
+        assert_eq!("prefix.some.meter:64|m|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .histogram_with_tags("some.histo", 27)
+            .with_tag("host", "www03.example.com")
+            .with_tag_value("rc1")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.histo:27|h|#host:www03.example.com,rc1",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.histogram_with_tags("some.histo", 27).try_send();
+
+        assert_eq!("prefix.some.histo:27|h|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_with_multiple_values() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.histogram_with_tags("some.histo", vec![27, 28, 29]).try_send();
+
+        assert_eq!("prefix.some.histo:27:28:29|h", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_duration() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.histogram("key", Duration::from_nanos(210));
+
+        assert_eq!("prefix.key:210|h", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_multiple_durations() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = vec![
+            Duration::from_nanos(210),
+            Duration::from_nanos(211),
+            Duration::from_nanos(212),
+        ];
+        let res = client.histogram("key", durations);
+
+        assert_eq!("prefix.key:210:211:212|h", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_durations() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = [
+            Duration::from_nanos(210),
+            Duration::from_nanos(211),
+            Duration::from_nanos(212),
+        ];
+
+        let res = client.histogram_durations("key", &durations);
+        assert_eq!("prefix.key:210:211:212|h", res.unwrap().as_metric_str());
+
+        let res = client
+            .histogram_durations_with_tags("key", &durations[..1])
+            .with_tag("foo", "bar")
+            .try_send();
+        assert_eq!("prefix.key:210|h|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_durations_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = [Duration::from_nanos(210), Duration::from_secs(u64::MAX)];
+
+        let res = client.histogram_durations("key", &durations);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_duration_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.histogram("key", Duration::from_secs(u64::MAX));
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_multiple_durations_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let durations = vec![
+            Duration::from_nanos(210),
+            Duration::from_secs(u64::MAX),
+            Duration::from_nanos(212),
+        ];
+
+        let res = client.histogram("key", durations);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_non_finite_floats() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        for val in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(ErrorKind::InvalidInput, client.gauge("key", val).unwrap_err().kind());
+            assert_eq!(
+                ErrorKind::InvalidInput,
+                client.histogram("key", val).unwrap_err().kind()
+            );
+            assert_eq!(
+                ErrorKind::InvalidInput,
+                client.distribution("key", val).unwrap_err().kind()
+            );
+
+            let packed = vec![1.5, val];
+            assert_eq!(
+                ErrorKind::InvalidInput,
+                client.histogram("key", packed.clone()).unwrap_err().kind()
+            );
+            assert_eq!(
+                ErrorKind::InvalidInput,
+                client.distribution("key", packed.as_slice()).unwrap_err().kind()
+            );
+        }
+    }
+
+    #[test]
+    fn test_statsd_client_gauge_pct() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_float_precision(3)
+            .build();
+
+        let res = client.gauge_pct("some.gauge", 0.3333, 1);
+        assert_eq!("prefix.some.gauge:33.3|g", res.unwrap().as_metric_str());
+
+        let res = client.gauge_pct("some.gauge", 1.0, 0);
+        assert_eq!("prefix.some.gauge:100|g", res.unwrap().as_metric_str());
+
+        let res = client
+            .gauge_pct_with_tags("some.gauge", 0.5, 2)
+            .with_tag("host", "web01")
+            .try_send();
+        assert_eq!("prefix.some.gauge:50|g|#host:web01", res.unwrap().as_metric_str());
+
+        let res = client.gauge_pct("some.gauge", f64::NAN, 1);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_iter() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let values = vec![1.5, 2.5, 3.5];
+
+        let res = client.time_iter("some.timer", (1..=3).map(Duration::from_millis));
+        assert_eq!("prefix.some.timer:1:2:3|ms", res.unwrap().as_metric_str());
+
+        let res = client.histogram_iter("some.histogram", values.iter().map(|v| v * 2.0));
+        assert_eq!("prefix.some.histogram:3:5:7|h", res.unwrap().as_metric_str());
+
+        let res = client
+            .distribution_iter_with_tags("some.distribution", values)
+            .with_tag("host", "web01")
+            .try_send();
+        assert_eq!(
+            "prefix.some.distribution:1.5:2.5:3.5|d|#host:web01",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_iter_invalid() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let res = client.time_iter("some.timer", std::iter::empty());
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.time_iter("some.timer", vec![Duration::from_secs(u64::MAX)]);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.histogram_iter("some.histogram", std::iter::empty());
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.distribution_iter("some.distribution", vec![1.0, f64::NAN]);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_finite_floats() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        assert_eq!("prefix.key:1.5|g", client.gauge("key", 1.5).unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.key:1.5|h",
+            client.histogram("key", 1.5).unwrap().as_metric_str()
+        );
+        assert_eq!(
+            "prefix.key:1.5:2.5|d",
+            client.distribution("key", vec![1.5, 2.5]).unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_duration_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .histogram_with_tags("key", Duration::from_nanos(4096))
+            .with_tag("foo", "bar")
+            .with_tag_value("beta")
+            .try_send();
+
+        assert_eq!("prefix.key:4096|h|#foo:bar,beta", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_duration_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.histogram_with_tags("key", Duration::from_nanos(4096)).try_send();
+
+        assert_eq!("prefix.key:4096|h|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_duration_with_tags_with_overflow() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .histogram_with_tags("key", Duration::from_millis(u64::MAX))
+            .with_tag("foo", "bar")
+            .with_tag_value("beta")
+            .try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .distribution_with_tags("some.distr", 27)
+            .with_tag("host", "www03.example.com")
+            .with_tag_value("rc1")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.distr:27|d|#host:www03.example.com,rc1",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client
+            .distribution_with_tags("some.distr", 27)
+            .with_tag("host", "www03.example.com")
+            .with_tag_value("rc1")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.distr:27|d|#foo:bar,host:www03.example.com,rc1",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_multiple_values_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .distribution_with_tags("some.distr", vec![27, 28, 29])
+            .with_tag("host", "www03.example.com")
+            .with_tag_value("rc1")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.distr:27:28:29|d|#host:www03.example.com,rc1",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_time_slice() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let values: Vec<u64> = vec![35, 34, 36];
+        let res = client.time("key", &values[1..]);
+
+        assert_eq!("prefix.key:34:36|ms", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_slice() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let values: [u64; 3] = [4, 5, 6];
+        let res = client
+            .histogram_with_tags("some.histo", &values[..])
+            .with_tag("foo", "bar")
+            .try_send();
+
+        assert_eq!("prefix.some.histo:4:5:6|h|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_float_slice() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let values = vec![4.5, 5.25];
+        let res = client.histogram("some.histo", values.as_slice());
+
+        assert_eq!("prefix.some.histo:4.5:5.25|h", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_slice() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let values: Vec<u64> = vec![27, 28, 29];
+        let res = client.distribution("some.distr", values.as_slice());
+
+        assert_eq!("prefix.some.distr:27:28:29|d", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_float_slice() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let values = [27.5, 28.0];
+        let res = client.distribution("some.distr", &values[..]);
+
+        assert_eq!("prefix.some.distr:27.5:28|d", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_with_sampling_rate() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client
+            .distribution_with_tags("some.distr", 4)
+            .with_sampling_rate(0.5)
+            .try_send();
+
+        assert_eq!("prefix.some.distr:4|d|@0.5", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_sampled_always() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let res = client.histogram_sampled("some.histo", 4, 1.0);
+
+        assert_eq!("prefix.some.histo:4|h", res.unwrap().as_metric_str());
+        assert_eq!("prefix.some.histo:4|h", String::from_utf8(rx.recv().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_sampled_rejected() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let res = client.histogram_sampled("some.histo", 4, 0.0);
+
+        assert_eq!("prefix.some.histo:4|h|@0", res.unwrap().as_metric_str());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_sampled_invalid_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let res = client.histogram_sampled("some.histo", 4, 1.5);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_sampled() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+
+        // Keep sending until a metric is picked by sampling. It's vanishingly
+        // unlikely that none of these are sent at a rate of 0.5.
+        for _ in 0..100 {
+            let res = client.distribution_sampled("some.distr", 4, 0.5);
+            assert_eq!("prefix.some.distr:4|d|@0.5", res.unwrap().as_metric_str());
+        }
+
+        assert_eq!(
+            "prefix.some.distr:4|d|@0.5",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_sampled_rejected() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let res = client.distribution_sampled("some.distr", 4, 0.0);
+
+        assert_eq!("prefix.some.distr:4|d|@0", res.unwrap().as_metric_str());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_statsd_client_default_sample_rate_rejected() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink)
+            .with_default_sample_rate(0.0)
+            .build();
+
+        let res = client.count("some.counter", 3);
+        assert_eq!("prefix.some.counter:3|c|@0", res.unwrap().as_metric_str());
+
+        let res = client
+            .time_with_tags("some.timer", 12)
+            .with_tag("foo", "bar")
+            .try_send();
+        assert_eq!("prefix.some.timer:12|ms|@0|#foo:bar", res.unwrap().as_metric_str());
+
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_statsd_client_default_sample_rate_override() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink)
+            .with_default_sample_rate(0.0)
+            .build();
+
+        let res = client
+            .count_with_tags("some.counter", 3)
+            .with_sample_rate(1.0)
+            .try_send();
+        assert_eq!("prefix.some.counter:3|c", res.unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.some.counter:3|c",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+
+        let res = client
+            .gauge_with_tags("some.gauge", 4)
+            .with_sampling_rate(0.5)
+            .try_send();
+        assert_eq!("prefix.some.gauge:4|g|@0.5", res.unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.some.gauge:4|g|@0.5",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_default_sample_rate_invalid() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_default_sample_rate(1.5)
+            .build();
+        let res = client.count("some.counter", 3);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_sample_rate_then_sampling_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let res = client
+            .histogram_with_tags("some.histo", 4)
+            .with_sample_rate(0.0)
+            .with_sampling_rate(0.5)
+            .try_send();
+
+        // The last rate wins and only forwards the rate without sampling
+        assert_eq!("prefix.some.histo:4|h|@0.5", res.unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.some.histo:4|h|@0.5",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_set_with_tags() {
+        let client = StatsdClient::from_sink("myapp", NopMetricSink);
+        let res = client.set_with_tags("some.set", 3).with_tag("foo", "bar").try_send();
+
+        assert_eq!("myapp.some.set:3|s|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_set_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.set_with_tags("some.set", 3).try_send();
+
+        assert_eq!("prefix.some.set:3|s|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_set_multiple_values() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.set("some.set", vec![1, 2, 3]);
+
+        assert_eq!("prefix.some.set:1:2:3|s", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_set_str() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.set("some.set", "user-1234");
+
+        assert_eq!("prefix.some.set:user-1234|s", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_set_string() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.set("some.set", format!("user-{}", 5678));
+
+        assert_eq!("prefix.some.set:user-5678|s", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_set_str_with_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client
+            .set_with_tags("users.uniques", "user_abc")
+            .with_tag("region", "us-east-1")
+            .try_send();
+
+        assert_eq!(
+            "prefix.users.uniques:user_abc|s|#foo:bar,region:us-east-1",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_set_str_reserved_characters() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        for member in ["user:abc", "user|abc", "user\nabc", ""] {
+            let res = client.set("users.uniques", member);
+            assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        }
+
+        let res = client.set("users.uniques", String::from("user:abc"));
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_with_tags_send_success() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+
+        client.count_with_tags("some.key", 1).with_tag("test", "a").send();
+        let sent = rx.recv().unwrap();
+
+        assert_eq!("prefix.some.key:1|c|#test:a", String::from_utf8(sent).unwrap());
+    }
+
+    #[test]
+    fn test_statsd_client_with_tags_send_error() {
+        struct ErrorSink;
+
+        impl MetricSink for ErrorSink {
+            fn emit(&self, _metric: &str) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::Other))
+            }
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_ref = count.clone();
+
+        let handler = move |_err: MetricError| {
+            count_ref.fetch_add(1, Ordering::Release);
+        };
+
+        let client = StatsdClient::builder("prefix", ErrorSink)
+            .with_error_handler(handler)
+            .build();
+
+        client.count_with_tags("some.key", 1).with_tag("tier", "web").send();
+
+        assert_eq!(1, count.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_statsd_client_with_emit_observer() {
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let observed_ref = observed.clone();
+
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_emit_observer(move |metric: &str| observed_ref.lock().unwrap().push(metric.to_string()))
+            .build();
+
+        client.count("some.counter", 1).unwrap();
+        client
+            .batch(|b| {
+                b.gauge("some.gauge", 2).unwrap();
+                b.time("some.timer", 3).unwrap();
+            })
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                "prefix.some.counter:1|c",
+                "prefix.some.gauge:2|g",
+                "prefix.some.timer:3|ms"
+            ],
+            *observed.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_with_emit_observer_send_error() {
+        struct ErrorSink;
+
+        impl MetricSink for ErrorSink {
+            fn emit(&self, _metric: &str) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::Other))
+            }
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_ref = count.clone();
+
+        let client = StatsdClient::builder("prefix", ErrorSink)
+            .with_emit_observer(move |_metric: &str| {
+                count_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
+
+        assert!(client.count("some.counter", 1).is_err());
+        assert_eq!(0, count.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_statsd_client_clone_shares_sink_and_tags() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_tag("env", "test").build();
+        let cloned = client.clone();
+
+        client.count("some.key", 1).unwrap();
+        cloned.count("some.key", 2).unwrap();
+
+        assert_eq!(
+            "prefix.some.key:1|c|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert_eq!(
+            "prefix.some.key:2|c|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_clone_shares_error_handler() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_ref = count.clone();
+
+        let client = StatsdClient::builder("prefix", TimedOutSink)
+            .with_error_handler(move |_err: MetricError| {
+                count_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
+        let cloned = client.clone();
+        drop(client);
+
+        cloned.count_with_tags("some.key", 1).send();
+
+        assert_eq!(1, count.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_statsd_client_production_udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let client = StatsdClient::production_udp("prefix", server.local_addr().unwrap()).unwrap();
+        client.count("some.counter", 1).unwrap();
+        // Dropping the client stops the queuing sink and flushes the buffered sink
+        drop(client);
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"prefix.some.counter:1|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_statsd_client_production_udp_builder() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let client = StatsdClient::production_udp_builder(
+            "prefix",
+            server.local_addr().unwrap(),
+            BufferedUdpMetricSinkBuilder::new().with_expected_metric_size(512),
+            QueuingMetricSinkBuilder::new().with_capacity(16),
+        )
+        .unwrap()
+        .with_tag("env", "test")
+        .build();
 
-#[cfg(test)]
-mod tests {
-    use super::{
-        Counted, CountedExt, Distributed, Gauged, Histogrammed, Metered, MetricClient, Setted, StatsdClient, Timed,
-    };
-    use crate::sinks::{MetricSink, NopMetricSink, QueuingMetricSink, SpyMetricSink};
-    use crate::types::{ErrorKind, Metric, MetricError};
-    use crate::StatsdClientBuilder;
-    use std::io;
-    use std::panic::RefUnwindSafe;
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::Arc;
-    use std::time::Duration;
+        // The expected metric size is as big as the buffer so each metric is
+        // sent right away without needing to flush or drop the client.
+        client.count("some.counter", 1).unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"prefix.some.counter:1|c|#env:test\n", &buf[..len]);
+    }
 
     #[test]
-    fn test_statsd_client_empty_prefix() {
-        let client = StatsdClient::from_sink("", NopMetricSink);
-        let res = client.count("some.method", 1);
+    fn test_statsd_client_production_udp_bad_address() {
+        let res = StatsdClient::production_udp("prefix", "asdf");
+        assert!(res.is_err());
+    }
 
-        assert_eq!("some.method:1|c", res.unwrap().as_metric_str());
+    #[cfg(unix)]
+    #[test]
+    fn test_statsd_client_production_unix() {
+        let temp = TempDir::new("test_statsd_client_production_unix").unwrap();
+        let path = temp.new_path("metrics.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let client = StatsdClient::production_unix("prefix", &path).unwrap();
+        client.count("some.counter", 1).unwrap();
+        // Dropping the client stops the queuing sink and flushes the buffered sink
+        drop(client);
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"prefix.some.counter:1|c\n", &buf[..len]);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_statsd_client_with_container_id() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_container_id("1234")
-            .build();
-        let res = client.count("some.method", 1);
+    fn test_statsd_client_production_unix_builder() {
+        let temp = TempDir::new("test_statsd_client_production_unix_builder").unwrap();
+        let path = temp.new_path("metrics.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
 
-        assert_eq!("prefix.some.method:1|c|c:1234", res.unwrap().as_metric_str());
+        let client = StatsdClient::production_unix_builder(
+            "prefix",
+            &path,
+            BufferedUnixMetricSinkBuilder::new().with_capacity(64),
+            QueuingMetricSinkBuilder::new().with_capacity(16),
+        )
+        .unwrap()
+        .with_tag("env", "test")
+        .build();
+
+        client.count("some.counter", 1).unwrap();
+        drop(client);
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"prefix.some.counter:1|c|#env:test\n", &buf[..len]);
     }
 
     #[test]
-    fn test_statsd_client_merging_default_tags_with_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("hello", "world")
-            .with_tag_value("production")
+    fn test_statsd_client_with_cardinality() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_tag("env", "test")
+            .with_container_id("1234")
+            .with_cardinality(Cardinality::Orchestrator)
             .build();
-        let res = client
-            .count_with_tags("some.counter", 3)
-            .with_tag("foo", "bar")
-            .with_tag_value("fizz")
-            .with_tag("bucket", "123")
-            .try_send();
 
+        let res = client.gauge_with_tags("some.gauge", 5).try_send();
         assert_eq!(
-            "prefix.some.counter:3|c|#hello:world,production,foo:bar,fizz,bucket:123",
+            "prefix.some.gauge:5|g|#env:test|c:1234|card:orchestrator",
             res.unwrap().as_metric_str()
         );
     }
 
     #[test]
-    fn test_statsd_client_count_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .count_with_tags("some.counter", 3)
-            .with_tag("foo", "bar")
-            .try_send();
-
-        assert_eq!("prefix.some.counter:3|c|#foo:bar", res.unwrap().as_metric_str());
-    }
-
-    #[test]
-    fn test_statsd_client_count_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("hello", "world")
+    fn test_statsd_client_with_cardinality_override() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_cardinality(Cardinality::Low)
             .build();
-        let res = client.count_with_tags("some.counter", 3).try_send();
 
-        assert_eq!("prefix.some.counter:3|c|#hello:world", res.unwrap().as_metric_str());
+        let res = client
+            .count_with_tags("some.counter", 1)
+            .with_cardinality(Cardinality::High)
+            .try_send();
+        assert_eq!("prefix.some.counter:1|c|card:high", res.unwrap().as_metric_str());
     }
 
     #[test]
-    fn test_statsd_client_incr_with_tags() {
+    fn test_statsd_client_without_cardinality() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.incr_with_tags("some.counter").with_tag("foo", "bar").try_send();
 
-        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+        let res = client.count_with_tags("some.counter", 1).try_send();
+        assert_eq!("prefix.some.counter:1|c", res.unwrap().as_metric_str());
     }
 
     #[test]
-    fn test_statsd_client_incr_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
-            .build();
-        let res = client.incr_with_tags("some.counter").try_send();
+    fn test_statsd_client_count_batch() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_tag("env", "test").build();
 
-        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+        client
+            .count_batch(vec![("some.counter", 1), ("other.counter", -2)])
+            .unwrap();
+
+        assert_eq!(
+            "prefix.some.counter:1|c|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert_eq!(
+            "prefix.other.counter:-2|c|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_decr_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.decr_with_tags("some.counter").with_tag("foo", "bar").try_send();
+    fn test_statsd_client_count_batch_empty() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
 
-        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+        client.count_batch(Vec::new()).unwrap();
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_decr_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
+    fn test_statsd_client_count_batch_default_sample_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_default_sample_rate(0.0)
             .build();
-        let res = client.decr_with_tags("some.counter").try_send();
 
-        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+        client
+            .count_batch(vec![("some.counter", 1), ("other.counter", -2)])
+            .unwrap();
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_gauge_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .gauge_with_tags("some.gauge", 4)
-            .with_tag("bucket", "A")
-            .with_tag_value("file-server")
-            .try_send();
+    fn test_statsd_client_histogram_weighted() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_tag("env", "test").build();
+
+        client
+            .histogram_weighted("some.histo", &[(4u64, 3), (9, 0), (12, 1)])
+            .unwrap();
+        client.histogram_weighted("other.histo", &[(0.5, 2)]).unwrap();
 
         assert_eq!(
-            "prefix.some.gauge:4|g|#bucket:A,file-server",
-            res.unwrap().as_metric_str()
+            "prefix.some.histo:4:4:4:12|h|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
         );
+        assert_eq!(
+            "prefix.other.histo:0.5:0.5|h|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_gauge_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
-            .build();
-        let res = client.gauge_with_tags("some.gauge", 4).try_send();
+    fn test_statsd_client_histogram_weighted_split() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
 
-        assert_eq!("prefix.some.gauge:4|g|#foo:bar", res.unwrap().as_metric_str());
+        client.histogram_weighted("some.histo", &[(1000u64, 300)]).unwrap();
+
+        let metrics: Vec<String> = rx.try_iter().map(|m| String::from_utf8(m).unwrap()).collect();
+        let mut values = 0;
+        for metric in metrics.iter() {
+            assert!(metric.len() <= DEFAULT_BUFFER_SIZE, "metric too large: {}", metric);
+            let packed = metric
+                .strip_prefix("prefix.some.histo:")
+                .and_then(|m| m.strip_suffix("|h"))
+                .unwrap();
+            assert!(packed.split(':').all(|v| v == "1000"));
+            values += packed.split(':').count();
+        }
+
+        assert!(metrics.len() > 1);
+        assert_eq!(300, values);
     }
 
     #[test]
-    fn test_statsd_client_gauge_with_timestamp() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .gauge_with_tags("some.gauge", 4)
-            .with_timestamp(1234567890)
-            .try_send();
+    fn test_statsd_client_histogram_weighted_too_many() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
 
-        assert_eq!("prefix.some.gauge:4|g|T1234567890", res.unwrap().as_metric_str());
+        let res = client.histogram_weighted("some.histo", &[(1u64, 5_000), (2, 5_001)]);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.histogram_weighted("some.histo", &[(1u64, u64::MAX), (2, 1)]);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_time_duration() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.time("key", Duration::from_millis(157));
+    fn test_statsd_client_histogram_weighted_empty() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
 
-        assert_eq!("prefix.key:157|ms", res.unwrap().as_metric_str());
+        client.histogram_weighted::<u64>("some.histo", &[]).unwrap();
+        client.histogram_weighted("some.histo", &[(1u64, 0)]).unwrap();
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_time_multiple_durations() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let durations = vec![
-            Duration::from_millis(157),
-            Duration::from_millis(158),
-            Duration::from_millis(159),
-        ];
-        let res = client.time("key", durations);
+    fn test_statsd_client_histogram_weighted_float_precision() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_float_precision(2).build();
 
-        assert_eq!("prefix.key:157:158:159|ms", res.unwrap().as_metric_str());
+        client.histogram_weighted("some.histo", &[(0.123456f64, 2)]).unwrap();
+
+        assert_eq!(
+            "prefix.some.histo:0.12:0.12|h",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_time_duration_with_overflow() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.time("key", Duration::from_secs(u64::MAX));
+    fn test_statsd_client_histogram_weighted_float_decimal_point() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_float_decimal_point(true)
+            .build();
 
-        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind())
+        client
+            .histogram_weighted("some.histo", &[(1.0f64, 2), (0.0, 1)])
+            .unwrap();
+        client.histogram_weighted("other.histo", &[(2u64, 1)]).unwrap();
+
+        assert_eq!(
+            "prefix.some.histo:1.0:1.0:0.0|h",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert_eq!("prefix.other.histo:2|h", String::from_utf8(rx.recv().unwrap()).unwrap());
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_time_multiple_durations_with_overflow() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let durations = vec![
-            Duration::from_millis(157),
-            Duration::from_secs(u64::MAX),
-            Duration::from_millis(159),
-        ];
-        let res = client.time("key", durations);
+    fn test_statsd_client_packet_too_large() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSinkBuilder::new()
+            .with_max_datagram_size(32)
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+        let client = StatsdClient::from_sink("prefix", sink);
 
-        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind())
+        let err = client.set("some.set", "x".repeat(32)).unwrap_err();
+        assert_eq!(ErrorKind::PacketTooLarge, err.kind());
+        assert_eq!(Some(("prefix.some.set", 50)), err.packet_too_large());
+
+        client.set("some.set", "x").unwrap();
     }
 
     #[test]
-    fn test_statsd_client_time_duration_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .time_with_tags("key", Duration::from_millis(157))
-            .with_tag("foo", "bar")
-            .with_tag_value("quux")
-            .try_send();
+    fn test_statsd_client_count_batch_error() {
+        let client = StatsdClient::from_sink("prefix", TimedOutSink);
 
-        assert_eq!("prefix.key:157|ms|#foo:bar,quux", res.unwrap().as_metric_str());
+        let res = client.count_batch(vec![("some.counter", 1), ("other.counter", 2)]);
+        assert_eq!(ErrorKind::IoError, res.unwrap_err().kind());
     }
 
     #[test]
-    fn test_statsd_client_time_duration_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
+    fn test_statsd_client_from_shared() {
+        let (rx, sink) = SpyMetricSink::new();
+        let shared = SharedSink::new(sink);
+        let first = StatsdClient::from_shared("first", &shared);
+        let second = StatsdClient::builder("second", shared.clone())
+            .with_tag("env", "prod")
             .build();
-        let res = client.time("key", Duration::from_millis(157));
 
-        assert_eq!("prefix.key:157|ms|#foo:bar", res.unwrap().as_metric_str());
+        first.count("some.counter", 1).unwrap();
+        second.count("some.counter", 2).unwrap();
+        drop(shared);
+
+        assert_eq!("first.some.counter:1|c", String::from_utf8(rx.recv().unwrap()).unwrap());
+        assert_eq!(
+            "second.some.counter:2|c|#env:prod",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
     }
 
     #[test]
-    fn test_statsd_client_time_multiple_durations_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let durations = vec![
-            Duration::from_millis(157),
-            Duration::from_millis(158),
-            Duration::from_millis(159),
-        ];
-        let res = client
-            .time_with_tags("key", durations)
-            .with_tag("foo", "bar")
-            .with_tag_value("quux")
-            .try_send();
+    fn test_statsd_client_raw() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::raw(sink);
 
-        assert_eq!("prefix.key:157:158:159|ms|#foo:bar,quux", res.unwrap().as_metric_str());
+        client.count("some.counter", 1).unwrap();
+        client
+            .gauge_with_tags("some.gauge", 2)
+            .with_tag("host", "web01")
+            .try_send()
+            .unwrap();
+
+        assert_eq!("some.counter:1|c", String::from_utf8(rx.recv().unwrap()).unwrap());
+        assert_eq!(
+            "some.gauge:2|g|#host:web01",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
     }
 
     #[test]
-    fn test_statsd_client_time_duration_with_tags_with_overflow() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .time_with_tags("key", Duration::from_secs(u64::MAX))
-            .with_tag("foo", "bar")
-            .with_tag_value("quux")
-            .try_send();
+    fn test_statsd_client_raw_empty_key() {
+        let client = StatsdClient::raw(NopMetricSink);
 
-        assert!(res.is_err());
+        let res = client.count("", 1);
         assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
     }
 
     #[test]
-    fn test_statsd_client_time_multiple_durations_with_tags_with_overflow() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let durations = vec![
-            Duration::from_millis(157),
-            Duration::from_secs(u64::MAX),
-            Duration::from_millis(159),
-        ];
-        let res = client
-            .time_with_tags("key", durations)
-            .with_tag("foo", "bar")
-            .with_tag_value("quux")
-            .try_send();
+    fn test_statsd_client_batch() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_tag("env", "test").build();
 
-        assert!(res.is_err());
-        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        client
+            .batch(|b| {
+                b.count("some.counter", 1).unwrap();
+                // Sent right away, before any of the metrics in the batch
+                client.count("other.counter", 2).unwrap();
+                b.time("some.timer", 35).unwrap();
+            })
+            .unwrap();
+
+        assert_eq!(
+            "prefix.other.counter:2|c|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert_eq!(
+            "prefix.some.counter:1|c|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert_eq!(
+            "prefix.some.timer:35|ms|#env:test",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[cfg(feature = "async")]
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct ThreadWaker(std::thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
     }
 
+    #[cfg(feature = "async")]
     #[test]
-    fn test_statsd_client_meter_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .meter_with_tags("some.meter", 64)
-            .with_tag("segment", "142")
-            .with_tag_value("beta")
-            .try_send();
+    fn test_statsd_client_time_future() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
 
-        assert_eq!("prefix.some.meter:64|m|#segment:142,beta", res.unwrap().as_metric_str());
+        let output = block_on(client.time_future("some.future", async { 42 }));
+
+        assert_eq!(42, output);
+        let metric = String::from_utf8(rx.recv().unwrap()).unwrap();
+        assert!(
+            metric.starts_with("prefix.some.future:"),
+            "unexpected metric {}",
+            metric
+        );
+        assert!(metric.ends_with("|ms"), "unexpected metric {}", metric);
     }
 
+    #[cfg(feature = "async")]
     #[test]
-    fn test_statsd_client_meter_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
-            .build();
-        let res = client.meter_with_tags("some.meter", 64).try_send();
+    fn test_statsd_client_time_future_with_clock() {
+        let (rx, sink) = SpyMetricSink::new();
+        let clock = MockClock::new();
+        let client = StatsdClient::builder("prefix", sink).with_clock(clock.clone()).build();
 
-        assert_eq!("prefix.some.meter:64|m|#foo:bar", res.unwrap().as_metric_str());
+        let output = block_on(client.time_future("some.future", async {
+            clock.advance(Duration::from_millis(250));
+            42
+        }));
+
+        assert_eq!(42, output);
+        assert_eq!(
+            "prefix.some.future:250|ms",
+            String::from_utf8(rx.recv().unwrap()).unwrap()
+        );
     }
 
+    #[cfg(feature = "async")]
     #[test]
-    fn test_statsd_client_histogram_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .histogram_with_tags("some.histo", 27)
-            .with_tag("host", "www03.example.com")
-            .with_tag_value("rc1")
-            .try_send();
+    fn test_statsd_client_time_future_with_tags() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
 
-        assert_eq!(
-            "prefix.some.histo:27|h|#host:www03.example.com,rc1",
-            res.unwrap().as_metric_str()
+        let (output, builder) = block_on(client.time_future_with_tags("some.future", async { "done" }));
+        // Nothing is sent until the builder is
+        assert!(rx.try_recv().is_err());
+
+        builder.with_tag("result", output).send();
+
+        assert_eq!("done", output);
+        let metric = String::from_utf8(rx.recv().unwrap()).unwrap();
+        assert!(
+            metric.starts_with("prefix.some.future:"),
+            "unexpected metric {}",
+            metric
         );
+        assert!(metric.ends_with("|ms|#result:done"), "unexpected metric {}", metric);
     }
 
     #[test]
-    fn test_statsd_client_histogram_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
-            .build();
-        let res = client.histogram_with_tags("some.histo", 27).try_send();
+    fn test_statsd_client_batch_error() {
+        let client = StatsdClient::from_sink("prefix", TimedOutSink);
 
-        assert_eq!("prefix.some.histo:27|h|#foo:bar", res.unwrap().as_metric_str());
+        let res = client.batch(|b| {
+            b.count("some.counter", 1).unwrap();
+        });
+        assert_eq!(ErrorKind::IoError, res.unwrap_err().kind());
     }
 
     #[test]
-    fn test_statsd_client_histogram_with_multiple_values() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.histogram_with_tags("some.histo", vec![27, 28, 29]).try_send();
+    fn test_statsd_client_count_batch_empty_name() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("", sink);
 
-        assert_eq!("prefix.some.histo:27:28:29|h", res.unwrap().as_metric_str());
+        let res = client.count_batch(vec![("some.counter", 1), ("", 2)]);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_histogram_duration() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.histogram("key", Duration::from_nanos(210));
+    fn test_statsd_client_empty_prefix_empty_key() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("", sink);
 
-        assert_eq!("prefix.key:210|h", res.unwrap().as_metric_str());
+        let res = client.count("", 1);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        assert!(rx.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_histogram_multiple_durations() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let durations = vec![
-            Duration::from_nanos(210),
-            Duration::from_nanos(211),
-            Duration::from_nanos(212),
-        ];
-        let res = client.histogram("key", durations);
+    fn test_statsd_client_empty_prefix_valid_key() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("", sink);
 
-        assert_eq!("prefix.key:210:211:212|h", res.unwrap().as_metric_str());
+        client.count("some.counter", 1).unwrap();
+        assert_eq!("some.counter:1|c", String::from_utf8(rx.recv().unwrap()).unwrap());
     }
 
     #[test]
-    fn test_statsd_client_histogram_duration_with_overflow() {
+    fn test_statsd_client_sink_ready() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.histogram("key", Duration::from_secs(u64::MAX));
+        assert!(client.sink_ready());
 
-        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        let client = StatsdClient::from_sink("prefix", NotReadySink);
+        assert!(!client.sink_ready());
     }
 
-    #[test]
-    fn test_statsd_client_histogram_multiple_durations_with_overflow() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let durations = vec![
-            Duration::from_nanos(210),
-            Duration::from_secs(u64::MAX),
-            Duration::from_nanos(212),
-        ];
+    struct NotReadySink;
 
-        let res = client.histogram("key", durations);
+    impl MetricSink for NotReadySink {
+        fn emit(&self, _metric: &str) -> io::Result<usize> {
+            Ok(0)
+        }
 
-        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        fn is_ready(&self) -> bool {
+            false
+        }
     }
 
-    #[test]
-    fn test_statsd_client_histogram_duration_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .histogram_with_tags("key", Duration::from_nanos(4096))
-            .with_tag("foo", "bar")
-            .with_tag_value("beta")
-            .try_send();
+    struct TimedOutSink;
 
-        assert_eq!("prefix.key:4096|h|#foo:bar,beta", res.unwrap().as_metric_str());
+    impl MetricSink for TimedOutSink {
+        fn emit(&self, _metric: &str) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::TimedOut))
+        }
     }
 
     #[test]
-    fn test_statsd_client_histogram_duration_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
+    fn test_statsd_client_queuing_builder_sink_error() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let client = StatsdClient::queuing_builder("prefix", TimedOutSink, QueuingMetricSinkBuilder::new())
+            .with_error_handler(move |err: MetricError| {
+                tx.send(err).unwrap();
+            })
             .build();
-        let res = client.histogram_with_tags("key", Duration::from_nanos(4096)).try_send();
 
-        assert_eq!("prefix.key:4096|h|#foo:bar", res.unwrap().as_metric_str());
+        client.count("some.key", 1).unwrap();
+        let err = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert_eq!(ErrorKind::IoError, err.kind());
     }
 
     #[test]
-    fn test_statsd_client_histogram_duration_with_tags_with_overflow() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .histogram_with_tags("key", Duration::from_millis(u64::MAX))
-            .with_tag("foo", "bar")
-            .with_tag_value("beta")
-            .try_send();
+    fn test_statsd_client_queuing_builder_existing_handler() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let client_count = Arc::new(AtomicUsize::new(0));
+        let client_count_ref = client_count.clone();
+
+        let queuing = QueuingMetricSinkBuilder::new().with_error_handler(move |err: io::Error| {
+            tx.send(err).unwrap();
+        });
+        let client = StatsdClient::queuing_builder("prefix", TimedOutSink, queuing)
+            .with_error_handler(move |_err: MetricError| {
+                client_count_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
 
-        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        client.count("some.key", 1).unwrap();
+        let err = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!(0, client_count.load(Ordering::Acquire));
     }
 
     #[test]
-    fn test_statsd_client_distribution_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .distribution_with_tags("some.distr", 27)
-            .with_tag("host", "www03.example.com")
-            .with_tag_value("rc1")
-            .try_send();
+    fn test_statsd_client_sink_downcast() {
+        let (_rx, spy) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", QueuingMetricSink::synchronous(spy));
 
-        assert_eq!(
-            "prefix.some.distr:27|d|#host:www03.example.com,rc1",
-            res.unwrap().as_metric_str()
-        );
+        client.count("some.key", 1).unwrap();
+        client.count("some.key", 2).unwrap();
+
+        let sink = client.sink();
+        let queuing = sink
+            .as_any()
+            .and_then(|s| s.downcast_ref::<QueuingMetricSink>())
+            .unwrap();
+        assert_eq!(0, queuing.queued());
+        assert_eq!(2, queuing.submitted());
     }
 
     #[test]
-    fn test_statsd_client_distribution_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
-            .build();
-        let res = client
-            .distribution_with_tags("some.distr", 27)
-            .with_tag("host", "www03.example.com")
-            .with_tag_value("rc1")
-            .try_send();
+    fn test_statsd_client_sink_downcast_unsupported() {
+        let client = StatsdClient::from_sink("prefix", TimedOutSink);
 
-        assert_eq!(
-            "prefix.some.distr:27|d|#foo:bar,host:www03.example.com,rc1",
-            res.unwrap().as_metric_str()
-        );
+        assert!(client.sink().as_any().is_none());
     }
 
     #[test]
-    fn test_statsd_client_distribution_multiple_values_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .distribution_with_tags("some.distr", vec![27, 28, 29])
-            .with_tag("host", "www03.example.com")
-            .with_tag_value("rc1")
-            .try_send();
-
-        assert_eq!(
-            "prefix.some.distr:27:28:29|d|#host:www03.example.com,rc1",
-            res.unwrap().as_metric_str()
-        );
+    fn test_statsd_client_replace_sink() {
+        let (rx1, first) = BufferedSpyMetricSink::new();
+        let (rx2, second) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", first);
+        let client_ref = client.clone();
+
+        client.count("some.key", 1).unwrap();
+        assert!(rx1.try_recv().is_err());
+
+        // The previous sink is flushed and copies of the client use the new one
+        client.replace_sink(second).unwrap();
+        assert_eq!(b"prefix.some.key:1|c\n", rx1.try_recv().unwrap().as_slice());
+
+        client_ref.count("some.key", 2).unwrap();
+        assert_eq!(b"prefix.some.key:2|c", rx2.try_recv().unwrap().as_slice());
+        assert!(rx1.try_recv().is_err());
     }
 
     #[test]
-    fn test_statsd_client_distribution_with_sampling_rate() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client
-            .distribution_with_tags("some.distr", 4)
-            .with_sampling_rate(0.5)
-            .try_send();
+    fn test_statsd_client_with_tags_from_lookup() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("env", "prod")
+            .with_tags_from_lookup(&[("region", "REGION"), ("missing", "MISSING")], |var| match var {
+                "REGION" => Some("us-east-1".to_string()),
+                _ => None,
+            })
+            .build();
+        let res = client.count("some.counter", 1);
 
-        assert_eq!("prefix.some.distr:4|d|@0.5", res.unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.some.counter:1|c|#env:prod,region:us-east-1",
+            res.unwrap().as_metric_str()
+        );
     }
 
     #[test]
-    fn test_statsd_client_set_with_tags() {
-        let client = StatsdClient::from_sink("myapp", NopMetricSink);
-        let res = client.set_with_tags("some.set", 3).with_tag("foo", "bar").try_send();
+    fn test_statsd_client_with_tags_from_env_missing() {
+        // Environment variables aren't modified by tests since that's unsound
+        // while other tests may be reading them, only read a variable that's
+        // never set.
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tags_from_env(&[("missing", "CADENCE_TEST_TAGS_FROM_ENV_MISSING")])
+            .build();
+        let res = client.count("some.counter", 1);
 
-        assert_eq!("myapp.some.set:3|s|#foo:bar", res.unwrap().as_metric_str());
+        assert_eq!("prefix.some.counter:1|c", res.unwrap().as_metric_str());
     }
 
     #[test]
-    fn test_statsd_client_set_with_default_tags() {
+    fn test_statsd_client_dd_unified_tags() {
         let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
+            .with_tags_from_lookup(DD_UNIFIED_TAGS, |var| match var {
+                "DD_ENV" => Some("staging".to_string()),
+                "DD_VERSION" => Some("1.2.3".to_string()),
+                _ => None,
+            })
             .build();
-        let res = client.set_with_tags("some.set", 3).try_send();
+        let res = client.count("some.counter", 1);
 
-        assert_eq!("prefix.some.set:3|s|#foo:bar", res.unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.some.counter:1|c|#env:staging,version:1.2.3",
+            res.unwrap().as_metric_str()
+        );
     }
 
     #[test]
-    fn test_statsd_client_with_tags_send_success() {
-        let (rx, sink) = SpyMetricSink::new();
+    fn test_statsd_client_flush_guard() {
+        let (rx, sink) = BufferedSpyMetricSink::new();
         let client = StatsdClient::from_sink("prefix", sink);
 
-        client.count_with_tags("some.key", 1).with_tag("test", "a").send();
-        let sent = rx.recv().unwrap();
+        {
+            let _guard = client.flush_guard();
+            client.count("some.key", 1).unwrap();
+            assert!(rx.try_recv().is_err());
+        }
 
-        assert_eq!("prefix.some.key:1|c|#test:a", String::from_utf8(sent).unwrap());
+        assert_eq!(b"prefix.some.key:1|c\n", rx.try_recv().unwrap().as_slice());
     }
 
     #[test]
-    fn test_statsd_client_with_tags_send_error() {
-        struct ErrorSink;
+    fn test_statsd_client_flush_guard_error() {
+        struct FlushErrorSink;
 
-        impl MetricSink for ErrorSink {
-            fn emit(&self, _metric: &str) -> io::Result<usize> {
-                Err(io::Error::from(io::ErrorKind::Other))
+        impl MetricSink for FlushErrorSink {
+            fn emit(&self, metric: &str) -> io::Result<usize> {
+                Ok(metric.len())
+            }
+
+            fn flush(&self) -> io::Result<()> {
+                Err(io::Error::from(io::ErrorKind::TimedOut))
             }
         }
 
         let count = Arc::new(AtomicUsize::new(0));
         let count_ref = count.clone();
-
-        let handler = move |_err: MetricError| {
-            count_ref.fetch_add(1, Ordering::Release);
-        };
-
-        let client = StatsdClient::builder("prefix", ErrorSink)
-            .with_error_handler(handler)
+        let client = StatsdClientBuilder::new("prefix", FlushErrorSink)
+            .with_error_handler(move |_err: MetricError| {
+                count_ref.fetch_add(1, Ordering::SeqCst);
+            })
             .build();
 
-        client.count_with_tags("some.key", 1).with_tag("tier", "web").send();
-
-        assert_eq!(1, count.load(Ordering::Acquire));
+        drop(client.flush_guard());
+        assert_eq!(1, count.load(Ordering::SeqCst));
     }
 
     // The following tests really just ensure that we've actually
     // implemented all the traits we're supposed to correctly. If
     // we hadn't, this wouldn't compile.
 
+    #[test]
+    fn test_statsd_client_with_cardinality_limit() {
+        let exceeded = Arc::new(AtomicUsize::new(0));
+        let exceeded_ref = exceeded.clone();
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_cardinality_limit(3, move |key: &str| {
+                assert_eq!("prefix.some.counter|#user:3", key);
+                exceeded_ref.fetch_add(1, Ordering::SeqCst);
+            })
+            .build();
+
+        for i in 0..10 {
+            let user = i.to_string();
+            client.count_with_tags("some.counter", 1).with_tag("user", &user).send();
+        }
+
+        assert_eq!(1, exceeded.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_statsd_client_send_metric_bytes() {
+        let (_rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let metric = Counter::new("prefix.", "some.counter", 5);
+
+        let bytes = client.send_metric_bytes(&metric).unwrap();
+        assert_eq!(metric.as_metric_str().len(), bytes);
+    }
+
+    #[test]
+    fn test_statsd_client_send_metric_bytes_nop_sink() {
+        // The no-op sink doesn't write anything so reports zero bytes
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let metric = Counter::new("prefix.", "some.counter", 5);
+
+        assert_eq!(0, client.send_metric_bytes(&metric).unwrap());
+    }
+
     #[test]
     fn test_statsd_client_as_counted_i64() {
         let client: Box<dyn Counted<i64>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
@@ -1655,6 +5511,17 @@ mod tests {
         client.count("some.counter", 20u64).unwrap();
     }
 
+    #[test]
+    fn test_statsd_client_count_u64_larger_than_i64() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.count("some.counter", u64::MAX);
+
+        assert_eq!(
+            "prefix.some.counter:18446744073709551615|c",
+            res.unwrap().as_metric_str()
+        );
+    }
+
     #[test]
     fn test_statsd_client_as_counted_u32() {
         let client: Box<dyn Counted<u32>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
@@ -1669,6 +5536,13 @@ mod tests {
         client.incr("some.counter").unwrap();
     }
 
+    #[test]
+    fn test_statsd_client_as_timedext() {
+        let client: Box<dyn TimedExt> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
+
+        client.time_since("some.timer", Instant::now()).unwrap();
+    }
+
     #[test]
     fn test_statsd_client_as_timed_u64() {
         let client: Box<dyn Timed<u64>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
@@ -1705,6 +5579,42 @@ mod tests {
         client.gauge("some.gauge", 3.2).unwrap();
     }
 
+    #[test]
+    fn test_statsd_client_as_gauged_i64() {
+        let client: Box<dyn Gauged<i64>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
+
+        client.gauge("some.gauge", -22).unwrap();
+    }
+
+    #[test]
+    fn test_statsd_client_as_gauged_bool() {
+        let client: Box<dyn Gauged<bool>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
+
+        client.gauge("some.gauge", true).unwrap();
+    }
+
+    #[test]
+    fn test_statsd_client_strict_meters() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink).with_strict_meters().build();
+
+        let res = client.meter("some.meter", 0);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        assert!(rx.try_recv().is_err());
+
+        let res = client.meter("some.meter", 3);
+        assert_eq!("prefix.some.meter:3|m", res.unwrap().as_metric_str());
+        assert_eq!("prefix.some.meter:3|m", String::from_utf8(rx.recv().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_statsd_client_meter_zero_not_strict() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.meter("some.meter", 0);
+
+        assert_eq!("prefix.some.meter:0|m", res.unwrap().as_metric_str());
+    }
+
     #[test]
     fn test_statsd_client_as_metered() {
         let client: Box<dyn Metered<u64>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
@@ -1740,6 +5650,15 @@ mod tests {
         client.histogram("some.histogram", vec![4.0, 5.0, 6.0]).unwrap();
     }
 
+    #[test]
+    fn test_statsd_client_as_histogrammed_slice() {
+        let client: Box<dyn for<'a> Histogrammed<&'a [u64]>> =
+            Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
+        let values = vec![4, 5, 6];
+
+        client.histogram("some.histogram", &values).unwrap();
+    }
+
     #[test]
     fn test_statsd_client_as_histogrammed_duration() {
         let client: Box<dyn Histogrammed<Duration>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
@@ -1755,6 +5674,15 @@ mod tests {
         client.histogram("some.histogram", durations).unwrap();
     }
 
+    #[test]
+    fn test_statsd_client_as_histogrammedext() {
+        let client: Box<dyn HistogrammedExt> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
+
+        client
+            .histogram_durations("some.histogram", &[Duration::from_nanos(4)])
+            .unwrap();
+    }
+
     #[test]
     fn test_statsd_client_as_distributed_u64() {
         let client: Box<dyn Distributed<u64>> = Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
@@ -1783,6 +5711,15 @@ mod tests {
         client.distribution("some.distribution", vec![33.0, 34.0]).unwrap();
     }
 
+    #[test]
+    fn test_statsd_client_as_distributed_float_slice() {
+        let client: Box<dyn for<'a> Distributed<&'a [f64]>> =
+            Box::new(StatsdClient::from_sink("prefix", NopMetricSink));
+        let values = vec![33.0, 34.0];
+
+        client.distribution("some.distribution", &values).unwrap();
+    }
+
     #[test]
     fn test_statsd_client_as_setted() {
         let client: Box<dyn Setted<i64>> = Box::new(StatsdClient::from_sink("myapp", NopMetricSink));
@@ -1790,6 +5727,27 @@ mod tests {
         client.set("some.set", 5).unwrap();
     }
 
+    #[test]
+    fn test_statsd_client_as_setted_packed() {
+        let client: Box<dyn Setted<Vec<i64>>> = Box::new(StatsdClient::from_sink("myapp", NopMetricSink));
+
+        client.set("some.set", vec![5, 6]).unwrap();
+    }
+
+    #[test]
+    fn test_statsd_client_as_setted_str() {
+        let client: Box<dyn for<'a> Setted<&'a str>> = Box::new(StatsdClient::from_sink("myapp", NopMetricSink));
+
+        client.set("some.set", "abc").unwrap();
+    }
+
+    #[test]
+    fn test_statsd_client_as_setted_string() {
+        let client: Box<dyn Setted<String>> = Box::new(StatsdClient::from_sink("myapp", NopMetricSink));
+
+        client.set("some.set", "abc".to_string()).unwrap();
+    }
+
     #[test]
     fn test_statsd_client_as_thread_and_panic_safe() {
         let client: Box<dyn MetricClient + Send + Sync + RefUnwindSafe> = Box::new(StatsdClient::from_sink(
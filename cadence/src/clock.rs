@@ -0,0 +1,109 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::panic::RefUnwindSafe;
+use std::time::Instant;
+
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+#[cfg(test)]
+use std::time::Duration;
+
+/// Source of the current time for components that behave differently
+/// based on how much time has passed.
+///
+/// Sinks and other parts of Cadence that need to know the current time
+/// (to decide when to flush a buffer, for example) read it from a `Clock`
+/// instead of calling `Instant::now()` directly. This allows the behavior
+/// to be tested deterministically without sleeping. Typical use of Cadence
+/// shouldn't require implementing this trait, the default `SystemClock` is
+/// used everywhere unless configured otherwise.
+pub trait Clock: fmt::Debug + Send + Sync + RefUnwindSafe {
+    /// Return the current instant according to this clock.
+    fn now(&self) -> Instant;
+}
+
+/// Implementation of `Clock` that uses the monotonic system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Implementation of `Clock` that only moves forward when told to. Used
+/// for testing time based behavior without sleeping.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub(crate) struct MockClock {
+    start: Instant,
+    offset: Arc<Mutex<Duration>>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub(crate) fn new() -> Self {
+        MockClock {
+            start: Instant::now(),
+            offset: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    /// Move the clock (and any clones of it) forward by the given duration.
+    pub(crate) fn advance(&self, by: Duration) {
+        let mut offset = self.offset.lock().unwrap();
+        *offset += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.offset.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, MockClock, SystemClock};
+    use std::time::Duration;
+
+    #[test]
+    fn test_system_clock_moves_forward() {
+        let clock = SystemClock;
+        let first = clock.now();
+        let second = clock.now();
+
+        assert!(second >= first);
+    }
+
+    #[test]
+    fn test_mock_clock_only_moves_when_advanced() {
+        let clock = MockClock::new();
+        let first = clock.now();
+
+        assert_eq!(first, clock.now());
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(Duration::from_secs(5), clock.now() - first);
+    }
+
+    #[test]
+    fn test_mock_clock_clones_share_time() {
+        let clock = MockClock::new();
+        let other = clock.clone();
+        let first = other.now();
+
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(Duration::from_millis(250), other.now() - first);
+    }
+}
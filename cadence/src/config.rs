@@ -0,0 +1,307 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::client::{StatsdClient, StatsdClientBuilder};
+use crate::sinks::{
+    bind_nonblocking, get_addr, BufferedUdpMetricSink, NopMetricSink, QueuingMetricSinkBuilder, UdpMetricSink,
+    DEFAULT_BUFFER_SIZE,
+};
+use crate::types::{ErrorKind, MetricError, MetricResult};
+use crate::DEFAULT_PORT;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Type of `MetricSink` built from a `StatsdConfig`.
+///
+/// Kinds are (de)serialized in kebab-case, e.g. `queuing-buffered-udp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SinkKind {
+    /// An `UdpMetricSink`, each metric is sent in a separate packet.
+    Udp,
+    /// A `BufferedUdpMetricSink`, metrics are buffered and sent in batches.
+    BufferedUdp,
+    /// A `BufferedUdpMetricSink` wrapped in a `QueuingMetricSink` so that
+    /// metrics are sent in a separate thread. This is the recommended sink
+    /// for production use.
+    QueuingBufferedUdp,
+    /// An `UnixMetricSink`, each metric is sent over a Unix datagram socket.
+    /// Only supported on Unix platforms.
+    Unix,
+    /// A `NopMetricSink`, all metrics are discarded.
+    Nop,
+}
+
+/// Serializable description of a `StatsdClient` and the sinks it uses.
+///
+/// This allows the metrics configuration of an application to be read from
+/// a configuration file (in any format supported by `serde`) instead of being
+/// built in code. Only `prefix` and `sink` are required, all other fields have
+/// defaults when deserialized.
+///
+/// UDP sockets created for the `udp`, `buffered-udp`, and `queuing-buffered-udp`
+/// sink kinds are put in non-blocking mode so that a slow or unreachable metric
+/// server never blocks the application.
+///
+/// This type is only available when the `serde` feature is enabled.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{SinkKind, StatsdConfig};
+///
+/// let config: StatsdConfig = serde_json::from_str(r#"{
+///     "prefix": "my.prefix",
+///     "sink": "queuing-buffered-udp",
+///     "host": "127.0.0.1",
+///     "tags": {"region": "us-east-1"}
+/// }"#).unwrap();
+///
+/// assert_eq!(SinkKind::QueuingBufferedUdp, config.sink);
+/// let client = config.into_client().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsdConfig {
+    /// Prefix for every metric emitted by the client, may be empty.
+    pub prefix: String,
+    /// Type of sink to send metrics with.
+    pub sink: SinkKind,
+    /// Host name or IP address of the metric server for UDP sinks. Defaults
+    /// to `127.0.0.1`.
+    #[serde(default = "default_host")]
+    pub host: String,
+    /// Port of the metric server for UDP sinks. Defaults to `8125`.
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Path of the metric server socket for Unix sinks. Required for the
+    /// `unix` sink kind.
+    #[serde(default)]
+    pub socket_path: Option<PathBuf>,
+    /// Size of the buffer in bytes for buffered sinks. Defaults to
+    /// `DEFAULT_BUFFER_SIZE`.
+    #[serde(default = "default_buffer_size")]
+    pub buffer_size: usize,
+    /// Maximum number of metrics queued by queuing sinks. Defaults to an
+    /// unbounded queue.
+    #[serde(default)]
+    pub queue_size: Option<usize>,
+    /// Key-value tags added to every metric emitted by the client.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// Value-only tags added to every metric emitted by the client.
+    #[serde(default)]
+    pub tag_values: Vec<String>,
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+fn default_buffer_size() -> usize {
+    DEFAULT_BUFFER_SIZE
+}
+
+impl StatsdConfig {
+    /// Create a new configuration using the given prefix and sink kind and
+    /// defaults for everything else.
+    pub fn new(prefix: &str, sink: SinkKind) -> Self {
+        StatsdConfig {
+            prefix: prefix.to_string(),
+            sink,
+            host: default_host(),
+            port: default_port(),
+            socket_path: None,
+            buffer_size: default_buffer_size(),
+            queue_size: None,
+            tags: BTreeMap::new(),
+            tag_values: Vec::new(),
+        }
+    }
+
+    /// Create the sinks described by this configuration and return a builder
+    /// for a client that uses them and has the configured default tags.
+    ///
+    /// The builder can be used to customize the client further, for example
+    /// by setting an error handler.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the host can't be resolved, a socket can't be
+    /// created, or no socket path is given for the `unix` sink kind.
+    pub fn into_builder(self) -> MetricResult<StatsdClientBuilder> {
+        let builder = match self.sink {
+            SinkKind::Udp => {
+                let addr = get_addr((self.host.as_str(), self.port))?;
+                let socket = bind_nonblocking(&addr)?;
+                StatsdClient::builder(&self.prefix, UdpMetricSink::from(addr, socket)?)
+            }
+            SinkKind::BufferedUdp => {
+                let addr = get_addr((self.host.as_str(), self.port))?;
+                let socket = bind_nonblocking(&addr)?;
+                let sink = BufferedUdpMetricSink::with_capacity(addr, socket, self.buffer_size)?;
+                StatsdClient::builder(&self.prefix, sink)
+            }
+            SinkKind::QueuingBufferedUdp => {
+                let addr = get_addr((self.host.as_str(), self.port))?;
+                let socket = bind_nonblocking(&addr)?;
+                let sink = BufferedUdpMetricSink::with_capacity(addr, socket, self.buffer_size)?;
+                let queuing = match self.queue_size {
+                    Some(capacity) => QueuingMetricSinkBuilder::new().with_capacity(capacity),
+                    None => QueuingMetricSinkBuilder::new(),
+                };
+                StatsdClient::queuing_builder(&self.prefix, sink, queuing)
+            }
+            SinkKind::Unix => Self::unix_builder(&self.prefix, self.socket_path.as_ref())?,
+            SinkKind::Nop => StatsdClient::builder(&self.prefix, NopMetricSink),
+        };
+
+        let builder = self
+            .tags
+            .into_iter()
+            .fold(builder, |builder, (key, value)| builder.with_tag(key, value));
+        let builder = self
+            .tag_values
+            .into_iter()
+            .fold(builder, |builder, value| builder.with_tag_value(value));
+
+        Ok(builder)
+    }
+
+    /// Create the sinks described by this configuration and a client that
+    /// uses them and has the configured default tags.
+    ///
+    /// # Errors
+    ///
+    /// See `StatsdConfig::into_builder()`.
+    pub fn into_client(self) -> MetricResult<StatsdClient> {
+        Ok(self.into_builder()?.build())
+    }
+
+    #[cfg(unix)]
+    fn unix_builder(prefix: &str, path: Option<&PathBuf>) -> MetricResult<StatsdClientBuilder> {
+        use crate::sinks::UnixMetricSink;
+        use std::os::unix::net::UnixDatagram;
+
+        let path =
+            path.ok_or_else(|| MetricError::from((ErrorKind::InvalidInput, "socket path is required for unix sinks")))?;
+        let socket = UnixDatagram::unbound()?;
+        socket.set_nonblocking(true)?;
+        Ok(StatsdClient::builder(prefix, UnixMetricSink::from(path, socket)))
+    }
+
+    #[cfg(not(unix))]
+    fn unix_builder(_prefix: &str, _path: Option<&PathBuf>) -> MetricResult<StatsdClientBuilder> {
+        Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "unix sinks are only supported on unix platforms",
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SinkKind, StatsdConfig};
+    use crate::prelude::*;
+    use crate::types::{ErrorKind, Metric};
+
+    #[test]
+    fn test_statsd_config_deserialize_defaults() {
+        let config: StatsdConfig = serde_json::from_str(r#"{"prefix": "my.prefix", "sink": "nop"}"#).unwrap();
+
+        assert_eq!(StatsdConfig::new("my.prefix", SinkKind::Nop), config);
+    }
+
+    #[test]
+    fn test_statsd_config_round_trip() {
+        let mut config = StatsdConfig::new("my.prefix", SinkKind::QueuingBufferedUdp);
+        config.host = "metrics.example.com".to_string();
+        config.port = 9125;
+        config.buffer_size = 1432;
+        config.queue_size = Some(1024);
+        config.tags.insert("region".to_string(), "us-east-1".to_string());
+        config.tag_values.push("beta".to_string());
+
+        let serialized = serde_json::to_string(&config).unwrap();
+        assert!(serialized.contains(r#""sink":"queuing-buffered-udp""#));
+
+        let deserialized: StatsdConfig = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(config, deserialized);
+    }
+
+    #[test]
+    fn test_statsd_config_unknown_sink() {
+        let res = serde_json::from_str::<StatsdConfig>(r#"{"prefix": "my.prefix", "sink": "carrier-pigeon"}"#);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_statsd_config_into_client_udp() {
+        let client = StatsdConfig::new("my.prefix", SinkKind::Udp).into_client().unwrap();
+        client.count("some.counter", 1).unwrap();
+    }
+
+    #[test]
+    fn test_statsd_config_into_client_buffered_udp() {
+        let client = StatsdConfig::new("my.prefix", SinkKind::BufferedUdp)
+            .into_client()
+            .unwrap();
+        client.count("some.counter", 1).unwrap();
+    }
+
+    #[test]
+    fn test_statsd_config_into_client_queuing_buffered_udp() {
+        let mut config = StatsdConfig::new("my.prefix", SinkKind::QueuingBufferedUdp);
+        config.queue_size = Some(16);
+
+        let client = config.into_client().unwrap();
+        client.count("some.counter", 1).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_statsd_config_into_client_unix() {
+        use crate::test::UnixServerHarness;
+
+        let harness = UnixServerHarness::new("test_statsd_config_into_client_unix");
+        harness.run_quiet(|path| {
+            let mut config = StatsdConfig::new("my.prefix", SinkKind::Unix);
+            config.socket_path = Some(path.into());
+
+            let client = config.into_client().unwrap();
+            client.count("some.counter", 1).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_statsd_config_into_client_unix_no_path() {
+        let res = StatsdConfig::new("my.prefix", SinkKind::Unix).into_client();
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_config_into_client_nop() {
+        let mut config = StatsdConfig::new("my.prefix", SinkKind::Nop);
+        config.tags.insert("region".to_string(), "us-east-1".to_string());
+        config.tag_values.push("beta".to_string());
+
+        let client = config.into_client().unwrap();
+        let res = client.count("some.counter", 1);
+
+        assert_eq!(
+            "my.prefix.some.counter:1|c|#region:us-east-1,beta",
+            res.unwrap().as_metric_str()
+        );
+    }
+}
@@ -24,6 +24,11 @@
 //! These can be used to allow your own custom types to be converted to
 //! metric values that Cadence understands.
 //!
+//! The `Clock` trait is used by components whose behavior depends on the
+//! passage of time. Sinks that flush or refresh state periodically, and
+//! clients measuring elapsed time for timers, read the current time from a
+//! `Clock` so that the behavior can be tested without sleeping.
+//!
 //! In summary, most users don't need to worry about these types but they
 //! are available for advanced use cases and subject to the same guarantees
 //! as the rest of the API (semantic versioning, etc.).
@@ -33,5 +38,6 @@ pub use crate::client::{
     MetricBackend, ToCounterValue, ToDistributionValue, ToGaugeValue, ToHistogramValue, ToMeterValue, ToSetValue,
     ToTimerValue,
 };
+pub use crate::clock::{Clock, SystemClock};
 pub use crate::io::MultiLineWriter;
 pub use crate::sinks::SocketStats;
@@ -0,0 +1,312 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::builder::{MetricBuilder, MetricFormatter};
+use crate::client::StatsdClient;
+use crate::ext::MetricBackend;
+use crate::sampling;
+use crate::types::{Metric, MetricError, MetricResult};
+
+/// Builder for sending multiple metrics together, or not at all.
+///
+/// Metrics are added to a group by passing the `MetricBuilder` for each
+/// of them, after adding any tags, to `.with_metric()`. All metrics in a
+/// group share a single sampling decision: they're either all sent or none
+/// of them are, which keeps values derived from multiple metrics (such as
+/// the ratio of errors to requests) consistent even when sampled. The sample rate of the
+/// group replaces any rate set on the individual metrics and is the default
+/// sample rate of the client, if any, unless changed with `.with_sample_rate()`.
+/// Metrics are formatted when the group is sent so they always include the
+/// sample rate the group is sent with, no matter when it was set.
+///
+/// When the group is sent, every metric is passed to the underlying
+/// `MetricSink` in a single call to `.emit_many()`. If any metric in the
+/// group is invalid, none of them are sent and the first error is returned
+/// from `.try_send()` or passed to the error handler of the client by
+/// `.send()`.
+///
+/// A `MetricGroup` is created using `StatsdClient::group()`.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{StatsdClient, NopMetricSink};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+///
+/// client.group()
+///     .with_sample_rate(0.5)
+///     .with_metric(client.count_with_tags("request.count", 1).with_tag("route", "/users"))
+///     .with_metric(client.time_with_tags("request.time", 35).with_tag("route", "/users"))
+///     .send();
+/// ```
+#[must_use = "Did you forget to call .send() after adding metrics?"]
+#[derive(Debug)]
+pub struct MetricGroup<'m, 'c> {
+    client: &'c StatsdClient,
+    sample_rate: Option<f64>,
+    metrics: Vec<(MetricFormatter<'m>, &'c StatsdClient)>,
+    error: Option<MetricError>,
+}
+
+impl<'m, 'c> MetricGroup<'m, 'c> {
+    pub(crate) fn new(client: &'c StatsdClient, sample_rate: Option<f64>) -> Self {
+        MetricGroup {
+            client,
+            sample_rate,
+            metrics: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Set the rate at which every metric in this group is sampled.
+    ///
+    /// This applies to every metric in the group, including metrics added
+    /// before it was set. A sample rate of 1 always sends the metrics and
+    /// doesn't include the rate with them.
+    /// A sample rate less than 0 or greater than 1 will result in an
+    /// `ErrorKind::InvalidInput` error when the group is sent.
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = Some(rate);
+        self
+    }
+
+    /// Add a metric to this group, using the sample rate of the group.
+    ///
+    /// The metric, including any tags added to the builder, is formatted
+    /// and sent when the group is.
+    pub fn with_metric<T>(mut self, builder: MetricBuilder<'m, 'c, T>) -> Self
+    where
+        T: Metric + From<String>,
+    {
+        if self.error.is_none() {
+            match builder.into_group_parts() {
+                Ok(parts) => self.metrics.push(parts),
+                Err(e) => self.error = Some(e),
+            }
+        }
+
+        self
+    }
+
+    /// Return the number of metrics added to this group.
+    pub fn len(&self) -> usize {
+        self.metrics.len()
+    }
+
+    /// Return `true` if no metrics have been added to this group.
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Send every metric in this group, returning the first error
+    /// encountered when adding or sending them, if any.
+    ///
+    /// Note that the group is consumed by this method and thus `.try_send()`
+    /// can only be called a single time per group.
+    pub fn try_send(self) -> MetricResult<()> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let rate = self.sample_rate.unwrap_or(1.0);
+        sampling::validate_rate(rate)?;
+
+        // Every metric is formatted (and so validated) before the sampling
+        // decision, the same as a single metric, so that invalid metrics are
+        // always reported.
+        let mut metrics = Vec::with_capacity(self.metrics.len());
+        for (mut formatter, client) in self.metrics {
+            formatter.with_sample_rate(rate);
+            metrics.push(client.format(&formatter)?);
+        }
+
+        if metrics.is_empty() || !sampling::should_send(rate) {
+            return Ok(());
+        }
+
+        let metrics: Vec<&str> = metrics.iter().map(String::as_str).collect();
+        self.client.emit_many_str(&metrics)?;
+        Ok(())
+    }
+
+    /// Send every metric in this group, invoking the error handler of the
+    /// client for any error encountered when adding or sending them.
+    ///
+    /// Note that the group is consumed by this method and thus `.send()`
+    /// can only be called a single time per group.
+    pub fn send(self) {
+        let client = self.client;
+        if let Err(e) = self.try_send() {
+            client.consume_error(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::sinks::{NopMetricSink, SpyMetricSink};
+    use crate::test::ErrorMetricSink;
+    use crate::types::ErrorKind;
+    use crate::StatsdClient;
+    use crossbeam_channel::Receiver;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn received(rx: &Receiver<Vec<u8>>) -> Vec<String> {
+        rx.try_iter().map(|m| String::from_utf8(m).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_metric_group_try_send() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_tag("env", "test").build();
+
+        let group = client
+            .group()
+            .with_metric(client.count_with_tags("some.counter", 1).with_tag("route", "/"))
+            .with_metric(client.time_with_tags("some.timer", 35));
+
+        assert_eq!(2, group.len());
+        group.try_send().unwrap();
+
+        assert_eq!(
+            vec![
+                "prefix.some.counter:1|c|#env:test,route:/",
+                "prefix.some.timer:35|ms|#env:test"
+            ],
+            received(&rx)
+        );
+    }
+
+    #[test]
+    fn test_metric_group_sample_rate_shared() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+
+        // Rates on individual metrics are replaced by the rate of the group
+        // and either every metric in the group is sent or none of them are.
+        for _ in 0..64 {
+            client
+                .group()
+                .with_sample_rate(0.5)
+                .with_metric(client.count_with_tags("some.counter", 1).with_sample_rate(0.1))
+                .with_metric(client.time_with_tags("some.timer", 35))
+                .try_send()
+                .unwrap();
+        }
+
+        let metrics = received(&rx);
+        assert!(
+            !metrics.is_empty() && metrics.len() < 128,
+            "sent {} metrics",
+            metrics.len()
+        );
+        for pair in metrics.chunks(2) {
+            assert_eq!(
+                vec!["prefix.some.counter:1|c|@0.5", "prefix.some.timer:35|ms|@0.5"],
+                pair
+            );
+        }
+    }
+
+    #[test]
+    fn test_metric_group_sample_rate_set_after_metrics() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+
+        // Metrics added before the rate is set are still sent with it so the
+        // server can scale them correctly.
+        loop {
+            client
+                .group()
+                .with_metric(client.count_with_tags("some.counter", 1))
+                .with_sample_rate(0.5)
+                .with_metric(client.time_with_tags("some.timer", 35))
+                .try_send()
+                .unwrap();
+
+            let metrics = received(&rx);
+            if !metrics.is_empty() {
+                assert_eq!(
+                    vec!["prefix.some.counter:1|c|@0.5", "prefix.some.timer:35|ms|@0.5"],
+                    metrics
+                );
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_metric_group_default_sample_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_default_sample_rate(0.0)
+            .build();
+
+        client
+            .group()
+            .with_metric(client.count_with_tags("some.counter", 1))
+            .try_send()
+            .unwrap();
+        assert!(received(&rx).is_empty());
+
+        client
+            .group()
+            .with_sample_rate(1.0)
+            .with_metric(client.count_with_tags("some.counter", 1))
+            .try_send()
+            .unwrap();
+        assert_eq!(vec!["prefix.some.counter:1|c"], received(&rx));
+    }
+
+    #[test]
+    fn test_metric_group_invalid_metric() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+
+        let res = client
+            .group()
+            .with_metric(client.count_with_tags("some.counter", 1))
+            .with_metric(client.gauge_with_tags("some.gauge", f64::NAN))
+            .try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        assert!(received(&rx).is_empty());
+    }
+
+    #[test]
+    fn test_metric_group_invalid_sample_rate() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.group().with_sample_rate(1.5).try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_metric_group_send_error() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_ref = count.clone();
+
+        let client = StatsdClient::builder("prefix", ErrorMetricSink::always())
+            .with_error_handler(move |_e| {
+                count_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
+
+        client
+            .group()
+            .with_metric(client.count_with_tags("some.counter", 1))
+            .send();
+        assert_eq!(1, count.load(Ordering::Acquire));
+    }
+}
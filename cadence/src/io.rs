@@ -9,7 +9,8 @@
 // except according to those terms.
 
 use std::io;
-use std::io::{BufWriter, Write};
+use std::io::Write;
+use std::mem;
 use std::str;
 
 #[derive(Debug, Default)]
@@ -23,6 +24,12 @@ struct WriterMetrics {
 /// trailing line ending string to every input written and only
 /// writes the complete input in a single call to the underlying
 /// writer.
+///
+/// If the underlying writer only accepts part of the input in a
+/// single call (as stream based writers like a `TcpStream` may), the
+/// remainder is written with subsequent calls so that lines are never
+/// truncated. An underlying writer that accepts zero bytes is treated
+/// as an error.
 #[derive(Debug)]
 pub struct MultiLineWriter<T>
 where
@@ -30,8 +37,10 @@ where
 {
     written: usize,
     capacity: usize,
+    expected: Option<usize>,
     metrics: WriterMetrics,
-    inner: BufWriter<T>,
+    buf: Vec<u8>,
+    inner: T,
     line_ending: Vec<u8>,
 }
 
@@ -51,15 +60,62 @@ where
         MultiLineWriter {
             written: 0,
             capacity: cap,
+            expected: None,
             metrics: WriterMetrics::default(),
-            inner: BufWriter::with_capacity(cap, inner),
+            buf: Vec::with_capacity(cap),
+            inner,
             line_ending: Vec::from(end.as_bytes()),
         }
     }
 
-    #[allow(dead_code)]
-    fn get_ref(&self) -> &T {
-        self.inner.get_ref()
+    /// Set the expected size of each input written, not including the
+    /// line ending.
+    ///
+    /// When set, the buffer is flushed right after a write that leaves too
+    /// little room for another input of the expected size instead of waiting
+    /// until an input that doesn't fit is written.
+    pub fn set_expected_size(&mut self, expected: Option<usize>) {
+        self.expected = expected;
+    }
+
+    pub(crate) fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Remove and return everything currently buffered without writing
+    /// it to the underlying writer.
+    pub(crate) fn take_buffered(&mut self) -> Vec<u8> {
+        self.written = 0;
+        mem::replace(&mut self.buf, Vec::with_capacity(self.capacity))
+    }
+
+    // Write the entire buffer to the underlying writer, retrying partial
+    // writes. Anything that couldn't be written due to an error is kept in
+    // the buffer to be written by the next flush.
+    fn flush_buf(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let mut res = Ok(());
+
+        while written < self.buf.len() {
+            match self.inner.write(&self.buf[written..]) {
+                Ok(0) => {
+                    res = Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the buffered data",
+                    ));
+                    break;
+                }
+                Ok(n) => written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+
+        self.buf.drain(..written);
+        res
     }
 
     #[allow(dead_code)]
@@ -80,7 +136,7 @@ where
             self.metrics.inner_write += 1;
             // If the user has given us a value bigger than our buffer
             // to write, bypass the buffer and write directly to the Write
-            // implementation that our BufWriter is wrapping. Note that we
+            // implementation that we're wrapping. Note that we
             // don't write a trailing newline in this case. The reasoning
             // is that the newlines are separators for putting multiple
             // "things" into a single write call to the underlying impl
@@ -88,7 +144,8 @@ where
             // a newline when we're only writing a single large value to
             // the underlying impl.
             // See https://github.com/56quarters/cadence/issues/87
-            Ok(self.inner.get_mut().write(buf)?)
+            self.inner.write_all(buf)?;
+            Ok(buf.len())
         } else {
             if left < required {
                 self.flush()?;
@@ -96,13 +153,23 @@ where
 
             self.metrics.buf_write += 1;
             // Perform the buffered write of user data and the trailing
-            // newlines. Increment the number of bytes written to the
-            // buffer after each write in case they return errors.
-            let write1 = self.inner.write(buf)?;
+            // newlines.
+            self.buf.extend_from_slice(buf);
+            let write1 = buf.len();
             self.written += write1;
 
-            let write2 = self.inner.write(&self.line_ending)?;
-            self.written += write2;
+            self.buf.extend_from_slice(&self.line_ending);
+            self.written += self.line_ending.len();
+
+            if let Some(expected) = self.expected {
+                if self.capacity - self.written < expected + self.line_ending.len() {
+                    // The input has already been buffered at this point so a
+                    // failure to flush early isn't reported here. Anything that
+                    // wasn't written stays in the buffer and the error will be
+                    // returned by the next write or flush.
+                    let _ = self.flush();
+                }
+            }
 
             // We keep track of the total number of bytes written above but
             // we only return the number of bytes from the provided buffer we
@@ -114,19 +181,49 @@ where
 
     fn flush(&mut self) -> io::Result<()> {
         self.metrics.flushed += 1;
+        self.flush_buf()?;
         self.inner.flush()?;
         self.written = 0;
         Ok(())
     }
 }
 
+impl<T> Drop for MultiLineWriter<T>
+where
+    T: Write,
+{
+    /// Write anything still buffered to the underlying writer, ignoring errors.
+    fn drop(&mut self) {
+        let _ = self.flush_buf();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::MultiLineWriter;
 
-    use std::io::Write;
+    use std::io::{self, Write};
     use std::str;
 
+    // Writer that accepts at most a few bytes per call, like a stream
+    // socket under pressure might.
+    struct ShortWriter {
+        max: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.max);
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[test]
     fn test_write_needs_flush() {
         let mut buffered = MultiLineWriter::new(vec![], 16);
@@ -186,6 +283,59 @@ mod tests {
         assert_eq!(8, in_buffer_after_write2);
     }
 
+    #[test]
+    fn test_write_expected_size_flushes_early() {
+        let mut buffered = MultiLineWriter::new(vec![], 20);
+        buffered.set_expected_size(Some(7));
+
+        // Leaves 12 bytes which is enough for another 7 byte input
+        buffered.write_all(b"foo:1|c").unwrap();
+        let written_after_write1 = buffered.get_ref().len();
+
+        // Leaves 4 bytes which isn't, so the buffer is flushed right away
+        buffered.write_all(b"bar:2|c").unwrap();
+        let written_after_write2 = buffered.get_ref().len();
+
+        assert_eq!(0, written_after_write1);
+        assert_eq!(16, written_after_write2);
+        assert_eq!(0, buffered.written);
+        assert_eq!(1, buffered.get_metrics().flushed);
+    }
+
+    #[test]
+    fn test_write_expected_size_exactly_fits() {
+        let mut buffered = MultiLineWriter::new(vec![], 16);
+        buffered.set_expected_size(Some(7));
+
+        // Leaves exactly 8 bytes, enough for another input and line ending
+        buffered.write_all(b"foo:1|c").unwrap();
+
+        assert_eq!(0, buffered.get_ref().len());
+        assert_eq!(8, buffered.written);
+        assert_eq!(0, buffered.get_metrics().flushed);
+    }
+
+    #[test]
+    fn test_write_expected_size_flush_error() {
+        let inner = ShortWriter {
+            max: 0,
+            written: vec![],
+        };
+        let mut writer = MultiLineWriter::new(inner, 16);
+        writer.set_expected_size(Some(7));
+
+        // Leaves 4 bytes so an early flush is attempted and fails but the
+        // input has been buffered so the write itself succeeds.
+        assert_eq!(7, writer.write(b"foo:1|c").unwrap());
+        assert_eq!(7, writer.write(b"bar:2|c").unwrap());
+        assert_eq!(16, writer.take_buffered().len());
+        assert!(writer.get_ref().written.is_empty());
+
+        writer.write_all(b"baz:3|c").unwrap();
+        let err = writer.flush().unwrap_err();
+        assert_eq!(io::ErrorKind::WriteZero, err.kind());
+    }
+
     #[test]
     fn test_buffer_write_equal_capacity() {
         let mut buffered = MultiLineWriter::new(vec![], 8);
@@ -221,7 +371,7 @@ mod tests {
         let mut buf: Vec<u8> = vec![];
 
         // Create our writer in a different scope to ensure that the
-        // BufWriter it's using internally is flushed when it goes out
+        // buffer it's using internally is flushed when it goes out
         // of scope and anything that was buffered gets written out.
         {
             let mut writer = MultiLineWriter::new(&mut buf, 32);
@@ -232,4 +382,69 @@ mod tests {
         assert_eq!(10, buf.len());
         assert_eq!("something\n", str::from_utf8(&buf).unwrap());
     }
+
+    #[test]
+    fn test_take_buffered() {
+        let mut writer = MultiLineWriter::new(vec![], 32);
+
+        writer.write_all(b"foo:1|c").unwrap();
+        writer.write_all(b"bar:2|c").unwrap();
+        let taken = writer.take_buffered();
+
+        // Nothing taken is written and the full capacity is available again
+        writer.flush().unwrap();
+        assert_eq!("foo:1|c\nbar:2|c\n", str::from_utf8(&taken).unwrap());
+        assert_eq!(0, writer.get_ref().len());
+        assert_eq!(0, writer.written);
+        assert!(writer.take_buffered().is_empty());
+    }
+
+    #[test]
+    fn test_buffered_write_partial_writes() {
+        let inner = ShortWriter {
+            max: 3,
+            written: vec![],
+        };
+        let mut writer = MultiLineWriter::new(inner, 32);
+
+        assert_eq!(10, writer.write(b"foo:1234|c").unwrap());
+        assert_eq!(10, writer.write(b"bar:5678|c").unwrap());
+        writer.flush().unwrap();
+
+        assert_eq!(
+            "foo:1234|c\nbar:5678|c\n",
+            str::from_utf8(&writer.get_ref().written).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_bigger_than_buffer_partial_writes() {
+        let inner = ShortWriter {
+            max: 4,
+            written: vec![],
+        };
+        let mut writer = MultiLineWriter::new(inner, 16);
+
+        assert_eq!(29, writer.write(b"some_really_long_metric:456|c").unwrap());
+        assert_eq!(
+            "some_really_long_metric:456|c",
+            str::from_utf8(&writer.get_ref().written).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_zero_length_write_is_error() {
+        let inner = ShortWriter {
+            max: 0,
+            written: vec![],
+        };
+        let mut writer = MultiLineWriter::new(inner, 16);
+
+        writer.write_all(b"foo:1|c").unwrap();
+        let err = writer.flush().unwrap_err();
+        assert_eq!(io::ErrorKind::WriteZero, err.kind());
+
+        let err = writer.write(b"some_really_long_metric:456|c").unwrap_err();
+        assert_eq!(io::ErrorKind::WriteZero, err.kind());
+    }
 }
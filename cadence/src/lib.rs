@@ -143,7 +143,22 @@
 //! client.time("my.service.call", 214);
 //! ```
 //!
-//! In the example above, we use the default constructor for the queuing
+//! The same client can be created in a single call using the `production_udp`
+//! constructor. The `production_udp_builder` method can be used when the sizes
+//! of the buffer or queue need to be customized.
+//!
+//! ```rust,no_run
+//! use cadence::prelude::*;
+//! use cadence::{StatsdClient, DEFAULT_PORT};
+//!
+//! let host = ("metrics.example.com", DEFAULT_PORT);
+//! let client = StatsdClient::production_udp("my.prefix", host).unwrap();
+//!
+//! client.count("my.counter.thing", 29);
+//! client.time("my.service.call", 214);
+//! ```
+//!
+//! In the first example above, we use the default constructor for the queuing
 //! sink which creates an **unbounded** queue, with no maximum size, to connect
 //! the main thread where the client sends metrics to the background thread
 //! in which the wrapped sink is running. If instead, you want to create a
@@ -447,6 +462,11 @@
 //! client.count("some.other.counter", 1);
 //! ```
 //!
+//! If you only need to process formatted metrics yourself (for example, to
+//! batch them and send them to the HTTP API of a metrics vendor), the
+//! `ChannelMetricSink` sends each metric to a channel that you can read from
+//! instead.
+//!
 //! ### Custom UDP Socket
 //!
 //! Most users of the Cadence `StatsdClient` will be using it to send metrics
@@ -506,34 +526,131 @@
 //! client.set("users.uniques", 42);
 //! ```
 //!
+//! Like UDP, a client using a `BufferedUnixMetricSink` wrapped in a `QueuingMetricSink`
+//! can be created in a single call using the `production_unix` constructor. The
+//! `production_unix_builder` method can be used when the buffered sink, the
+//! queuing sink, or the client need to be customized.
+//!
+//! ```rust,no_run
+//! use cadence::prelude::*;
+//! use cadence::StatsdClient;
+//!
+//! let client = StatsdClient::production_unix("my.prefix", "/run/statsd.sock").unwrap();
+//! client.count("my.counter.thing", 29);
+//! ```
+//!
 //! NOTE: This feature is only available on Unix platforms (Linux, BSD, MacOS).
 //!
+//! ### Configuration Files
+//!
+//! When the optional `serde` feature is enabled, a client and the sinks it uses
+//! can be described by a `StatsdConfig` that is read from a configuration file
+//! in any format supported by `serde` (TOML, YAML, JSON, etc.).
+//!
+//! ```toml
+//! [dependencies]
+//! cadence = { version = "x.y.z", features = ["serde"] }
+//! ```
+//!
+//! See the documentation for `StatsdConfig` for the supported settings.
+//!
+//! ### HTTP Sink
+//!
+//! Some metrics vendors accept newline delimited Statsd metrics in the body of
+//! an HTTP request instead of over UDP. When the optional `http` feature is
+//! enabled, the `HttpMetricSink` can be used to send metrics to them. Since each
+//! request may take a while, it should be wrapped by a `QueuingMetricSink`.
+//! Note that the `http` feature requires Rust 1.71 or newer.
+//!
+//! ```rust,ignore
+//! use cadence::prelude::*;
+//! use cadence::{HttpMetricSink, QueuingMetricSink, StatsdClient};
+//!
+//! let sink = HttpMetricSink::builder()
+//!     .with_header("Authorization", "Bearer some-api-key")
+//!     .build("https://metrics.example.com/v1/statsd")
+//!     .unwrap();
+//! let client = StatsdClient::from_sink("my.prefix", QueuingMetricSink::from(sink));
+//!
+//! client.count("my.counter.thing", 29);
+//! ```
+//!
+//! ### Tracing Sink
+//!
+//! When the optional `tracing` feature is enabled, the `TracingMetricSink` can
+//! be used to emit each metric as a structured [tracing](https://docs.rs/tracing/)
+//! event with the fields `name`, `value`, `type`, and `tags`, instead of sending
+//! it to a Statsd server.
+//!
+//! ```toml
+//! [dependencies]
+//! cadence = { version = "x.y.z", features = ["tracing"] }
+//! ```
+//!
+//! ```rust,ignore
+//! use cadence::prelude::*;
+//! use cadence::{StatsdClient, TracingMetricSink};
+//! use tracing::Level;
+//!
+//! let client = StatsdClient::from_sink("my.prefix", TracingMetricSink::new(Level::INFO));
+//!
+//! client.count("my.counter.thing", 29);
+//! ```
+//!
+//! ### Timing Futures
+//!
+//! When the optional `async` feature is enabled, the time it takes for a future
+//! to complete can be recorded as a timer with the `.time_future()` method of
+//! the client. This doesn't depend on any particular async runtime.
+//!
+//! ```toml
+//! [dependencies]
+//! cadence = { version = "x.y.z", features = ["async"] }
+//! ```
+//!
+//! ```rust,ignore
+//! use cadence::{NopMetricSink, StatsdClient};
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//! let response = client.time_future("some.request", send_request()).await;
+//! ```
+//!
 
 #![forbid(unsafe_code)]
 
 pub const DEFAULT_PORT: u16 = 8125;
 
-pub use self::builder::MetricBuilder;
+pub use self::builder::{MetricBuilder, TypeCodeMap};
 
 pub use self::client::{
-    Counted, CountedExt, Distributed, Gauged, Histogrammed, Metered, MetricClient, Setted, StatsdClient,
-    StatsdClientBuilder, Timed,
+    Counted, CountedExt, Distributed, FlushGuard, Gauged, Histogrammed, HistogrammedExt, Metered, MetricClient, Setted,
+    StatsdClient, StatsdClientBuilder, Timed, TimedExt,
 };
 
+pub use self::group::MetricGroup;
+
 pub use self::sinks::{
-    BufferedSpyMetricSink, BufferedUdpMetricSink, MetricSink, NopMetricSink, QueuingMetricSink,
-    QueuingMetricSinkBuilder, SinkStats, SpyMetricSink, UdpMetricSink,
+    fits_in_datagram, BufferedSpyMetricSink, BufferedUdpMetricSink, BufferedUdpMetricSinkBuilder, ChannelMetricSink,
+    FileMetricSink, LatestGaugeMetricSink, MappingMetricSink, MetricSink, NamedMetricSink, NopMetricSink,
+    OverflowPolicy, QueuingMetricSink, QueuingMetricSinkBuilder, RingBufferMetricSink, SharedSink, SinkStats,
+    SpyMetricSink, TapMetricSink, TeeMetricSink, UdpMetricSink, DEFAULT_BUFFER_SIZE, MAX_UDP_PAYLOAD,
 };
 
 pub use self::types::{
-    Counter, Distribution, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, MetricResult, Set, Timer,
+    Cardinality, Counter, Distribution, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, MetricKind,
+    MetricResult, ScalingMode, Set, TagSet, Timer,
 };
 
 mod builder;
+mod cardinality;
 mod client;
+mod clock;
 pub mod ext;
+mod group;
 mod io;
 pub mod prelude;
+mod sampling;
+mod self_metrics;
 mod sinks;
 mod types;
 
@@ -544,7 +661,22 @@ pub mod test;
 
 // Sinks for sending metrics over Unix datagram sockets
 #[cfg(unix)]
-pub use crate::sinks::{BufferedUnixMetricSink, UnixMetricSink};
+pub use crate::sinks::{BufferedUnixMetricSink, BufferedUnixMetricSinkBuilder, UnixMetricSink};
+
+// Sink for sending metrics over HTTP
+#[cfg(feature = "http")]
+pub use crate::sinks::{HttpMetricSink, HttpMetricSinkBuilder, DEFAULT_HTTP_BUFFER_SIZE};
+
+// Sink for emitting metrics as tracing events
+#[cfg(feature = "tracing")]
+pub use crate::sinks::TracingMetricSink;
+
+// Building clients from configuration files
+#[cfg(feature = "serde")]
+mod config;
+
+#[cfg(feature = "serde")]
+pub use crate::config::{SinkKind, StatsdConfig};
 
 mod sealed {
     pub trait Sealed {}
@@ -0,0 +1,112 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::types::{ErrorKind, MetricError, MetricResult};
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+thread_local! {
+    // Per-thread state of a xorshift64* generator. Sampling decisions don't need
+    // to be cryptographically secure, they just need to be cheap and roughly
+    // uniform. The seed comes from the random keys std uses for hash maps so we
+    // don't need to depend on a random number generation crate.
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0x9e37_79b9_7f4a_7c15);
+    // xorshift generators must never be seeded with zero
+    hasher.finish() | 1
+}
+
+fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    })
+}
+
+/// Return a random number in the range `[0, 1)`.
+fn next_f64() -> f64 {
+    // Use the upper 53 bits since that's how many bits of precision a f64 has.
+    (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Return an `InvalidInput` error if the sample rate isn't between 0 and 1.
+pub(crate) fn validate_rate(rate: f64) -> MetricResult<()> {
+    if (0.0..=1.0).contains(&rate) {
+        Ok(())
+    } else {
+        Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "sample rate must be between 0 and 1",
+        )))
+    }
+}
+
+/// Randomly decide if a metric sampled at the given rate should be sent.
+///
+/// A rate of `1.0` always returns `true` and a rate of `0.0` always returns
+/// `false`. The rate is assumed to be valid.
+pub(crate) fn should_send(rate: f64) -> bool {
+    next_f64() < rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_f64, should_send, validate_rate};
+    use crate::ErrorKind;
+
+    #[test]
+    fn test_validate_rate_valid() {
+        assert!(validate_rate(0.0).is_ok());
+        assert!(validate_rate(0.5).is_ok());
+        assert!(validate_rate(1.0).is_ok());
+    }
+
+    #[test]
+    fn test_validate_rate_invalid() {
+        assert_eq!(ErrorKind::InvalidInput, validate_rate(-0.1).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidInput, validate_rate(1.1).unwrap_err().kind());
+        assert_eq!(ErrorKind::InvalidInput, validate_rate(f64::NAN).unwrap_err().kind());
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            validate_rate(f64::INFINITY).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn test_next_f64_range() {
+        for _ in 0..10_000 {
+            let v = next_f64();
+            assert!((0.0..1.0).contains(&v), "{} out of range", v);
+        }
+    }
+
+    #[test]
+    fn test_should_send_always_never() {
+        for _ in 0..10_000 {
+            assert!(should_send(1.0));
+            assert!(!should_send(0.0));
+        }
+    }
+
+    #[test]
+    fn test_should_send_roughly_uniform() {
+        let sent = (0..10_000).filter(|_| should_send(0.5)).count();
+        assert!((4_000..6_000).contains(&sent), "sent {} of 10000", sent);
+    }
+}
@@ -0,0 +1,198 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::client::{Counted, Gauged, StatsdClient};
+use crate::sinks::QueueStats;
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Cumulative values reported by a sink at a point in time, used to compute
+/// the counters emitted for each interval.
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    bytes_sent: u64,
+    packets_sent: u64,
+    bytes_dropped: u64,
+    packets_dropped: u64,
+    panics: u64,
+}
+
+/// Thread that periodically emits statistics about the sink of a client
+/// using the same sink.
+///
+/// The thread is stopped and joined when the reporter is dropped, which
+/// happens when the last clone of the client that owns it is dropped.
+pub(crate) struct SelfMetricsReporter {
+    // Both wrapped in a mutex so that the reporter is Sync and RefUnwindSafe
+    // like the rest of the client. They're only accessed when dropped.
+    stop: Mutex<Option<Sender<()>>>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SelfMetricsReporter {
+    /// Start reporting statistics every `interval` using `client`, which
+    /// should be configured with the prefix for self-metrics.
+    pub(crate) fn spawn(client: StatsdClient, interval: Duration, queue: Option<QueueStats>) -> Self {
+        let (tx, rx) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            let mut previous = Totals::default();
+            // Nothing is ever sent to the channel, the sender being dropped
+            // is the signal to stop.
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                previous = report(&client, queue.as_ref(), &previous);
+            }
+        });
+
+        SelfMetricsReporter {
+            stop: Mutex::new(Some(tx)),
+            handle: Mutex::new(Some(handle)),
+        }
+    }
+}
+
+fn report(client: &StatsdClient, queue: Option<&QueueStats>, previous: &Totals) -> Totals {
+    let stats = client.sink_stats();
+    let current = Totals {
+        bytes_sent: stats.bytes_sent,
+        packets_sent: stats.packets_sent,
+        bytes_dropped: stats.bytes_dropped,
+        packets_dropped: stats.packets_dropped,
+        panics: queue.map_or(0, QueueStats::panics),
+    };
+
+    client
+        .count_with_tags(
+            "sink.bytes_sent",
+            current.bytes_sent.saturating_sub(previous.bytes_sent),
+        )
+        .send();
+    client
+        .count_with_tags(
+            "sink.packets_sent",
+            current.packets_sent.saturating_sub(previous.packets_sent),
+        )
+        .send();
+    client
+        .count_with_tags(
+            "sink.bytes_dropped",
+            current.bytes_dropped.saturating_sub(previous.bytes_dropped),
+        )
+        .send();
+    client
+        .count_with_tags(
+            "sink.packets_dropped",
+            current.packets_dropped.saturating_sub(previous.packets_dropped),
+        )
+        .send();
+
+    if let Some(queue) = queue {
+        client.gauge_with_tags("queue.depth", queue.queued()).send();
+        client
+            .count_with_tags("queue.panics", current.panics.saturating_sub(previous.panics))
+            .send();
+    }
+
+    current
+}
+
+impl Drop for SelfMetricsReporter {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up immediately instead of after
+        // the rest of the current interval.
+        self.stop.get_mut().unwrap_or_else(|e| e.into_inner()).take();
+
+        if let Some(handle) = self.handle.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl fmt::Debug for SelfMetricsReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SelfMetricsReporter {{ ... }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{Counted, StatsdClient};
+    use crate::sinks::{QueuingMetricSinkBuilder, SpyMetricSink};
+    use crossbeam_channel::{Receiver, TryRecvError};
+    use std::time::Duration;
+
+    // Receive metrics until one with the given name is found, returning it.
+    fn recv_named(rx: &Receiver<Vec<u8>>, name: &str) -> String {
+        loop {
+            let metric = String::from_utf8(rx.recv_timeout(Duration::from_secs(5)).unwrap()).unwrap();
+            if metric.starts_with(name) {
+                return metric;
+            }
+        }
+    }
+
+    #[test]
+    fn test_self_metrics_emitted() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("my.prefix", sink)
+            .with_tag("env", "test")
+            .with_self_metrics("cadence", Duration::from_millis(10))
+            .build();
+
+        client.count("some.counter", 1).unwrap();
+
+        assert_eq!("my.prefix.some.counter:1|c|#env:test", recv_named(&rx, "my.prefix."));
+        assert_eq!(
+            "cadence.sink.bytes_sent:0|c|#env:test",
+            recv_named(&rx, "cadence.sink.bytes_sent")
+        );
+        assert_eq!(
+            "cadence.sink.packets_dropped:0|c|#env:test",
+            recv_named(&rx, "cadence.sink.packets_dropped")
+        );
+    }
+
+    #[test]
+    fn test_self_metrics_queue_emitted() {
+        let (rx, sink) = SpyMetricSink::new();
+        let _client = StatsdClient::queuing_builder("my.prefix", sink, QueuingMetricSinkBuilder::new())
+            .with_self_metrics("cadence", Duration::from_millis(10))
+            .build();
+
+        assert!(recv_named(&rx, "cadence.queue.depth:").ends_with("|g"));
+        assert_eq!("cadence.queue.panics:0|c", recv_named(&rx, "cadence.queue.panics"));
+    }
+
+    #[test]
+    fn test_self_metrics_stopped_with_client() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("my.prefix", sink)
+            .with_self_metrics("cadence", Duration::from_millis(10))
+            .build();
+        let clone = client.clone();
+
+        recv_named(&rx, "cadence.sink.bytes_sent");
+        drop(client);
+        clone.count("some.counter", 1).unwrap();
+        drop(clone);
+
+        // The reporter thread is joined when the last client is dropped, which
+        // drops the last reference to the sink and disconnects the channel.
+        loop {
+            match rx.try_recv() {
+                Ok(_) => continue,
+                Err(TryRecvError::Disconnected) => break,
+                Err(TryRecvError::Empty) => panic!("self-metrics still running after client dropped"),
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::MetricSink;
+use std::any::Any;
+use std::io::{self, ErrorKind};
+use std::sync::{mpsc, Mutex};
+
+/// The sending half of one of the supported kinds of channels.
+#[derive(Debug)]
+enum ChannelSender {
+    Crossbeam(crossbeam_channel::Sender<String>),
+    // std::sync::mpsc::Sender isn't Sync before Rust 1.72 but metric sinks
+    // used by a client must be. Wrap it in a mutex so that it can be shared.
+    Std(Mutex<mpsc::Sender<String>>),
+    StdSync(mpsc::SyncSender<String>),
+}
+
+/// `MetricSink` implementation that sends all metrics to a channel.
+///
+/// This sink is an extension point for sending metrics somewhere other than
+/// a Statsd server. Each metric is sent to the channel as a `String` (without
+/// a trailing newline) where it can be read from the receiving half of the
+/// channel to be batched, transformed, or shipped elsewhere (e.g. to the HTTP
+/// API of a metrics vendor).
+///
+/// The sending half of a `crossbeam_channel` channel or a `std::sync::mpsc`
+/// channel can be used. Metrics are sent without blocking: if the channel
+/// is bounded and full, an error is returned from `.emit()` and the metric
+/// is discarded. An error is also returned when the receiving half of the
+/// channel has been dropped.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::mpsc;
+/// use cadence::prelude::*;
+/// use cadence::{ChannelMetricSink, StatsdClient};
+///
+/// let (tx, rx) = mpsc::channel();
+/// let client = StatsdClient::from_sink("my.prefix", ChannelMetricSink::from(tx));
+///
+/// client.count("some.counter", 1).unwrap();
+/// assert_eq!("my.prefix.some.counter:1|c", rx.recv().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct ChannelMetricSink {
+    sender: ChannelSender,
+}
+
+impl From<crossbeam_channel::Sender<String>> for ChannelMetricSink {
+    fn from(sender: crossbeam_channel::Sender<String>) -> Self {
+        ChannelMetricSink {
+            sender: ChannelSender::Crossbeam(sender),
+        }
+    }
+}
+
+impl From<mpsc::Sender<String>> for ChannelMetricSink {
+    fn from(sender: mpsc::Sender<String>) -> Self {
+        ChannelMetricSink {
+            sender: ChannelSender::Std(Mutex::new(sender)),
+        }
+    }
+}
+
+impl From<mpsc::SyncSender<String>> for ChannelMetricSink {
+    fn from(sender: mpsc::SyncSender<String>) -> Self {
+        ChannelMetricSink {
+            sender: ChannelSender::StdSync(sender),
+        }
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(ErrorKind::Other, "channel disconnected")
+}
+
+fn full() -> io::Error {
+    io::Error::new(ErrorKind::Other, "channel full")
+}
+
+impl MetricSink for ChannelMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let res = match self.sender {
+            ChannelSender::Crossbeam(ref sender) => sender.try_send(metric.to_string()).map_err(|e| match e {
+                crossbeam_channel::TrySendError::Full(_) => full(),
+                crossbeam_channel::TrySendError::Disconnected(_) => disconnected(),
+            }),
+            // The lock is never held while running code that can panic so it
+            // can't be poisoned.
+            ChannelSender::Std(ref sender) => sender
+                .lock()
+                .unwrap()
+                .send(metric.to_string())
+                .map_err(|_| disconnected()),
+            ChannelSender::StdSync(ref sender) => sender.try_send(metric.to_string()).map_err(|e| match e {
+                mpsc::TrySendError::Full(_) => full(),
+                mpsc::TrySendError::Disconnected(_) => disconnected(),
+            }),
+        };
+
+        res.map(|_| metric.len())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelMetricSink;
+    use crate::sinks::core::MetricSink;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_channel_metric_sink_crossbeam() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let sink = ChannelMetricSink::from(tx);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!("foo:54|c", rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_channel_metric_sink_crossbeam_full() {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let sink = ChannelMetricSink::from(tx);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert!(sink.emit("foo:67|c").is_err());
+        assert_eq!("foo:54|c", rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_channel_metric_sink_crossbeam_disconnected() {
+        let (tx, rx) = crossbeam_channel::unbounded::<String>();
+        let sink = ChannelMetricSink::from(tx);
+        drop(rx);
+
+        assert!(sink.emit("foo:54|c").is_err());
+    }
+
+    #[test]
+    fn test_channel_metric_sink_std() {
+        let (tx, rx) = mpsc::channel();
+        let sink = ChannelMetricSink::from(tx);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!("foo:54|c", rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_channel_metric_sink_std_disconnected() {
+        let (tx, rx) = mpsc::channel::<String>();
+        let sink = ChannelMetricSink::from(tx);
+        drop(rx);
+
+        assert!(sink.emit("foo:54|c").is_err());
+    }
+
+    #[test]
+    fn test_channel_metric_sink_std_sync_full() {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let sink = ChannelMetricSink::from(tx);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert!(sink.emit("foo:67|c").is_err());
+        assert_eq!("foo:54|c", rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_channel_metric_sink_std_sync_disconnected() {
+        let (tx, rx) = mpsc::sync_channel::<String>(1);
+        let sink = ChannelMetricSink::from(tx);
+        drop(rx);
+
+        assert!(sink.emit("foo:54|c").is_err());
+    }
+}
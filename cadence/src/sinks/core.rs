@@ -8,9 +8,13 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::sinks::named::NamedMetricSink;
+use crate::sinks::util::sum_or_first_error;
+use std::any::Any;
 use std::io;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// I/O telemetry for a `MetricSink` implementation.
 #[derive(Clone, Debug, Default)]
@@ -162,6 +166,17 @@ pub trait MetricSink {
     /// interpret this as an error.
     fn emit(&self, metric: &str) -> io::Result<usize>;
 
+    /// Send multiple Statsd metrics using this sink and return the total number
+    /// of bytes written or the first I/O error encountered.
+    ///
+    /// Every metric is sent even if sending an earlier one fails. The default
+    /// implementation calls `.emit()` for each metric. Sinks that can send
+    /// multiple metrics more efficiently than one at a time (for example, by
+    /// only acquiring a lock once) should override this method.
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        sum_or_first_error(metrics.iter().map(|m| self.emit(m)))
+    }
+
     /// Flush any currently buffered metrics to the underlying backend, returning
     /// an I/O error if they could not be written for some reason.
     ///
@@ -171,6 +186,30 @@ pub trait MetricSink {
         Ok(())
     }
 
+    /// Flush any currently buffered metrics to the underlying backend, giving
+    /// up and returning an I/O error if they can't be written within (roughly)
+    /// the given timeout.
+    ///
+    /// This is meant for callers that must not block forever when the backend
+    /// has stalled, such as during graceful shutdown. The default implementation
+    /// calls `.flush()` and ignores the timeout. Sinks that are able to honor a
+    /// deadline should override this method.
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        let _ = timeout;
+        self.flush()
+    }
+
+    /// Return `true` if this sink is able to accept more metrics without
+    /// blocking or dropping them, `false` if it's under backpressure.
+    ///
+    /// This is an advisory signal meant to allow callers to skip computing
+    /// expensive metrics when they're likely to be dropped. It's not a guarantee
+    /// that the next metric emitted will (or won't) be accepted. The default
+    /// implementation always returns `true`.
+    fn is_ready(&self) -> bool {
+        true
+    }
+
     /// Return I/O telemetry like bytes / packets sent or dropped.
     ///
     /// Note that not all sinks implement this method and the default implementation
@@ -178,6 +217,61 @@ pub trait MetricSink {
     fn stats(&self) -> SinkStats {
         SinkStats::default()
     }
+
+    /// Return the name of this sink, `None` if it doesn't have one.
+    ///
+    /// Sinks that send metrics using other sinks include the name of the
+    /// wrapped sink in errors returned by it, to make it clear which one
+    /// failed. Names can be given to any sink with `.with_name()`. The default
+    /// implementation returns `None`.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Give this sink a name, returning a `NamedMetricSink` that wraps it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{MetricSink, NopMetricSink};
+    ///
+    /// let sink = NopMetricSink.with_name("nop");
+    /// assert_eq!(Some("nop"), sink.name());
+    /// ```
+    fn with_name(self, name: &str) -> NamedMetricSink<Self>
+    where
+        Self: Sized,
+    {
+        NamedMetricSink::new(name, self)
+    }
+
+    /// Return this sink as `Any` so that callers can downcast it to its
+    /// concrete type, `None` if the sink doesn't support it.
+    ///
+    /// This allows reading statistics specific to a type of sink, such as
+    /// `QueuingMetricSink::queued()`, from code that only has the sink as a
+    /// trait object (for example, using `MetricBackend::sink()` of a client).
+    /// All sinks provided by Cadence support it. Other sinks can by returning
+    /// `Some(self)`. The default implementation returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::ext::MetricBackend;
+    /// use cadence::{QueuingMetricSink, NopMetricSink, StatsdClient};
+    ///
+    /// let client = StatsdClient::from_sink("my.prefix", QueuingMetricSink::from(NopMetricSink));
+    ///
+    /// let sink = client.sink();
+    /// let queuing = sink
+    ///     .as_any()
+    ///     .and_then(|s| s.downcast_ref::<QueuingMetricSink>())
+    ///     .unwrap();
+    /// println!("metrics queued: {}", queuing.queued());
+    /// ```
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
 }
 
 /// Implementation of a `MetricSink` that discards all metrics.
@@ -190,14 +284,47 @@ impl MetricSink for NopMetricSink {
     fn emit(&self, _metric: &str) -> io::Result<usize> {
         Ok(0)
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{MetricSink, NopMetricSink};
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
     #[test]
     fn test_nop_metric_sink() {
         let sink = NopMetricSink;
         assert_eq!(0, sink.emit("baz:4|c").unwrap());
     }
+
+    #[test]
+    fn test_metric_sink_default_flush_with_timeout() {
+        struct FlushCountingSink {
+            flushes: AtomicUsize,
+        }
+
+        impl MetricSink for FlushCountingSink {
+            fn emit(&self, _metric: &str) -> io::Result<usize> {
+                Ok(0)
+            }
+
+            fn flush(&self) -> io::Result<()> {
+                self.flushes.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        let sink = FlushCountingSink {
+            flushes: AtomicUsize::new(0),
+        };
+
+        sink.flush_with_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!(1, sink.flushes.load(Ordering::Relaxed));
+    }
 }
@@ -0,0 +1,284 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+use crate::types::MetricResult;
+use std::any::Any;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Identity of a file used to detect when the path of the sink refers to a
+/// different file than the one currently open (i.e. it was rotated or deleted).
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Path that a file is renamed to when it's rotated, the original path with
+/// a `.1` suffix.
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_os_string();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// File currently being written to along with its size and identity.
+#[derive(Debug)]
+struct OpenFile {
+    file: File,
+    size: u64,
+    id: Option<(u64, u64)>,
+}
+
+impl OpenFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let meta = file.metadata()?;
+        Ok(OpenFile {
+            file,
+            size: meta.len(),
+            id: file_id(&meta),
+        })
+    }
+
+    // Has the file at the path been replaced or removed since it was opened?
+    fn is_replaced(&self, path: &Path) -> bool {
+        match fs::metadata(path) {
+            Ok(meta) => file_id(&meta) != self.id,
+            Err(_) => true,
+        }
+    }
+}
+
+/// Implementation of a `MetricSink` that appends metrics to a file, one per line.
+///
+/// This is useful for disconnected environments or batch ingestion, where a
+/// separate agent tails the file and forwards metrics to a server. Each metric
+/// is written to the file with a trailing newline as soon as it's emitted.
+/// Metrics aren't buffered so lines are never split between writes, even when
+/// the sink is shared between threads.
+///
+/// The file can optionally be rotated when it reaches a maximum size. When the
+/// next metric would make the file larger than the maximum size, the file is
+/// renamed with a `.1` suffix (e.g. `metrics.log` becomes `metrics.log.1`),
+/// replacing any previously rotated file, and a new file is created at the
+/// original path. If the file is rotated or deleted by something else, such as
+/// `logrotate`, the sink reopens the path before writing the next metric. This
+/// is detected by comparing the device and inode of the file on Unix platforms,
+/// and by checking that the path exists on other platforms.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{FileMetricSink, StatsdClient};
+///
+/// // Rotate the file when it reaches 10MB
+/// let sink = FileMetricSink::with_max_size("/var/log/metrics.log", 10 * 1024 * 1024).unwrap();
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+/// ```
+#[derive(Debug)]
+pub struct FileMetricSink {
+    path: PathBuf,
+    max_size: Option<u64>,
+    file: Mutex<OpenFile>,
+    stats: SocketStats,
+}
+
+impl FileMetricSink {
+    /// Construct a new `FileMetricSink` that appends metrics to the file at
+    /// the given path, creating it if it doesn't exist. The file is never
+    /// rotated by the sink.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if the file can't be opened for writing.
+    pub fn from<P>(path: P) -> MetricResult<FileMetricSink>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(path.as_ref(), None)
+    }
+
+    /// Construct a new `FileMetricSink` that appends metrics to the file at
+    /// the given path, creating it if it doesn't exist, and rotates it before
+    /// it grows larger than `max_size` bytes.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if the file can't be opened for writing.
+    pub fn with_max_size<P>(path: P, max_size: u64) -> MetricResult<FileMetricSink>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(path.as_ref(), Some(max_size))
+    }
+
+    fn new(path: &Path, max_size: Option<u64>) -> MetricResult<FileMetricSink> {
+        let file = OpenFile::open(path)?;
+        Ok(FileMetricSink {
+            path: path.to_path_buf(),
+            max_size,
+            file: Mutex::new(file),
+            stats: SocketStats::default(),
+        })
+    }
+
+    fn write_line(&self, line: &[u8]) -> io::Result<usize> {
+        let mut current = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if current.is_replaced(&self.path) {
+            *current = OpenFile::open(&self.path)?;
+        }
+
+        if let Some(max_size) = self.max_size {
+            // Files that are empty are written to regardless of the size of the
+            // metric since rotating them wouldn't make any room.
+            if current.size > 0 && current.size + line.len() as u64 > max_size {
+                fs::rename(&self.path, rotated_path(&self.path))?;
+                *current = OpenFile::open(&self.path)?;
+            }
+        }
+
+        current.file.write_all(line)?;
+        current.size += line.len() as u64;
+        Ok(line.len())
+    }
+}
+
+impl MetricSink for FileMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut line = Vec::with_capacity(metric.len() + 1);
+        line.extend_from_slice(metric.as_bytes());
+        line.push(b'\n');
+
+        self.stats.update(self.write_line(&line), line.len())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner()).file.flush()
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rotated_path, FileMetricSink};
+    use crate::sinks::core::MetricSink;
+    use crate::test::TempDir;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_rotated_path() {
+        assert_eq!(Path::new("/tmp/foo.log.1"), rotated_path(Path::new("/tmp/foo.log")));
+    }
+
+    #[test]
+    fn test_file_metric_sink_emit() {
+        let temp = TempDir::new("cadence-file-sink-emit").unwrap();
+        let path = temp.new_path("metrics.log");
+        let sink = FileMetricSink::from(&path).unwrap();
+
+        assert_eq!(9, sink.emit("foo:54|c").unwrap());
+        assert_eq!(9, sink.emit("bar:12|g").unwrap());
+
+        assert_eq!("foo:54|c\nbar:12|g\n", fs::read_to_string(&path).unwrap());
+        assert_eq!(2, sink.stats().packets_sent);
+        assert_eq!(18, sink.stats().bytes_sent);
+    }
+
+    #[test]
+    fn test_file_metric_sink_appends() {
+        let temp = TempDir::new("cadence-file-sink-appends").unwrap();
+        let path = temp.new_path("metrics.log");
+        fs::write(&path, "foo:1|c\n").unwrap();
+
+        let sink = FileMetricSink::from(&path).unwrap();
+        sink.emit("foo:2|c").unwrap();
+
+        assert_eq!("foo:1|c\nfoo:2|c\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn test_file_metric_sink_rotation() {
+        let temp = TempDir::new("cadence-file-sink-rotation").unwrap();
+        let path = temp.new_path("metrics.log");
+        let rotated = temp.new_path("metrics.log.1");
+        let sink = FileMetricSink::with_max_size(&path, 20).unwrap();
+
+        sink.emit("foo:1|c").unwrap();
+        sink.emit("foo:2|c").unwrap();
+        assert!(!rotated.exists());
+
+        sink.emit("foo:3|c").unwrap();
+        assert_eq!("foo:1|c\nfoo:2|c\n", fs::read_to_string(&rotated).unwrap());
+        assert_eq!("foo:3|c\n", fs::read_to_string(&path).unwrap());
+
+        sink.emit("foo:4|c").unwrap();
+        sink.emit("foo:5|c").unwrap();
+        assert_eq!("foo:3|c\nfoo:4|c\n", fs::read_to_string(&rotated).unwrap());
+        assert_eq!("foo:5|c\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn test_file_metric_sink_larger_than_max_size() {
+        let temp = TempDir::new("cadence-file-sink-larger").unwrap();
+        let path = temp.new_path("metrics.log");
+        let sink = FileMetricSink::with_max_size(&path, 4).unwrap();
+
+        sink.emit("foo:1|c").unwrap();
+        sink.emit("foo:2|c").unwrap();
+
+        assert_eq!("foo:1|c\n", fs::read_to_string(temp.new_path("metrics.log.1")).unwrap());
+        assert_eq!("foo:2|c\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn test_file_metric_sink_reopens_rotated_file() {
+        let temp = TempDir::new("cadence-file-sink-reopen").unwrap();
+        let path = temp.new_path("metrics.log");
+        let moved = temp.new_path("metrics.log.old");
+        let sink = FileMetricSink::from(&path).unwrap();
+
+        sink.emit("foo:1|c").unwrap();
+        fs::rename(&path, &moved).unwrap();
+        sink.emit("foo:2|c").unwrap();
+
+        assert_eq!("foo:1|c\n", fs::read_to_string(&moved).unwrap());
+        assert_eq!("foo:2|c\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn test_file_metric_sink_reopens_deleted_file() {
+        let temp = TempDir::new("cadence-file-sink-deleted").unwrap();
+        let path = temp.new_path("metrics.log");
+        let sink = FileMetricSink::from(&path).unwrap();
+
+        sink.emit("foo:1|c").unwrap();
+        fs::remove_file(&path).unwrap();
+        sink.emit("foo:2|c").unwrap();
+
+        assert_eq!("foo:2|c\n", fs::read_to_string(&path).unwrap());
+    }
+}
@@ -0,0 +1,504 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::clock::{Clock, SystemClock};
+use crate::io::MultiLineWriter;
+use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+use crate::sinks::util::sum_or_first_error;
+use crate::types::{ErrorKind, MetricError, MetricResult};
+use std::any::Any;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default size, in bytes, of the buffer used by `HttpMetricSink`.
+///
+/// This is much larger than the buffer used for UDP sinks since an HTTP request
+/// isn't limited by the size of a single packet and each request has a fair
+/// amount of overhead.
+pub const DEFAULT_HTTP_BUFFER_SIZE: usize = 64 * 1024;
+
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Builder for creating and customizing an `HttpMetricSink`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cadence::{HttpMetricSink, QueuingMetricSink, StatsdClient};
+///
+/// let sink = HttpMetricSink::builder()
+///     .with_header("Authorization", "Bearer some-api-key")
+///     .with_flush_interval(Duration::from_secs(5))
+///     .build("https://metrics.example.com/v1/statsd")
+///     .unwrap();
+///
+/// // Wrap the sink so that HTTP requests are made in a separate thread
+/// let queuing = QueuingMetricSink::from(sink);
+/// let client = StatsdClient::from_sink("my.prefix", queuing);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpMetricSinkBuilder {
+    headers: Vec<(String, String)>,
+    capacity: usize,
+    flush_interval: Duration,
+    timeout: Duration,
+}
+
+impl HttpMetricSinkBuilder {
+    /// Construct a new builder with default settings.
+    pub fn new() -> Self {
+        HttpMetricSinkBuilder {
+            headers: Vec::new(),
+            capacity: DEFAULT_HTTP_BUFFER_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Add a header to every request made by the sink, such as an
+    /// `Authorization` header with credentials for the metrics vendor.
+    pub fn with_header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        K: ToString,
+        V: ToString,
+    {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the size of the buffer in bytes. Metrics are sent when the buffer
+    /// doesn't have room for the next metric. Defaults to `DEFAULT_HTTP_BUFFER_SIZE`.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the maximum amount of time metrics are buffered before they are
+    /// sent. Defaults to ten seconds.
+    ///
+    /// Note that the interval is only checked when metrics are emitted (there
+    /// is no background thread sending them) so buffered metrics may be kept
+    /// longer when metrics aren't emitted frequently.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Set the timeout for each HTTP request. Defaults to five seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Construct a new `HttpMetricSink` that sends metrics to the given URL.
+    ///
+    /// # Failures
+    ///
+    /// This method will fail if the URL doesn't use the `http` or `https` scheme.
+    pub fn build(self, url: &str) -> MetricResult<HttpMetricSink> {
+        self.build_with_clock(url, SystemClock)
+    }
+
+    fn build_with_clock<C>(self, url: &str, clock: C) -> MetricResult<HttpMetricSink>
+    where
+        C: Clock + 'static,
+    {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(MetricError::from((
+                ErrorKind::InvalidInput,
+                "URL must use http or https",
+            )));
+        }
+
+        let stats = SocketStats::default();
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+        let adapter = HttpWriteAdapter {
+            agent,
+            url: url.to_string(),
+            headers: self.headers,
+            stats: stats.clone(),
+        };
+
+        let last_flush = clock.now();
+        Ok(HttpMetricSink {
+            buffer: Mutex::new(HttpBuffer {
+                writer: MultiLineWriter::new(adapter, self.capacity),
+                capacity: self.capacity,
+                last_flush,
+            }),
+            flush_interval: self.flush_interval,
+            clock: Box::new(clock),
+            stats,
+        })
+    }
+}
+
+impl Default for HttpMetricSinkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adapter for sending the contents of a buffer in the body of an HTTP
+/// request via the `Write` trait.
+struct HttpWriteAdapter {
+    agent: ureq::Agent,
+    url: String,
+    headers: Vec<(String, String)>,
+    stats: SocketStats,
+}
+
+impl HttpWriteAdapter {
+    fn post(&self, buf: &[u8]) -> io::Result<usize> {
+        let mut req = self.agent.post(&self.url).set("Content-Type", "text/plain");
+        for (name, value) in self.headers.iter() {
+            req = req.set(name, value);
+        }
+
+        req.send_bytes(buf)
+            .map(|_| buf.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+impl Write for HttpWriteAdapter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stats.update(self.post(buf), buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HttpWriteAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Headers aren't included since they probably contain credentials
+        write!(f, "HttpWriteAdapter {{ url: {:?}, ... }}", self.url)
+    }
+}
+
+#[derive(Debug)]
+struct HttpBuffer {
+    writer: MultiLineWriter<HttpWriteAdapter>,
+    capacity: usize,
+    last_flush: Instant,
+}
+
+impl HttpBuffer {
+    fn write(&mut self, metric: &str) -> io::Result<usize> {
+        // Metrics too large for the buffer are sent in their own request
+        // without touching anything already buffered. Otherwise, an error
+        // means sending the full buffer to make room for the metric failed.
+        if metric.len() + 1 > self.capacity {
+            return self.writer.write(metric.as_bytes());
+        }
+
+        self.writer.write(metric.as_bytes()).map_err(|e| {
+            self.discard();
+            e
+        })
+    }
+
+    fn flush(&mut self, now: Instant) -> io::Result<()> {
+        self.last_flush = now;
+        self.writer.flush().map_err(|e| {
+            self.discard();
+            e
+        })
+    }
+
+    // The writer keeps anything it couldn't send to be retried by the next
+    // flush. Failed requests are discarded instead so that a request the
+    // server rejects isn't retried forever, blocking any new metrics.
+    fn discard(&mut self) {
+        let _ = self.writer.take_buffered();
+    }
+}
+
+/// Implementation of a `MetricSink` that buffers metrics and sends them
+/// in the body of an HTTP `POST` request.
+///
+/// Some metrics vendors accept newline delimited Statsd metrics over HTTP
+/// instead of (or in addition to) UDP. This sink allows Cadence to be used
+/// with them. Metrics are buffered, separated by newlines, and sent when the
+/// buffer is full, when a metric is emitted after the flush interval has
+/// passed since metrics were last sent, when `.flush()` is called, or when
+/// the sink is dropped. Each request uses the `Content-Type: text/plain`
+/// header along with any headers given to the builder.
+///
+/// Requests are made in the thread that emits metrics and may take a while
+/// so this sink should almost always be wrapped by a `QueuingMetricSink`. If
+/// a request fails (including when the server responds with an error status
+/// code), the metrics in the request are discarded, not retried. The error is
+/// returned from `.flush()` or, when the request was made to make room for a
+/// metric or because the flush interval passed, from the call emitting that
+/// metric. In that case the metric being emitted is not buffered either. A
+/// metric is only buffered to be sent later when emitting it succeeds.
+///
+/// This sink is only available when the `http` feature is enabled. Note that
+/// this feature requires a newer version of Rust than the rest of Cadence.
+#[derive(Debug)]
+pub struct HttpMetricSink {
+    buffer: Mutex<HttpBuffer>,
+    flush_interval: Duration,
+    clock: Box<dyn Clock>,
+    stats: SocketStats,
+}
+
+impl HttpMetricSink {
+    /// Construct a new builder for `HttpMetricSink`.
+    pub fn builder() -> HttpMetricSinkBuilder {
+        HttpMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `HttpMetricSink` that sends metrics to the given URL
+    /// using default settings.
+    ///
+    /// # Failures
+    ///
+    /// This method will fail if the URL doesn't use the `http` or `https` scheme.
+    pub fn from(url: &str) -> MetricResult<HttpMetricSink> {
+        Self::builder().build(url)
+    }
+
+    fn write_metric(&self, buffer: &mut HttpBuffer, metric: &str) -> io::Result<usize> {
+        // Send anything buffered for longer than the flush interval before
+        // buffering this metric so that an error from sending them is never
+        // returned for a metric that has been buffered.
+        let now = self.clock.now();
+        if now.saturating_duration_since(buffer.last_flush) >= self.flush_interval {
+            buffer.flush(now)?;
+        }
+
+        buffer.write(metric)
+    }
+}
+
+impl MetricSink for HttpMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.write_metric(&mut buffer, metric)
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut buffer = self.buffer.lock().unwrap();
+        sum_or_first_error(metrics.iter().map(|m| self.write_metric(&mut buffer, m)))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.flush(self.clock.now())
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpMetricSink;
+    use crate::clock::MockClock;
+    use crate::sinks::core::MetricSink;
+    use crate::types::ErrorKind;
+    use crossbeam_channel::Receiver;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Request received by the test HTTP server
+    struct Request {
+        headers: Vec<String>,
+        body: String,
+    }
+
+    /// Start a minimal HTTP server that responds to each request with the given
+    /// status and return its URL and a channel of the requests it received.
+    fn run_server(status: &'static str) -> (String, Receiver<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/statsd", listener.local_addr().unwrap());
+        let (tx, rx) = crossbeam_channel::unbounded();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                loop {
+                    let mut headers = Vec::new();
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                        headers.push(line.trim_end().to_string());
+                        line.clear();
+                    }
+
+                    if headers.is_empty() {
+                        break;
+                    }
+
+                    let len = headers
+                        .iter()
+                        .find_map(|h| h.to_lowercase().strip_prefix("content-length: ").map(|v| v.to_string()))
+                        .map_or(0, |v| v.parse().unwrap());
+                    let mut body = vec![0; len];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+                    stream.write_all(response.as_bytes()).unwrap();
+                    tx.send(Request {
+                        headers,
+                        body: String::from_utf8(body).unwrap(),
+                    })
+                    .unwrap();
+                }
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[test]
+    fn test_http_metric_sink_invalid_url() {
+        let res = HttpMetricSink::from("udp://metrics.example.com");
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_http_metric_sink_flush() {
+        let (url, rx) = run_server("200 OK");
+        let sink = HttpMetricSink::builder()
+            .with_header("Authorization", "Bearer abc123")
+            .build(&url)
+            .unwrap();
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(8, sink.emit("foo:67|c").unwrap());
+        assert!(rx.try_recv().is_err());
+        sink.flush().unwrap();
+
+        let req = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!("POST /statsd HTTP/1.1", req.headers[0]);
+        assert!(req.headers.iter().any(|h| h == "Authorization: Bearer abc123"));
+        assert!(req.headers.iter().any(|h| h == "Content-Type: text/plain"));
+        assert_eq!("foo:54|c\nfoo:67|c\n", req.body);
+        assert_eq!(1, sink.stats().packets_sent);
+    }
+
+    #[test]
+    fn test_http_metric_sink_buffer_full() {
+        let (url, rx) = run_server("200 OK");
+        let sink = HttpMetricSink::builder().with_capacity(16).build(&url).unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        sink.emit("foo:67|c").unwrap();
+
+        let req = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!("foo:54|c\n", req.body);
+    }
+
+    #[test]
+    fn test_http_metric_sink_flush_interval() {
+        let (url, rx) = run_server("200 OK");
+        let clock = MockClock::new();
+        let sink = HttpMetricSink::builder()
+            .with_flush_interval(Duration::from_secs(10))
+            .build_with_clock(&url, clock.clone())
+            .unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        assert!(rx.try_recv().is_err());
+
+        clock.advance(Duration::from_secs(10));
+        sink.emit("foo:67|c").unwrap();
+
+        let req = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!("foo:54|c\n", req.body);
+
+        sink.flush().unwrap();
+        let req = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!("foo:67|c\n", req.body);
+    }
+
+    #[test]
+    fn test_http_metric_sink_emit_many() {
+        let (url, rx) = run_server("200 OK");
+        let sink = HttpMetricSink::from(&url).unwrap();
+
+        assert_eq!(16, sink.emit_many(&["foo:54|c", "foo:67|c"]).unwrap());
+        sink.flush().unwrap();
+
+        let req = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!("foo:54|c\nfoo:67|c\n", req.body);
+    }
+
+    #[test]
+    fn test_http_metric_sink_error_status() {
+        let (url, rx) = run_server("500 Internal Server Error");
+        let sink = HttpMetricSink::from(&url).unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        assert!(sink.flush().is_err());
+        assert_eq!("foo:54|c\n", rx.recv_timeout(Duration::from_secs(5)).unwrap().body);
+        assert_eq!(1, sink.stats().packets_dropped);
+
+        // The failed request was discarded so it isn't sent again
+        sink.emit("foo:67|c").unwrap();
+        assert!(sink.flush().is_err());
+        assert_eq!("foo:67|c\n", rx.recv_timeout(Duration::from_secs(5)).unwrap().body);
+        assert_eq!(2, sink.stats().packets_dropped);
+    }
+
+    #[test]
+    fn test_http_metric_sink_error_status_buffer_full() {
+        let (url, rx) = run_server("500 Internal Server Error");
+        let sink = HttpMetricSink::builder().with_capacity(16).build(&url).unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        assert!(sink.emit("foo:67|c").is_err());
+        assert_eq!("foo:54|c\n", rx.recv_timeout(Duration::from_secs(5)).unwrap().body);
+
+        // Neither the failed request nor the metric that triggered it are
+        // kept so there's room for new metrics.
+        assert_eq!(8, sink.emit("foo:89|c").unwrap());
+        assert!(sink.flush().is_err());
+        assert_eq!("foo:89|c\n", rx.recv_timeout(Duration::from_secs(5)).unwrap().body);
+    }
+
+    #[test]
+    fn test_http_metric_sink_error_status_flush_interval() {
+        let (url, rx) = run_server("500 Internal Server Error");
+        let clock = MockClock::new();
+        let sink = HttpMetricSink::builder()
+            .with_flush_interval(Duration::from_secs(10))
+            .build_with_clock(&url, clock.clone())
+            .unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        clock.advance(Duration::from_secs(10));
+
+        // The error is for the buffered metrics sent because the interval
+        // passed, the metric being emitted isn't buffered.
+        assert!(sink.emit("foo:67|c").is_err());
+        assert_eq!("foo:54|c\n", rx.recv_timeout(Duration::from_secs(5)).unwrap().body);
+
+        sink.flush().unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::clock::{Clock, SystemClock};
+use crate::sinks::core::{MetricSink, SinkStats};
+use crate::sinks::util::sum_or_first_error;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::panic::RefUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Return the name and everything after the value (type, tags, etc.) of a
+/// metric if it's a gauge along with whether it's a relative gauge, `None`
+/// otherwise.
+///
+/// Relative gauges (values starting with `+` or `-`) change the value of the
+/// gauge on the server instead of replacing it and so can't be collapsed.
+fn gauge_key(metric: &str) -> Option<(String, bool)> {
+    let type_start = metric.find('|')?;
+    let (name_value, rest) = metric.split_at(type_start);
+    let type_end = rest[1..].find('|').map_or(rest.len(), |i| i + 1);
+    if &rest[1..type_end] != "g" {
+        return None;
+    }
+
+    let value_start = name_value.rfind(':')?;
+    let value = &name_value[value_start + 1..];
+    let relative = value.starts_with('+') || value.starts_with('-');
+    Some((format!("{}{}", &name_value[..value_start], rest), relative))
+}
+
+/// Most recent value of each gauge since the last flush, in the order each
+/// gauge was first seen.
+#[derive(Debug)]
+struct PendingGauges {
+    indexes: HashMap<String, usize>,
+    metrics: Vec<String>,
+    last_flush: Instant,
+}
+
+impl PendingGauges {
+    fn new(now: Instant) -> Self {
+        PendingGauges {
+            indexes: HashMap::new(),
+            metrics: Vec::new(),
+            last_flush: now,
+        }
+    }
+
+    fn insert(&mut self, key: String, metric: &str) {
+        match self.indexes.get(&key) {
+            Some(&i) => self.metrics[i] = metric.to_string(),
+            None => {
+                self.indexes.insert(key, self.metrics.len());
+                self.metrics.push(metric.to_string());
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<String> {
+        let removed = self.indexes.remove(key)?;
+        for i in self.indexes.values_mut() {
+            if *i > removed {
+                *i -= 1;
+            }
+        }
+
+        Some(self.metrics.remove(removed))
+    }
+
+    fn take(&mut self, now: Instant) -> Vec<String> {
+        self.last_flush = now;
+        self.indexes.clear();
+        mem::take(&mut self.metrics)
+    }
+}
+
+/// Implementation of a `MetricSink` that only sends the most recent value of
+/// each gauge during an interval, passing all other metrics through to the
+/// wrapped sink unchanged.
+///
+/// Gauges record the latest value of something and any value replaced by a
+/// newer one before the server reports it has no effect. When gauges are set
+/// very frequently, this sink can dramatically reduce the number of metrics
+/// sent without changing the values recorded by the server. Gauges are
+/// identified by their full name and everything after their value (type,
+/// sampling rate, tags, etc.) so that gauges with different tags are kept
+/// separately. Relative gauges (values starting with `+` or `-`) are passed
+/// through since each of them changes the value recorded by the server. Any
+/// pending value of the same gauge is sent right before a relative gauge so
+/// that the change is applied to it instead of being replaced by it.
+///
+/// Gauges are sent to the wrapped sink when a metric is emitted after the
+/// interval has passed since they were last sent, when `.flush()` is called,
+/// or when the sink is dropped. This adds up to the length of the interval
+/// to the time taken for a gauge to be sent, or longer if no metrics at all
+/// are emitted after the interval. Call `.flush()` periodically if gauges
+/// must be sent even when the application is otherwise idle.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::prelude::*;
+/// use cadence::{LatestGaugeMetricSink, NopMetricSink, StatsdClient};
+///
+/// let sink = LatestGaugeMetricSink::new(NopMetricSink, Duration::from_secs(1));
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+///
+/// for i in 0..1000 {
+///     // Only the last of these is sent when the interval passes
+///     client.gauge("queue.size", i).unwrap();
+/// }
+/// ```
+pub struct LatestGaugeMetricSink {
+    sink: Box<dyn MetricSink + Send + Sync + RefUnwindSafe>,
+    pending: Mutex<PendingGauges>,
+    interval: Duration,
+    clock: Box<dyn Clock>,
+}
+
+impl LatestGaugeMetricSink {
+    /// Construct a new `LatestGaugeMetricSink` that sends the most recent value
+    /// of each gauge with `sink` every `interval`.
+    pub fn new<T>(sink: T, interval: Duration) -> Self
+    where
+        T: MetricSink + Send + Sync + RefUnwindSafe + 'static,
+    {
+        Self::with_clock(sink, interval, SystemClock)
+    }
+
+    fn with_clock<T, C>(sink: T, interval: Duration, clock: C) -> Self
+    where
+        T: MetricSink + Send + Sync + RefUnwindSafe + 'static,
+        C: Clock + 'static,
+    {
+        LatestGaugeMetricSink {
+            sink: Box::new(sink),
+            pending: Mutex::new(PendingGauges::new(clock.now())),
+            interval,
+            clock: Box::new(clock),
+        }
+    }
+
+    // Send the pending gauges with the wrapped sink, outside of the lock.
+    fn send_pending(&self, now: Instant, force: bool) -> io::Result<usize> {
+        let metrics = {
+            let mut pending = self.pending.lock().unwrap();
+            if !force && now.saturating_duration_since(pending.last_flush) < self.interval {
+                return Ok(0);
+            }
+
+            pending.take(now)
+        };
+
+        if metrics.is_empty() {
+            return Ok(0);
+        }
+
+        let metrics: Vec<&str> = metrics.iter().map(String::as_str).collect();
+        self.sink.emit_many(&metrics)
+    }
+
+    fn write_metric(&self, metric: &str) -> io::Result<usize> {
+        match gauge_key(metric) {
+            Some((key, false)) => {
+                self.pending.lock().unwrap().insert(key, metric);
+                Ok(metric.len())
+            }
+            Some((key, true)) => {
+                let absolute = self.pending.lock().unwrap().remove(&key);
+                if let Some(absolute) = absolute {
+                    self.sink.emit(&absolute)?;
+                }
+
+                self.sink.emit(metric)
+            }
+            None => self.sink.emit(metric),
+        }
+    }
+}
+
+impl MetricSink for LatestGaugeMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let written = self.write_metric(metric)?;
+        self.send_pending(self.clock.now(), false)?;
+        Ok(written)
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        let written = sum_or_first_error(metrics.iter().map(|m| self.write_metric(m)))?;
+        self.send_pending(self.clock.now(), false)?;
+        Ok(written)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.send_pending(self.clock.now(), true)?;
+        self.sink.flush()
+    }
+
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.send_pending(self.clock.now(), true)?;
+        self.sink.flush_with_timeout(timeout)
+    }
+
+    fn is_ready(&self) -> bool {
+        self.sink.is_ready()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl Drop for LatestGaugeMetricSink {
+    fn drop(&mut self) {
+        let _ = self.send_pending(self.clock.now(), true);
+    }
+}
+
+impl fmt::Debug for LatestGaugeMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LatestGaugeMetricSink {{ interval: {:?}, ... }}", self.interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gauge_key, LatestGaugeMetricSink};
+    use crate::clock::MockClock;
+    use crate::sinks::core::MetricSink;
+    use crate::sinks::SpyMetricSink;
+    use crossbeam_channel::Receiver;
+    use std::time::Duration;
+
+    fn received(rx: &Receiver<Vec<u8>>) -> Vec<String> {
+        rx.try_iter().map(|m| String::from_utf8(m).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_gauge_key() {
+        assert_eq!(Some(("foo|g".to_string(), false)), gauge_key("foo:1|g"));
+        assert_eq!(Some(("foo|g|#a:b".to_string(), false)), gauge_key("foo:1.5|g|#a:b"));
+        assert_eq!(Some(("foo|g|@0.5".to_string(), false)), gauge_key("foo:1|g|@0.5"));
+        assert_eq!(Some(("foo|g".to_string(), true)), gauge_key("foo:+1|g"));
+        assert_eq!(Some(("foo|g".to_string(), true)), gauge_key("foo:-1|g"));
+        assert_eq!(None, gauge_key("foo:1|c"));
+        assert_eq!(None, gauge_key("foo:1|ms|#a:g"));
+        assert_eq!(None, gauge_key("foo"));
+    }
+
+    #[test]
+    fn test_latest_gauge_metric_sink_latest_per_window() {
+        let clock = MockClock::new();
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = LatestGaugeMetricSink::with_clock(spy, Duration::from_secs(10), clock.clone());
+
+        sink.emit("foo:1|g").unwrap();
+        sink.emit("foo:2|g").unwrap();
+        sink.emit("foo:2|g|#region:us-east-1").unwrap();
+        sink.emit("bar:1|c").unwrap();
+        sink.emit("foo:3|g").unwrap();
+
+        // Other metrics are passed through immediately
+        assert_eq!(vec!["bar:1|c"], received(&rx));
+
+        clock.advance(Duration::from_secs(10));
+        sink.emit("foo:4|g|#region:us-east-1").unwrap();
+
+        assert_eq!(vec!["foo:3|g", "foo:4|g|#region:us-east-1"], received(&rx));
+
+        sink.emit("foo:5|g").unwrap();
+        clock.advance(Duration::from_secs(5));
+        sink.emit("foo:6|g").unwrap();
+
+        assert!(received(&rx).is_empty());
+
+        clock.advance(Duration::from_secs(5));
+        sink.emit("baz:1|ms").unwrap();
+
+        assert_eq!(vec!["baz:1|ms", "foo:6|g"], received(&rx));
+    }
+
+    #[test]
+    fn test_latest_gauge_metric_sink_relative_gauges() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = LatestGaugeMetricSink::with_clock(spy, Duration::from_secs(10), MockClock::new());
+
+        sink.emit("foo:+1|g").unwrap();
+        sink.emit("foo:-2|g").unwrap();
+
+        assert_eq!(vec!["foo:+1|g", "foo:-2|g"], received(&rx));
+    }
+
+    #[test]
+    fn test_latest_gauge_metric_sink_relative_after_absolute() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = LatestGaugeMetricSink::with_clock(spy, Duration::from_secs(10), MockClock::new());
+
+        sink.emit("bar:1|g").unwrap();
+        sink.emit("q:7|g").unwrap();
+        sink.emit("q:10|g").unwrap();
+        sink.emit("baz:1|g").unwrap();
+        sink.emit("q:10|g|#a:b").unwrap();
+
+        // The pending absolute value of the same gauge is sent first so the
+        // server applies the change to it and records 15.
+        sink.emit("q:+5|g").unwrap();
+        assert_eq!(vec!["q:10|g", "q:+5|g"], received(&rx));
+
+        sink.emit("baz:2|g").unwrap();
+        sink.flush().unwrap();
+        assert_eq!(vec!["bar:1|g", "baz:2|g", "q:10|g|#a:b"], received(&rx));
+    }
+
+    #[test]
+    fn test_latest_gauge_metric_sink_emit_many() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = LatestGaugeMetricSink::with_clock(spy, Duration::from_secs(10), MockClock::new());
+
+        assert_eq!(21, sink.emit_many(&["foo:1|g", "bar:1|c", "foo:2|g"]).unwrap());
+        assert_eq!(vec!["bar:1|c"], received(&rx));
+
+        sink.flush().unwrap();
+        assert_eq!(vec!["foo:2|g"], received(&rx));
+    }
+
+    #[test]
+    fn test_latest_gauge_metric_sink_flush() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = LatestGaugeMetricSink::with_clock(spy, Duration::from_secs(10), MockClock::new());
+
+        sink.emit("foo:1|g").unwrap();
+        sink.emit("foo:2|g").unwrap();
+        sink.flush().unwrap();
+        sink.flush().unwrap();
+
+        assert_eq!(vec!["foo:2|g"], received(&rx));
+    }
+
+    #[test]
+    fn test_latest_gauge_metric_sink_drop() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = LatestGaugeMetricSink::with_clock(spy, Duration::from_secs(10), MockClock::new());
+
+        sink.emit("foo:1|g").unwrap();
+        sink.emit("foo:2|g").unwrap();
+        drop(sink);
+
+        assert_eq!(vec!["foo:2|g"], received(&rx));
+    }
+}
@@ -0,0 +1,153 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::{MetricSink, SinkStats};
+use crate::sinks::util::NamedSinkError;
+use std::any::Any;
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::time::Duration;
+
+type MappingFn = dyn Fn(&str) -> Cow<'_, str> + Send + Sync + RefUnwindSafe;
+
+/// Implementation of a `MetricSink` that applies a function to every metric
+/// before sending the result with another sink.
+///
+/// This is a general hook for transforming metrics at the sink boundary
+/// instead of in the client, for example to add a suffix or tags identifying
+/// the source of metrics, rewrite names, or redact values. The function is
+/// invoked with each metric in the thread that emits it and may return the
+/// metric unchanged (`Cow::Borrowed`) to avoid allocating. The function is
+/// applied to metrics exactly as they're formatted by the client so it must
+/// produce valid metrics itself; nothing is validated after it runs. Results
+/// from the wrapped sink, such as the number of bytes written for the mapped
+/// metrics, are returned unchanged. Errors include the name of the wrapped
+/// sink if it has one.
+///
+/// # Example
+///
+/// ```
+/// use std::borrow::Cow;
+/// use cadence::{MappingMetricSink, NopMetricSink, StatsdClient};
+///
+/// // Add a tag identifying the source of every metric
+/// let sink = MappingMetricSink::new(NopMetricSink, |metric: &str| {
+///     if metric.contains("|#") {
+///         Cow::Owned(format!("{},source:sidecar", metric))
+///     } else {
+///         Cow::Owned(format!("{}|#source:sidecar", metric))
+///     }
+/// });
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+/// ```
+pub struct MappingMetricSink {
+    sink: Box<dyn MetricSink + Send + Sync + RefUnwindSafe>,
+    mapping: Box<MappingFn>,
+}
+
+impl MappingMetricSink {
+    /// Construct a new `MappingMetricSink` that sends the result of calling
+    /// `mapping` with each metric using `sink`.
+    pub fn new<T, F>(sink: T, mapping: F) -> Self
+    where
+        T: MetricSink + Send + Sync + RefUnwindSafe + 'static,
+        F: Fn(&str) -> Cow<'_, str> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        MappingMetricSink {
+            sink: Box::new(sink),
+            mapping: Box::new(mapping),
+        }
+    }
+}
+
+impl MetricSink for MappingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.sink
+            .emit(&(self.mapping)(metric))
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mapped: Vec<Cow<'_, str>> = metrics.iter().map(|m| (self.mapping)(m)).collect();
+        let mapped: Vec<&str> = mapped.iter().map(|m| m.as_ref()).collect();
+        self.sink
+            .emit_many(&mapped)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush().map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.sink
+            .flush_with_timeout(timeout)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn is_ready(&self) -> bool {
+        self.sink.is_ready()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl fmt::Debug for MappingMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MappingMetricSink {{ ... }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MappingMetricSink;
+    use crate::sinks::core::MetricSink;
+    use crate::sinks::SpyMetricSink;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_mapping_metric_sink_emit() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = MappingMetricSink::new(spy, |metric: &str| Cow::Owned(format!("{}|#source:test", metric)));
+
+        assert_eq!(21, sink.emit("foo:54|c").unwrap());
+        assert_eq!(42, sink.emit_many(&["foo:67|c", "bar:12|g"]).unwrap());
+
+        assert_eq!(b"foo:54|c|#source:test", rx.recv().unwrap().as_slice());
+        assert_eq!(b"foo:67|c|#source:test", rx.recv().unwrap().as_slice());
+        assert_eq!(b"bar:12|g|#source:test", rx.recv().unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_mapping_metric_sink_borrowed() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = MappingMetricSink::new(spy, |metric: &str| {
+            if metric.starts_with("secret.") {
+                Cow::Owned(metric.replacen("secret.", "redacted.", 1))
+            } else {
+                Cow::Borrowed(metric)
+            }
+        });
+
+        sink.emit("secret.foo:1|c").unwrap();
+        sink.emit("public.foo:1|c").unwrap();
+
+        assert_eq!(b"redacted.foo:1|c", rx.recv().unwrap().as_slice());
+        assert_eq!(b"public.foo:1|c", rx.recv().unwrap().as_slice());
+    }
+}
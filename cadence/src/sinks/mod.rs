@@ -8,18 +8,53 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod channel;
 mod core;
+mod file;
+mod latest;
+mod mapping;
+mod named;
 mod queuing;
+mod ring;
+mod shared;
 mod spy;
+mod tap;
+mod tee;
 mod udp;
+mod util;
 
+pub use crate::sinks::channel::ChannelMetricSink;
 pub use crate::sinks::core::{MetricSink, NopMetricSink, SinkStats, SocketStats};
+pub use crate::sinks::file::FileMetricSink;
+pub use crate::sinks::latest::LatestGaugeMetricSink;
+pub use crate::sinks::mapping::MappingMetricSink;
+pub use crate::sinks::named::NamedMetricSink;
+pub(crate) use crate::sinks::queuing::QueueStats;
 pub use crate::sinks::queuing::{QueuingMetricSink, QueuingMetricSinkBuilder};
-pub use crate::sinks::spy::{BufferedSpyMetricSink, SpyMetricSink};
-pub use crate::sinks::udp::{BufferedUdpMetricSink, UdpMetricSink};
+pub use crate::sinks::ring::RingBufferMetricSink;
+pub use crate::sinks::shared::SharedSink;
+pub use crate::sinks::spy::{BufferedSpyMetricSink, OverflowPolicy, SpyMetricSink};
+pub use crate::sinks::tap::TapMetricSink;
+pub use crate::sinks::tee::TeeMetricSink;
+pub(crate) use crate::sinks::udp::{bind_nonblocking, get_addr};
+pub use crate::sinks::udp::{BufferedUdpMetricSink, BufferedUdpMetricSinkBuilder, UdpMetricSink};
+pub use crate::sinks::util::{fits_in_datagram, DEFAULT_BUFFER_SIZE, MAX_UDP_PAYLOAD};
+pub(crate) use crate::sinks::util::{NamedSinkError, PacketTooLarge};
 
 #[cfg(unix)]
 mod unix;
 
 #[cfg(unix)]
-pub use crate::sinks::unix::{BufferedUnixMetricSink, UnixMetricSink};
+pub use crate::sinks::unix::{BufferedUnixMetricSink, BufferedUnixMetricSinkBuilder, UnixMetricSink};
+
+#[cfg(feature = "http")]
+mod http;
+
+#[cfg(feature = "http")]
+pub use crate::sinks::http::{HttpMetricSink, HttpMetricSinkBuilder, DEFAULT_HTTP_BUFFER_SIZE};
+
+#[cfg(feature = "tracing")]
+mod tracing;
+
+#[cfg(feature = "tracing")]
+pub use crate::sinks::tracing::TracingMetricSink;
@@ -0,0 +1,123 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::{MetricSink, SinkStats};
+use std::any::Any;
+use std::io;
+use std::time::Duration;
+
+/// Implementation of a `MetricSink` that gives another sink a name.
+///
+/// Sinks that wrap other sinks (such as `TeeMetricSink`, `TapMetricSink`,
+/// `MappingMetricSink`, and `QueuingMetricSink`) include the name of the
+/// wrapped sink, if any, in errors returned by it. This makes it possible
+/// to tell which sink failed when metrics are sent to multiple destinations.
+/// Everything else is passed through to the wrapped sink unchanged.
+///
+/// A `NamedMetricSink` can be created using `MetricSink::with_name()` or
+/// `NamedMetricSink::new()`.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{MetricSink, NopMetricSink, StatsdClient, TeeMetricSink};
+///
+/// let primary = NopMetricSink.with_name("primary");
+/// let sink = TeeMetricSink::new(primary, 100);
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NamedMetricSink<T> {
+    name: String,
+    sink: T,
+}
+
+impl<T> NamedMetricSink<T>
+where
+    T: MetricSink,
+{
+    /// Construct a new `NamedMetricSink` that gives `sink` the given name.
+    pub fn new(name: &str, sink: T) -> Self {
+        NamedMetricSink {
+            name: name.to_string(),
+            sink,
+        }
+    }
+
+    /// Return a reference to the wrapped sink.
+    pub fn get_ref(&self) -> &T {
+        &self.sink
+    }
+}
+
+impl<T> MetricSink for NamedMetricSink<T>
+where
+    T: MetricSink + 'static,
+{
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.sink.emit(metric)
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        self.sink.emit_many(metrics)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.sink.flush_with_timeout(timeout)
+    }
+
+    fn is_ready(&self) -> bool {
+        self.sink.is_ready()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NamedMetricSink;
+    use crate::sinks::core::MetricSink;
+    use crate::sinks::{NopMetricSink, SpyMetricSink};
+
+    #[test]
+    fn test_named_metric_sink() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = spy.with_name("spy");
+
+        assert_eq!(Some("spy"), sink.name());
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(b"foo:54|c", rx.recv().unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_named_metric_sink_as_any() {
+        let sink = NamedMetricSink::new("nop", NopMetricSink);
+        let named = sink
+            .as_any()
+            .and_then(|s| s.downcast_ref::<NamedMetricSink<NopMetricSink>>());
+
+        assert!(named.is_some());
+        assert_eq!(None, NopMetricSink.name());
+    }
+}
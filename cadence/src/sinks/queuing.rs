@@ -9,19 +9,26 @@
 // except according to those terms.
 
 use crate::sinks::core::{MetricSink, SinkStats};
+use crate::sinks::util::NamedSinkError;
 use crossbeam_channel::{self, Receiver, Sender, TrySendError};
+use std::any::Any;
 use std::fmt;
 use std::io::{self, ErrorKind};
-use std::panic::RefUnwindSafe;
+use std::panic::{self, AssertUnwindSafe, RefUnwindSafe};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
+
+// Percentage of the capacity of a bounded queue that can be used before the
+// sink reports that it isn't ready for more metrics.
+const READY_THRESHOLD_PERCENT: u64 = 90;
 
 /// Implementation of a builder pattern for `QueuingMetricSink`.
 ///
-/// The builder can be used to set an error handler for the sink being
-/// wrapped by a `QueuingMetricSink` as well as setting the capacity of
-/// the `QueuingMetricSink`.
+/// The builder can be used to set an error handler and a panic handler for
+/// the sink being wrapped by a `QueuingMetricSink` as well as setting the
+/// capacity of the `QueuingMetricSink`.
 ///
 /// # Example
 ///
@@ -43,9 +50,15 @@ use std::thread;
 #[derive(Default)]
 pub struct QueuingMetricSinkBuilder {
     error_handler: Option<Box<dyn Fn(io::Error) + Sync + Send + RefUnwindSafe + 'static>>,
+    panic_handler: Option<PanicHandler>,
     capacity: Option<usize>,
+    max_restarts: Option<usize>,
+    batch_size: Option<usize>,
 }
 
+type PanicHandler = Box<dyn Fn(&str) + Sync + Send + RefUnwindSafe + 'static>;
+type WorkerTask = dyn Fn(&[String]) + Sync + Send + RefUnwindSafe + 'static;
+
 impl QueuingMetricSinkBuilder {
     /// Construct a new builder.
     pub fn new() -> Self {
@@ -58,19 +71,60 @@ impl QueuingMetricSinkBuilder {
     where
         T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
     {
+        let queuing = self.build_with_mode(sink, false);
+        spawn_worker_in_thread(queuing.worker.clone());
+        queuing
+    }
+
+    /// Construct a new `QueuingMetricSink` instance wrapping another sink based on
+    /// the builder configuration that sends metrics to the wrapped sink in the
+    /// calling thread instead of queuing them. **This is only meant for tests.**
+    ///
+    /// See `QueuingMetricSink::synchronous` for more information.
+    pub fn build_synchronous<T>(self, sink: T) -> QueuingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.build_with_mode(sink, true)
+    }
+
+    fn build_with_mode<T>(mut self, sink: T, synchronous: bool) -> QueuingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        let panic_handler = self.panic_handler.take();
         let sink = Arc::new(sink);
         let sink_c = sink.clone();
-        let worker = Arc::new(Worker::new(self.capacity, move |v: String| {
-            if let Err(e) = sink_c.emit(&v) {
+        let task = move |vals: &[String]| {
+            let res = if let [v] = vals {
+                sink_c.emit(v)
+            } else {
+                let metrics: Vec<&str> = vals.iter().map(String::as_str).collect();
+                sink_c.emit_many(&metrics)
+            };
+
+            if let Err(e) = res {
                 if let Some(error_handler) = &self.error_handler {
-                    error_handler(e);
+                    error_handler(NamedSinkError::wrap(sink_c.name(), e));
                 }
             }
-        }));
+        };
 
-        spawn_worker_in_thread(worker.clone());
+        let worker = if synchronous {
+            Worker::synchronous(task)
+        } else {
+            Worker {
+                panic_handler,
+                max_restarts: self.max_restarts,
+                batch_size: self.batch_size.unwrap_or(1).max(1),
+                ..Worker::new(self.capacity, task)
+            }
+        };
 
-        QueuingMetricSink { worker, sink }
+        QueuingMetricSink {
+            worker: Arc::new(worker),
+            sink,
+        }
     }
 
     /// Set error handler called when the wrapped sink fails to emit a metric.
@@ -85,6 +139,57 @@ impl QueuingMetricSinkBuilder {
         self
     }
 
+    /// Set a handler called with the panic message each time the wrapped sink
+    /// panics and the thread it runs in needs to be restarted.
+    ///
+    /// This can be used to log why the wrapped sink keeps failing. The handler
+    /// will be run in the thread that panicked, before it's restarted, and must
+    /// not panic. See `QueuingMetricSink::last_panic()` to read the message of
+    /// the most recent panic instead.
+    pub fn with_panic_handler<F>(mut self, panic_handler: F) -> Self
+    where
+        F: Fn(&str) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.panic_handler = Some(Box::new(panic_handler));
+        self
+    }
+
+    /// Set the maximum number of times the thread running the wrapped sink is
+    /// restarted after it panics.
+    ///
+    /// By default, the thread is always restarted. When a wrapped sink panics
+    /// every time it's used, this results in threads being restarted endlessly.
+    /// After the given number of restarts, the next panic stops the thread for
+    /// good: the `QueuingMetricSink` is then considered dead and every call to
+    /// `.emit()` fails with an error instead of queuing the metric. Metrics
+    /// still queued at that point are never sent. See `QueuingMetricSink::is_dead()`.
+    pub fn with_max_restarts(mut self, max_restarts: usize) -> Self {
+        self.max_restarts = Some(max_restarts);
+        self
+    }
+
+    /// Set the maximum number of queued metrics passed to the wrapped sink at
+    /// once.
+    ///
+    /// By default, metrics are passed to the wrapped sink one at a time using
+    /// its `.emit()` method. When the batch size is greater than one, metrics
+    /// that are already queued when the thread running the wrapped sink reads
+    /// the next metric are passed along with it in a single call to the
+    /// `.emit_many()` method of the wrapped sink, up to `batch_size` metrics at
+    /// a time. The thread never waits for more metrics to arrive to fill a
+    /// batch. Metrics are still passed to the wrapped sink in the order they
+    /// were queued. This reduces the overhead of sending each metric for sinks
+    /// that can send multiple metrics more efficiently, such as buffered sinks
+    /// that only lock their buffer once per call.
+    ///
+    /// The error handler is called at most once per batch, with the first error
+    /// returned by the wrapped sink. If the wrapped sink panics, the entire
+    /// batch being sent is lost. A batch size of zero is treated as one.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
     /// Set queue size used to send metrics to the wrapped sink.
     ///
     /// See `QueuingMetricSink::with_capacity` for more information.
@@ -92,6 +197,18 @@ impl QueuingMetricSinkBuilder {
         self.capacity = Some(capacity);
         self
     }
+
+    // Set an error handler only if one hasn't been set by the caller already
+    pub(crate) fn with_default_error_handler<F>(mut self, error_handler: F) -> Self
+    where
+        F: Fn(io::Error) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        if self.error_handler.is_none() {
+            self.error_handler = Some(Box::new(error_handler));
+        }
+
+        self
+    }
 }
 
 /// Implementation of a `MetricSink` that wraps another implementation
@@ -141,6 +258,18 @@ impl QueuingMetricSinkBuilder {
 /// At the end of this code block, all metrics are guaranteed to be sent to
 /// the underlying wrapped metric sink before the thread used by the queuing
 /// sink is stopped.
+///
+/// # Ordering
+///
+/// Metrics are passed to the wrapped sink in the order they were accepted by
+/// the queuing sink (first in, first out). Metrics emitted from different
+/// threads are ordered by when each call to `.emit()` enqueued them.
+///
+/// This also holds when the wrapped sink panics. The metric being sent when
+/// the panic happened is lost, but the worker is restarted in a new thread
+/// that reads from the same queue, only once the thread that panicked is no
+/// longer reading from it. Metrics that were queued before the panic are
+/// passed to the wrapped sink before any that are submitted afterwards.
 #[derive(Clone)]
 pub struct QueuingMetricSink {
     worker: Arc<Worker>,
@@ -233,6 +362,35 @@ impl QueuingMetricSink {
         Self::builder().with_capacity(capacity).build(sink)
     }
 
+    /// Construct a new `QueuingMetricSink` instance wrapping another sink
+    /// implementation that sends metrics to it in the calling thread.
+    ///
+    /// **This defeats the purpose of the `QueuingMetricSink` and is only meant
+    /// for tests.** No thread is created and nothing is queued: each call to
+    /// `.emit()` calls `.emit()` on the wrapped sink before returning. This
+    /// allows tests of code that uses a `QueuingMetricSink` to check the metrics
+    /// that were sent without sleeping or otherwise waiting for them to be sent
+    /// from another thread. Since the wrapped sink runs in the calling thread,
+    /// any panics from it are not caught.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{MetricSink, QueuingMetricSink, SpyMetricSink};
+    ///
+    /// let (rx, spy) = SpyMetricSink::new();
+    /// let queuing = QueuingMetricSink::synchronous(spy);
+    /// queuing.emit("foo.counter:4|c").unwrap();
+    ///
+    /// assert_eq!(b"foo.counter:4|c".to_vec(), rx.try_recv().unwrap());
+    /// ```
+    pub fn synchronous<T>(sink: T) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().build_synchronous(sink)
+    }
+
     /// Return the number of times the wrapped sink or underlying worker thread
     /// has panicked and needed to be restarted. In typical use this should always
     /// be `0` but may be `> 0` for buggy `MetricSink` implementations.
@@ -240,6 +398,25 @@ impl QueuingMetricSink {
         self.worker.stats.panics()
     }
 
+    /// Return `true` if the thread running the wrapped sink has panicked more
+    /// times than it's allowed to be restarted and was stopped for good, `false`
+    /// otherwise. Metrics emitted to a dead sink are rejected with an error.
+    ///
+    /// Sinks are never considered dead unless a maximum number of restarts was
+    /// set with `QueuingMetricSinkBuilder::with_max_restarts()`.
+    pub fn is_dead(&self) -> bool {
+        self.worker.is_dead()
+    }
+
+    /// Return the message of the most recent panic of the wrapped sink or
+    /// underlying worker thread, `None` if it has never panicked.
+    ///
+    /// Panics with a payload that isn't a string (i.e. not raised by `panic!()`
+    /// with a message) are reported as `"unknown panic"`.
+    pub fn last_panic(&self) -> Option<String> {
+        self.worker.stats.last_panic()
+    }
+
     /// Return the number of currently queued metrics. Note that due to the way
     /// this number is computed (submitted metrics - processed metrics), it is
     /// necessarily approximate.
@@ -259,10 +436,47 @@ impl QueuingMetricSink {
     pub fn drained(&self) -> u64 {
         self.worker.stats.drained()
     }
+
+    /// Return the maximum number of metrics that can be queued, `None` if the
+    /// queue is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.worker.sender.capacity()
+    }
+
+    // Handle for reading the queue statistics of this sink that doesn't keep
+    // the worker thread running, unlike clones of the sink itself.
+    pub(crate) fn queue_stats(&self) -> QueueStats {
+        QueueStats {
+            worker: self.worker.clone(),
+        }
+    }
+}
+
+/// Read-only view of the statistics of a `QueuingMetricSink`.
+#[derive(Debug, Clone)]
+pub(crate) struct QueueStats {
+    worker: Arc<Worker>,
+}
+
+impl QueueStats {
+    pub(crate) fn panics(&self) -> u64 {
+        self.worker.stats.panics()
+    }
+
+    pub(crate) fn queued(&self) -> u64 {
+        self.worker.stats.queued()
+    }
 }
 
 impl MetricSink for QueuingMetricSink {
     fn emit(&self, metric: &str) -> io::Result<usize> {
+        if self.worker.is_dead() {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "wrapped sink stopped after too many panics",
+            ));
+        }
+
         match self.worker.submit(metric.to_string()) {
             Err(TrySendError::Disconnected(_)) => Err(io::Error::new(ErrorKind::Other, "channel disconnected")),
             Err(TrySendError::Full(_)) => Err(io::Error::new(ErrorKind::Other, "channel full")),
@@ -271,12 +485,38 @@ impl MetricSink for QueuingMetricSink {
     }
 
     fn flush(&self) -> Result<(), std::io::Error> {
-        self.sink.flush()
+        self.sink.flush().map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.sink
+            .flush_with_timeout(timeout)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    /// Return `false` when the queue is more than 90% full, the wrapped sink
+    /// isn't ready, or the sink is dead, `true` otherwise. Sinks with an unbounded
+    /// queue are ready as long as the wrapped sink is.
+    fn is_ready(&self) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+
+        let queue_ready = match self.capacity() {
+            Some(capacity) => self.queued() * 100 <= capacity as u64 * READY_THRESHOLD_PERCENT,
+            None => true,
+        };
+
+        queue_ready && self.sink.is_ready()
     }
 
     fn stats(&self) -> SinkStats {
         self.sink.stats()
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
 impl Drop for QueuingMetricSink {
@@ -297,6 +537,7 @@ impl Drop for QueuingMetricSink {
 #[derive(Debug)]
 struct WorkerStats {
     panics: AtomicU64,
+    last_panic: Mutex<Option<String>>,
     submitted: AtomicU64,
     drained: AtomicU64,
 }
@@ -305,11 +546,20 @@ impl WorkerStats {
     fn new() -> WorkerStats {
         WorkerStats {
             panics: AtomicU64::new(0),
+            last_panic: Mutex::new(None),
             submitted: AtomicU64::new(0),
             drained: AtomicU64::new(0),
         }
     }
 
+    fn set_last_panic(&self, message: &str) {
+        *self.last_panic.lock().unwrap_or_else(|e| e.into_inner()) = Some(message.to_string());
+    }
+
+    fn last_panic(&self) -> Option<String> {
+        self.last_panic.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     fn incr_panic(&self) {
         self.panics.fetch_add(1, Ordering::Release);
     }
@@ -326,8 +576,8 @@ impl WorkerStats {
         self.submitted.load(Ordering::Acquire)
     }
 
-    fn incr_drained(&self) {
-        self.drained.fetch_add(1, Ordering::Release);
+    fn incr_drained(&self, n: usize) {
+        self.drained.fetch_add(n as u64, Ordering::Release);
     }
 
     fn drained(&self) -> u64 {
@@ -337,12 +587,7 @@ impl WorkerStats {
     fn queued(&self) -> u64 {
         let submitted = self.submitted.load(Ordering::Acquire);
         let drained = self.drained.load(Ordering::Acquire);
-
-        if submitted > drained {
-            submitted - drained
-        } else {
-            0
-        }
+        submitted.saturating_sub(drained)
     }
 }
 
@@ -354,11 +599,27 @@ impl WorkerStats {
 fn spawn_worker_in_thread(worker: Arc<Worker>) -> thread::JoinHandle<()> {
     thread::spawn(move || {
         let mut sentinel = Sentinel::new(&worker);
-        worker.run();
+        // Catch the panic only to record its message and then continue
+        // unwinding so that the sentinel restarts the worker as usual.
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| worker.run())) {
+            worker.panicked(panic_message(payload.as_ref()));
+            panic::resume_unwind(payload);
+        }
         sentinel.cancel();
     })
 }
 
+/// Return the message of a panic payload if it has one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
 /// Struct for ensuring a worker runs to completion correctly, without
 /// panicking.
 ///
@@ -387,9 +648,13 @@ impl<'a> Drop for Sentinel<'a> {
             // This sentinel didn't have its `.cancel()`method called so
             // the thread must have panicked. Increment a counter indicating
             // that this was a panic and spawn a new thread with an Arc of
-            // the worker.
+            // the worker, unless it has already been restarted too many times.
             self.worker.stats.incr_panic();
-            spawn_worker_in_thread(self.worker.clone());
+            if self.worker.can_restart() {
+                spawn_worker_in_thread(self.worker.clone());
+            } else {
+                self.worker.dead.store(true, Ordering::Release);
+            }
         }
     }
 }
@@ -416,28 +681,50 @@ impl<'a> Drop for Sentinel<'a> {
 /// But, if you're wondering why the stopped flag and methods to wait
 /// for it or inspect it even exist: testing is the reason.
 struct Worker {
-    task: Box<dyn Fn(String) + Sync + Send + RefUnwindSafe + 'static>,
+    task: Box<WorkerTask>,
+    panic_handler: Option<PanicHandler>,
+    max_restarts: Option<usize>,
+    batch_size: usize,
     sender: Sender<Option<String>>,
     receiver: Receiver<Option<String>>,
+    synchronous: bool,
     stopped: AtomicBool,
+    dead: AtomicBool,
     stats: WorkerStats,
 }
 
 impl Worker {
     fn new<F>(capacity: Option<usize>, task: F) -> Self
     where
-        F: Fn(String) + Sync + Send + RefUnwindSafe + 'static,
+        F: Fn(&[String]) + Sync + Send + RefUnwindSafe + 'static,
     {
         let (tx, rx) = Self::get_channels(capacity);
         Worker {
             task: Box::new(task),
+            panic_handler: None,
+            max_restarts: None,
+            batch_size: 1,
             sender: tx,
             receiver: rx,
+            synchronous: false,
             stopped: AtomicBool::new(false),
+            dead: AtomicBool::new(false),
             stats: WorkerStats::new(),
         }
     }
 
+    // Create a worker that runs the task in the thread that submits values
+    // instead of sending them to another thread. Only meant for testing.
+    fn synchronous<F>(task: F) -> Self
+    where
+        F: Fn(&[String]) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Worker {
+            synchronous: true,
+            ..Self::new(None, task)
+        }
+    }
+
     fn get_channels(capacity: Option<usize>) -> (Sender<Option<String>>, Receiver<Option<String>>) {
         if let Some(v) = capacity {
             crossbeam_channel::bounded(v)
@@ -447,6 +734,13 @@ impl Worker {
     }
 
     fn submit(&self, v: String) -> Result<(), TrySendError<Option<String>>> {
+        if self.synchronous {
+            self.stats.incr_submitted();
+            self.stats.incr_drained(1);
+            (self.task)(std::slice::from_ref(&v));
+            return Ok(());
+        }
+
         let res = self.sender.try_send(Some(v));
         if res.is_ok() {
             self.stats.incr_submitted();
@@ -456,11 +750,34 @@ impl Worker {
     }
 
     fn run(&self) {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut stopping = false;
+
         for opt in self.receiver.iter() {
-            if let Some(v) = opt {
-                self.stats.incr_drained();
-                (self.task)(v);
-            } else {
+            match opt {
+                Some(v) => batch.push(v),
+                None => break,
+            }
+
+            // Add any other values that are already waiting to the batch but
+            // don't wait for more to arrive. Values queued before the poison
+            // pill are still processed before stopping.
+            while batch.len() < self.batch_size {
+                match self.receiver.try_recv() {
+                    Ok(Some(v)) => batch.push(v),
+                    Ok(None) => {
+                        stopping = true;
+                        break;
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            self.stats.incr_drained(batch.len());
+            (self.task)(&batch);
+            batch.clear();
+
+            if stopping {
                 break;
             }
         }
@@ -471,6 +788,26 @@ impl Worker {
         self.stopped.store(true, Ordering::Release);
     }
 
+    // Record the message of a panic from the run loop and pass it to the
+    // panic handler, if any.
+    fn panicked(&self, message: &str) {
+        self.stats.set_last_panic(message);
+        if let Some(ref handler) = self.panic_handler {
+            handler(message);
+        }
+    }
+
+    // Can the worker be restarted after the most recent panic, based on the
+    // number of panics so far?
+    fn can_restart(&self) -> bool {
+        self.max_restarts.map_or(true, |max| self.stats.panics() <= max as u64)
+    }
+
+    // Has the worker panicked too many times to be restarted?
+    fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::Acquire)
+    }
+
     fn stop(&self) {
         // Send a `None` poison pill value to stop the run loop.
         let _ = self.sender.try_send(None);
@@ -509,14 +846,15 @@ impl fmt::Debug for Worker {
 
 #[cfg(test)]
 mod tests {
-    use super::{QueuingMetricSink, Worker};
+    use super::{panic_message, spawn_worker_in_thread, QueuingMetricSink, QueuingMetricSinkBuilder, Worker};
     use crate::sinks::MetricSink;
     use crate::sinks::SpyMetricSink;
-    use crate::test::PanickingMetricSink;
+    use crate::test::{ErrorMetricSink, PanickingMetricSink};
+    use std::any::Any;
     use std::io;
     use std::panic;
     use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
     use std::thread;
 
     const QUEUE_SIZE: Option<usize> = Some(128);
@@ -526,8 +864,8 @@ mod tests {
         let flag = Arc::new(AtomicBool::new(false));
         let flag_ref = flag.clone();
 
-        let task = move |v: String| {
-            if v == "foo" {
+        let task = move |vals: &[String]| {
+            if vals.iter().any(|v| v == "foo") {
                 flag_ref.store(true, Ordering::Release);
             }
         };
@@ -549,7 +887,7 @@ mod tests {
 
     #[test]
     fn test_worker_stop() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {}));
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: &[String]| {}));
         let worker_ref = worker.clone();
 
         let t = thread::spawn(move || {
@@ -564,7 +902,7 @@ mod tests {
 
     #[test]
     fn test_worker_stop_and_wait() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {}));
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: &[String]| {}));
         let worker_ref = worker.clone();
 
         let _t = thread::spawn(move || {
@@ -579,7 +917,7 @@ mod tests {
     // when the producer size of the channel panics.
     #[test]
     fn test_worker_panic_on_submit_side() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {}));
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: &[String]| {}));
         let worker_ref1 = worker.clone();
         let worker_ref2 = worker.clone();
 
@@ -606,7 +944,7 @@ mod tests {
     // when the consumer side of the channel panics.
     #[test]
     fn test_worker_panic_on_run_side() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: &[String]| {
             panic!("This thread is supposed to panic");
         }));
         let worker_ref1 = worker.clone();
@@ -646,6 +984,54 @@ mod tests {
         assert_eq!("baz.counter:3|c".as_bytes(), m3.as_slice());
     }
 
+    #[test]
+    fn test_queuing_sink_synchronous_emit() {
+        let (rx, spy) = SpyMetricSink::new();
+        let queuing = QueuingMetricSink::synchronous(spy);
+
+        queuing.emit("foo.counter:1|c").unwrap();
+        // Sent before emit returns, no need to stop the worker or wait
+        assert_eq!("foo.counter:1|c".as_bytes(), rx.try_recv().unwrap().as_slice());
+
+        queuing.emit("bar.counter:2|c").unwrap();
+        assert_eq!("bar.counter:2|c".as_bytes(), rx.try_recv().unwrap().as_slice());
+
+        assert_eq!(2, queuing.submitted());
+        assert_eq!(2, queuing.drained());
+        assert_eq!(0, queuing.queued());
+        assert!(!queuing.worker.is_stopped());
+    }
+
+    #[test]
+    fn test_queuing_sink_synchronous_error_handler() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let flag_ref = flag.clone();
+
+        let queuing = QueuingMetricSinkBuilder::new()
+            .with_error_handler(move |_e: io::Error| {
+                flag_ref.store(true, Ordering::Release);
+            })
+            .build_synchronous(ErrorMetricSink::always());
+
+        queuing.emit("foo.counter:1|c").unwrap();
+        assert!(flag.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_queuing_sink_error_handler_named() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_ref = errors.clone();
+
+        let queuing = QueuingMetricSinkBuilder::new()
+            .with_error_handler(move |e: io::Error| {
+                errors_ref.lock().unwrap().push(e.to_string());
+            })
+            .build_synchronous(ErrorMetricSink::always().with_name("upstream"));
+
+        queuing.emit("foo.counter:1|c").unwrap();
+        assert_eq!(vec!["sink upstream: timed out"], *errors.lock().unwrap());
+    }
+
     #[test]
     fn test_queuing_sink_emit_panics() {
         let queuing = QueuingMetricSink::from(PanickingMetricSink::always());
@@ -674,6 +1060,171 @@ mod tests {
         assert_eq!(3, queuing.drained());
     }
 
+    // Make sure that metrics still queued when the wrapped sink panics are sent
+    // in the order they were submitted, before any metrics submitted afterwards.
+    #[test]
+    fn test_queuing_sink_panic_preserves_order() {
+        struct PanicOnMetricSink {
+            spy: SpyMetricSink,
+        }
+
+        impl MetricSink for PanicOnMetricSink {
+            fn emit(&self, metric: &str) -> io::Result<usize> {
+                if metric.starts_with("panic") {
+                    panic!("This sink is supposed to panic");
+                }
+
+                self.spy.emit(metric)
+            }
+        }
+
+        let (rx, spy) = SpyMetricSink::new();
+        let queuing = QueuingMetricSinkBuilder::new().build_with_mode(PanicOnMetricSink { spy }, false);
+
+        // Queue everything before the worker starts so that there are metrics
+        // waiting to be sent when the wrapped sink panics.
+        for metric in ["a:1|c", "b:1|c", "panic:1|c", "c:1|c", "d:1|c", "panic:2|c", "e:1|c"] {
+            queuing.emit(metric).unwrap();
+        }
+
+        spawn_worker_in_thread(queuing.worker.clone());
+        queuing.emit("f:1|c").unwrap();
+        queuing.worker.stop_and_wait();
+
+        let received: Vec<String> = rx.try_iter().map(|m| String::from_utf8(m).unwrap()).collect();
+        assert_eq!(vec!["a:1|c", "b:1|c", "c:1|c", "d:1|c", "e:1|c", "f:1|c"], received);
+        assert_eq!(2, queuing.panics());
+        assert_eq!(8, queuing.drained());
+    }
+
+    #[test]
+    fn test_queuing_sink_last_panic() {
+        let queuing = QueuingMetricSink::from(PanickingMetricSink::every(2));
+        assert_eq!(None, queuing.last_panic());
+
+        queuing.emit("foo.counter:4|c").unwrap();
+        queuing.emit("foo.counter:5|c").unwrap();
+        queuing.worker.stop_and_wait();
+
+        assert_eq!(1, queuing.panics());
+        assert_eq!(Some("This sink is supposed to panic".to_string()), queuing.last_panic());
+    }
+
+    #[test]
+    fn test_queuing_sink_panic_handler_called() {
+        struct FormattedPanicSink;
+
+        impl MetricSink for FormattedPanicSink {
+            fn emit(&self, metric: &str) -> io::Result<usize> {
+                panic!("Unable to send {}", metric);
+            }
+        }
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let messages_ref = messages.clone();
+
+        let queuing = QueuingMetricSinkBuilder::new()
+            .with_panic_handler(move |message: &str| {
+                messages_ref.lock().unwrap().push(message.to_string());
+            })
+            .build(FormattedPanicSink);
+
+        queuing.emit("foo.counter:4|c").unwrap();
+        queuing.emit("foo.counter:5|c").unwrap();
+        queuing.worker.stop_and_wait();
+
+        assert_eq!(2, queuing.panics());
+        assert_eq!(
+            vec![
+                "Unable to send foo.counter:4|c".to_string(),
+                "Unable to send foo.counter:5|c".to_string()
+            ],
+            *messages.lock().unwrap()
+        );
+        assert_eq!(Some("Unable to send foo.counter:5|c".to_string()), queuing.last_panic());
+    }
+
+    #[test]
+    fn test_queuing_sink_max_restarts() {
+        let queuing = QueuingMetricSinkBuilder::new()
+            .with_max_restarts(3)
+            .build_with_mode(PanickingMetricSink::always(), false);
+
+        // Queue everything before the worker starts so that there are more
+        // metrics than restarts allowed when the wrapped sink starts panicking.
+        for i in 0..10 {
+            queuing.emit(&format!("foo.counter:{}|c", i)).unwrap();
+        }
+
+        assert!(!queuing.is_dead());
+        spawn_worker_in_thread(queuing.worker.clone());
+
+        while !queuing.is_dead() {
+            thread::yield_now();
+        }
+
+        // One panic for the original thread and one for each restart
+        assert_eq!(4, queuing.panics());
+        assert_eq!(4, queuing.drained());
+        assert!(!queuing.is_ready());
+
+        let err = queuing.emit("foo.counter:11|c").unwrap_err();
+        assert_eq!(io::ErrorKind::Other, err.kind());
+        assert_eq!(4, queuing.panics());
+    }
+
+    #[test]
+    fn test_queuing_sink_batch_size() {
+        struct RecordingSink {
+            calls: Arc<Mutex<Vec<Vec<String>>>>,
+        }
+
+        impl MetricSink for RecordingSink {
+            fn emit(&self, metric: &str) -> io::Result<usize> {
+                self.calls.lock().unwrap().push(vec![metric.to_string()]);
+                Ok(metric.len())
+            }
+
+            fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+                self.calls
+                    .lock()
+                    .unwrap()
+                    .push(metrics.iter().map(|m| m.to_string()).collect());
+                Ok(metrics.iter().map(|m| m.len()).sum())
+            }
+        }
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let sink = RecordingSink { calls: calls.clone() };
+        let queuing = QueuingMetricSinkBuilder::new()
+            .with_batch_size(4)
+            .build_with_mode(sink, false);
+
+        // Queue everything before the worker starts so that the batches
+        // it sends are always full, except for the last one.
+        for i in 0..10 {
+            queuing.emit(&format!("foo.counter:{}|c", i)).unwrap();
+        }
+
+        spawn_worker_in_thread(queuing.worker.clone());
+        queuing.worker.stop_and_wait();
+
+        let calls = calls.lock().unwrap();
+        let sizes: Vec<usize> = calls.iter().map(Vec::len).collect();
+        let metrics: Vec<String> = calls.iter().flatten().cloned().collect();
+        let expected: Vec<String> = (0..10).map(|i| format!("foo.counter:{}|c", i)).collect();
+
+        assert_eq!(vec![4, 4, 2], sizes);
+        assert_eq!(expected, metrics);
+        assert_eq!(10, queuing.drained());
+    }
+
+    #[test]
+    fn test_panic_message_unknown_payload() {
+        let payload: Box<dyn Any + Send> = Box::new(42);
+        assert_eq!("unknown panic", panic_message(payload.as_ref()));
+    }
+
     // Make sure that our queuing sink is unwind safe (it has the auto trait) and
     // that it handles any expected panics on its own, resulting in calling code not
     // seeing any panics.
@@ -698,16 +1249,6 @@ mod tests {
     // exits.
     #[test]
     fn test_queuing_metric_sink_blocking_sink_back_pressure() {
-        struct BlockingMetricSink;
-
-        impl MetricSink for BlockingMetricSink {
-            fn emit(&self, _m: &str) -> io::Result<usize> {
-                loop {
-                    thread::park();
-                }
-            }
-        }
-
         let queueing = QueuingMetricSink::with_capacity(BlockingMetricSink, 1);
         let results = [
             queueing.emit("foo.counter:1|c"),
@@ -728,4 +1269,68 @@ mod tests {
         );
         assert!(failure >= 1, "At least one submission to the queue should have failed");
     }
+
+    #[test]
+    fn test_queuing_metric_sink_is_ready_unbounded() {
+        let queuing = QueuingMetricSink::from(BlockingMetricSink);
+        for _ in 0..100 {
+            queuing.emit("foo.counter:1|c").unwrap();
+        }
+
+        assert_eq!(None, queuing.capacity());
+        assert!(queuing.is_ready());
+    }
+
+    #[test]
+    fn test_queuing_metric_sink_is_ready_bounded() {
+        let queuing = QueuingMetricSink::with_capacity(BlockingMetricSink, 10);
+        assert_eq!(Some(10), queuing.capacity());
+        assert!(queuing.is_ready());
+
+        // Wait for the worker thread to remove a metric from the queue and block
+        // forever in the wrapped sink. Then fill the queue.
+        queuing.emit("foo.counter:1|c").unwrap();
+        while queuing.drained() == 0 {
+            thread::yield_now();
+        }
+
+        for _ in 0..20 {
+            let _ = queuing.emit("foo.counter:1|c");
+        }
+
+        assert_eq!(10, queuing.queued());
+        assert!(!queuing.is_ready());
+    }
+
+    #[test]
+    fn test_queuing_metric_sink_is_ready_wrapped_sink() {
+        struct NotReadySink;
+
+        impl MetricSink for NotReadySink {
+            fn emit(&self, _m: &str) -> io::Result<usize> {
+                Ok(0)
+            }
+
+            fn is_ready(&self) -> bool {
+                false
+            }
+        }
+
+        let queuing = QueuingMetricSink::from(NotReadySink);
+        assert!(!queuing.is_ready());
+    }
+
+    // Sink that blocks forever when a metric is emitted so that metrics are
+    // never drained from the queue. Since all threads in Rust are daemon threads
+    // we don't care that the worker thread won't stop, it'll be killed when the
+    // test process exits.
+    struct BlockingMetricSink;
+
+    impl MetricSink for BlockingMetricSink {
+        fn emit(&self, _m: &str) -> io::Result<usize> {
+            loop {
+                thread::park();
+            }
+        }
+    }
 }
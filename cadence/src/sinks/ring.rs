@@ -0,0 +1,163 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::MetricSink;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// Implementation of a `MetricSink` that keeps the most recently emitted
+/// metrics in memory so that they can be inspected at runtime.
+///
+/// Up to `capacity` metrics are kept. Once full, each metric emitted replaces
+/// the oldest one. This is meant for live introspection, such as serving the
+/// latest metrics from a debug endpoint of an application. For inspecting
+/// metrics in tests, `SpyMetricSink` is usually a better fit.
+///
+/// Metrics are only kept in memory and aren't sent anywhere else. To keep
+/// the most recent metrics while still sending every metric to a server, use
+/// `TeeMetricSink` instead.
+///
+/// Clones of the sink share the same buffer of metrics. Keep a clone of the
+/// sink before passing it to a client in order to call `.snapshot()` later.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{RingBufferMetricSink, StatsdClient};
+///
+/// let sink = RingBufferMetricSink::new(2);
+/// let client = StatsdClient::from_sink("my.prefix", sink.clone());
+///
+/// client.count("some.counter", 1).unwrap();
+/// client.count("some.counter", 2).unwrap();
+/// client.count("some.counter", 3).unwrap();
+///
+/// assert_eq!(
+///     vec!["my.prefix.some.counter:2|c", "my.prefix.some.counter:3|c"],
+///     sink.snapshot()
+/// );
+/// ```
+#[derive(Clone)]
+pub struct RingBufferMetricSink {
+    buffer: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl RingBufferMetricSink {
+    /// Construct a new `RingBufferMetricSink` that keeps up to `capacity`
+    /// metrics.
+    pub fn new(capacity: usize) -> Self {
+        RingBufferMetricSink {
+            buffer: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Return a copy of the metrics currently kept by the sink, from oldest
+    /// to newest.
+    pub fn snapshot(&self) -> Vec<String> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn record<'a, I>(&self, metrics: I)
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        for metric in metrics {
+            if buffer.len() == self.capacity {
+                buffer.pop_front();
+            }
+
+            buffer.push_back(metric.to_string());
+        }
+    }
+}
+
+impl MetricSink for RingBufferMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.record(Some(metric));
+        Ok(metric.len())
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        self.record(metrics.iter().copied());
+        Ok(metrics.iter().map(|m| m.len()).sum())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl fmt::Debug for RingBufferMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RingBufferMetricSink {{ capacity: {}, ... }}", self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RingBufferMetricSink;
+    use crate::sinks::core::MetricSink;
+
+    #[test]
+    fn test_ring_buffer_metric_sink_wraparound() {
+        let sink = RingBufferMetricSink::new(3);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(vec!["foo:54|c"], sink.snapshot());
+
+        sink.emit("foo:55|c").unwrap();
+        sink.emit("foo:56|c").unwrap();
+        sink.emit("foo:57|c").unwrap();
+        sink.emit("foo:58|c").unwrap();
+
+        assert_eq!(vec!["foo:56|c", "foo:57|c", "foo:58|c"], sink.snapshot());
+    }
+
+    #[test]
+    fn test_ring_buffer_metric_sink_emit_many() {
+        let sink = RingBufferMetricSink::new(2);
+
+        assert_eq!(24, sink.emit_many(&["foo:54|c", "foo:55|c", "foo:56|c"]).unwrap());
+        assert_eq!(vec!["foo:55|c", "foo:56|c"], sink.snapshot());
+    }
+
+    #[test]
+    fn test_ring_buffer_metric_sink_zero_capacity() {
+        let sink = RingBufferMetricSink::new(0);
+
+        sink.emit("foo:54|c").unwrap();
+        assert!(sink.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_ring_buffer_metric_sink_shared_clones() {
+        let sink = RingBufferMetricSink::new(2);
+        let clone = sink.clone();
+
+        clone.emit("foo:54|c").unwrap();
+        assert_eq!(vec!["foo:54|c"], sink.snapshot());
+    }
+}
@@ -0,0 +1,128 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::{MetricSink, SinkStats};
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Implementation of a `MetricSink` that allows a single sink to be shared
+/// by multiple clients.
+///
+/// This is useful for creating several independent clients, each with their
+/// own prefix, tags, or error handler, that all send metrics using the same
+/// underlying sink. For example, different components of an application can
+/// share one UDP socket or one `QueuingMetricSink` (and its thread) instead
+/// of each creating their own.
+///
+/// Clones of the sink share the same wrapped sink. Use `StatsdClient::from_shared()`
+/// to create a client with a shared sink or pass a clone of the sink to
+/// `StatsdClient::builder()` to customize the client. Calls to `.name()` and
+/// `.as_any()` are forwarded to the wrapped sink so that it can be identified
+/// and downcast to its concrete type when accessed from a client.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{NopMetricSink, QueuingMetricSink, SharedSink, StatsdClient};
+///
+/// let shared = SharedSink::new(QueuingMetricSink::from(NopMetricSink));
+/// let api = StatsdClient::from_shared("my.app.api", &shared);
+/// let worker = StatsdClient::from_shared("my.app.worker", &shared);
+///
+/// api.count("requests", 1).unwrap();
+/// worker.count("jobs", 1).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SharedSink {
+    sink: Arc<dyn MetricSink + Send + Sync + RefUnwindSafe>,
+}
+
+impl SharedSink {
+    /// Construct a new `SharedSink` that sends metrics with `sink`.
+    pub fn new<T>(sink: T) -> Self
+    where
+        T: MetricSink + Send + Sync + RefUnwindSafe + 'static,
+    {
+        SharedSink { sink: Arc::new(sink) }
+    }
+}
+
+impl MetricSink for SharedSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.sink.emit(metric)
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        self.sink.emit_many(metrics)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.sink.flush_with_timeout(timeout)
+    }
+
+    fn is_ready(&self) -> bool {
+        self.sink.is_ready()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.sink.name()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.sink.as_any()
+    }
+}
+
+impl fmt::Debug for SharedSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SharedSink {{ clients: {}, ... }}", Arc::strong_count(&self.sink))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedSink;
+    use crate::sinks::core::MetricSink;
+    use crate::sinks::{NopMetricSink, QueuingMetricSink, SpyMetricSink};
+
+    #[test]
+    fn test_shared_metric_sink_emit() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = SharedSink::new(spy);
+        let other = sink.clone();
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(8, other.emit("bar:12|g").unwrap());
+
+        assert_eq!(b"foo:54|c", rx.recv().unwrap().as_slice());
+        assert_eq!(b"bar:12|g", rx.recv().unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_shared_metric_sink_as_any() {
+        let sink = SharedSink::new(QueuingMetricSink::from(NopMetricSink));
+        let queuing = sink.as_any().and_then(|s| s.downcast_ref::<QueuingMetricSink>());
+
+        assert!(queuing.is_some());
+    }
+}
@@ -10,26 +10,56 @@
 
 use crate::io::MultiLineWriter;
 use crate::sinks::core::MetricSink;
+use crate::sinks::util::DEFAULT_BUFFER_SIZE;
 use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TrySendError};
+use std::any::Any;
 use std::io::{self, ErrorKind, Write};
 use std::sync::Mutex;
 
-// Default size of the buffer for buffered metric sinks, picked for
-// consistency with the UDP implementation.
-const DEFAULT_BUFFER_SIZE: usize = 512;
+/// What a bounded `SpyMetricSink` does when its channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Silently discard the metric being emitted. `.emit()` returns
+    /// zero bytes written in this case.
+    Drop,
+    /// Block the caller of `.emit()` until the receiver makes room in
+    /// the channel. Only use this when another thread is reading from the
+    /// receiver, otherwise the caller will block forever.
+    Block,
+}
 
 /// `MetricSink` implementation that writes all metrics to the `Sender` half of
 /// a channel while callers are given ownership of the `Receiver` half.
 ///
 /// This is not a general purpose sink, rather it's a sink meant for verifying
 /// metrics written during the course of integration tests. By default, the channel
-/// used is unbounded. The channel size can be limited using the `with_capacity` method.
+/// used is unbounded. There are a few ways to limit the size of the channel,
+/// depending on what should happen when it is full:
+///
+/// * `with_capacity` returns an error from `.emit()` for each metric that
+///   doesn't fit in the channel.
+/// * `bounded` drops metrics or blocks the caller, based on the given
+///   `OverflowPolicy`.
+/// * `ring` discards the oldest metric in the channel to make room for the
+///   newest one, which is useful for tests that only check the last few
+///   metrics emitted.
 ///
 /// Each metric is sent to the underlying channel when the `.emit()` method is
 /// called, in the thread of the caller.
 #[derive(Debug)]
 pub struct SpyMetricSink {
     sender: Sender<Vec<u8>>,
+    overflow: Overflow,
+}
+
+// Strategy for dealing with a full channel. Ring buffer behavior requires
+// a handle to the receiving half so that the oldest metric can be removed.
+#[derive(Debug)]
+enum Overflow {
+    Error,
+    Drop,
+    Block,
+    Evict(Receiver<Vec<u8>>),
 }
 
 impl SpyMetricSink {
@@ -41,16 +71,114 @@ impl SpyMetricSink {
         Self::with_queue_capacity(Some(queue))
     }
 
+    /// Create a new sink that holds at most `capacity` metrics that haven't
+    /// been read from the receiver yet.
+    ///
+    /// When the channel is full, metrics are either dropped or the caller
+    /// blocks until there is room, based on the `OverflowPolicy` given.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{MetricSink, OverflowPolicy, SpyMetricSink};
+    ///
+    /// let (rx, sink) = SpyMetricSink::bounded(1, OverflowPolicy::Drop);
+    /// sink.emit("some.counter:1|c").unwrap();
+    /// sink.emit("some.counter:2|c").unwrap();
+    ///
+    /// assert_eq!(b"some.counter:1|c", rx.recv().unwrap().as_slice());
+    /// assert!(rx.is_empty());
+    /// ```
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> (Receiver<Vec<u8>>, Self) {
+        let (tx, rx) = new_channel(Some(capacity));
+        let overflow = match policy {
+            OverflowPolicy::Drop => Overflow::Drop,
+            OverflowPolicy::Block => Overflow::Block,
+        };
+
+        let sink = SpyMetricSink { sender: tx, overflow };
+        (rx, sink)
+    }
+
+    /// Create a new sink that keeps only the `n` most recently emitted
+    /// metrics that haven't been read from the receiver yet.
+    ///
+    /// When the channel is full, the oldest metric is discarded to make
+    /// room for the metric being emitted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{MetricSink, SpyMetricSink};
+    ///
+    /// let (rx, sink) = SpyMetricSink::ring(2);
+    /// sink.emit("some.counter:1|c").unwrap();
+    /// sink.emit("some.counter:2|c").unwrap();
+    /// sink.emit("some.counter:3|c").unwrap();
+    ///
+    /// assert_eq!(b"some.counter:2|c", rx.recv().unwrap().as_slice());
+    /// assert_eq!(b"some.counter:3|c", rx.recv().unwrap().as_slice());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `n` is zero.
+    pub fn ring(n: usize) -> (Receiver<Vec<u8>>, Self) {
+        assert!(n > 0, "ring capacity must be greater than zero");
+        let (tx, rx) = new_channel(Some(n));
+        let sink = SpyMetricSink {
+            sender: tx,
+            overflow: Overflow::Evict(rx.clone()),
+        };
+        (rx, sink)
+    }
+
     fn with_queue_capacity(queue: Option<usize>) -> (Receiver<Vec<u8>>, Self) {
         let (tx, rx) = new_channel(queue);
-        let sink = SpyMetricSink { sender: tx };
+        let sink = SpyMetricSink {
+            sender: tx,
+            overflow: Overflow::Error,
+        };
         (rx, sink)
     }
 }
 
 impl MetricSink for SpyMetricSink {
     fn emit(&self, metric: &str) -> io::Result<usize> {
-        send_metric(&self.sender, metric.as_bytes())
+        match self.overflow {
+            Overflow::Error => send_metric(&self.sender, metric.as_bytes()),
+            Overflow::Drop => match self.sender.try_send(metric.as_bytes().to_vec()) {
+                Err(TrySendError::Disconnected(_)) => Err(io::Error::new(ErrorKind::Other, "channel disconnected")),
+                Err(TrySendError::Full(_)) => Ok(0),
+                Ok(_) => Ok(metric.len()),
+            },
+            Overflow::Block => match self.sender.send(metric.as_bytes().to_vec()) {
+                Err(_) => Err(io::Error::new(ErrorKind::Other, "channel disconnected")),
+                Ok(_) => Ok(metric.len()),
+            },
+            Overflow::Evict(ref receiver) => {
+                let mut val = metric.as_bytes().to_vec();
+                loop {
+                    match self.sender.try_send(val) {
+                        Err(TrySendError::Disconnected(_)) => {
+                            return Err(io::Error::new(ErrorKind::Other, "channel disconnected"))
+                        }
+                        Err(TrySendError::Full(v)) => {
+                            // Make room by discarding the oldest metric. Another
+                            // thread may have made room already so we don't care
+                            // if there was anything to receive.
+                            let _ = receiver.try_recv();
+                            val = v;
+                        }
+                        Ok(_) => return Ok(metric.len()),
+                    }
+                }
+            }
+        }
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
     }
 }
 
@@ -111,6 +239,10 @@ impl MetricSink for BufferedSpyMetricSink {
         let mut writer = self.writer.lock().unwrap();
         writer.flush()
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
 #[derive(Debug)]
@@ -152,7 +284,8 @@ fn send_metric(sender: &Sender<Vec<u8>>, metric: &[u8]) -> io::Result<usize> {
 
 #[cfg(test)]
 mod test {
-    use super::{BufferedSpyMetricSink, MetricSink, SpyMetricSink};
+    use super::{BufferedSpyMetricSink, MetricSink, OverflowPolicy, SpyMetricSink};
+    use std::thread;
 
     #[test]
     fn test_spy_metric_sink() {
@@ -163,6 +296,65 @@ mod test {
         assert_eq!(b"buz:1|c", sent.as_slice());
     }
 
+    #[test]
+    fn test_spy_metric_sink_with_capacity_full() {
+        let (_rx, sink) = SpyMetricSink::with_capacity(1);
+        sink.emit("buz:1|c").unwrap();
+
+        assert!(sink.emit("buz:2|c").is_err());
+    }
+
+    #[test]
+    fn test_spy_metric_sink_bounded_drop() {
+        let (rx, sink) = SpyMetricSink::bounded(2, OverflowPolicy::Drop);
+
+        assert_eq!(7, sink.emit("buz:1|c").unwrap());
+        assert_eq!(7, sink.emit("buz:2|c").unwrap());
+        assert_eq!(0, sink.emit("buz:3|c").unwrap());
+
+        assert_eq!(b"buz:1|c", rx.recv().unwrap().as_slice());
+        assert_eq!(b"buz:2|c", rx.recv().unwrap().as_slice());
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn test_spy_metric_sink_bounded_block() {
+        let (rx, sink) = SpyMetricSink::bounded(1, OverflowPolicy::Block);
+        let t = thread::spawn(move || {
+            for i in 0..10 {
+                sink.emit(&format!("buz:{}|c", i)).unwrap();
+            }
+        });
+
+        let received: Vec<Vec<u8>> = rx.iter().take(10).collect();
+        t.join().unwrap();
+
+        assert_eq!(10, received.len());
+        assert_eq!(b"buz:9|c", received[9].as_slice());
+    }
+
+    #[test]
+    fn test_spy_metric_sink_bounded_disconnected() {
+        let (rx, sink) = SpyMetricSink::bounded(1, OverflowPolicy::Block);
+        drop(rx);
+
+        assert!(sink.emit("buz:1|c").is_err());
+    }
+
+    #[test]
+    fn test_spy_metric_sink_ring() {
+        let (rx, sink) = SpyMetricSink::ring(3);
+        for i in 0..10 {
+            sink.emit(&format!("buz:{}|c", i)).unwrap();
+        }
+
+        let received: Vec<Vec<u8>> = rx.try_iter().collect();
+        assert_eq!(
+            vec![b"buz:7|c".to_vec(), b"buz:8|c".to_vec(), b"buz:9|c".to_vec()],
+            received
+        );
+    }
+
     #[test]
     fn test_buffered_spy_metric_sink() {
         // Make sure the sink is dropped before checking what was written
@@ -0,0 +1,155 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::{MetricSink, SinkStats};
+use crate::sinks::util::NamedSinkError;
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::time::Duration;
+
+/// Implementation of a `MetricSink` that passes every metric to a callback
+/// before sending it with another sink.
+///
+/// This is useful for observing the metrics emitted by an application
+/// (for example, logging them while debugging) without changing where
+/// they are sent. The callback is invoked with each metric in the thread
+/// that emits it and results from the wrapped sink are returned unchanged,
+/// except that errors include the name of the wrapped sink if it has one.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, StatsdClient, TapMetricSink};
+///
+/// let sink = TapMetricSink::new(NopMetricSink, |metric: &str| {
+///     println!("emitting metric: {}", metric);
+/// });
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+/// ```
+pub struct TapMetricSink {
+    sink: Box<dyn MetricSink + Send + Sync + RefUnwindSafe>,
+    tap: Box<dyn Fn(&str) + Send + Sync + RefUnwindSafe>,
+}
+
+impl TapMetricSink {
+    /// Construct a new `TapMetricSink` that invokes `tap` with each metric
+    /// before sending it with `sink`.
+    pub fn new<T, F>(sink: T, tap: F) -> Self
+    where
+        T: MetricSink + Send + Sync + RefUnwindSafe + 'static,
+        F: Fn(&str) + Send + Sync + RefUnwindSafe + 'static,
+    {
+        TapMetricSink {
+            sink: Box::new(sink),
+            tap: Box::new(tap),
+        }
+    }
+}
+
+impl MetricSink for TapMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        (self.tap)(metric);
+        self.sink
+            .emit(metric)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        for metric in metrics {
+            (self.tap)(metric);
+        }
+
+        self.sink
+            .emit_many(metrics)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush().map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.sink
+            .flush_with_timeout(timeout)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn is_ready(&self) -> bool {
+        self.sink.is_ready()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl fmt::Debug for TapMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TapMetricSink {{ ... }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TapMetricSink;
+    use crate::sinks::core::MetricSink;
+    use crate::sinks::SpyMetricSink;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_tap_metric_sink_emit() {
+        let tapped = Arc::new(Mutex::new(Vec::new()));
+        let tapped_ref = tapped.clone();
+
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = TapMetricSink::new(spy, move |metric: &str| {
+            tapped_ref.lock().unwrap().push(metric.to_string());
+        });
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(16, sink.emit_many(&["foo:67|c", "bar:12|g"]).unwrap());
+
+        assert_eq!(
+            vec!["foo:54|c".to_string(), "foo:67|c".to_string(), "bar:12|g".to_string()],
+            *tapped.lock().unwrap()
+        );
+        assert_eq!(b"foo:54|c", rx.recv().unwrap().as_slice());
+        assert_eq!(b"foo:67|c", rx.recv().unwrap().as_slice());
+        assert_eq!(b"bar:12|g", rx.recv().unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_tap_metric_sink_emit_error() {
+        struct ErrorSink;
+
+        impl MetricSink for ErrorSink {
+            fn emit(&self, _metric: &str) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::TimedOut))
+            }
+        }
+
+        let tapped = Arc::new(Mutex::new(Vec::new()));
+        let tapped_ref = tapped.clone();
+        let sink = TapMetricSink::new(ErrorSink, move |metric: &str| {
+            tapped_ref.lock().unwrap().push(metric.to_string());
+        });
+
+        let err = sink.emit("foo:54|c").unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!(vec!["foo:54|c".to_string()], *tapped.lock().unwrap());
+    }
+}
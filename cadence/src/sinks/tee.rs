@@ -0,0 +1,168 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::{MetricSink, SinkStats};
+use crate::sinks::ring::RingBufferMetricSink;
+use crate::sinks::util::NamedSinkError;
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Implementation of a `MetricSink` that sends every metric with a primary
+/// sink while also keeping the most recent metrics in memory.
+///
+/// This allows live debugging of the metrics sent by an application, for
+/// example by exposing the last `capacity` metrics from an admin endpoint,
+/// without changing where they are sent. Metrics are recorded before being
+/// sent with the primary sink and are kept even when the primary sink fails
+/// to send them. Results from the primary sink are returned unchanged, except
+/// that errors include the name of the primary sink if it has one.
+///
+/// The recent metrics are kept with a `RingBufferMetricSink`, which can be
+/// used directly when metrics only need to be kept in memory.
+///
+/// Clones of the sink share the same primary sink and recent metrics. Keep a
+/// clone of the sink before passing it to a client in order to call
+/// `.recent()` later.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{NopMetricSink, StatsdClient, TeeMetricSink};
+///
+/// let sink = TeeMetricSink::new(NopMetricSink, 100);
+/// let client = StatsdClient::from_sink("my.prefix", sink.clone());
+///
+/// client.count("some.counter", 1).unwrap();
+///
+/// assert_eq!(vec!["my.prefix.some.counter:1|c"], sink.recent());
+/// ```
+#[derive(Clone)]
+pub struct TeeMetricSink {
+    sink: Arc<dyn MetricSink + Send + Sync + RefUnwindSafe>,
+    recent: RingBufferMetricSink,
+}
+
+impl TeeMetricSink {
+    /// Construct a new `TeeMetricSink` that sends each metric with `sink`
+    /// and keeps the most recent `capacity` metrics.
+    pub fn new<T>(sink: T, capacity: usize) -> Self
+    where
+        T: MetricSink + Send + Sync + RefUnwindSafe + 'static,
+    {
+        TeeMetricSink {
+            sink: Arc::new(sink),
+            recent: RingBufferMetricSink::new(capacity),
+        }
+    }
+
+    /// Return a copy of the most recent metrics emitted, from oldest to
+    /// newest.
+    pub fn recent(&self) -> Vec<String> {
+        self.recent.snapshot()
+    }
+}
+
+impl MetricSink for TeeMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let _ = self.recent.emit(metric);
+        self.sink
+            .emit(metric)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        let _ = self.recent.emit_many(metrics);
+        self.sink
+            .emit_many(metrics)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush().map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.sink
+            .flush_with_timeout(timeout)
+            .map_err(|e| NamedSinkError::wrap(self.sink.name(), e))
+    }
+
+    fn is_ready(&self) -> bool {
+        self.sink.is_ready()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl fmt::Debug for TeeMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TeeMetricSink {{ recent: {:?}, ... }}", self.recent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TeeMetricSink;
+    use crate::sinks::core::MetricSink;
+    use crate::sinks::SpyMetricSink;
+    use crate::test::ErrorMetricSink;
+    use std::io;
+
+    #[test]
+    fn test_tee_metric_sink_emit() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = TeeMetricSink::new(spy, 2);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(16, sink.emit_many(&["foo:67|c", "bar:12|g"]).unwrap());
+
+        assert_eq!(vec!["foo:67|c", "bar:12|g"], sink.recent());
+        assert_eq!(b"foo:54|c", rx.recv().unwrap().as_slice());
+        assert_eq!(b"foo:67|c", rx.recv().unwrap().as_slice());
+        assert_eq!(b"bar:12|g", rx.recv().unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_tee_metric_sink_emit_error() {
+        struct ErrorSink;
+
+        impl MetricSink for ErrorSink {
+            fn emit(&self, _metric: &str) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::TimedOut))
+            }
+        }
+
+        let sink = TeeMetricSink::new(ErrorSink, 2);
+
+        let err = sink.emit("foo:54|c").unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!(vec!["foo:54|c"], sink.recent());
+    }
+
+    #[test]
+    fn test_tee_metric_sink_emit_error_named() {
+        let sink = TeeMetricSink::new(ErrorMetricSink::always().with_name("primary"), 2);
+
+        let err = sink.emit("foo:54|c").unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!("sink primary: timed out", err.to_string());
+    }
+}
@@ -0,0 +1,243 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sinks::core::MetricSink;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::io;
+use tracing::Level;
+
+// Emit an event at a level only known at runtime. The level of each use of
+// `tracing::event!` must be a constant so there's one per level.
+macro_rules! event_at_level {
+    ($level:expr, $($fields:tt)+) => {
+        match $level {
+            Level::ERROR => ::tracing::event!(Level::ERROR, $($fields)+),
+            Level::WARN => ::tracing::event!(Level::WARN, $($fields)+),
+            Level::INFO => ::tracing::event!(Level::INFO, $($fields)+),
+            Level::DEBUG => ::tracing::event!(Level::DEBUG, $($fields)+),
+            Level::TRACE => ::tracing::event!(Level::TRACE, $($fields)+),
+        }
+    };
+}
+
+/// Parts of a metric string recorded as fields of a `tracing` event.
+#[derive(Debug, PartialEq)]
+struct ParsedMetric<'a> {
+    name: &'a str,
+    value: &'a str,
+    type_: &'a str,
+    tags: BTreeMap<&'a str, &'a str>,
+}
+
+// Split a metric into its name, value (multiple values are left packed
+// together, e.g. `1:2:3`), type, and tags. Tags with only a value are
+// included with an empty value. Other sections of the metric (sampling
+// rate, container ID, etc.) are ignored.
+fn parse_metric(metric: &str) -> Option<ParsedMetric<'_>> {
+    let mut sections = metric.split('|');
+    let (name, value) = sections.next()?.split_once(':')?;
+    let type_ = sections.next()?;
+    if name.is_empty() || value.is_empty() || type_.is_empty() {
+        return None;
+    }
+
+    let mut tags = BTreeMap::new();
+    for section in sections {
+        if let Some(tag_list) = section.strip_prefix('#') {
+            for tag in tag_list.split(',').filter(|t| !t.is_empty()) {
+                match tag.split_once(':') {
+                    Some((k, v)) => tags.insert(k, v),
+                    None => tags.insert(tag, ""),
+                };
+            }
+        }
+    }
+
+    Some(ParsedMetric {
+        name,
+        value,
+        type_,
+        tags,
+    })
+}
+
+/// Implementation of a `MetricSink` that emits each metric as a structured
+/// `tracing` event instead of sending it to a Statsd server.
+///
+/// Each metric results in an event at the configured level with the fields
+/// `name`, `value`, `type`, and `tags`. Multiple values of a single metric are
+/// recorded together as the `value` (e.g. `1:2:3`) and `tags` is recorded as
+/// a map of tag keys to values, using an empty value for tags with only a
+/// value. This allows metrics to flow into a structured logging or
+/// observability pipeline built on `tracing`, which is more useful than
+/// logging metric strings as-is. Metrics that can't be parsed result in an
+/// error with the kind `io::ErrorKind::InvalidInput`.
+///
+/// This sink is only available when the optional `tracing` feature is enabled.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{StatsdClient, TracingMetricSink};
+/// use tracing::Level;
+///
+/// let sink = TracingMetricSink::new(Level::DEBUG);
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct TracingMetricSink {
+    level: Level,
+}
+
+impl TracingMetricSink {
+    /// Construct a new `TracingMetricSink` that emits an event at the given
+    /// level for each metric.
+    pub fn new(level: Level) -> Self {
+        TracingMetricSink { level }
+    }
+}
+
+impl Default for TracingMetricSink {
+    /// Construct a new `TracingMetricSink` that emits events at the `INFO` level.
+    fn default() -> Self {
+        Self::new(Level::INFO)
+    }
+}
+
+impl MetricSink for TracingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let parsed = parse_metric(metric)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unable to parse metric"))?;
+
+        event_at_level!(
+            self.level,
+            name = parsed.name,
+            value = parsed.value,
+            r#type = parsed.type_,
+            tags = ?parsed.tags,
+        );
+
+        Ok(metric.len())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_metric, ParsedMetric, TracingMetricSink};
+    use crate::sinks::core::MetricSink;
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata, Subscriber};
+
+    type RecordedEvents = Arc<Mutex<Vec<(Level, BTreeMap<String, String>)>>>;
+
+    // Subscriber that records the level and fields of each event.
+    struct RecordingSubscriber {
+        events: RecordedEvents,
+    }
+
+    struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+    impl<'a> Visit for FieldVisitor<'a> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for RecordingSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = BTreeMap::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            self.events.lock().unwrap().push((*event.metadata().level(), fields));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_parse_metric() {
+        let mut tags = BTreeMap::new();
+        tags.insert("env", "prod");
+        tags.insert("beta", "");
+
+        assert_eq!(
+            Some(ParsedMetric {
+                name: "some.timer",
+                value: "12:13",
+                type_: "ms",
+                tags,
+            }),
+            parse_metric("some.timer:12:13|ms|@0.5|#env:prod,beta|c:1234")
+        );
+    }
+
+    #[test]
+    fn test_parse_metric_invalid() {
+        assert_eq!(None, parse_metric("some.counter"));
+        assert_eq!(None, parse_metric("some.counter:1"));
+        assert_eq!(None, parse_metric(":1|c"));
+        assert_eq!(None, parse_metric("some.counter:|c"));
+    }
+
+    #[test]
+    fn test_tracing_metric_sink_emit() {
+        let events = RecordedEvents::default();
+        let subscriber = RecordingSubscriber { events: events.clone() };
+        let sink = TracingMetricSink::new(Level::DEBUG);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(27, sink.emit("foo.bar:54|c|#env:prod,beta").unwrap());
+        });
+
+        let events = events.lock().unwrap();
+        let (level, fields) = &events[0];
+        assert_eq!(1, events.len());
+        assert_eq!(Level::DEBUG, *level);
+        assert_eq!("foo.bar", fields["name"]);
+        assert_eq!("54", fields["value"]);
+        assert_eq!("c", fields["type"]);
+        assert_eq!(r#"{"beta": "", "env": "prod"}"#, fields["tags"]);
+    }
+
+    #[test]
+    fn test_tracing_metric_sink_emit_invalid() {
+        let sink = TracingMetricSink::default();
+        let err = sink.emit("foo.bar").unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+}
@@ -8,38 +8,138 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
 use std::io;
 use std::io::Write;
 use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
-use std::sync::Mutex;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
+use crate::clock::{Clock, SystemClock};
 use crate::io::MultiLineWriter;
 use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+use crate::sinks::util::{fits_in_datagram, sum_or_first_error, PacketTooLarge, DEFAULT_BUFFER_SIZE, MAX_UDP_PAYLOAD};
 use crate::types::{ErrorKind, MetricError, MetricResult};
 
-// Default size of the buffer for buffered metric sinks. This
-// is a rather conservative value, picked to make sure the entire
-// buffer fits in a small UDP packet. Users may want to use a
-// different value based on the configuration of the network
-// their application runs in.
-const DEFAULT_BUFFER_SIZE: usize = 512;
-
 /// Attempt to convert anything implementing the `ToSocketAddrs` trait
-/// into a concrete `SocketAddr` instance, returning an `InvalidInput`
-/// error if the address could not be parsed.
+/// into a concrete `SocketAddr` instance, returning an `AddrResolution`
+/// error if the address could not be parsed or resolved.
 // Public portion of the API (the sink constructors) is pass by value so
 // there's no point in changing this to be pass by reference yet.
 #[allow(clippy::needless_pass_by_value)]
-fn get_addr<A: ToSocketAddrs>(addr: A) -> MetricResult<SocketAddr> {
-    match addr.to_socket_addrs()?.next() {
+pub(crate) fn get_addr<A: ToSocketAddrs>(addr: A) -> MetricResult<SocketAddr> {
+    let mut addrs = addr.to_socket_addrs().map_err(|e| {
+        MetricError::from((
+            ErrorKind::AddrResolution,
+            "unable to resolve the address of the metric server",
+            Box::new(e) as Box<dyn Error + Send + Sync>,
+        ))
+    })?;
+
+    match addrs.next() {
         Some(addr) => Ok(addr),
         None => Err(MetricError::from((
-            ErrorKind::InvalidInput,
-            "No socket addresses yielded",
+            ErrorKind::AddrResolution,
+            "no addresses found for the metric server",
         ))),
     }
 }
 
+/// Bind a non-blocking UDP socket to an ephemeral port on all interfaces, using
+/// the same address family (IPv4 or IPv6) as the address metrics will be sent to.
+pub(crate) fn bind_nonblocking(addr: &SocketAddr) -> io::Result<UdpSocket> {
+    let local = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Address of the metric server that metrics are sent to, either resolved
+/// once or looked up again periodically.
+#[derive(Debug)]
+enum Target {
+    Fixed(SocketAddr),
+    Refreshing(Refresher),
+}
+
+impl Target {
+    fn addr(&self) -> SocketAddr {
+        match self {
+            Target::Fixed(addr) => *addr,
+            Target::Refreshing(refresher) => refresher.addr(),
+        }
+    }
+}
+
+/// Resolves the address of the metric server again once a given amount of
+/// time has passed since it was last resolved.
+///
+/// Resolution happens in the thread of the caller that notices the address
+/// is out of date. Only a single caller resolves the address at a time, any
+/// others continue to use the previous address until it has been updated.
+struct Refresher {
+    resolve: Box<dyn Fn() -> MetricResult<SocketAddr> + Send + Sync + RefUnwindSafe>,
+    interval: Duration,
+    clock: Box<dyn Clock>,
+    current: RwLock<(SocketAddr, Instant)>,
+    refreshing: AtomicBool,
+}
+
+impl Refresher {
+    fn new<A, C>(to_addr: A, interval: Duration, clock: C) -> MetricResult<Refresher>
+    where
+        A: ToSocketAddrs + Send + Sync + RefUnwindSafe + 'static,
+        C: Clock + 'static,
+    {
+        let resolve = move || get_addr(&to_addr);
+        let addr = resolve()?;
+        let now = clock.now();
+
+        Ok(Refresher {
+            resolve: Box::new(resolve),
+            interval,
+            clock: Box::new(clock),
+            current: RwLock::new((addr, now)),
+            refreshing: AtomicBool::new(false),
+        })
+    }
+
+    fn addr(&self) -> SocketAddr {
+        let (addr, resolved_at) = *self.current.read().unwrap();
+        let now = self.clock.now();
+
+        if now.saturating_duration_since(resolved_at) < self.interval
+            || self
+                .refreshing
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            return addr;
+        }
+
+        // If the name can't be resolved right now, keep using the previous
+        // address and try again once another interval has passed.
+        let new_addr = (self.resolve)().unwrap_or(addr);
+        *self.current.write().unwrap() = (new_addr, now);
+        self.refreshing.store(false, Ordering::Release);
+        new_addr
+    }
+}
+
+impl fmt::Debug for Refresher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Refresher {{ resolve: ..., interval: {:?}, clock: {:?}, current: {:?} }}",
+            self.interval, self.clock, self.current
+        )
+    }
+}
+
 /// Implementation of a `MetricSink` that emits metrics over UDP.
 ///
 /// This is the most basic version of `MetricSink` that sends metrics over
@@ -50,7 +150,7 @@ fn get_addr<A: ToSocketAddrs>(addr: A) -> MetricResult<SocketAddr> {
 /// called, in the thread of the caller.
 #[derive(Debug)]
 pub struct UdpMetricSink {
-    addr: SocketAddr,
+    target: Target,
     socket: UdpSocket,
     stats: SocketStats,
 }
@@ -97,46 +197,171 @@ impl UdpMetricSink {
     ///
     /// This method may fail if:
     ///
-    /// * It is unable to resolve the hostname of the metric server.
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
     /// * The host address is otherwise unable to be parsed
     pub fn from<A>(to_addr: A, socket: UdpSocket) -> MetricResult<UdpMetricSink>
     where
         A: ToSocketAddrs,
     {
         let addr = get_addr(to_addr)?;
+        Ok(Self::from_socket_addr(addr, socket))
+    }
+
+    /// Construct a new `UdpMetricSink` instance with a socket bound to the
+    /// given local address.
+    ///
+    /// This is useful on hosts with multiple network interfaces to control
+    /// which interface metrics are sent from, without having to create the
+    /// socket separately. The socket is left in blocking mode. Use the `from`
+    /// method with a socket that's already bound when any other configuration
+    /// of the socket is needed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{UdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = UdpMetricSink::from_with_bind(host, "10.0.0.12:0");
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
+    /// * The host address is otherwise unable to be parsed
+    /// * It is unable to bind a socket to the local address (`ErrorKind::IoError`).
+    pub fn from_with_bind<A, B>(to_addr: A, bind_addr: B) -> MetricResult<UdpMetricSink>
+    where
+        A: ToSocketAddrs,
+        B: ToSocketAddrs,
+    {
+        let addr = get_addr(to_addr)?;
+        let socket = UdpSocket::bind(bind_addr)?;
+        Ok(Self::from_socket_addr(addr, socket))
+    }
+
+    /// Construct a new `UdpMetricSink` instance from an address that has
+    /// already been resolved.
+    ///
+    /// Unlike the `from` method, this never performs any name resolution
+    /// and so cannot fail. This allows callers to control how and when the
+    /// address of the metric server is looked up. The socket should already
+    /// be bound to a local address with any desired configuration applied
+    /// (blocking vs non-blocking, timeouts, etc.).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::{SocketAddr, UdpSocket};
+    /// use cadence::{UdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let addr = SocketAddr::from(([10, 0, 0, 5], DEFAULT_PORT));
+    /// let sink = UdpMetricSink::from_socket_addr(addr, socket);
+    /// ```
+    pub fn from_socket_addr(addr: SocketAddr, socket: UdpSocket) -> UdpMetricSink {
+        Self::from_target(Target::Fixed(addr), socket)
+    }
+
+    /// Construct a new `UdpMetricSink` instance that looks up the address of
+    /// the metric server again each time the given interval passes.
+    ///
+    /// This is useful when the metric server is a DNS name whose addresses
+    /// change over time, such as a service behind a load balancer. Otherwise,
+    /// the address is only resolved once when the sink is created and metrics
+    /// will be sent to a stale address after it changes.
+    ///
+    /// The address is resolved again in the thread of a caller of `.emit()`
+    /// which means that emitting a metric may occasionally block for as long
+    /// as it takes to resolve the name. Wrapping this sink in a
+    /// `QueuingMetricSink` moves this work off of application threads. If the
+    /// name can't be resolved, the previous address continues to be used until
+    /// the next interval.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::UdpSocket;
+    /// use std::time::Duration;
+    /// use cadence::{UdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = UdpMetricSink::with_refresh(host, socket, Duration::from_secs(60));
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
+    /// * The host address is otherwise unable to be parsed
+    pub fn with_refresh<A>(to_addr: A, socket: UdpSocket, interval: Duration) -> MetricResult<UdpMetricSink>
+    where
+        A: ToSocketAddrs + Send + Sync + RefUnwindSafe + 'static,
+    {
+        let refresher = Refresher::new(to_addr, interval, SystemClock)?;
+        Ok(Self::from_target(Target::Refreshing(refresher), socket))
+    }
+
+    fn from_target(target: Target, socket: UdpSocket) -> UdpMetricSink {
         let stats = SocketStats::default();
-        Ok(UdpMetricSink { addr, socket, stats })
+        UdpMetricSink { target, socket, stats }
     }
 }
 
 impl MetricSink for UdpMetricSink {
     fn emit(&self, metric: &str) -> io::Result<usize> {
+        if !fits_in_datagram(metric, MAX_UDP_PAYLOAD) {
+            return self
+                .stats
+                .update(Err(PacketTooLarge::error(metric, MAX_UDP_PAYLOAD)), metric.len());
+        }
+
         self.stats
-            .update(self.socket.send_to(metric.as_bytes(), self.addr), metric.len())
+            .update(self.socket.send_to(metric.as_bytes(), self.target.addr()), metric.len())
     }
 
     fn stats(&self) -> SinkStats {
         (&self.stats).into()
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
+type FlushObserver = dyn Fn(&[u8]) + Send + Sync + RefUnwindSafe;
+
 /// Adapter for writing to a `UdpSocket` via the `Write` trait
-#[derive(Debug)]
 pub(crate) struct UdpWriteAdapter {
-    addr: SocketAddr,
+    target: Target,
     socket: UdpSocket,
     stats: SocketStats,
+    observer: Option<Arc<FlushObserver>>,
 }
 
 impl UdpWriteAdapter {
-    pub(crate) fn new(addr: SocketAddr, socket: UdpSocket, stats: SocketStats) -> UdpWriteAdapter {
-        UdpWriteAdapter { addr, socket, stats }
+    fn new(target: Target, socket: UdpSocket, stats: SocketStats) -> UdpWriteAdapter {
+        UdpWriteAdapter {
+            target,
+            socket,
+            stats,
+            observer: None,
+        }
     }
 }
 
 impl Write for UdpWriteAdapter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.stats.update(self.socket.send_to(buf, self.addr), buf.len())
+        if let Some(ref observer) = self.observer {
+            observer(buf);
+        }
+
+        self.stats
+            .update(self.socket.send_to(buf, self.target.addr()), buf.len())
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -144,6 +369,148 @@ impl Write for UdpWriteAdapter {
     }
 }
 
+impl fmt::Debug for UdpWriteAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpWriteAdapter")
+            .field("target", &self.target)
+            .field("socket", &self.socket)
+            .field("stats", &self.stats)
+            .field("observer", &self.observer.as_ref().map(|_| "..."))
+            .finish()
+    }
+}
+
+/// Implementation of a builder pattern for `BufferedUdpMetricSink`.
+///
+/// The builder can be used to set the size of the buffer used by the sink
+/// as well as the maximum size of datagrams the sink is allowed to send.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{BufferedUdpMetricSinkBuilder, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let host = ("metrics.example.com", DEFAULT_PORT);
+/// let sink = BufferedUdpMetricSinkBuilder::new()
+///     .with_capacity(8192)
+///     .with_max_datagram_size(1432)
+///     .build(host, socket)
+///     .unwrap();
+/// ```
+#[derive(Default, Clone)]
+pub struct BufferedUdpMetricSinkBuilder {
+    capacity: Option<usize>,
+    max_datagram_size: Option<usize>,
+    expected_metric_size: Option<usize>,
+    flush_observer: Option<Arc<FlushObserver>>,
+}
+
+impl BufferedUdpMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a new `BufferedUdpMetricSink` instance based on the builder
+    /// configuration.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
+    /// * The host address is otherwise unable to be parsed
+    pub fn build<A>(self, sink_addr: A, socket: UdpSocket) -> MetricResult<BufferedUdpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = get_addr(sink_addr)?;
+        Ok(BufferedUdpMetricSink::from_builder(Target::Fixed(addr), socket, self))
+    }
+
+    /// Set the size of the buffer metrics are stored in before being sent.
+    ///
+    /// See `BufferedUdpMetricSink::with_capacity` for more information. If a
+    /// maximum datagram size is also set, the buffer will be no larger than it.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Set the largest datagram, in bytes, that the sink will ever send.
+    ///
+    /// By default, a single metric bigger than the buffer is sent as its own
+    /// datagram as long as it fits in a UDP datagram at all (`MAX_UDP_PAYLOAD`).
+    /// Datagrams bigger than the MTU of the network between the client and the
+    /// metric server may be fragmented or silently dropped. When this is set,
+    /// metrics are buffered such that no datagram is ever larger than `size`
+    /// and a single metric bigger than `size` is rejected with an
+    /// `io::ErrorKind::InvalidInput` error instead of being sent. When returned
+    /// by a `StatsdClient`, this is an `ErrorKind::PacketTooLarge` error.
+    pub fn with_max_datagram_size(mut self, size: usize) -> Self {
+        self.max_datagram_size = Some(size);
+        self
+    }
+
+    /// Set the typical size, in bytes, of a single metric sent by the sink.
+    ///
+    /// By default, the buffer is only sent once a metric that doesn't fit in
+    /// it is emitted. When this is set, the buffer is sent as soon as there
+    /// isn't room left for another metric of the expected size. This keeps
+    /// metrics from sitting in a nearly full buffer until the next one is
+    /// emitted while still filling each datagram as much as possible.
+    pub fn with_expected_metric_size(mut self, size: usize) -> Self {
+        self.expected_metric_size = Some(size);
+        self
+    }
+
+    /// Set a function to be invoked with the contents of each datagram right
+    /// before it's sent.
+    ///
+    /// The function is called with the buffer of the sink directly, without
+    /// copying it, while the sink is locked. This allows callers to inspect,
+    /// checksum, or mirror exactly what's sent to the server (e.g. for
+    /// debugging or auditing) without wrapping the socket. Metrics bigger
+    /// than the buffer that are sent by themselves are passed to the function
+    /// as well. The function is invoked even if sending the datagram fails
+    /// and should return quickly since it delays every other metric emitted
+    /// with the sink.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::UdpSocket;
+    /// use cadence::{BufferedUdpMetricSinkBuilder, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = BufferedUdpMetricSinkBuilder::new()
+    ///     .with_flush_observer(|buf: &[u8]| eprintln!("sending {} bytes", buf.len()))
+    ///     .build(host, socket)
+    ///     .unwrap();
+    /// ```
+    pub fn with_flush_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&[u8]) + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.flush_observer = Some(Arc::new(observer));
+        self
+    }
+}
+
+impl fmt::Debug for BufferedUdpMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedUdpMetricSinkBuilder")
+            .field("capacity", &self.capacity)
+            .field("max_datagram_size", &self.max_datagram_size)
+            .field("expected_metric_size", &self.expected_metric_size)
+            .field("flush_observer", &self.flush_observer.as_ref().map(|_| "..."))
+            .finish()
+    }
+}
+
 /// Implementation of a `MetricSink` that buffers metrics before
 /// sending them to a UDP socket.
 ///
@@ -151,7 +518,9 @@ impl Write for UdpWriteAdapter {
 /// after each metric written to this sink. When the buffer is sufficiently
 /// full and a write is attempted, the contents of the buffer are flushed to
 /// a UDP socket and then the metric is written to the buffer. The buffer is
-/// also flushed when this sink is destroyed.
+/// also flushed when this sink is destroyed, including when it's wrapped by a
+/// `QueuingMetricSink` that is destroyed. Errors flushing the buffer at that
+/// point are ignored since they can't be returned to the caller.
 ///
 /// The default size of the buffer is 512 bytes. This is the "safest"
 /// size for a UDP packet according to the Etsy Statsd docs. The
@@ -159,7 +528,9 @@ impl Write for UdpWriteAdapter {
 /// to create the sink if desired.
 ///
 /// If a metric larger than the buffer is emitted, it will be written
-/// directly to the underlying UDP socket, bypassing the buffer.
+/// directly to the underlying UDP socket, bypassing the buffer. Metrics
+/// larger than `MAX_UDP_PAYLOAD` (or the maximum datagram size, if set)
+/// are rejected with an `ErrorKind::PacketTooLarge` error instead.
 ///
 /// Note that since metrics are buffered until a certain size is reached, it's
 /// possible that they may sit in the buffer for a while for applications
@@ -169,10 +540,16 @@ impl Write for UdpWriteAdapter {
 #[derive(Debug)]
 pub struct BufferedUdpMetricSink {
     buffer: Mutex<MultiLineWriter<UdpWriteAdapter>>,
+    max_datagram_size: Option<usize>,
     stats: SocketStats,
 }
 
 impl BufferedUdpMetricSink {
+    /// Construct a new builder for `BufferedUdpMetricSink`.
+    pub fn builder() -> BufferedUdpMetricSinkBuilder {
+        BufferedUdpMetricSinkBuilder::new()
+    }
+
     /// Construct a new `BufferedUdpMetricSink` instance with a default
     /// buffer size of 512 bytes.
     ///
@@ -201,7 +578,7 @@ impl BufferedUdpMetricSink {
     ///
     /// This method may fail if:
     ///
-    /// * It is unable to resolve the hostname of the metric server.
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
     /// * The host address is otherwise unable to be parsed
     pub fn from<A>(sink_addr: A, socket: UdpSocket) -> MetricResult<BufferedUdpMetricSink>
     where
@@ -240,28 +617,171 @@ impl BufferedUdpMetricSink {
     ///
     /// This method may fail if:
     ///
-    /// * It is unable to resolve the hostname of the metric server.
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
     /// * The host address is otherwise unable to be parsed
     pub fn with_capacity<A>(sink_addr: A, socket: UdpSocket, cap: usize) -> MetricResult<BufferedUdpMetricSink>
     where
         A: ToSocketAddrs,
     {
         let addr = get_addr(sink_addr)?;
+        Ok(Self::with_capacity_socket_addr(addr, socket, cap))
+    }
+
+    /// Construct a new `BufferedUdpMetricSink` instance with a default buffer
+    /// size of 512 bytes from an address that has already been resolved.
+    ///
+    /// Unlike the `from` method, this never performs any name resolution and
+    /// so cannot fail. This allows callers to control how and when the address
+    /// of the metric server is looked up.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::{SocketAddr, UdpSocket};
+    /// use cadence::{BufferedUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let addr = SocketAddr::from(([10, 0, 0, 5], DEFAULT_PORT));
+    /// let sink = BufferedUdpMetricSink::from_socket_addr(addr, socket);
+    /// ```
+    pub fn from_socket_addr(addr: SocketAddr, socket: UdpSocket) -> BufferedUdpMetricSink {
+        Self::with_capacity_socket_addr(addr, socket, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Construct a new `BufferedUdpMetricSink` instance with a custom buffer
+    /// size from an address that has already been resolved.
+    ///
+    /// Unlike the `with_capacity` method, this never performs any name resolution
+    /// and so cannot fail.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::{SocketAddr, UdpSocket};
+    /// use cadence::{BufferedUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let addr = SocketAddr::from(([10, 0, 0, 5], DEFAULT_PORT));
+    /// let sink = BufferedUdpMetricSink::with_capacity_socket_addr(addr, socket, 1432);
+    /// ```
+    pub fn with_capacity_socket_addr(addr: SocketAddr, socket: UdpSocket, cap: usize) -> BufferedUdpMetricSink {
+        Self::from_builder(
+            Target::Fixed(addr),
+            socket,
+            BufferedUdpMetricSinkBuilder::new().with_capacity(cap),
+        )
+    }
+
+    /// Construct a new `BufferedUdpMetricSink` instance with a custom buffer
+    /// size that looks up the address of the metric server again each time
+    /// the given interval passes.
+    ///
+    /// See `UdpMetricSink::with_refresh` for more information about how and
+    /// when the address is resolved again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::UdpSocket;
+    /// use std::time::Duration;
+    /// use cadence::{BufferedUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = BufferedUdpMetricSink::with_refresh(host, socket, 1432, Duration::from_secs(60));
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server (`ErrorKind::AddrResolution`).
+    /// * The host address is otherwise unable to be parsed
+    pub fn with_refresh<A>(
+        sink_addr: A,
+        socket: UdpSocket,
+        cap: usize,
+        interval: Duration,
+    ) -> MetricResult<BufferedUdpMetricSink>
+    where
+        A: ToSocketAddrs + Send + Sync + RefUnwindSafe + 'static,
+    {
+        let refresher = Refresher::new(sink_addr, interval, SystemClock)?;
+        Ok(Self::from_builder(
+            Target::Refreshing(refresher),
+            socket,
+            BufferedUdpMetricSinkBuilder::new().with_capacity(cap),
+        ))
+    }
+
+    /// Remove all metrics currently buffered and return them as a `String`,
+    /// one metric per line, without sending them.
+    ///
+    /// This is meant as a diagnostic escape hatch, e.g. for logging what's
+    /// pending when a sink appears stuck. Metrics returned by this method are
+    /// discarded from the send path and will never be sent by the sink.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::UdpSocket;
+    /// use cadence::{BufferedUdpMetricSink, MetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let sink = BufferedUdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+    ///
+    /// sink.emit("some.counter:1|c").unwrap();
+    /// assert_eq!("some.counter:1|c\n", sink.take_pending());
+    /// ```
+    pub fn take_pending(&self) -> String {
+        let pending = self.buffer.lock().unwrap().take_buffered();
+        String::from_utf8_lossy(&pending).into_owned()
+    }
+
+    fn from_builder(target: Target, socket: UdpSocket, builder: BufferedUdpMetricSinkBuilder) -> BufferedUdpMetricSink {
+        let max_datagram_size = builder.max_datagram_size;
+        // The buffer is sent as a single datagram when flushed so it can't be
+        // any larger than the biggest datagram we're allowed to send.
+        let cap = builder.capacity.unwrap_or(DEFAULT_BUFFER_SIZE);
+        let cap = max_datagram_size.map_or(cap, |max| cap.min(max));
         let stats = SocketStats::default();
-        Ok(BufferedUdpMetricSink {
-            buffer: Mutex::new(MultiLineWriter::new(
-                UdpWriteAdapter::new(addr, socket, stats.clone()),
-                cap,
-            )),
+
+        let mut adapter = UdpWriteAdapter::new(target, socket, stats.clone());
+        adapter.observer = builder.flush_observer;
+
+        let mut writer = MultiLineWriter::new(adapter, cap);
+        writer.set_expected_size(builder.expected_metric_size);
+
+        BufferedUdpMetricSink {
+            buffer: Mutex::new(writer),
+            max_datagram_size,
             stats,
-        })
+        }
+    }
+}
+
+impl BufferedUdpMetricSink {
+    fn write_metric(&self, writer: &mut MultiLineWriter<UdpWriteAdapter>, metric: &str) -> io::Result<usize> {
+        // Metrics larger than the buffer are sent by themselves, so they can't
+        // be larger than a datagram even if no maximum size was configured.
+        let max = self.max_datagram_size.unwrap_or(MAX_UDP_PAYLOAD);
+        if !fits_in_datagram(metric, max) {
+            return self.stats.update(Err(PacketTooLarge::error(metric, max)), metric.len());
+        }
+
+        writer.write(metric.as_bytes())
     }
 }
 
 impl MetricSink for BufferedUdpMetricSink {
     fn emit(&self, metric: &str) -> io::Result<usize> {
         let mut writer = self.buffer.lock().unwrap();
-        writer.write(metric.as_bytes())
+        self.write_metric(&mut writer, metric)
+    }
+
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        sum_or_first_error(metrics.iter().map(|m| self.write_metric(&mut writer, m)))
     }
 
     fn flush(&self) -> io::Result<()> {
@@ -269,20 +789,86 @@ impl MetricSink for BufferedUdpMetricSink {
         writer.flush()
     }
 
+    /// Flush buffered metrics with the write timeout of the socket set to `timeout`,
+    /// restoring the original write timeout afterwards.
+    ///
+    /// This only has an effect when the socket is in blocking mode. A zero
+    /// `timeout` is invalid and results in an error.
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        let mut writer = self.buffer.lock().unwrap();
+        let previous = writer.get_ref().socket.write_timeout()?;
+        writer.get_ref().socket.set_write_timeout(Some(timeout))?;
+
+        let res = writer.flush();
+        writer.get_ref().socket.set_write_timeout(previous)?;
+        res
+    }
+
     fn stats(&self) -> SinkStats {
         (&self.stats).into()
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl Drop for BufferedUdpMetricSink {
+    /// Flush any buffered metrics, ignoring errors.
+    fn drop(&mut self) {
+        let writer = self.buffer.get_mut().unwrap_or_else(|e| e.into_inner());
+        let _ = writer.flush();
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{get_addr, BufferedUdpMetricSink, MetricSink, UdpMetricSink};
-    use std::net::UdpSocket;
+    use super::{
+        get_addr, BufferedUdpMetricSink, BufferedUdpMetricSinkBuilder, MetricSink, PacketTooLarge, Refresher, Target,
+        UdpMetricSink, MAX_UDP_PAYLOAD,
+    };
+    use crate::clock::MockClock;
+    use crate::sinks::QueuingMetricSink;
+    use crate::types::ErrorKind;
+    use std::error::Error;
+    use std::io;
+    use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Name that resolves to each of a list of addresses in turn, to simulate
+    // DNS records changing over time.
+    struct ChangingAddrs {
+        addrs: Vec<SocketAddr>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ToSocketAddrs for ChangingAddrs {
+        type Iter = std::option::IntoIter<SocketAddr>;
+
+        fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(self.addrs.get(call).copied().into_iter())
+        }
+    }
+
+    fn recv_str(server: &UdpSocket) -> String {
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
 
     #[test]
     fn test_get_addr_bad_address() {
         let res = get_addr("asdf");
-        assert!(res.is_err());
+        assert_eq!(ErrorKind::AddrResolution, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_get_addr_no_addresses() {
+        let res = get_addr(Vec::<SocketAddr>::new().as_slice());
+        assert_eq!(ErrorKind::AddrResolution, res.unwrap_err().kind());
     }
 
     #[test]
@@ -298,6 +884,28 @@ mod tests {
         assert_eq!(7, sink.emit("buz:1|m").unwrap());
     }
 
+    #[test]
+    fn test_udp_metric_sink_unresolvable_host() {
+        // The .invalid TLD is reserved and guaranteed to never resolve
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let err = UdpMetricSink::from(("metrics.example.invalid", 8125), socket).unwrap_err();
+
+        assert_eq!(ErrorKind::AddrResolution, err.kind());
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn test_udp_metric_sink_packet_too_large() {
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let sink = UdpMetricSink::from("127.0.0.1:8125", socket).unwrap();
+        let metric = format!("foo:{}|s", "x".repeat(MAX_UDP_PAYLOAD));
+
+        let err = sink.emit(&metric).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert!(err.get_ref().unwrap().is::<PacketTooLarge>());
+        assert_eq!(1, sink.stats().packets_dropped);
+    }
+
     #[test]
     fn test_non_blocking_udp_metric_sink() {
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -317,6 +925,56 @@ mod tests {
         assert_eq!(8, sink.emit("foo:67|c").unwrap());
     }
 
+    #[test]
+    fn test_buffered_udp_metric_sink_flush_on_drop() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 64).unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        drop(sink);
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_flush_on_drop_queuing() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 64).unwrap();
+        let queuing = QueuingMetricSink::from(sink);
+
+        queuing.emit("foo:54|c").unwrap();
+        drop(queuing);
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_take_pending() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_nonblocking(true).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::from(server.local_addr().unwrap(), socket).unwrap();
+
+        sink.emit("foo:1|c").unwrap();
+        sink.emit("bar:2|c").unwrap();
+        assert_eq!("foo:1|c\nbar:2|c\n", sink.take_pending());
+        assert_eq!("", sink.take_pending());
+
+        // Taken metrics aren't sent when the sink is flushed
+        sink.flush().unwrap();
+        let mut buf = [0; 64];
+        assert!(server.recv(&mut buf).is_err());
+        assert_eq!(0, sink.stats().packets_sent);
+    }
+
     #[test]
     fn test_buffered_udp_metric_sink_flush() {
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -327,4 +985,271 @@ mod tests {
         assert_eq!(8, sink.emit("foo:54|c").unwrap());
         assert!(sink.flush().is_ok());
     }
+
+    #[test]
+    fn test_udp_metric_sink_from_socket_addr() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = server.local_addr().unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = UdpMetricSink::from_socket_addr(addr, socket);
+
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"buz:1|m", &buf[..len]);
+    }
+
+    #[test]
+    fn test_udp_metric_sink_from_with_bind() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = server.local_addr().unwrap();
+        let sink = UdpMetricSink::from_with_bind(addr, "127.0.0.1:0").unwrap();
+
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+
+        let mut buf = [0; 64];
+        let (len, from) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(b"buz:1|m", &buf[..len]);
+        assert!(from.ip().is_loopback());
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_from_socket_addr() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = server.local_addr().unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity_socket_addr(addr, socket, 64);
+
+        sink.emit("foo:54|c").unwrap();
+        sink.emit("foo:67|c").unwrap();
+        sink.flush().unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\nfoo:67|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_udp_metric_sink_with_refresh() {
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let sink = UdpMetricSink::with_refresh("127.0.0.1:8125", socket, Duration::from_secs(60)).unwrap();
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+    }
+
+    #[test]
+    fn test_udp_metric_sink_with_refresh_bad_address() {
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let res = UdpMetricSink::with_refresh("asdf", socket, Duration::from_secs(60));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_udp_metric_sink_refreshes_address() {
+        let server1 = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server2 = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let addrs = ChangingAddrs {
+            addrs: vec![server1.local_addr().unwrap(), server2.local_addr().unwrap()],
+            calls: calls.clone(),
+        };
+
+        let clock = MockClock::new();
+        let refresher = Refresher::new(addrs, Duration::from_secs(60), clock.clone()).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = UdpMetricSink::from_target(Target::Refreshing(refresher), socket);
+
+        sink.emit("foo:1|c").unwrap();
+        assert_eq!("foo:1|c", recv_str(&server1));
+
+        clock.advance(Duration::from_secs(30));
+        sink.emit("foo:2|c").unwrap();
+        assert_eq!("foo:2|c", recv_str(&server1));
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        clock.advance(Duration::from_secs(30));
+        sink.emit("foo:3|c").unwrap();
+        assert_eq!("foo:3|c", recv_str(&server2));
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_udp_metric_sink_refresh_failure_keeps_address() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        // Only a single address, every lookup after the first yields nothing
+        let addrs = ChangingAddrs {
+            addrs: vec![server.local_addr().unwrap()],
+            calls: calls.clone(),
+        };
+
+        let clock = MockClock::new();
+        let refresher = Refresher::new(addrs, Duration::from_secs(60), clock.clone()).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = UdpMetricSink::from_target(Target::Refreshing(refresher), socket);
+
+        clock.advance(Duration::from_secs(60));
+        sink.emit("foo:1|c").unwrap();
+        assert_eq!("foo:1|c", recv_str(&server));
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_refreshes_address() {
+        let server1 = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server2 = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addrs = ChangingAddrs {
+            addrs: vec![server1.local_addr().unwrap(), server2.local_addr().unwrap()],
+            calls: Arc::new(AtomicUsize::new(0)),
+        };
+
+        let clock = MockClock::new();
+        let refresher = Refresher::new(addrs, Duration::from_secs(60), clock.clone()).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::from_builder(
+            Target::Refreshing(refresher),
+            socket,
+            BufferedUdpMetricSinkBuilder::new().with_capacity(64),
+        );
+
+        sink.emit("foo:1|c").unwrap();
+        sink.flush().unwrap();
+        assert_eq!("foo:1|c\n", recv_str(&server1));
+
+        clock.advance(Duration::from_secs(60));
+        sink.emit("foo:2|c").unwrap();
+        sink.flush().unwrap();
+        assert_eq!("foo:2|c\n", recv_str(&server2));
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_builder_defaults() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSinkBuilder::new()
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+
+        sink.emit("foo:1|c").unwrap();
+        sink.flush().unwrap();
+        assert_eq!("foo:1|c\n", recv_str(&server));
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_max_datagram_flushes_first() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::builder()
+            .with_capacity(512)
+            .with_max_datagram_size(16)
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+
+        // Both metrics with their newlines are exactly 16 bytes and fit in a
+        // single datagram. The third would exceed it and so forces a flush.
+        sink.emit("foo:1|c").unwrap();
+        sink.emit("bar:2|c").unwrap();
+        sink.emit("baz:3|c").unwrap();
+        assert_eq!("foo:1|c\nbar:2|c\n", recv_str(&server));
+
+        sink.flush().unwrap();
+        assert_eq!("baz:3|c\n", recv_str(&server));
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_max_datagram_exact_size() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::builder()
+            .with_max_datagram_size(12)
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+
+        // Doesn't fit in the buffer along with a newline but is exactly the
+        // maximum datagram size so it's sent on its own without one.
+        assert_eq!(12, sink.emit("foo.bar:1|c|").unwrap());
+        assert_eq!("foo.bar:1|c|", recv_str(&server));
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_max_datagram_too_large() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::builder()
+            .with_max_datagram_size(12)
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+
+        let err = sink.emit("foo.bar:10|c|").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!(1, sink.stats().packets_dropped);
+        assert_eq!(13, sink.stats().bytes_dropped);
+        assert_eq!(0, sink.stats().packets_sent);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_expected_metric_size() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::builder()
+            .with_capacity(20)
+            .with_expected_metric_size(7)
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+
+        // After the second metric there's no room for a third of the same size
+        // so both are sent immediately without needing to flush.
+        sink.emit("foo:1|c").unwrap();
+        sink.emit("bar:2|c").unwrap();
+        assert_eq!("foo:1|c\nbar:2|c\n", recv_str(&server));
+        assert_eq!(1, sink.stats().packets_sent);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_emit_many() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::builder()
+            .with_max_datagram_size(16)
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+
+        // The oversized metric is rejected but the others are still buffered
+        let err = sink
+            .emit_many(&["foo:1|c", "too.big.metric:1|c", "bar:2|c"])
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+        sink.flush().unwrap();
+        assert_eq!("foo:1|c\nbar:2|c\n", recv_str(&server));
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_flush_observer() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_ref = seen.clone();
+        let sink = BufferedUdpMetricSink::builder()
+            .with_capacity(16)
+            .with_flush_observer(move |buf: &[u8]| seen_ref.lock().unwrap().push(buf.to_vec()))
+            .build(server.local_addr().unwrap(), socket)
+            .unwrap();
+
+        sink.emit("foo:1|c").unwrap();
+        sink.emit("bar:2|c").unwrap();
+        assert!(seen.lock().unwrap().is_empty());
+
+        sink.flush().unwrap();
+        assert_eq!("foo:1|c\nbar:2|c\n", recv_str(&server));
+
+        // Metrics bigger than the buffer bypass it but are still observed
+        sink.emit("some.very.long.metric:1|c").unwrap();
+        assert_eq!("some.very.long.metric:1|c", recv_str(&server));
+
+        assert_eq!(
+            vec![b"foo:1|c\nbar:2|c\n".to_vec(), b"some.very.long.metric:1|c".to_vec()],
+            *seen.lock().unwrap()
+        );
+    }
 }
@@ -9,21 +9,17 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::any::Any;
 use std::io;
 use std::io::Write;
 use std::os::unix::net::UnixDatagram;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::time::Duration;
 
 use crate::io::MultiLineWriter;
 use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
-
-// Default size of the buffer for buffered metric sinks. This
-// is a rather conservative value, picked for consistency with
-// the UDP implementation.  Users may want to use a different
-// value based on the configuration of the server their
-// application is running on.
-const DEFAULT_BUFFER_SIZE: usize = 512;
+use crate::sinks::util::{sum_or_first_error, DEFAULT_BUFFER_SIZE};
 
 /// Implementation of a `MetricSink` that emits metrics over a Unix socket.
 ///
@@ -98,6 +94,10 @@ impl MetricSink for UnixMetricSink {
     fn stats(&self) -> SinkStats {
         (&self.stats).into()
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
 /// Adapter for writing to a `UnixDatagram` socket via the `Write` trait
@@ -131,6 +131,74 @@ impl Write for UnixWriteAdapter {
     }
 }
 
+/// Implementation of a builder pattern for `BufferedUnixMetricSink`.
+///
+/// The builder can be used to set the size of the buffer used by the sink
+/// and the typical size of metrics sent by it.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::os::unix::net::UnixDatagram;
+/// use cadence::BufferedUnixMetricSinkBuilder;
+///
+/// let socket = UnixDatagram::unbound().unwrap();
+/// let sink = BufferedUnixMetricSinkBuilder::new()
+///     .with_capacity(8192)
+///     .build("/run/statsd.sock", socket);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BufferedUnixMetricSinkBuilder {
+    capacity: usize,
+    expected_metric_size: Option<usize>,
+}
+
+impl BufferedUnixMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        BufferedUnixMetricSinkBuilder {
+            capacity: DEFAULT_BUFFER_SIZE,
+            expected_metric_size: None,
+        }
+    }
+
+    /// Construct a new `BufferedUnixMetricSink` instance based on the builder
+    /// configuration.
+    ///
+    /// The socket does not need to be bound (i.e. `UnixDatagram::unbound()` is
+    /// fine) but should have any desired configuration already applied
+    /// (blocking vs non-blocking, timeouts, etc.).
+    pub fn build<P>(self, path: P, socket: UnixDatagram) -> BufferedUnixMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        BufferedUnixMetricSink::from_builder(path, socket, self)
+    }
+
+    /// Set the size of the buffer metrics are stored in before being sent.
+    /// Defaults to `DEFAULT_BUFFER_SIZE`.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the typical size, in bytes, of a single metric sent by the sink.
+    ///
+    /// By default, the buffer is only sent once a metric that doesn't fit in
+    /// it is emitted. When this is set, the buffer is sent as soon as there
+    /// isn't room left for another metric of the expected size.
+    pub fn with_expected_metric_size(mut self, size: usize) -> Self {
+        self.expected_metric_size = Some(size);
+        self
+    }
+}
+
+impl Default for BufferedUnixMetricSinkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Implementation of a `MetricSink` that buffers metrics before
 /// sending them to a Unix socket.
 ///
@@ -138,7 +206,9 @@ impl Write for UnixWriteAdapter {
 /// after each metric written to this sink. When the buffer is sufficiently
 /// full and a write is attempted, the contents of the buffer are flushed to
 /// a Unix socket and then the metric is written to the buffer. The buffer is
-/// also flushed when this sink is destroyed.
+/// also flushed when this sink is destroyed, including when it's wrapped by a
+/// `QueuingMetricSink` that is destroyed. Errors flushing the buffer at that
+/// point are ignored since they can't be returned to the caller.
 ///
 /// The default size of the buffer is 512 bytes. This is to be consistent with
 /// the default for the `BufferedUdpMetricSink`. The buffer size can be customized
@@ -212,15 +282,24 @@ impl BufferedUnixMetricSink {
     /// let sink = BufferedUnixMetricSink::with_capacity("/run/statsd.sock", socket, 1432);
     /// ```
     pub fn with_capacity<P>(path: P, socket: UnixDatagram, cap: usize) -> BufferedUnixMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        BufferedUnixMetricSinkBuilder::new()
+            .with_capacity(cap)
+            .build(path, socket)
+    }
+
+    fn from_builder<P>(path: P, socket: UnixDatagram, builder: BufferedUnixMetricSinkBuilder) -> Self
     where
         P: AsRef<Path>,
     {
         let stats = SocketStats::default();
+        let mut writer = MultiLineWriter::new(UnixWriteAdapter::new(socket, path, stats.clone()), builder.capacity);
+        writer.set_expected_size(builder.expected_metric_size);
+
         BufferedUnixMetricSink {
-            buffer: Mutex::new(MultiLineWriter::new(
-                UnixWriteAdapter::new(socket, path, stats.clone()),
-                cap,
-            )),
+            buffer: Mutex::new(writer),
             stats,
         }
     }
@@ -232,21 +311,55 @@ impl MetricSink for BufferedUnixMetricSink {
         writer.write(metric.as_bytes())
     }
 
+    fn emit_many(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        sum_or_first_error(metrics.iter().map(|m| writer.write(m.as_bytes())))
+    }
+
     fn flush(&self) -> io::Result<()> {
         let mut writer = self.buffer.lock().unwrap();
         writer.flush()
     }
 
+    /// Flush buffered metrics with the write timeout of the socket set to `timeout`,
+    /// restoring the original write timeout afterwards.
+    ///
+    /// This only has an effect when the socket is in blocking mode. A zero
+    /// `timeout` is invalid and results in an error.
+    fn flush_with_timeout(&self, timeout: Duration) -> io::Result<()> {
+        let mut writer = self.buffer.lock().unwrap();
+        let previous = writer.get_ref().socket.write_timeout()?;
+        writer.get_ref().socket.set_write_timeout(Some(timeout))?;
+
+        let res = writer.flush();
+        writer.get_ref().socket.set_write_timeout(previous)?;
+        res
+    }
+
     fn stats(&self) -> SinkStats {
         (&self.stats).into()
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+impl Drop for BufferedUnixMetricSink {
+    /// Flush any buffered metrics, ignoring errors.
+    fn drop(&mut self) {
+        let writer = self.buffer.get_mut().unwrap_or_else(|e| e.into_inner());
+        let _ = writer.flush();
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{BufferedUnixMetricSink, MetricSink, UnixMetricSink};
-    use crate::test::UnixServerHarness;
+    use super::{BufferedUnixMetricSink, BufferedUnixMetricSinkBuilder, MetricSink, UnixMetricSink};
+    use crate::test::{TempDir, UnixServerHarness};
+    use std::io;
     use std::os::unix::net::UnixDatagram;
+    use std::time::Duration;
 
     #[test]
     fn test_unix_metric_sink() {
@@ -304,4 +417,84 @@ mod tests {
             assert!(sink.flush().is_ok());
         });
     }
+
+    #[test]
+    fn test_buffered_unix_metric_sink_emit_many() {
+        let harness = UnixServerHarness::new("test_buffered_unix_metric_sink_emit_many");
+
+        harness.run_quiet(|path| {
+            let socket = UnixDatagram::unbound().unwrap();
+            let sink = BufferedUnixMetricSink::with_capacity(path, socket, 16);
+
+            assert_eq!(16, sink.emit_many(&["foo:54|c", "foo:67|c"]).unwrap());
+            assert!(sink.flush().is_ok());
+        });
+    }
+
+    #[test]
+    fn test_buffered_unix_metric_sink_flush_on_drop() {
+        let temp = TempDir::new("test_buffered_unix_metric_sink_flush_on_drop").unwrap();
+        let path = temp.new_path("metrics.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let sink = BufferedUnixMetricSink::with_capacity(&path, UnixDatagram::unbound().unwrap(), 64);
+
+        sink.emit("foo:54|c").unwrap();
+        drop(sink);
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_unix_metric_sink_builder() {
+        let temp = TempDir::new("test_buffered_unix_metric_sink_builder").unwrap();
+        let path = temp.new_path("metrics.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let sink = BufferedUnixMetricSinkBuilder::new()
+            .with_capacity(32)
+            .with_expected_metric_size(8)
+            .build(&path, UnixDatagram::unbound().unwrap());
+
+        // Leaves 14 bytes which is enough for another metric of the expected size
+        sink.emit("foo:54|c").unwrap();
+        sink.emit("foo:67|c").unwrap();
+        // Leaves 6 bytes which isn't, so the buffer is sent right away
+        sink.emit("foo:8|c").unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\nfoo:67|c\nfoo:8|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_unix_metric_sink_flush_with_timeout() {
+        let temp = TempDir::new("test_buffered_unix_metric_sink_flush_with_timeout").unwrap();
+        let path = temp.new_path("stalled.sock");
+        let sink = BufferedUnixMetricSink::from(&path, UnixDatagram::unbound().unwrap());
+
+        // Bind a socket that never reads anything and fill its queue so that
+        // any further blocking writes to it wait until they time out. This is
+        // dropped before the sink so flushing on drop fails instead of blocking.
+        let _server = UnixDatagram::bind(&path).unwrap();
+        let filler = UnixDatagram::unbound().unwrap();
+        filler.set_nonblocking(true).unwrap();
+        while filler.send_to(b"filler:1|c", &path).is_ok() {}
+
+        sink.emit("foo:54|c").unwrap();
+        let err = sink.flush_with_timeout(Duration::from_millis(10)).unwrap_err();
+        assert!(
+            matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut),
+            "unexpected error {:?}",
+            err
+        );
+        // The original timeout of the socket is restored after flushing
+        assert_eq!(
+            None,
+            sink.buffer.lock().unwrap().get_ref().socket.write_timeout().unwrap()
+        );
+    }
 }
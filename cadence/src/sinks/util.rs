@@ -0,0 +1,254 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Largest payload, in bytes, that can be sent in a single UDP datagram.
+///
+/// This is the maximum size of an IPv4 packet (65,535 bytes) minus the
+/// minimum IPv4 header (20 bytes) and the UDP header (8 bytes). IPv6 allows
+/// slightly larger payloads (65,527 bytes) since the IPv6 header isn't counted
+/// as part of the payload length but the IPv4 limit is used so that this is
+/// safe regardless of the protocol used.
+///
+/// Note that datagrams this large will almost certainly be fragmented by the
+/// network and may be dropped by the metric server or anything in between.
+/// This is a hard upper bound, not a recommended size.
+pub const MAX_UDP_PAYLOAD: usize = 65_507;
+
+/// Size, in bytes, of the buffer used by buffered sinks when one isn't given.
+///
+/// This is the size recommended by the Statsd project for metrics sent over
+/// the internet. Every IPv4 host must be able to accept packets of at least
+/// 576 bytes. After accounting for up to 60 bytes of IPv4 header and 8 bytes
+/// of UDP header, 512 bytes of payload leaves a little room to spare. IPv6
+/// guarantees a larger minimum MTU (1,280 bytes) but a 40 byte header so this
+/// size is safe for it as well.
+///
+/// When metrics are only sent within a network with a known MTU, a larger
+/// buffer can be used. For example, with a standard Ethernet MTU of 1,500
+/// bytes, payloads of up to 1,472 bytes (IPv4) or 1,452 bytes (IPv6) fit in
+/// a single packet.
+pub const DEFAULT_BUFFER_SIZE: usize = 512;
+
+/// Return true if the given metric can be sent in a single datagram whose
+/// payload is at most `mtu` bytes.
+///
+/// Note that `mtu` is the space available for the payload of the datagram,
+/// after any IP and UDP headers, not the MTU of the network link itself. When
+/// multiple metrics are combined in a single datagram each is followed by a
+/// newline which must also be accounted for.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{fits_in_datagram, DEFAULT_BUFFER_SIZE};
+///
+/// assert!(fits_in_datagram("some.counter:1|c", DEFAULT_BUFFER_SIZE));
+/// assert!(!fits_in_datagram("some.counter:1|c", 8));
+/// ```
+pub fn fits_in_datagram(metric: &str, mtu: usize) -> bool {
+    metric.len() <= mtu
+}
+
+/// Error for a metric that can't be sent because it's larger than a single
+/// datagram can be, wrapped by an `io::Error` returned by sinks.
+///
+/// `MetricError` looks for this when converting an `io::Error` so that callers
+/// get an `ErrorKind::PacketTooLarge` error with the key and size of the metric.
+#[derive(Debug)]
+pub(crate) struct PacketTooLarge {
+    pub(crate) key: String,
+    pub(crate) size: usize,
+    pub(crate) max: usize,
+}
+
+impl PacketTooLarge {
+    /// Return an `InvalidInput` I/O error for a metric larger than `max` bytes.
+    pub(crate) fn error(metric: &str, max: usize) -> io::Error {
+        // Names can't contain ':' so everything before the first one is the
+        // name of the metric, including the prefix.
+        let key = metric.find(':').map_or(metric, |i| &metric[..i]);
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            PacketTooLarge {
+                key: key.to_string(),
+                size: metric.len(),
+                max,
+            },
+        )
+    }
+}
+
+impl fmt::Display for PacketTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "metric {} is larger than the maximum datagram size ({} > {} bytes)",
+            self.key, self.size, self.max
+        )
+    }
+}
+
+impl error::Error for PacketTooLarge {}
+
+/// Error returned by a named sink, wrapped by an `io::Error` with the same
+/// kind by sinks that send metrics using other sinks.
+#[derive(Debug)]
+pub(crate) struct NamedSinkError {
+    pub(crate) name: String,
+    pub(crate) source: io::Error,
+}
+
+impl NamedSinkError {
+    /// Include the name of the sink that returned `err`, if it has one.
+    pub(crate) fn wrap(name: Option<&str>, err: io::Error) -> io::Error {
+        match name {
+            Some(name) => io::Error::new(
+                err.kind(),
+                NamedSinkError {
+                    name: name.to_string(),
+                    source: err,
+                },
+            ),
+            None => err,
+        }
+    }
+}
+
+impl fmt::Display for NamedSinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sink {}: {}", self.name, self.source)
+    }
+}
+
+impl error::Error for NamedSinkError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+// Add up the number of bytes written for each result, returning the first
+// error if any of them failed. Every result is consumed either way.
+pub(crate) fn sum_or_first_error<I>(results: I) -> io::Result<usize>
+where
+    I: IntoIterator<Item = io::Result<usize>>,
+{
+    let mut written = 0;
+    let mut first_err = None;
+
+    for res in results {
+        match res {
+            Ok(n) => written += n,
+            Err(e) => {
+                if first_err.is_none() {
+                    first_err = Some(e);
+                }
+            }
+        }
+    }
+
+    match first_err {
+        Some(e) => Err(e),
+        None => Ok(written),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fits_in_datagram, sum_or_first_error, NamedSinkError, PacketTooLarge, DEFAULT_BUFFER_SIZE, MAX_UDP_PAYLOAD,
+    };
+    use std::io;
+
+    #[test]
+    fn test_fits_in_datagram_boundaries() {
+        assert!(fits_in_datagram("foo:1|c", 8));
+        assert!(fits_in_datagram("foo:1|c", 7));
+        assert!(!fits_in_datagram("foo:1|c", 6));
+    }
+
+    #[test]
+    fn test_fits_in_datagram_empty() {
+        assert!(fits_in_datagram("", 0));
+    }
+
+    #[test]
+    fn test_fits_in_datagram_max_payload() {
+        let metric = "x".repeat(MAX_UDP_PAYLOAD);
+        assert!(fits_in_datagram(&metric, MAX_UDP_PAYLOAD));
+
+        let metric = "x".repeat(MAX_UDP_PAYLOAD + 1);
+        assert!(!fits_in_datagram(&metric, MAX_UDP_PAYLOAD));
+    }
+
+    #[test]
+    fn test_fits_in_datagram_default_buffer_size() {
+        let metric = "x".repeat(DEFAULT_BUFFER_SIZE);
+        assert!(fits_in_datagram(&metric, DEFAULT_BUFFER_SIZE));
+        assert!(fits_in_datagram(&metric, MAX_UDP_PAYLOAD));
+    }
+
+    #[test]
+    fn test_sum_or_first_error_success() {
+        let results = vec![Ok(3), Ok(4), Ok(0)];
+        assert_eq!(7, sum_or_first_error(results).unwrap());
+    }
+
+    #[test]
+    fn test_sum_or_first_error_consumes_all() {
+        let mut seen = 0;
+        let results = vec![
+            Ok(3),
+            Err(io::Error::from(io::ErrorKind::TimedOut)),
+            Err(io::Error::from(io::ErrorKind::WouldBlock)),
+            Ok(4),
+        ];
+
+        let res = sum_or_first_error(results.into_iter().inspect(|_| seen += 1));
+        assert_eq!(io::ErrorKind::TimedOut, res.unwrap_err().kind());
+        assert_eq!(4, seen);
+    }
+
+    #[test]
+    fn test_packet_too_large_error() {
+        let err = PacketTooLarge::error("some.key:123|c|#foo:bar", 12);
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+        let inner = err.get_ref().unwrap().downcast_ref::<PacketTooLarge>().unwrap();
+        assert_eq!("some.key", inner.key);
+        assert_eq!(23, inner.size);
+        assert_eq!(12, inner.max);
+        assert_eq!(
+            "metric some.key is larger than the maximum datagram size (23 > 12 bytes)",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_named_sink_error_wrap() {
+        let err = NamedSinkError::wrap(Some("primary"), io::Error::from(io::ErrorKind::TimedOut));
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert_eq!("sink primary: timed out", err.to_string());
+
+        let inner = err.get_ref().unwrap().downcast_ref::<NamedSinkError>().unwrap();
+        assert_eq!("primary", inner.name);
+        assert_eq!(io::ErrorKind::TimedOut, inner.source.kind());
+    }
+
+    #[test]
+    fn test_named_sink_error_wrap_no_name() {
+        let err = NamedSinkError::wrap(None, io::Error::from(io::ErrorKind::TimedOut));
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert!(err.get_ref().is_none());
+    }
+}
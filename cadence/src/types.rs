@@ -9,14 +9,49 @@
 // except according to those terms.
 
 use crate::builder::{MetricFormatter, MetricValue};
+use crate::sinks::{NamedSinkError, PacketTooLarge};
 use std::error;
 use std::fmt;
 use std::io;
+use std::str::FromStr;
+
+// Check that a metric line is made of a name, one or more values, and the
+// type code of the given kind of metric, optionally followed by anything else (sampling rate, tags,
+// etc.). Values aren't checked to be numbers since sets accept any value.
+fn validate_metric_str(s: &str, kind: MetricKind) -> MetricResult<()> {
+    let (name_values, rest) = s
+        .split_once('|')
+        .ok_or_else(|| MetricError::from((ErrorKind::InvalidInput, "metric is missing a type")))?;
+    let (name, values) = name_values
+        .split_once(':')
+        .ok_or_else(|| MetricError::from((ErrorKind::InvalidInput, "metric is missing a value")))?;
+
+    if name.is_empty() {
+        return Err(MetricError::from((ErrorKind::InvalidInput, "metric name is empty")));
+    }
+
+    if values.split(':').any(str::is_empty) {
+        return Err(MetricError::from((ErrorKind::InvalidInput, "metric value is empty")));
+    }
+
+    if rest.split('|').next() != Some(kind.as_str()) {
+        return Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "metric has a different type",
+        )));
+    }
+
+    Ok(())
+}
 
 /// Trait for metrics to expose Statsd metric string slice representation.
 ///
 /// Implementing metrics know how to turn themselves into one of the supported
 /// types of metrics as defined in the [Statsd spec](https://github.com/b/statsd_spec).
+///
+/// The metric types of Cadence also implement `Display`, which writes the same
+/// string, and `FromStr`, which accepts a metric string of the matching type
+/// (e.g. `"some.counter:1|c".parse::<Counter>()`).
 pub trait Metric {
     fn as_metric_str(&self) -> &str;
 }
@@ -47,6 +82,21 @@ impl Metric for Counter {
     }
 }
 
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_metric_str().fmt(f)
+    }
+}
+
+impl FromStr for Counter {
+    type Err = MetricError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_metric_str(s, MetricKind::Counter)?;
+        Ok(Counter::from(s.to_string()))
+    }
+}
+
 /// Timers are a positive number of milliseconds between a start and end point.
 ///
 /// Statistical distribution of timer values is often computed by the server.
@@ -75,6 +125,21 @@ impl Metric for Timer {
     }
 }
 
+impl fmt::Display for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_metric_str().fmt(f)
+    }
+}
+
+impl FromStr for Timer {
+    type Err = MetricError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_metric_str(s, MetricKind::Timer)?;
+        Ok(Timer::from(s.to_string()))
+    }
+}
+
 /// Gauges are an instantaneous value determined by the client.
 ///
 /// See the `Gauged` trait for more information.
@@ -105,6 +170,21 @@ impl Metric for Gauge {
     }
 }
 
+impl fmt::Display for Gauge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_metric_str().fmt(f)
+    }
+}
+
+impl FromStr for Gauge {
+    type Err = MetricError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_metric_str(s, MetricKind::Gauge)?;
+        Ok(Gauge::from(s.to_string()))
+    }
+}
+
 /// Meters measure the rate at which events occur as determined by the server.
 ///
 /// See the `Metered` trait for more information.
@@ -131,6 +211,21 @@ impl Metric for Meter {
     }
 }
 
+impl fmt::Display for Meter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_metric_str().fmt(f)
+    }
+}
+
+impl FromStr for Meter {
+    type Err = MetricError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_metric_str(s, MetricKind::Meter)?;
+        Ok(Meter::from(s.to_string()))
+    }
+}
+
 /// Histograms are values whose distribution is calculated by the server.
 ///
 /// The distribution calculated for histograms is often similar to that of
@@ -165,6 +260,21 @@ impl Metric for Histogram {
     }
 }
 
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_metric_str().fmt(f)
+    }
+}
+
+impl FromStr for Histogram {
+    type Err = MetricError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_metric_str(s, MetricKind::Histogram)?;
+        Ok(Histogram::from(s.to_string()))
+    }
+}
+
 /// Distributions represent a global statistical distribution of a set of values.
 ///
 /// See the `Distributed` trait for more information.
@@ -195,6 +305,21 @@ impl Metric for Distribution {
     }
 }
 
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_metric_str().fmt(f)
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = MetricError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_metric_str(s, MetricKind::Distribution)?;
+        Ok(Distribution::from(s.to_string()))
+    }
+}
+
 /// Sets count the number of unique elements in a group.
 ///
 /// See the `Setted` trait for more information.
@@ -221,15 +346,230 @@ impl Metric for Set {
     }
 }
 
+impl fmt::Display for Set {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_metric_str().fmt(f)
+    }
+}
+
+impl FromStr for Set {
+    type Err = MetricError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_metric_str(s, MetricKind::Set)?;
+        Ok(Set::from(s.to_string()))
+    }
+}
+
+/// Tag cardinality hint used by the Datadog agent when adding tags based on
+/// the origin of a metric.
+///
+/// Newer versions of the Datadog agent can detect which container a metric was
+/// sent from and add tags describing it. The cardinality determines how specific
+/// those tags are, from no tags at all (`None`) to tags common to many containers
+/// (`Low`) to tags unique to a single container (`High`). When not set, the default cardinality configured
+/// for the agent is used. See the
+/// [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=metrics#the-dogstatsd-protocol)
+/// for more information.
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum Cardinality {
+    None,
+    Low,
+    Orchestrator,
+    High,
+}
+
+impl fmt::Display for Cardinality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Cardinality::None => "none".fmt(f),
+            Cardinality::Low => "low".fmt(f),
+            Cardinality::Orchestrator => "orchestrator".fmt(f),
+            Cardinality::High => "high".fmt(f),
+        }
+    }
+}
+
+/// How the value of a sampled counter is scaled to account for the metrics
+/// that weren't sent.
+///
+/// When a counter is only sent some of the time, its value needs to be scaled
+/// up by the inverse of the sample rate to estimate the true count. Standard
+/// Statsd servers do this themselves when the rate is included in the metric
+/// (e.g. `some.counter:1|c|@0.1` is counted as 10). Some servers ignore the
+/// rate, in which case Cadence can scale the value before sending it instead
+/// (e.g. `some.counter:10|c`).
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum ScalingMode {
+    /// Send the value unchanged along with the sample rate and let the server
+    /// scale it. This is the standard behavior and the default.
+    ServerSide,
+    /// Multiply the value by the inverse of the sample rate (rounding to the
+    /// nearest integer for integer values) and send it without the sample
+    /// rate.
+    ClientSide,
+}
+
+impl Default for ScalingMode {
+    fn default() -> Self {
+        ScalingMode::ServerSide
+    }
+}
+
+/// Type of a metric emitted by a client.
+///
+/// Used to select the metrics that some options of a client apply to, such as
+/// the tags added by `StatsdClientBuilder::with_type_tag()`. Each kind can be
+/// converted to and from the standard Statsd code identifying its type in a
+/// metric (`c` for counters, `ms` for timers, etc.), which is useful for tools
+/// that parse or proxy metrics.
+///
+/// # Example
+///
+/// ```
+/// use cadence::MetricKind;
+///
+/// assert_eq!("ms", MetricKind::Timer.as_str());
+/// assert_eq!(MetricKind::Gauge, MetricKind::try_from("g").unwrap());
+/// assert!(MetricKind::try_from("x").is_err());
+/// ```
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum MetricKind {
+    Counter,
+    Timer,
+    Gauge,
+    Meter,
+    Histogram,
+    Set,
+    Distribution,
+}
+
+impl MetricKind {
+    /// Return the standard Statsd code for this type of metric.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            MetricKind::Counter => "c",
+            MetricKind::Timer => "ms",
+            MetricKind::Gauge => "g",
+            MetricKind::Meter => "m",
+            MetricKind::Histogram => "h",
+            MetricKind::Set => "s",
+            MetricKind::Distribution => "d",
+        }
+    }
+}
+
+impl fmt::Display for MetricKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl TryFrom<&str> for MetricKind {
+    type Error = MetricError;
+
+    fn try_from(code: &str) -> Result<Self, Self::Error> {
+        match code {
+            "c" => Ok(MetricKind::Counter),
+            "ms" => Ok(MetricKind::Timer),
+            "g" => Ok(MetricKind::Gauge),
+            "m" => Ok(MetricKind::Meter),
+            "h" => Ok(MetricKind::Histogram),
+            "s" => Ok(MetricKind::Set),
+            "d" => Ok(MetricKind::Distribution),
+            _ => Err(MetricError::from((ErrorKind::InvalidInput, "unknown metric type"))),
+        }
+    }
+}
+
+/// Set of tags that can be built once and added to many metrics.
+///
+/// When many metrics are emitted with the same tags (for example, all the
+/// metrics emitted while handling a single request), the tags can be collected
+/// in a `TagSet` once and passed to methods like `Counted::count_with_tagset()`
+/// or `MetricBuilder::with_tag_set()` for each metric. Adding the tags of a
+/// `TagSet` to a metric doesn't copy them. They're written after the default
+/// tags of the client, in the order they were added to the set.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{StatsdClient, NopMetricSink, Metric, TagSet};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let tags = TagSet::new().with("endpoint", "/users").with_value("beta");
+///
+/// let res = client.count_with_tagset("requests", 1, &tags).try_send();
+/// assert_eq!("my.prefix.requests:1|c|#endpoint:/users,beta", res.unwrap().as_metric_str());
+///
+/// let res = client.time_with_tagset("latency", 34, &tags).try_send();
+/// assert_eq!("my.prefix.latency:34|ms|#endpoint:/users,beta", res.unwrap().as_metric_str());
+/// ```
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Default)]
+pub struct TagSet {
+    tags: Vec<(Option<String>, String)>,
+}
+
+impl TagSet {
+    /// Create a new, empty, set of tags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a tag with a key and value to this set.
+    pub fn with<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: ToString,
+        V: ToString,
+    {
+        self.tags.push((Some(key.to_string()), value.to_string()));
+        self
+    }
+
+    /// Add a tag with only a value to this set.
+    pub fn with_value<V>(mut self, value: V) -> Self
+    where
+        V: ToString,
+    {
+        self.tags.push((None, value.to_string()));
+        self
+    }
+
+    /// Return the number of tags in this set.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Return `true` if this set doesn't have any tags.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Option<&str>, &str)> {
+        self.tags.iter().map(|(key, value)| (key.as_deref(), value.as_str()))
+    }
+}
+
 /// Potential categories an error from this library falls into.
 #[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
 pub enum ErrorKind {
     InvalidInput,
     IoError,
+    /// A metric was too large to be sent in a single datagram. The error
+    /// includes the key and size of the metric, see `MetricError::packet_too_large()`.
+    PacketTooLarge,
+    /// The address of the metric server couldn't be parsed or resolved, or
+    /// didn't resolve to any addresses.
+    AddrResolution,
 }
 
 /// Error generated by this library potentially wrapping another
 /// type of error (exposed via the `Error` trait).
+///
+/// When an error was caused by another error, such as an `io::Error` from
+/// a sink, the original error is returned by `Error::source()` so that the
+/// full chain of errors can be inspected or reported.
 #[derive(Debug)]
 pub struct MetricError {
     repr: ErrorRepr,
@@ -238,7 +578,9 @@ pub struct MetricError {
 #[derive(Debug)]
 enum ErrorRepr {
     WithDescription(ErrorKind, &'static str),
+    WithSource(ErrorKind, &'static str, Box<dyn error::Error + Send + Sync>),
     IoError(io::Error),
+    PacketTooLarge(String, usize, io::Error),
 }
 
 impl MetricError {
@@ -247,6 +589,20 @@ impl MetricError {
         match self.repr {
             ErrorRepr::IoError(_) => ErrorKind::IoError,
             ErrorRepr::WithDescription(kind, _) => kind,
+            ErrorRepr::WithSource(kind, _, _) => kind,
+            ErrorRepr::PacketTooLarge(_, _, _) => ErrorKind::PacketTooLarge,
+        }
+    }
+
+    /// Return the key (including any prefix) and size in bytes of the metric
+    /// that caused this error if it's an `ErrorKind::PacketTooLarge` error,
+    /// `None` otherwise.
+    ///
+    /// This can be used to log or trim the offending metric.
+    pub fn packet_too_large(&self) -> Option<(&str, usize)> {
+        match self.repr {
+            ErrorRepr::PacketTooLarge(ref key, size, _) => Some((key, size)),
+            _ => None,
         }
     }
 }
@@ -256,6 +612,8 @@ impl fmt::Display for MetricError {
         match self.repr {
             ErrorRepr::IoError(ref err) => err.fmt(f),
             ErrorRepr::WithDescription(_, desc) => desc.fmt(f),
+            ErrorRepr::WithSource(_, desc, _) => desc.fmt(f),
+            ErrorRepr::PacketTooLarge(_, _, ref err) => err.fmt(f),
         }
     }
 }
@@ -264,6 +622,8 @@ impl error::Error for MetricError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self.repr {
             ErrorRepr::IoError(ref err) => Some(err),
+            ErrorRepr::WithSource(_, _, ref err) => Some(err.as_ref()),
+            ErrorRepr::PacketTooLarge(_, _, ref err) => Some(err),
             _ => None,
         }
     }
@@ -277,6 +637,8 @@ impl error::Error for MetricError {
         match self.repr {
             ErrorRepr::IoError(ref err) => err.description(),
             ErrorRepr::WithDescription(_, desc) => desc,
+            ErrorRepr::WithSource(_, desc, _) => desc,
+            ErrorRepr::PacketTooLarge(_, _, _) => "metric is larger than the maximum datagram size",
         }
     }
 
@@ -285,11 +647,28 @@ impl error::Error for MetricError {
     }
 }
 
+// Find the error for a metric too large to send, if any, including when it
+// was returned by a named sink wrapped by another sink.
+fn packet_too_large(err: &io::Error) -> Option<&PacketTooLarge> {
+    let inner = err.get_ref()?;
+    match inner.downcast_ref::<NamedSinkError>() {
+        Some(named) => packet_too_large(&named.source),
+        None => inner.downcast_ref::<PacketTooLarge>(),
+    }
+}
+
 impl From<io::Error> for MetricError {
     fn from(err: io::Error) -> MetricError {
-        MetricError {
-            repr: ErrorRepr::IoError(err),
-        }
+        // Sinks indicate that a metric is too large to send with a specific
+        // type of error wrapped by the I/O error.
+        let too_large = packet_too_large(&err).map(|inner| (inner.key.clone(), inner.size));
+
+        let repr = match too_large {
+            Some((key, size)) => ErrorRepr::PacketTooLarge(key, size, err),
+            None => ErrorRepr::IoError(err),
+        };
+
+        MetricError { repr }
     }
 }
 
@@ -301,16 +680,122 @@ impl From<(ErrorKind, &'static str)> for MetricError {
     }
 }
 
+impl From<(ErrorKind, &'static str, Box<dyn error::Error + Send + Sync>)> for MetricError {
+    fn from((kind, desc, source): (ErrorKind, &'static str, Box<dyn error::Error + Send + Sync>)) -> MetricError {
+        MetricError {
+            repr: ErrorRepr::WithSource(kind, desc, source),
+        }
+    }
+}
+
 pub type MetricResult<T> = Result<T, MetricError>;
 
 #[cfg(test)]
 mod tests {
     #![allow(deprecated, deprecated_in_future)]
 
-    use super::{Counter, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, Set, Timer};
+    use super::{
+        Cardinality, Counter, Distribution, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, MetricKind, Set,
+        TagSet, Timer,
+    };
+    use crate::sinks::{NamedSinkError, PacketTooLarge};
     use std::error::Error;
     use std::io;
 
+    #[test]
+    fn test_metric_kind_round_trip() {
+        let kinds = [
+            MetricKind::Counter,
+            MetricKind::Timer,
+            MetricKind::Gauge,
+            MetricKind::Meter,
+            MetricKind::Histogram,
+            MetricKind::Set,
+            MetricKind::Distribution,
+        ];
+
+        for kind in kinds {
+            assert_eq!(kind, MetricKind::try_from(kind.as_str()).unwrap());
+            assert_eq!(kind.as_str(), kind.to_string());
+        }
+    }
+
+    #[test]
+    fn test_metric_kind_try_from_invalid() {
+        for code in ["", "x", "C", "ms|", "|c"] {
+            let err = MetricKind::try_from(code).unwrap_err();
+            assert_eq!(ErrorKind::InvalidInput, err.kind());
+        }
+    }
+
+    #[test]
+    fn test_metric_display() {
+        assert_eq!(
+            "my.app.test.counter:4|c",
+            Counter::new("my.app.", "test.counter", 4).to_string()
+        );
+        assert_eq!(
+            "my.app.test.timer:34|ms",
+            Timer::new("my.app.", "test.timer", 34).to_string()
+        );
+        assert_eq!(
+            "my.app.test.gauge:2|g",
+            format!("{}", Gauge::new("my.app.", "test.gauge", 2))
+        );
+        assert_eq!("my.app.test.set:4|s", format!("{}", Set::new("my.app.", "test.set", 4)));
+    }
+
+    #[test]
+    fn test_metric_from_str() {
+        assert_eq!(
+            Counter::new("prefix.", "k", 1),
+            "prefix.k:1|c".parse::<Counter>().unwrap()
+        );
+        assert_eq!(
+            Timer::new("prefix.", "k", 34),
+            "prefix.k:34|ms".parse::<Timer>().unwrap()
+        );
+        assert_eq!(
+            Gauge::new_f64("prefix.", "k", 1.5),
+            "prefix.k:1.5|g".parse::<Gauge>().unwrap()
+        );
+        assert_eq!(Meter::new("prefix.", "k", 5), "prefix.k:5|m".parse::<Meter>().unwrap());
+        assert_eq!(
+            Histogram::new("prefix.", "k", 5),
+            "prefix.k:5|h".parse::<Histogram>().unwrap()
+        );
+        assert_eq!(
+            Distribution::new("prefix.", "k", 5),
+            "prefix.k:5|d".parse::<Distribution>().unwrap()
+        );
+        assert_eq!(Set::new("prefix.", "k", 5), "prefix.k:5|s".parse::<Set>().unwrap());
+    }
+
+    #[test]
+    fn test_metric_from_str_extensions() {
+        let metric = "prefix.k:1:2:3|h|@0.5|#foo:bar|T1700000000";
+        assert_eq!(metric, metric.parse::<Histogram>().unwrap().as_metric_str());
+        assert_eq!(metric, metric.parse::<Histogram>().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_metric_from_str_invalid() {
+        for metric in [
+            "",
+            "prefix.k",
+            "prefix.k|c",
+            ":1|c",
+            "prefix.k:|c",
+            "prefix.k:1::2|c",
+            "prefix.k:1|g",
+        ] {
+            let err = metric.parse::<Counter>().unwrap_err();
+            assert_eq!(ErrorKind::InvalidInput, err.kind(), "unexpected error for {:?}", metric);
+        }
+
+        assert!("prefix.k:1|ms".parse::<Meter>().is_err());
+    }
+
     #[test]
     fn test_counter_to_metric_string() {
         let counter = Counter::new("my.app.", "test.counter", 4);
@@ -383,6 +868,14 @@ mod tests {
         assert_eq!("test.set:4|s", set.as_metric_str());
     }
 
+    #[test]
+    fn test_cardinality_to_string() {
+        assert_eq!("none", Cardinality::None.to_string());
+        assert_eq!("low", Cardinality::Low.to_string());
+        assert_eq!("orchestrator", Cardinality::Orchestrator.to_string());
+        assert_eq!("high", Cardinality::High.to_string());
+    }
+
     #[test]
     fn test_metric_error_kind_io_error() {
         let io_err = io::Error::new(io::ErrorKind::BrokenPipe, "Broken pipe");
@@ -390,6 +883,42 @@ mod tests {
         assert_eq!(ErrorKind::IoError, our_err.kind());
     }
 
+    #[test]
+    fn test_metric_error_kind_packet_too_large() {
+        let io_err = PacketTooLarge::error("some.key:1234|c", 8);
+        let our_err = MetricError::from(io_err);
+
+        assert_eq!(ErrorKind::PacketTooLarge, our_err.kind());
+        assert_eq!(Some(("some.key", 15)), our_err.packet_too_large());
+        assert_eq!(
+            "metric some.key is larger than the maximum datagram size (15 > 8 bytes)",
+            our_err.to_string()
+        );
+        assert!(our_err.source().is_some());
+    }
+
+    #[test]
+    fn test_metric_error_kind_packet_too_large_named_sink() {
+        let io_err = NamedSinkError::wrap(Some("primary"), PacketTooLarge::error("some.key:1234|c", 8));
+        let our_err = MetricError::from(io_err);
+
+        assert_eq!(ErrorKind::PacketTooLarge, our_err.kind());
+        assert_eq!(Some(("some.key", 15)), our_err.packet_too_large());
+        assert_eq!(
+            "sink primary: metric some.key is larger than the maximum datagram size (15 > 8 bytes)",
+            our_err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_metric_error_packet_too_large_other() {
+        let io_err = io::Error::new(io::ErrorKind::InvalidInput, "Too big!");
+        let our_err = MetricError::from(io_err);
+
+        assert_eq!(ErrorKind::IoError, our_err.kind());
+        assert_eq!(None, our_err.packet_too_large());
+    }
+
     #[test]
     fn test_metric_error_kind_invalid_input() {
         let our_err = MetricError::from((ErrorKind::InvalidInput, "Nope"));
@@ -400,7 +929,7 @@ mod tests {
     fn test_metric_error_description_io_error() {
         let io_err = io::Error::new(io::ErrorKind::PermissionDenied, "Permission!");
         let our_err = MetricError::from(io_err);
-        assert_eq!("Permission!", our_err.description());
+        assert_eq!("Permission!", our_err.to_string());
     }
 
     #[test]
@@ -413,7 +942,7 @@ mod tests {
     fn test_metric_error_cause_io_error() {
         let io_err = io::Error::new(io::ErrorKind::TimedOut, "Timeout!");
         let our_err = MetricError::from(io_err);
-        assert_eq!("Timeout!", our_err.source().unwrap().description());
+        assert_eq!("Timeout!", our_err.source().unwrap().to_string());
     }
 
     #[test]
@@ -421,4 +950,67 @@ mod tests {
         let our_err = MetricError::from((ErrorKind::InvalidInput, "Nope!"));
         assert!(our_err.source().is_none());
     }
+
+    #[test]
+    fn test_metric_error_with_source() {
+        let parse_err = "abc".parse::<u64>().unwrap_err();
+        let source: Box<dyn Error + Send + Sync> = Box::new(parse_err.clone());
+        let our_err = MetricError::from((ErrorKind::InvalidInput, "Bad value!", source));
+
+        assert_eq!(ErrorKind::InvalidInput, our_err.kind());
+        assert_eq!("Bad value!", our_err.to_string());
+        assert_eq!("Bad value!", our_err.description());
+        assert_eq!(
+            Some(&parse_err),
+            our_err
+                .source()
+                .and_then(|e| e.downcast_ref::<std::num::ParseIntError>())
+        );
+    }
+
+    #[test]
+    fn test_metric_error_source_chain() {
+        let io_err = io::Error::new(io::ErrorKind::Other, "Inner!");
+        let source: Box<dyn Error + Send + Sync> = Box::new(io_err);
+        let our_err = MetricError::from((ErrorKind::IoError, "Outer!", source));
+
+        let mut chain = Vec::new();
+        let mut current: Option<&dyn Error> = Some(&our_err);
+        while let Some(err) = current {
+            chain.push(err.to_string());
+            current = err.source();
+        }
+
+        assert_eq!(vec!["Outer!".to_string(), "Inner!".to_string()], chain);
+    }
+
+    #[test]
+    fn test_metric_error_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync + Error + 'static>() {}
+        assert_send_sync::<MetricError>();
+    }
+
+    #[test]
+    fn test_tag_set_empty() {
+        let tags = TagSet::new();
+
+        assert!(tags.is_empty());
+        assert_eq!(0, tags.len());
+        assert_eq!(0, tags.iter().count());
+    }
+
+    #[test]
+    fn test_tag_set_with_tags() {
+        let tags = TagSet::new()
+            .with("region", "us-east-1")
+            .with_value("beta")
+            .with("shard", 4);
+
+        assert!(!tags.is_empty());
+        assert_eq!(3, tags.len());
+        assert_eq!(
+            vec![(Some("region"), "us-east-1"), (None, "beta"), (Some("shard"), "4")],
+            tags.iter().collect::<Vec<_>>()
+        );
+    }
 }